@@ -0,0 +1,664 @@
+//! The backups made in the backup directory: their index and retention.
+
+use crate::error::Context;
+use crate::style::Paint;
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDateTime};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The separator between the name of a backed up file and the time of its
+/// backup, in the name of the backups made by earlier versions, all in the
+/// backup directory itself.
+const LEGACY_BACKUP_SEPARATOR: &str = "_backup_";
+
+/// The format of the times naming the backups and the directories of the runs
+/// (e.g. `20240102T030405`): sortable, and without `:`, which Windows doesn't
+/// allow in file names.
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// The name of the index of the backups, in the backup directory.
+const INDEX_FILENAME: &str = "index.toml";
+
+/// The extension of the archives of the backups of a run (see [`archive_run`]).
+const ARCHIVE_EXTENSION: &str = ".tar.gz";
+
+/// Backups, each one with the time it has been made.
+type Backups = Vec<(DateTime<FixedOffset>, PathBuf)>;
+
+/// A backup made, as recorded in the [`Index`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// The path of the backup.
+    #[serde(with = "crate::path_repr")]
+    pub backup: PathBuf,
+    /// The path of the backed up file.
+    #[serde(with = "crate::path_repr")]
+    pub original: PathBuf,
+    /// When the backup has been made (see [`time_name`]).
+    pub time: String,
+    /// The run during which the backup has been made, identified by the time
+    /// it started (see [`run_name`]).
+    pub run: String,
+    /// The target of the link that replaced the backed up file.
+    #[serde(with = "crate::path_repr")]
+    pub target: PathBuf,
+    /// The archive the backup has been moved into, if any (see
+    /// [`archive_run`]). Extracting it in the backup directory brings the
+    /// backup back to `backup`.
+    #[serde(default, with = "crate::path_repr::option")]
+    pub archive: Option<PathBuf>,
+    /// The SHA-256 hash of the contents of the backup, in hexadecimal, if it
+    /// is a regular file (see [`crate::utils::hash_file`]).
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// The index of the backups made, kept in the backup directory, to know
+/// where each backup comes from without guessing from its path.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::backups::Index;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let backup_dir = Path::new("/home/me/.config/mksls/backups");
+/// let index = Index::load(backup_dir)?;
+/// for record in index.iter() {
+///     println!("{} backed up to {}", record.original.display(), record.backup.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Index {
+    /// The backups made, in the order they have been made.
+    backup: Vec<Record>,
+}
+
+impl Index {
+    /// Returns the path of the index in `backup_dir`.
+    pub fn path(backup_dir: &Path) -> PathBuf {
+        backup_dir.join(INDEX_FILENAME)
+    }
+
+    /// Loads the index of `backup_dir`, or an empty one if it has none yet.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the index can't be read or is not a valid index.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn load(backup_dir: &Path) -> crate::Result<Self> {
+        let path = Self::path(backup_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path).backup_context(|| {
+            format!(
+                "Failed to read the index of the backups {}.",
+                path.display()
+            )
+        })?;
+        toml::from_str(&contents).parse_context(|| {
+            format!(
+                "Failed to parse the index of the backups {}.",
+                path.display()
+            )
+        })
+    }
+
+    /// Saves the index in `backup_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the index can't be written (e.g. because a path is not
+    /// valid UTF-8).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn save(&self, backup_dir: &Path) -> crate::Result<()> {
+        let path = Self::path(backup_dir);
+        let contents = toml::to_string(self).parse_context(|| {
+            format!(
+                "Failed to serialize the index of the backups to save it at {}.",
+                path.display()
+            )
+        })?;
+        fs::write(&path, contents).backup_context(|| {
+            format!(
+                "Failed to write the index of the backups {}.",
+                path.display()
+            )
+        })
+    }
+
+    /// Adds `record` to the index.
+    pub fn add(&mut self, record: Record) {
+        self.backup.push(record);
+    }
+
+    /// Removes the record of the backup `backup` from the index, or the
+    /// records of the backups archived in `backup` if it is an archive.
+    pub fn remove(&mut self, backup: &Path) {
+        self.backup
+            .retain(|record| record.backup != backup && record.archive.as_deref() != Some(backup));
+    }
+
+    /// Whether a backup of `original` whose contents hash to `hash` has
+    /// already been made, and still exists (possibly in an archive).
+    pub fn has_identical(&self, original: &Path, hash: &str) -> bool {
+        self.backup.iter().any(|record| {
+            record.original == original
+                && record.hash.as_deref() == Some(hash)
+                && match &record.archive {
+                    Some(archive) => archive.exists(),
+                    None => record.backup.exists(),
+                }
+        })
+    }
+
+    /// Records that the backups made during the run `run` have been moved
+    /// into the archive `archive`.
+    pub fn archived(&mut self, run: &str, archive: &Path) {
+        for record in self.backup.iter_mut().filter(|record| record.run == run) {
+            record.archive = Some(archive.to_path_buf());
+        }
+    }
+
+    /// Returns an iterator over the records of the index, in the order the
+    /// backups have been made.
+    pub fn iter(&self) -> impl Iterator<Item = &Record> {
+        self.backup.iter()
+    }
+}
+
+/// Returns the name of the current time, followed by `-<n>` for the first
+/// `n` making it not `taken` (e.g. by a backup made within the same second).
+pub fn time_name(taken: impl Fn(&str) -> bool) -> String {
+    let time = Local::now().format(TIME_FORMAT).to_string();
+    let mut name = time.clone();
+    let mut n = 0;
+    while taken(&name) {
+        n += 1;
+        name = format!("{}-{}", time, n);
+    }
+
+    name
+}
+
+/// Returns the name of a run starting now, identifying its backups in
+/// `backup_dir`: its directory, then its archive (see [`archive_run`]).
+pub fn run_name(backup_dir: &Path) -> String {
+    time_name(|name| {
+        backup_dir.join(name).exists()
+            || backup_dir
+                .join(format!("{}{}", name, ARCHIVE_EXTENSION))
+                .exists()
+    })
+}
+
+/// Archives the backups made during the run `run`, i.e. the directory
+/// `backup_dir/run`, into `backup_dir/run.tar.gz`, then removes the
+/// directory.
+///
+/// Symlinks are archived as symlinks.
+///
+/// # Returns
+///
+/// The path of the archive, or `None` if no backup has been made during the
+/// run.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - The archive fails to be written.
+/// - The directory of the run fails to be removed.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn archive_run(backup_dir: &Path, run: &str) -> crate::Result<Option<PathBuf>> {
+    let run_dir = backup_dir.join(run);
+    if !run_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let archive = backup_dir.join(format!("{}{}", run, ARCHIVE_EXTENSION));
+    let write_archive = || -> io::Result<()> {
+        let file = File::create(&archive)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        builder.follow_symlinks(false);
+        builder.append_dir_all(run, &run_dir)?;
+        builder.into_inner()?.finish()?.sync_all()
+    };
+    write_archive().backup_context(|| {
+        format!(
+            "Failed to archive the backups of {} into {}.",
+            run_dir.display(),
+            archive.display()
+        )
+    })?;
+    fs::remove_dir_all(&run_dir).backup_context(|| {
+        format!(
+            "Failed to remove directory {} once archived into {}.",
+            run_dir.display(),
+            archive.display()
+        )
+    })?;
+
+    Ok(Some(archive))
+}
+
+/// Deletes the backups within `backup_dir` that are not to be kept anymore.
+///
+/// Backups are grouped by the file they are a backup of. Within a group, a
+/// backup is deleted when it is older than `max_age_days` days, or when
+/// `max_backups_per_link` more recent backups exist. `None` means no limit.
+///
+/// The archives of the backups of a run (see [`archive_run`]) are deleted
+/// as a whole, when the run is older than `max_age_days` days.
+///
+/// The deleted backups are removed from the [`Index`], and the directories
+/// they leave empty are removed.
+///
+/// For each backup deleted, a line with the following format is printed:
+///
+/// ```text
+/// (p) <backup>
+/// ```
+///
+/// # Returns
+///
+/// The number of backups deleted.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - `backup_dir` can't be walked.
+/// - A backup fails to be deleted.
+/// - Reading/writing the index fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::backups;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let pruned = backups::prune(Path::new("/home/me/.config/mksls/backups"), Some(3), Some(30))?;
+/// println!("{} backups deleted.", pruned);
+/// # Ok(())
+/// # }
+/// ```
+pub fn prune(
+    backup_dir: &Path,
+    max_backups_per_link: Option<usize>,
+    max_age_days: Option<u64>,
+) -> crate::Result<u64> {
+    let mut index = Index::load(backup_dir)?;
+    let now = Local::now().fixed_offset();
+    let too_old = |time: DateTime<FixedOffset>| {
+        max_age_days
+            .is_some_and(|days| now - time > Duration::days(days.try_into().unwrap_or(i64::MAX)))
+    };
+    let (backups, archives) = find(backup_dir)?;
+    let mut to_delete = vec![];
+    for mut backups in backups.into_values() {
+        // Most recent first.
+        backups.sort_by_key(|(time, _)| Reverse(*time));
+        for (i, (time, backup)) in backups.into_iter().enumerate() {
+            let too_many = max_backups_per_link.is_some_and(|max| i >= max);
+            if too_many || too_old(time) {
+                to_delete.push(backup);
+            }
+        }
+    }
+    to_delete.extend(
+        archives
+            .into_iter()
+            .filter(|(time, _)| too_old(*time))
+            .map(|(_, archive)| archive),
+    );
+
+    let mut pruned = 0;
+    for backup in to_delete {
+        let removed = if backup.is_dir() && !backup.is_symlink() {
+            fs::remove_dir_all(&backup)
+        } else {
+            fs::remove_file(&backup)
+        };
+        removed.backup_context(|| format!("Failed to delete backup {}.", backup.display()))?;
+        println!(
+            "{}",
+            format!("(p) {}", backup.to_string_lossy()).dark_magenta()
+        );
+        index.remove(&backup);
+        remove_empty_parents(backup_dir, &backup);
+        pruned += 1;
+    }
+    if pruned > 0 && Index::path(backup_dir).exists() {
+        index.save(backup_dir)?;
+    }
+
+    Ok(pruned)
+}
+
+/// Finds the backups within `backup_dir`, grouped by the file they are a
+/// backup of, each one with the time it has been made.
+///
+/// The directories of the runs, directly within `backup_dir`, are looked
+/// into, as well as the directories mirroring the paths of the backed up
+/// files.
+///
+/// # Returns
+///
+/// The backups, grouped by the path of the file they are a backup of,
+/// relative to the root directory, and the archives of the backups of runs
+/// (see [`archive_run`]), each one with the time the run started.
+///
+/// # Errors
+///
+/// Fails when `backup_dir` can't be walked.
+///
+/// See [`crate::Error`] for the kinds of errors.
+fn find(backup_dir: &Path) -> crate::Result<(BTreeMap<PathBuf, Backups>, Backups)> {
+    let mut found: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    let mut archives = vec![];
+    let mut walker = WalkDir::new(backup_dir).min_depth(1).into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry.backup_context(|| {
+            format!(
+                "Failed to walk backup directory {} to find the backups.",
+                backup_dir.display()
+            )
+        })?;
+        let Some(name) = entry.file_name().to_str() else {
+            continue;
+        };
+        if entry.depth() == 1 && entry.file_type().is_file() {
+            if let Some(time) = name.strip_suffix(ARCHIVE_EXTENSION).and_then(parse_time) {
+                archives.push((time, entry.into_path()));
+                continue;
+            }
+        }
+        let Some((original, time)) = parse_name(name) else {
+            continue;
+        };
+        if entry.depth() == 1 && original.is_empty() && entry.file_type().is_dir() {
+            // The directory of a run.
+            continue;
+        }
+        // Don't look for backups within a backed up directory.
+        if entry.file_type().is_dir() {
+            walker.skip_current_dir();
+        }
+
+        let mut original_path: PathBuf = entry
+            .path()
+            .parent()
+            .and_then(|parent| parent.strip_prefix(backup_dir).ok())
+            .map(|parent| {
+                parent
+                    .components()
+                    .skip_while(|component| {
+                        // Skip the directory of the run.
+                        component
+                            .as_os_str()
+                            .to_str()
+                            .is_some_and(|name| parse_time(name).is_some())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        original_path.push(original);
+        found
+            .entry(original_path)
+            .or_default()
+            .push((time, entry.into_path()));
+    }
+
+    Ok((found, archives))
+}
+
+/// Removes the directories containing `backup` that are empty (e.g. after
+/// the deletion of `backup`), up to `backup_dir` excluded.
+fn remove_empty_parents(backup_dir: &Path, backup: &Path) {
+    for dir in backup.ancestors().skip(1) {
+        if dir == backup_dir || !dir.starts_with(backup_dir) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Parses a time named by [`time_name`], or in RFC 3339 as named by earlier
+/// versions.
+fn parse_time(name: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(name) {
+        return Some(time);
+    }
+
+    let time = match name.split_once('-') {
+        Some((time, n)) if !n.is_empty() && n.bytes().all(|byte| byte.is_ascii_digit()) => time,
+        Some(_) => return None,
+        None => name,
+    };
+    let time = NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+
+    Some(time.and_local_timezone(Local).earliest()?.fixed_offset())
+}
+
+/// Parses the name of a backup.
+///
+/// Backups are named after the time they have been made, within a directory
+/// mirroring the path of the backed up file (see [`crate::utils::move_into`]).
+/// Backups made by earlier versions, named
+/// `<file stem>_backup_<time>[.<file extension>]`, are recognized as well.
+///
+/// # Returns
+///
+/// The name of the backed up file (empty if it is given by the directory
+/// containing the backup) and the time of the backup, or `None` if `name` is
+/// not the name of a backup.
+fn parse_name(name: &str) -> Option<(String, DateTime<FixedOffset>)> {
+    if let Some(time) = parse_time(name) {
+        return Some((String::new(), time));
+    }
+
+    let (stem, rest) = name.rsplit_once(LEGACY_BACKUP_SEPARATOR)?;
+    // The time ends with its offset, written `+HH:MM` or `-HH:MM`.
+    let offset_start = rest.rfind(['+', '-'])?;
+    let time_end = (offset_start + "+HH:MM".len()).min(rest.len());
+    let (time, extension) = rest.split_at(time_end);
+    let time = DateTime::parse_from_rfc3339(time).ok()?;
+
+    Some((format!("{}{}", stem, extension), time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn time_names_are_parsed_back_and_without_colons() {
+        let name = time_name(|_| false);
+        assert!(!name.contains(':'), "{} contains a ':'.", name);
+        let time = parse_time(&name).unwrap();
+        assert!(Local::now().fixed_offset() - time < Duration::minutes(1));
+
+        let taken = [name.clone(), format!("{}-1", name)];
+        let next = time_name(|name| taken.iter().any(|taken| taken == name));
+        if next.starts_with(&name) {
+            assert_eq!(next, format!("{}-2", name));
+        }
+        assert_eq!(parse_time(&format!("{}-2", name)), Some(time));
+        assert_eq!(parse_time(&format!("{}-x", name)), None);
+    }
+
+    #[test]
+    fn parse_name_recognizes_backups() {
+        let (original, time) = parse_name("20240102T030405-1").unwrap();
+        assert_eq!(original, "");
+        assert_eq!(
+            time.naive_local(),
+            NaiveDateTime::parse_from_str("20240102T030405", TIME_FORMAT).unwrap()
+        );
+        let (original, time) = parse_name("2024-01-02T03:04:05.123+01:00").unwrap();
+        assert_eq!(original, "");
+        assert_eq!(
+            time,
+            DateTime::parse_from_rfc3339("2024-01-02T03:04:05.123+01:00").unwrap()
+        );
+        let (original, time) = parse_name("app_backup_2024-01-02T03:04:05.123+01:00.conf").unwrap();
+        assert_eq!(original, "app.conf");
+        assert_eq!(
+            time,
+            DateTime::parse_from_rfc3339("2024-01-02T03:04:05.123+01:00").unwrap()
+        );
+        assert_eq!(
+            parse_name(".bashrc_backup_2024-01-02T03:04:05-05:00")
+                .unwrap()
+                .0,
+            ".bashrc"
+        );
+        assert!(parse_name("app.conf").is_none());
+        assert!(parse_name("app_backup_yesterday.conf").is_none());
+    }
+
+    #[test]
+    fn index_round_trips() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+
+        assert_eq!(Index::load(&dir)?, Index::default());
+
+        let record = |backup: &str| Record {
+            backup: dir.child(backup).to_path_buf(),
+            original: "/home/me/.bashrc".into(),
+            time: backup.into(),
+            run: "20240102T030400".into(),
+            target: "/home/me/.dotfiles/bashrc".into(),
+            archive: None,
+            hash: Some("ba7816bf".into()),
+        };
+        let mut index = Index::default();
+        index.add(record("2024-01-02T03:04:05+01:00"));
+        index.add(record("2024-01-02T03:04:06+01:00"));
+        index.remove(&dir.child("2024-01-02T03:04:05+01:00"));
+        index.save(&dir)?;
+
+        assert!(!index.has_identical("/home/me/.bashrc".as_ref(), "ba7816bf"));
+        dir.child("2024-01-02T03:04:06+01:00").touch()?;
+        assert!(index.has_identical("/home/me/.bashrc".as_ref(), "ba7816bf"));
+        assert!(!index.has_identical("/home/me/.bashrc".as_ref(), "cb00753f"));
+
+        let loaded = Index::load(&dir)?;
+        assert_eq!(loaded, index);
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            vec![&record("2024-01-02T03:04:06+01:00")]
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn archive_run_archives_the_backups_of_the_run() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let run = "20240102T030400";
+
+        assert_eq!(archive_run(&dir, run)?, None);
+
+        let backup = dir.child(format!("{}/home/me/.bashrc/2024-01-02T03:04:05+01:00", run));
+        backup.write_str("Contents of the backup.")?;
+        let symlink = dir.child(format!("{}/home/me/.vimrc/2024-01-02T03:04:06+01:00", run));
+        symlink.parent().map(fs::create_dir_all).transpose()?;
+        crate::platform::symlink("/does/not/exist", &symlink)?;
+
+        let archive = archive_run(&dir, run)?;
+        assert_eq!(
+            archive,
+            Some(dir.child(format!("{}.tar.gz", run)).to_path_buf())
+        );
+        dir.child(run).assert(predicates::path::missing());
+
+        let mut entries = vec![];
+        let file = File::open(archive.unwrap())?;
+        for entry in tar::Archive::new(flate2::read::GzDecoder::new(file)).entries()? {
+            let entry = entry?;
+            entries.push((entry.path()?.into_owned(), entry.header().entry_type()));
+        }
+        assert!(entries.contains(&(
+            backup.strip_prefix(&dir)?.to_path_buf(),
+            tar::EntryType::Regular
+        )));
+        assert!(entries.contains(&(
+            symlink.strip_prefix(&dir)?.to_path_buf(),
+            tar::EntryType::Symlink
+        )));
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn prune_keeps_the_most_recent_backups() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let recent = Local::now().fixed_offset();
+        let old = recent - Duration::days(10);
+        let older = recent - Duration::days(20);
+        let name = |time: DateTime<FixedOffset>| time.format(TIME_FORMAT).to_string();
+        for time in [recent, old, older] {
+            dir.child(format!("{}/home/me/a.conf/{}", name(time), name(time)))
+                .touch()?;
+            dir.child(format!("home/me/.config/{}/b", time.to_rfc3339()))
+                .touch()?;
+            // Made by an earlier version.
+            dir.child(format!("c_backup_{}.conf", time.to_rfc3339()))
+                .touch()?;
+        }
+        dir.child("not_a_backup").touch()?;
+        dir.child(format!("{}.tar.gz", name(older))).touch()?;
+
+        assert_eq!(prune(&dir, None, None)?, 0);
+        assert_eq!(prune(&dir, Some(2), None)?, 3);
+        // The directory of the run is removed once empty.
+        dir.child(name(older)).assert(predicates::path::missing());
+        dir.child(format!("{}/home/me/a.conf/{}", name(old), name(old)))
+            .assert(predicates::path::exists());
+        dir.child(format!("home/me/.config/{}", older.to_rfc3339()))
+            .assert(predicates::path::missing());
+        dir.child(format!("c_backup_{}.conf", older.to_rfc3339()))
+            .assert(predicates::path::missing());
+        dir.child(format!("{}.tar.gz", name(older)))
+            .assert(predicates::path::exists());
+        assert_eq!(prune(&dir, None, Some(5))?, 4);
+        dir.child(format!("{}.tar.gz", name(older)))
+            .assert(predicates::path::missing());
+        dir.child(format!("home/me/.config/{}/b", recent.to_rfc3339()))
+            .assert(predicates::path::exists());
+        dir.child(format!("c_backup_{}.conf", recent.to_rfc3339()))
+            .assert(predicates::path::exists());
+        dir.child("not_a_backup").assert(predicates::path::exists());
+
+        dir.close()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+//! The cache of the symlink-specification files processed by the previous
+//! runs, so that the ones unchanged since can be skipped (see
+//! [`crate::cli::Cli::changed_only`]).
+
+use crate::engine::{RunStatus, Summary};
+use crate::error::Context;
+use crate::params::Params;
+use crate::template;
+use crate::utils;
+use crate::Error;
+use clap::crate_name;
+use directories::ProjectDirs;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The symlink-specification files processed by the previous runs, each one
+/// as it was when last processed, and with what has been done for it then.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::cache::{Cache, CachedFile, FileState};
+/// use mksls::engine::Summary;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = Cache::default_path()?;
+/// let mut cache = Cache::load(&path)?;
+/// let sls = Path::new("/home/me/dotfiles/sls");
+/// if !cache.is_unchanged(sls, "") {
+///     // Process it, then:
+///     cache.add(
+///         sls,
+///         CachedFile {
+///             files: vec![FileState::of(sls)?],
+///             params: String::new(),
+///             outcome: Summary::default(),
+///         },
+///     );
+/// }
+/// cache.save(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Stored", into = "Stored")]
+pub struct Cache {
+    /// The (canonical) symlink-specification files processed.
+    files: BTreeMap<PathBuf, CachedFile>,
+}
+
+/// How [`Cache`] is written.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Stored {
+    sls: Vec<StoredSls>,
+}
+
+/// A symlink-specification file in [`Stored::sls`].
+#[derive(Serialize, Deserialize)]
+struct StoredSls {
+    #[serde(with = "crate::path_repr")]
+    path: PathBuf,
+    #[serde(flatten)]
+    cached: CachedFile,
+}
+
+impl From<Stored> for Cache {
+    fn from(stored: Stored) -> Self {
+        let files = stored
+            .sls
+            .into_iter()
+            .map(|stored| (stored.path, stored.cached))
+            .collect();
+
+        Cache { files }
+    }
+}
+
+impl From<Cache> for Stored {
+    fn from(cache: Cache) -> Self {
+        let sls = cache
+            .files
+            .into_iter()
+            .map(|(path, cached)| StoredSls { path, cached })
+            .collect();
+
+        Stored { sls }
+    }
+}
+
+/// A symlink-specification file, as processed by the last run processing
+/// it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedFile {
+    /// The file and the ones it includes, as they were.
+    pub files: Vec<FileState>,
+    /// The fingerprint of the parameters of the run (see [`fingerprint`]).
+    #[serde(default)]
+    pub params: String,
+    /// What has been done for the links specified in the file (and in the
+    /// ones it includes).
+    pub outcome: Summary,
+}
+
+/// A file as it was at some point.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    /// Path to the file.
+    #[serde(with = "crate::path_repr")]
+    pub path: PathBuf,
+    /// Size of the file, in bytes.
+    pub size: u64,
+    /// Last modification time of the file, if supported by the platform.
+    pub modified: Option<SystemTime>,
+    /// SHA-256 hash of the contents of the file, in hexadecimal.
+    pub hash: String,
+}
+
+impl FileState {
+    /// Returns the current state of the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file can't be read.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+
+        Ok(FileState {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            hash: utils::hash_file(path)?,
+        })
+    }
+
+    /// Whether the file is still in this state.
+    ///
+    /// The contents are only hashed to be compared when the size is the same
+    /// but the modification time is not.
+    pub fn is_current(&self) -> bool {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return false;
+        };
+        if metadata.len() != self.size {
+            return false;
+        }
+        if self.modified.is_some() && metadata.modified().ok() == self.modified {
+            return true;
+        }
+        utils::hash_file(&self.path).is_ok_and(|hash| hash == self.hash)
+    }
+}
+
+impl Cache {
+    /// Returns the path of the cache in the data directory of the app.
+    ///
+    /// # Errors
+    ///
+    /// Fails when no home directory is found for the current user.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn default_path() -> crate::Result<PathBuf> {
+        let project = ProjectDirs::from("rs", "", crate_name!()).ok_or_else(|| Error::Io {
+            message: String::from(
+                "Failed to find the data directory where to keep the cache of the symlink-specification files.",
+            ),
+            source: io::ErrorKind::NotFound.into(),
+        })?;
+
+        Ok(project.data_dir().join("cache.toml"))
+    }
+
+    /// Loads the cache at `path`, or an empty one if there is no file at
+    /// `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file at `path` can't be read or is not valid.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read the cache {}.", path.display()))?;
+        toml::from_str(&contents)
+            .parse_context(|| format!("Failed to parse the cache {}.", path.display()))
+    }
+
+    /// Saves the cache at `path`, creating the parent directories if needed.
+    ///
+    /// The file is replaced at once, so that it is never left half-written.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file can't be written.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).io_context(|| {
+                format!(
+                    "Failed to create directory {} to save the cache in it.",
+                    parent.display()
+                )
+            })?;
+        }
+        let contents = toml::to_string(self).parse_context(|| {
+            format!(
+                "Failed to serialize the cache to save it at {}.",
+                path.display()
+            )
+        })?;
+        utils::write_atomic(path, contents)
+            .io_context(|| format!("Failed to write the cache {}.", path.display()))
+    }
+
+    /// Adds the symlink-specification file `sls` to the cache, as `cached`,
+    /// replacing what was cached for it.
+    pub fn add(&mut self, sls: &Path, cached: CachedFile) {
+        self.files.insert(sls.to_path_buf(), cached);
+    }
+
+    /// Removes the symlink-specification file `sls` from the cache.
+    pub fn remove(&mut self, sls: &Path) {
+        self.files.remove(sls);
+    }
+
+    /// Whether the symlink-specification file `sls` (and the ones it
+    /// includes) are unchanged since the last run processing it, with the
+    /// parameters of fingerprint `params` (see [`fingerprint`]), and every
+    /// link it specifies has been made then.
+    pub fn is_unchanged(&self, sls: &Path, params: &str) -> bool {
+        self.files.get(sls).is_some_and(|cached| {
+            cached.params == params
+                && cached.outcome.status() == RunStatus::Success
+                && cached.outcome.deferred == 0
+                && cached.files.iter().all(FileState::is_current)
+        })
+    }
+}
+
+/// Returns the fingerprint of the parameters of a run changing what is done
+/// for the links specified, whatever sets them (e.g. the variables, the
+/// kind of links made, or the profile), so that a file processed with other
+/// ones is not skipped.
+///
+/// The built-in variables (see [`template::BUILTIN_VARS`]) are part of it,
+/// as the paths and templates can reference them.
+pub fn fingerprint(params: &Params) -> String {
+    let vars: BTreeMap<_, _> = params.vars.iter().collect();
+    let cli_vars: BTreeMap<_, _> = params.cli_vars.iter().collect();
+    let builtin_vars: Vec<_> = template::BUILTIN_VARS
+        .iter()
+        .map(|name| template::builtin_var(name))
+        .collect();
+    let policy: Vec<_> = params
+        .policy
+        .iter()
+        .map(|(pattern, action)| (pattern.as_str(), action))
+        .collect();
+    let protected: Vec<_> = params.protected.iter().map(Pattern::as_str).collect();
+    let effective = format!(
+        "{:?}",
+        (
+            (vars, cli_vars, builtin_vars),
+            (params.operation, params.relative, params.exact_targets),
+            (params.mkdirs, params.allow_missing_targets),
+            (params.relink, params.replace_broken, &params.backup_dir),
+            (
+                params.always_skip,
+                params.always_backup,
+                params.always_overwrite,
+                params.always_adopt
+            ),
+            (policy, protected),
+        )
+    );
+
+    format!("{:x}", Sha256::digest(effective))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn changed_files_are_told_apart() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data/cache.toml");
+        let sls = dir.child("sls");
+        sls.write_str("a /home/a\n")?;
+        let included = dir.child("included");
+        included.write_str("b /home/b\n")?;
+        let cached = || -> io::Result<CachedFile> {
+            Ok(CachedFile {
+                files: vec![FileState::of(&sls)?, FileState::of(&included)?],
+                params: String::from("params"),
+                outcome: Summary {
+                    created: 2,
+                    ..Summary::default()
+                },
+            })
+        };
+
+        let mut cache = Cache::load(&path)?;
+        assert!(!cache.is_unchanged(&sls, "params"));
+        cache.add(&sls, cached()?);
+        cache.save(&path)?;
+        let mut cache = Cache::load(&path)?;
+        assert!(cache.is_unchanged(&sls, "params"));
+        assert!(!cache.is_unchanged(&sls, "other params"));
+
+        included.write_str("changed /home/c\n")?;
+        assert!(!cache.is_unchanged(&sls, "params"));
+
+        cache.add(
+            &sls,
+            CachedFile {
+                outcome: Summary {
+                    skipped: 1,
+                    ..Summary::default()
+                },
+                ..cached()?
+            },
+        );
+        assert!(!cache.is_unchanged(&sls, "params"));
+
+        cache.remove(&sls);
+        assert_eq!(cache, Cache::default());
+
+        Ok(())
+    }
+}
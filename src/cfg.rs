@@ -1,12 +1,85 @@
 //! Everything related to the app's configuration file.
 
-use clap::crate_name;
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use crate::error::Context;
+use crate::line;
+use crate::log::LogLevel;
+use crate::platform;
+use crate::prompt::NoninteractiveAction;
+use crate::Error;
+use clap::{crate_name, ValueEnum};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{self, Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the local configuration file, looked for in the directory to
+/// scan (see [`Config::with_local`]).
+pub const LOCAL_FILENAME: &str = ".mksls.toml";
+
+/// The entries of the configuration file holding a single value (or a list of
+/// strings), i.e. the ones that can be given by an environment variable (see
+/// [`Config::with_env`]) or set with [`Config::with_value`].
+pub const VALUE_ENTRIES: [&str; 27] = [
+    "default_dir",
+    "filename",
+    "extension",
+    "exclude",
+    "follow_links",
+    "sorted",
+    "strict",
+    "jobs",
+    "transactional",
+    "keep_going",
+    "mkdirs",
+    "allow_missing_targets",
+    "relative",
+    "relink",
+    "replace_broken",
+    "exact_targets",
+    "log_file",
+    "log_level",
+    "noninteractive_action",
+    "backup_dir",
+    "max_backups_per_link",
+    "max_age_days",
+    "archive_backups",
+    "rename_suffix",
+    "always_skip",
+    "always_backup",
+    "always_adopt",
+];
+
+/// The entries of the configuration file holding a table or a list of
+/// tables, besides the ones of [`VALUE_ENTRIES`].
+pub const TABLE_ENTRIES: [&str; 5] = ["vars", "policy", "protected", "hooks", "profiles"];
+
+/// The version of the format of the configuration file written by this
+/// version of the app (see [`Config::version`]).
+pub const CONFIG_VERSION: u32 = 1;
+
+/// The migrations of the configuration file, the one at index `i` upgrading
+/// the entries of a file of version `i` to version `i + 1`.
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize] = [
+    // Files written before versioning (version 0) have the same entries as
+    // the ones of version 1.
+    |_| {},
+];
+
+/// The prefix of the environment variables overriding the entries of the
+/// configuration file (see [`Config::with_env`]).
+pub const ENV_PREFIX: &str = "MKSLS_";
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 /// Defines the configuration file entries.
-/// It is used with [`confy::load()`].
+/// It is used with [`confy::load()`], or [`Config::load`] for a file at
+/// another location.
+///
+/// Entries missing from the configuration file take their default value.
+/// Unknown entries are rejected (see [`Config::load`] for helpful errors).
 ///
 /// # Examples
 ///
@@ -22,30 +95,1151 @@ use std::path::PathBuf;
 /// # }
 /// ```
 pub struct Config {
+    /// The version of the format of the file (see [`CONFIG_VERSION`]).
+    ///
+    /// Files without it are of version 0, i.e. written before versioning.
+    /// Files of an older version are migrated when loaded (see
+    /// [`Config::load`]), so that they don't fail to deserialize after
+    /// entries are renamed or reshaped.
+    pub version: u32,
+
+    /// The directory in which to scan for files specifying symlinks when none
+    /// is given (see [`crate::cli::Cli::dir`]), e.g. a dotfiles repository.
+    ///
+    /// It should be absolute, or start with `~` (expanded to the home
+    /// directory).
+    pub default_dir: Option<PathBuf>,
+
     /// Same as [`crate::cli::Cli::filename`].
-    pub filename: String,
+    ///
+    /// Can be given as a single string or as an array of strings.
+    #[serde(deserialize_with = "one_or_many")]
+    pub filename: Vec<String>,
+
+    /// Same as [`crate::cli::Cli::extension`].
+    ///
+    /// Can be given as a single string or as an array of strings.
+    #[serde(deserialize_with = "one_or_many")]
+    pub extension: Vec<String>,
+
+    /// Same as [`crate::cli::Cli::exclude`].
+    ///
+    /// Can be given as a single string or as an array of strings.
+    #[serde(deserialize_with = "one_or_many")]
+    pub exclude: Vec<String>,
+
+    /// Same as [`crate::cli::Cli::follow_links`].
+    pub follow_links: bool,
+
+    /// Same as [`crate::cli::Cli::sorted`].
+    pub sorted: bool,
+
+    /// Same as [`crate::cli::Cli::strict`].
+    pub strict: bool,
+
+    /// Same as [`crate::cli::Cli::jobs`].
+    pub jobs: usize,
+
+    /// Same as [`crate::cli::Cli::transactional`].
+    pub transactional: bool,
+
+    /// Same as [`crate::cli::Cli::keep_going`].
+    pub keep_going: bool,
+
+    /// Same as [`crate::cli::Cli::mkdirs`].
+    pub mkdirs: bool,
+
+    /// Same as [`crate::cli::Cli::allow_missing_targets`].
+    pub allow_missing_targets: bool,
+
+    /// Same as [`crate::cli::Cli::relative`].
+    pub relative: bool,
+
+    /// Same as [`crate::cli::Cli::relink`].
+    pub relink: bool,
+
+    /// Same as [`crate::cli::Cli::replace_broken`].
+    pub replace_broken: bool,
+
+    /// Same as [`crate::cli::Cli::exact_targets`].
+    pub exact_targets: bool,
+
+    /// Same as [`crate::cli::Cli::log_file`].
+    pub log_file: Option<PathBuf>,
+
+    /// Same as [`crate::cli::Cli::log_level`].
+    pub log_level: LogLevel,
+
+    /// Same as [`crate::cli::Cli::noninteractive_action`].
+    pub noninteractive_action: NoninteractiveAction,
 
     /// Same as [`crate::cli::Cli::backup_dir`].
     pub backup_dir: PathBuf,
 
+    /// The maximum number of backups to keep for a given file when pruning
+    /// backups (see [`crate::backups::prune`]), the most recent ones being
+    /// kept. No limit if not set.
+    pub max_backups_per_link: Option<usize>,
+
+    /// The maximum age, in days, of the backups to keep when pruning backups
+    /// (see [`crate::backups::prune`]). No limit if not set.
+    pub max_age_days: Option<u64>,
+
+    /// Whether to archive the backups of each run into a single
+    /// `<run start time>.tar.gz` in the backup directory, at the end of the
+    /// run, to keep backups small.
+    pub archive_backups: bool,
+
+    /// What to append to the name of a conflicting file renamed in place, when
+    /// choosing to rename it at the prompt (`.orig` by default).
+    pub rename_suffix: String,
+
     /// Same as [`crate::cli::Cli::always_skip`].
     pub always_skip: bool,
 
     /// Same as [`crate::cli::Cli::always_backup`].
     pub always_backup: bool,
+
+    /// Same as [`crate::cli::Cli::always_adopt`].
+    pub always_adopt: bool,
+
+    /// User-defined variables, usable in paths (as `{NAME}`) and in template
+    /// files.
+    pub vars: HashMap<String, String>,
+
+    /// Actions to take, without prompting, for the links conflicting with an
+    /// existing file, depending on their path.
+    ///
+    /// The first policy whose pattern matches the path of the link applies.
+    pub policy: Vec<Policy>,
+
+    /// Glob patterns of paths never to be replaced by links, whatever the
+    /// options, policies or answers to the prompts: a link conflicting with
+    /// an existing file matching one of them fails instead.
+    pub protected: Vec<String>,
+
+    /// Shell commands to run at some points of a run.
+    pub hooks: Hooks,
+
+    /// Named sets of entries overriding the ones above, selected with
+    /// [`crate::cli::Cli::profile`] (see [`Config::with_profile`]).
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Shell commands to run at some points of a run (see [`crate::hook::run`]).
+///
+/// In the configuration file, it is written as:
+///
+/// ```toml
+/// [hooks]
+/// post_link = '[ "$LINK" != ~/.ssh/config ] || chmod 600 "$LINK"'
+/// ```
+///
+/// Every hook gets the environment variable `ACTION`, set to the name of the
+/// hook for `pre_run` and `post_run`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Run before the symlink-specification files are processed.
+    pub pre_run: Option<String>,
+    /// Run once all the symlink-specification files have been processed
+    /// successfully.
+    pub post_run: Option<String>,
+    /// Run after each link made (whether there was a conflict or not), with
+    /// `LINK` and `TARGET` set to the paths of the link and its target, and
+    /// `ACTION` to what has been done: `created`, `backed_up`, `overwritten`,
+    /// `renamed`, `relinked`, `repaired` or `adopted`.
+    pub post_link: Option<String>,
+}
+
+/// An action to take for the links conflicting with an existing file whose
+/// path matches a pattern.
+///
+/// In the configuration file, it is written as:
+///
+/// ```toml
+/// policy = [
+///     { pattern = "~/.cache/**", action = "overwrite" },
+///     { pattern = "~/.ssh/**", action = "skip" },
+/// ]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// A glob pattern, matched against the paths of the links.
+    ///
+    /// It should be absolute, or start with `~` (expanded to the home
+    /// directory). `*` doesn't match `/`, but `**` matches any number of
+    /// directories.
+    pub pattern: String,
+    /// The action to take.
+    pub action: PolicyAction,
+}
+
+/// Entries of the configuration file overriding the top-level ones when the
+/// profile is selected (see [`Config::with_profile`]).
+///
+/// In the configuration file, it is written as:
+///
+/// ```toml
+/// [profiles.work]
+/// filename = "work.sls"
+/// backup_dir = "/home/me/work/backups"
+/// policy = [{ pattern = "~/.ssh/**", action = "skip" }]
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Same as [`Config::filename`].
+    #[serde(deserialize_with = "some_one_or_many")]
+    pub filename: Option<Vec<String>>,
+    /// Same as [`Config::exclude`].
+    #[serde(deserialize_with = "some_one_or_many")]
+    pub exclude: Option<Vec<String>>,
+    /// Same as [`Config::backup_dir`].
+    pub backup_dir: Option<PathBuf>,
+    /// Same as [`Config::policy`].
+    pub policy: Option<Vec<Policy>>,
+}
+
+/// The entries of a local configuration file, shipped in the directory to
+/// scan (e.g. a dotfiles repository), overriding the ones of the
+/// configuration file of the user (see [`Config::with_local`]).
+///
+/// It is written as:
+///
+/// ```toml
+/// filename = "*.sls"
+/// action = "backup"
+/// relative = true
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalConfig {
+    /// Same as [`Config::filename`].
+    #[serde(deserialize_with = "some_one_or_many")]
+    pub filename: Option<Vec<String>>,
+    /// Same as [`Config::exclude`].
+    #[serde(deserialize_with = "some_one_or_many")]
+    pub exclude: Option<Vec<String>>,
+    /// The action to take, without prompting, for the links conflicting with
+    /// an existing file, i.e. what [`Config::always_skip`],
+    /// [`Config::always_backup`], [`Config::always_adopt`] and
+    /// [`Config::relink`] set. It can't be [`PolicyAction::Overwrite`].
+    pub action: Option<PolicyAction>,
+    /// Same as [`Config::relative`].
+    pub relative: Option<bool>,
+    /// Same as [`Config::mkdirs`].
+    pub mkdirs: Option<bool>,
+}
+
+/// The actions a [`Policy`] can specify, named like their prompt options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    /// Don't make the link and move on.
+    Skip,
+    /// Backup the existing file, then make the link.
+    Backup,
+    /// Make the link without backup, overwriting the existing file.
+    Overwrite,
+    /// Move the existing file to the target path, then make the link.
+    Adopt,
+    /// Replace the existing symlink by the link. Only applies when the
+    /// existing file is a symlink, pointing somewhere else.
+    Relink,
 }
 
 impl std::default::Default for Config {
     fn default() -> Self {
         Self {
-            filename: String::from("sls"),
-            backup_dir: confy::get_configuration_file_path(crate_name!(), crate_name!())
-                .unwrap()
-                .parent()
-                .unwrap()
-                .join("backups/"),
+            version: CONFIG_VERSION,
+            default_dir: None,
+            filename: vec![String::from("sls")],
+            extension: vec![],
+            exclude: vec![],
+            follow_links: false,
+            sorted: false,
+            strict: false,
+            jobs: 1,
+            transactional: false,
+            keep_going: false,
+            mkdirs: false,
+            allow_missing_targets: false,
+            relative: false,
+            relink: false,
+            replace_broken: false,
+            exact_targets: false,
+            log_file: None,
+            log_level: LogLevel::default(),
+            noninteractive_action: NoninteractiveAction::default(),
+            backup_dir: default_backup_dir(
+                &confy::get_configuration_file_path(crate_name!(), crate_name!()).unwrap(),
+            ),
+            max_backups_per_link: None,
+            max_age_days: None,
+            archive_backups: false,
+            rename_suffix: String::from(".orig"),
             always_skip: false,
             always_backup: false,
+            always_adopt: false,
+            vars: HashMap::new(),
+            policy: vec![],
+            protected: vec![],
+            hooks: Hooks::default(),
+            profiles: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the path of the configuration file at the location chosen by
+    /// [`confy`].
+    ///
+    /// # Errors
+    ///
+    /// Fails when no home directory is found for the current user.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn default_path() -> crate::Result<PathBuf> {
+        confy::get_configuration_file_path(crate_name!(), crate_name!())
+            .map_err(io::Error::other)
+            .io_context(|| "Failed to find the directory where to keep the configuration file.")
+    }
+
+    /// Returns the path of the system-wide configuration file, on which the
+    /// one of the user is overlaid (see [`Config::with_system`]):
+    /// `/etc/mksls/config.toml` (or `%PROGRAMDATA%\mksls\config.toml` on
+    /// Windows).
+    pub fn system_path() -> PathBuf {
+        platform::system_config_dir()
+            .join(crate_name!())
+            .join("config.toml")
+    }
+
+    /// Loads the configuration file at `path`, instead of the one at the
+    /// location chosen by [`confy`] (see [`crate::cli::Cli::config`]).
+    ///
+    /// Unlike [`confy::load()`], no file is written if there is none at `path`.
+    /// When `backup_dir` is missing from the file, it defaults to the
+    /// `backups/` directory next to it.
+    ///
+    /// A file of an older version (see [`Config::version`]) is migrated to
+    /// the current one, in memory (the file is left as is).
+    ///
+    /// Errors tell which entry is unknown (suggesting the closest valid one)
+    /// or has a value of the wrong type, and where the file is.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - The file at `path` can't be read.
+    /// - The file at `path` is of a version newer than [`CONFIG_VERSION`].
+    /// - The file at `path` is not a valid configuration file, e.g. it has
+    ///   an unknown entry or an entry with a value of the wrong type.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mksls::cfg::Config;
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cfg = Config::load(Path::new("/home/me/.dotfiles/mksls.toml"))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read the configuration file {}.", path.display()))?;
+        Config::from_toml(&contents, path)
+    }
+
+    /// Parses the `contents` of the configuration file at `path` (see
+    /// [`Config::load`]).
+    fn from_toml(contents: &str, path: &Path) -> crate::Result<Self> {
+        let message = || format!("Failed to parse the configuration file {}.", path.display());
+        let table: toml::Table = toml::from_str(contents).parse_context(message)?;
+        let has_backup_dir = table.contains_key("backup_dir");
+        let mut cfg = Config::from_table(table, path)?;
+        if !has_backup_dir {
+            cfg.backup_dir = default_backup_dir(path);
+        }
+
+        Ok(cfg)
+    }
+
+    /// Deserializes the `table` of the configuration file at `path`, once
+    /// migrated, telling which entry is wrong, if any.
+    fn from_table(table: toml::Table, path: &Path) -> crate::Result<Self> {
+        let table = migrate(table, path)?;
+        if let Some(entry) = table.keys().find(|entry| !is_entry(entry)) {
+            let closest = entries()
+                .min_by_key(|valid| strsim::levenshtein(entry, valid))
+                .filter(|valid| strsim::levenshtein(entry, valid) <= entry.len() / 3 + 1);
+            return Err(invalid(format!(
+                "Got an unknown entry {} in the configuration file {}{}. The valid entries are: {}.",
+                entry,
+                path.display(),
+                closest
+                    .map(|valid| format!(" (did you mean {}?)", valid))
+                    .unwrap_or_default(),
+                entries().collect::<Vec<_>>().join(", ")
+            )));
+        }
+
+        table.clone().try_into().map_err(|err| {
+            // The first entry failing on its own is the culprit.
+            let message = match table.iter().find(|(entry, value)| {
+                toml::Table::from_iter([((*entry).clone(), (*value).clone())])
+                    .try_into::<Config>()
+                    .is_err()
+            }) {
+                Some((entry, _)) => format!(
+                    "Got an invalid value for {} in the configuration file {}.",
+                    entry,
+                    path.display()
+                ),
+                None => format!("Failed to parse the configuration file {}.", path.display()),
+            };
+            Error::Parse {
+                message,
+                source: Box::new(err),
+            }
+        })
+    }
+
+    /// Loads the configuration file of the user at `path` (or the one at the
+    /// location chosen by [`confy`] if `None`), overlaid on the system-wide
+    /// configuration file at `system` (see [`Config::system_path`]), if there
+    /// is one, so that administrators can set defaults (e.g. a backup
+    /// directory on a shared volume, or protected paths) that users can still
+    /// override.
+    ///
+    /// The entries set in the file of the user replace the ones of the
+    /// system-wide file, whole (e.g. `protected` replaces the list of the
+    /// system-wide file, instead of extending it), even when set to their
+    /// default value. Both files are migrated first (see [`Config::load`]).
+    ///
+    /// Unlike [`confy::load()`], no file is written: when `path` is `None` and
+    /// there is no file at the location chosen by [`confy`], only the
+    /// system-wide file is read.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - No home directory is found for the current user, if `path` is
+    ///   `None`.
+    /// - One of the files can't be read (the one at `path` only if `path` is
+    ///   given), or is not a valid configuration file.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mksls::cfg::Config;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cfg = Config::load_with_system(None, &Config::system_path())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_with_system(path: Option<&Path>, system: &Path) -> crate::Result<Self> {
+        let (path, user) = match path {
+            Some(path) => (path.to_path_buf(), Some(read_table(path)?)),
+            None => {
+                let path = Config::default_path()?;
+                let user = path.exists().then(|| read_table(&path)).transpose()?;
+                (path, user)
+            }
+        };
+        let mut table = match system.exists() {
+            true => read_table(system)?,
+            false => toml::Table::new(),
+        };
+        table.extend(user.unwrap_or_default());
+        let has_backup_dir = table.contains_key("backup_dir");
+
+        let mut cfg = Config::from_table(table, &path)?;
+        if !has_backup_dir {
+            cfg.backup_dir = default_backup_dir(&path);
+        }
+
+        Ok(cfg)
+    }
+
+    /// Returns the configuration with the entries set in the profile `name`
+    /// replacing the top-level ones.
+    ///
+    /// # Errors
+    ///
+    /// Fails when there is no profile `name` in the configuration.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn with_profile(mut self, name: &str) -> crate::Result<Self> {
+        let Some(profile) = self.profiles.remove(name) else {
+            let mut names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            return Err(invalid(format!(
+                "Got an unknown profile: {} (the profiles of the configuration file are: {}).",
+                name,
+                if names.is_empty() {
+                    String::from("none")
+                } else {
+                    names.join(", ")
+                }
+            )));
+        };
+
+        if let Some(filename) = profile.filename {
+            self.filename = filename;
+        }
+        if let Some(exclude) = profile.exclude {
+            self.exclude = exclude;
+        }
+        if let Some(backup_dir) = profile.backup_dir {
+            self.backup_dir = backup_dir;
+        }
+        if let Some(policy) = profile.policy {
+            self.policy = policy;
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the configuration with the entries set in the local
+    /// configuration file of `dir` (see [`LocalConfig`]) replacing its own, if
+    /// `dir` is a directory containing a [`LOCAL_FILENAME`] file.
+    ///
+    /// Only the local configuration file directly within `dir` is read, not
+    /// the ones of its subdirectories.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - The local configuration file can't be read.
+    /// - The local configuration file is not a valid one.
+    /// - The local configuration file sets `overwrite` as action.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn with_local(self, dir: &Path) -> crate::Result<Self> {
+        let path = dir.join(LOCAL_FILENAME);
+        if !path.is_file() {
+            return Ok(self);
+        }
+
+        let contents = fs::read_to_string(&path).io_context(|| {
+            format!(
+                "Failed to read the local configuration file {}.",
+                path.display()
+            )
+        })?;
+        let local: LocalConfig = toml::from_str(&contents).parse_context(|| {
+            format!(
+                "Failed to parse the local configuration file {}.",
+                path.display()
+            )
+        })?;
+        self.with_local_config(local)
+    }
+
+    /// Returns the configuration with the entries set in `local` replacing its
+    /// own (see [`Config::with_local`]).
+    fn with_local_config(mut self, local: LocalConfig) -> crate::Result<Self> {
+        if let Some(filename) = local.filename {
+            self.filename = filename;
+        }
+        if let Some(exclude) = local.exclude {
+            self.exclude = exclude;
+        }
+        if let Some(action) = local.action {
+            if action == PolicyAction::Overwrite {
+                return Err(invalid(format!(
+                    "Got overwrite as action in the local configuration file {}, but overwriting without asking can only be asked for with --always-overwrite.",
+                    LOCAL_FILENAME
+                )));
+            }
+            self.always_skip = action == PolicyAction::Skip;
+            self.always_backup = action == PolicyAction::Backup;
+            self.always_adopt = action == PolicyAction::Adopt;
+            self.relink = action == PolicyAction::Relink;
+        }
+        if let Some(relative) = local.relative {
+            self.relative = relative;
+        }
+        if let Some(mkdirs) = local.mkdirs {
+            self.mkdirs = mkdirs;
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the configuration with the entries for which an environment
+    /// variable is set overridden by its value.
+    ///
+    /// The environment variable of an entry is its name in uppercase,
+    /// prefixed by [`ENV_PREFIX`] (e.g. `MKSLS_BACKUP_DIR` for `backup_dir`).
+    /// Booleans are given as `true`/`1` or `false`/`0`, and lists (`filename`,
+    /// `extension` and `exclude`) as comma-separated values. `vars`, `policy`,
+    /// `protected`, `hooks` and `profiles` can't be overridden.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the value of a variable is invalid for its entry.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mksls::cfg::Config;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let cfg = cfg.with_env()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_env(self) -> crate::Result<Self> {
+        self.with_vars(|name| env::var(name).ok())
+    }
+
+    /// Returns the configuration with `entry` set to `value`, given as for
+    /// [`Config::with_env`].
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - `entry` is not one of [`VALUE_ENTRIES`].
+    /// - `value` is invalid for `entry`.
+    /// - The resulting configuration is invalid (see [`Config::validate`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::cfg::Config;
+    ///
+    /// let cfg = Config::default().with_value("jobs", "4").unwrap();
+    /// assert_eq!(cfg.jobs, 4);
+    /// assert!(cfg.with_value("backup_dir", "backups").is_err());
+    /// ```
+    pub fn with_value(self, entry: &str, value: &str) -> crate::Result<Self> {
+        if !VALUE_ENTRIES.contains(&entry) {
+            return Err(invalid(format!(
+                "Got an unknown entry: {} (the entries that can be set are: {}).",
+                entry,
+                VALUE_ENTRIES.join(", ")
+            )));
+        }
+
+        let cfg = self.with_values(|other| {
+            (other == entry).then(|| (String::from(entry), String::from(value)))
+        })?;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    /// Checks that the configuration is valid, i.e. that `backup_dir` and
+    /// `default_dir` (once `~` is expanded) are absolute, that
+    /// `rename_suffix` is a non-empty suffix of a file name,
+    /// and that at most one of `always_skip`, `always_backup` and
+    /// `always_adopt` is true.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the configuration is invalid.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.backup_dir.is_relative() {
+            return Err(invalid(String::from("Got a relative path for backup_dir in the configuration file, but backup_dir should be absolute.")));
+        }
+
+        if self
+            .default_dir
+            .as_ref()
+            .is_some_and(|dir| line::expand_tilde(&dir.to_string_lossy()).is_relative())
+        {
+            return Err(invalid(String::from("Got a relative path for default_dir in the configuration file, but default_dir should be absolute (or start with ~).")));
+        }
+
+        if self.rename_suffix.is_empty() || self.rename_suffix.contains(['/', '\\']) {
+            return Err(invalid(format!("Got {:?} for rename_suffix in the configuration file, but rename_suffix should be non-empty and without path separators.", self.rename_suffix)));
+        }
+
+        // always_overwrite can only come from Cli, on purpose.
+        if [self.always_skip, self.always_backup, self.always_adopt]
+            .into_iter()
+            .filter(|b| *b)
+            .count()
+            > 1
+        {
+            return Err(invalid(String::from("Got more than one of always_skip, always_backup and always_adopt set to true in the configuration file, but only one of them can be true.")));
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`Config::with_env`], with `var` giving the value of the
+    /// variable of the given name, if set.
+    fn with_vars<F: Fn(&str) -> Option<String>>(self, var: F) -> crate::Result<Self> {
+        self.with_values(|entry| {
+            let name = format!("{}{}", ENV_PREFIX, entry.to_uppercase());
+            var(&name).map(|value| (format!("the environment variable {}", name), value))
+        })
+    }
+
+    /// Returns the configuration with the entries for which `var` gives a
+    /// value (along with where it comes from, for error messages) set to it.
+    fn with_values<F: Fn(&str) -> Option<(String, String)>>(
+        mut self,
+        var: F,
+    ) -> crate::Result<Self> {
+        if let Some((_, value)) = var("default_dir") {
+            self.default_dir = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = var("filename") {
+            self.filename = list(&value);
+        }
+        if let Some((_, value)) = var("extension") {
+            self.extension = list(&value);
+        }
+        if let Some((_, value)) = var("exclude") {
+            self.exclude = list(&value);
+        }
+        for (entry, field) in [
+            ("follow_links", &mut self.follow_links),
+            ("sorted", &mut self.sorted),
+            ("strict", &mut self.strict),
+            ("transactional", &mut self.transactional),
+            ("keep_going", &mut self.keep_going),
+            ("mkdirs", &mut self.mkdirs),
+            ("allow_missing_targets", &mut self.allow_missing_targets),
+            ("relative", &mut self.relative),
+            ("relink", &mut self.relink),
+            ("replace_broken", &mut self.replace_broken),
+            ("exact_targets", &mut self.exact_targets),
+            ("archive_backups", &mut self.archive_backups),
+            ("always_skip", &mut self.always_skip),
+            ("always_backup", &mut self.always_backup),
+            ("always_adopt", &mut self.always_adopt),
+        ] {
+            if let Some((name, value)) = var(entry) {
+                *field = match value.as_str() {
+                    "1" => true,
+                    "0" => false,
+                    _ => parse(&name, &value)?,
+                };
+            }
+        }
+        if let Some((name, value)) = var("jobs") {
+            self.jobs = parse(&name, &value)?;
+        }
+        if let Some((_, value)) = var("log_file") {
+            self.log_file = Some(PathBuf::from(value));
+        }
+        if let Some((name, value)) = var("log_level") {
+            self.log_level =
+                LogLevel::from_str(&value, true).params_context(|| invalid_value(&name, &value))?;
+        }
+        if let Some((name, value)) = var("noninteractive_action") {
+            self.noninteractive_action = NoninteractiveAction::from_str(&value, true)
+                .params_context(|| invalid_value(&name, &value))?;
+        }
+        if let Some((_, value)) = var("rename_suffix") {
+            self.rename_suffix = value;
+        }
+        if let Some((_, value)) = var("backup_dir") {
+            self.backup_dir = PathBuf::from(value);
+        }
+        if let Some((name, value)) = var("max_backups_per_link") {
+            self.max_backups_per_link = Some(parse(&name, &value)?);
+        }
+        if let Some((name, value)) = var("max_age_days") {
+            self.max_age_days = Some(parse(&name, &value)?);
+        }
+
+        Ok(self)
+    }
+}
+
+/// Returns the entries of the configuration file.
+fn entries() -> impl Iterator<Item = &'static str> {
+    ["version"]
+        .into_iter()
+        .chain(VALUE_ENTRIES)
+        .chain(TABLE_ENTRIES)
+}
+
+/// Whether `entry` is an entry of the configuration file.
+fn is_entry(entry: &str) -> bool {
+    entries().any(|valid| valid == entry)
+}
+
+/// Returns the `table` of the configuration file at `path` migrated to
+/// [`CONFIG_VERSION`] (see [`MIGRATIONS`]).
+fn migrate(mut table: toml::Table, path: &Path) -> crate::Result<toml::Table> {
+    let version = match table.get("version") {
+        None => 0,
+        Some(toml::Value::Integer(version)) => u32::try_from(*version).map_err(|_| {
+            invalid(format!(
+                "Got {} for version in the configuration file {}, but version should be positive.",
+                version,
+                path.display()
+            ))
+        })?,
+        Some(_) => {
+            return Err(invalid(format!(
+                "Got an invalid value for version in the configuration file {}, but version should be an integer.",
+                path.display()
+            )))
+        }
+    };
+    if version > CONFIG_VERSION {
+        return Err(invalid(format!(
+            "Got version {} for the configuration file {}, but this version of {} only reads up to version {}: upgrade it.",
+            version,
+            path.display(),
+            crate_name!(),
+            CONFIG_VERSION
+        )));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut table);
+    }
+    table.insert(
+        String::from("version"),
+        toml::Value::Integer(CONFIG_VERSION.into()),
+    );
+
+    Ok(table)
+}
+
+/// Returns the default backup directory when the configuration file is at
+/// `path`, i.e. the `backups/` directory next to it.
+///
+/// A relative `path` (e.g. `--config mksls.toml`) is made absolute first, so
+/// that the backups don't depend on the directory the app is run from.
+fn default_backup_dir(path: &Path) -> PathBuf {
+    let path = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    path.parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("backups/")
+}
+
+/// Reads the entries set in the configuration file at `path`, migrated to
+/// the current version (see [`Config::load`]).
+///
+/// # Errors
+///
+/// Fails when the file can't be read or is not a valid configuration file.
+///
+/// See [`crate::Error`] for the kinds of errors.
+fn read_table(path: &Path) -> crate::Result<toml::Table> {
+    let contents = fs::read_to_string(path)
+        .io_context(|| format!("Failed to read the configuration file {}.", path.display()))?;
+    let table: toml::Table = toml::from_str(&contents)
+        .parse_context(|| format!("Failed to parse the configuration file {}.", path.display()))?;
+    let table = migrate(table, path)?;
+    // Checked on its own, for the errors to tell which file is invalid.
+    Config::from_table(table.clone(), path)?;
+
+    Ok(table)
+}
+
+/// Returns the error telling that the configuration is invalid, because of
+/// `message`.
+fn invalid(message: String) -> Error {
+    Error::Params {
+        message,
+        source: None,
+    }
+}
+
+/// Splits the comma-separated `value` of an entry into a list.
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parses `value`, given for `name`.
+fn parse<T>(name: &str, value: &str) -> crate::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value.parse().params_context(|| invalid_value(name, value))
+}
+
+/// Returns the message telling that `value` is invalid for `name`.
+fn invalid_value(name: &str, value: &str) -> String {
+    format!("Got an invalid value for {}: {}", name, value)
+}
+
+/// Deserializes either a single string or a sequence of strings into a vector of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+/// Same as [`one_or_many`], for an optional entry.
+fn some_one_or_many<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    one_or_many(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn config_is_overlaid_on_the_system_one() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let system = dir.child("config.toml");
+        system.write_str(
+            "backup_dir = \"/shared/backups\"\nrelative = true\nprotected = [\"/etc/**\"]\n",
+        )?;
+
+        let user = dir.child("user.toml");
+        user.write_str("mkdirs = true\nprotected = [\"~/.ssh/**\"]\n")?;
+
+        let cfg = Config::load_with_system(Some(&user), &system)?;
+        assert_eq!(cfg.backup_dir, PathBuf::from("/shared/backups"));
+        assert!(cfg.relative);
+        assert!(cfg.mkdirs);
+        assert_eq!(cfg.protected, vec![String::from("~/.ssh/**")]);
+
+        // An entry set back to its default value still replaces the one of the
+        // system-wide file.
+        user.write_str("relative = false\n")?;
+        let cfg = Config::load_with_system(Some(&user), &system)?;
+        assert!(!cfg.relative);
+        assert_eq!(cfg.backup_dir, PathBuf::from("/shared/backups"));
+
+        let cfg = Config::load_with_system(Some(&user), &dir.child("missing.toml"))?;
+        assert!(!cfg.relative);
+        assert_eq!(cfg.backup_dir, dir.child("backups/").to_path_buf());
+
+        assert!(Config::load_with_system(Some(&dir.child("missing.toml")), &system).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn filename_can_be_one_or_many() {
+        let cfg: Config = toml::from_str("filename = \"sls\"").unwrap();
+        assert_eq!(cfg.filename, vec![String::from("sls")]);
+
+        let cfg: Config = toml::from_str("filename = [\"sls\", \"*.sls\"]").unwrap();
+        assert_eq!(
+            cfg.filename,
+            vec![String::from("sls"), String::from("*.sls")]
+        );
+    }
+
+    #[test]
+    fn policy_deserializes_from_inline_tables() {
+        let cfg: Config = toml::from_str(
+            r#"policy = [
+                { pattern = "~/.cache/**", action = "overwrite" },
+                { pattern = "~/.ssh/**", action = "skip" },
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.policy,
+            vec![
+                Policy {
+                    pattern: String::from("~/.cache/**"),
+                    action: PolicyAction::Overwrite,
+                },
+                Policy {
+                    pattern: String::from("~/.ssh/**"),
+                    action: PolicyAction::Skip,
+                },
+            ]
+        );
+
+        assert!(
+            toml::from_str::<Config>(r#"policy = [{ pattern = "/a", action = "delete" }]"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn config_errors_tell_which_entry_is_wrong() {
+        let path = Path::new("/dots/mksls.toml");
+
+        let err = Config::from_toml("alway_skip = true", path).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("unknown entry alway_skip"));
+        assert!(message.contains("/dots/mksls.toml"));
+        assert!(message.contains("did you mean always_skip?"));
+        assert!(message.contains("The valid entries are: version, default_dir, filename"));
+
+        let err = Config::from_toml("blah = 1", path).unwrap_err();
+        assert!(!err.to_string().contains("did you mean"));
+
+        let err = Config::from_toml("strict = true\nalways_skip = \"yes\"", path).unwrap_err();
+        assert!(matches!(err, Error::Parse { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Got an invalid value for always_skip in the configuration file /dots/mksls.toml."
+        );
+
+        assert!(Config::from_toml("[profiles.work]\nstrictt = true", path).is_err());
+    }
+
+    #[test]
+    fn old_config_files_are_migrated() {
+        let path = Path::new("/dots/mksls.toml");
+
+        let cfg = Config::from_toml("strict = true", path).unwrap();
+        assert_eq!(cfg.version, CONFIG_VERSION);
+        assert!(cfg.strict);
+
+        let cfg = Config::from_toml("version = 0\nstrict = true", path).unwrap();
+        assert_eq!(cfg.version, CONFIG_VERSION);
+
+        let err =
+            Config::from_toml(&format!("version = {}", CONFIG_VERSION + 1), path).unwrap_err();
+        assert!(err.to_string().contains("upgrade it"));
+        assert!(Config::from_toml("version = -1", path).is_err());
+    }
+
+    #[test]
+    fn backup_dir_defaults_to_next_to_the_config_file() {
+        let path = Path::new("/dots/mksls.toml");
+
+        let cfg = Config::from_toml("strict = true", path).unwrap();
+        assert!(cfg.strict);
+        assert_eq!(cfg.backup_dir, PathBuf::from("/dots/backups/"));
+
+        let cfg = Config::from_toml("backup_dir = \"/backups\"", path).unwrap();
+        assert_eq!(cfg.backup_dir, PathBuf::from("/backups"));
+
+        let cfg = Config::from_toml("strict = true", Path::new("mksls.toml")).unwrap();
+        assert_eq!(cfg.backup_dir, env::current_dir().unwrap().join("backups/"));
+
+        assert!(Config::from_toml("strict = \"yes\"", path).is_err());
+    }
+
+    #[test]
+    fn profile_overrides_entries() {
+        let cfg: Config = toml::from_str(
+            r#"
+            filename = "sls"
+            exclude = ".git"
+
+            [profiles.work]
+            filename = ["work.sls", "*.sls"]
+            backup_dir = "/work/backups"
+            policy = [{ pattern = "/a", action = "skip" }]
+            "#,
+        )
+        .unwrap();
+        let cfg = cfg.with_profile("work").unwrap();
+
+        assert_eq!(
+            cfg.filename,
+            vec![String::from("work.sls"), String::from("*.sls")]
+        );
+        assert_eq!(cfg.exclude, vec![String::from(".git")]);
+        assert_eq!(cfg.backup_dir, PathBuf::from("/work/backups"));
+        assert_eq!(
+            cfg.policy,
+            vec![Policy {
+                pattern: String::from("/a"),
+                action: PolicyAction::Skip,
+            }]
+        );
+        assert!(Config::default().with_profile("work").is_err());
+    }
+
+    #[test]
+    fn local_config_overrides_entries() {
+        let cfg = Config {
+            always_backup: true,
+            relink: true,
+            ..Config::default()
+        };
+        let local: LocalConfig =
+            toml::from_str("filename = \"*.sls\"\naction = \"skip\"\nrelative = true").unwrap();
+        let cfg = cfg.with_local_config(local).unwrap();
+
+        assert_eq!(cfg.filename, vec![String::from("*.sls")]);
+        assert!(cfg.always_skip);
+        assert!(!cfg.always_backup);
+        assert!(!cfg.relink);
+        assert!(cfg.relative);
+        assert!(!cfg.mkdirs);
+
+        let local: LocalConfig = toml::from_str("action = \"overwrite\"").unwrap();
+        assert!(Config::default().with_local_config(local).is_err());
+        assert!(toml::from_str::<LocalConfig>("backup_dir = \"/backups\"").is_err());
+    }
+
+    #[test]
+    fn env_vars_override_entries() {
+        let vars = HashMap::from([
+            ("MKSLS_FILENAME", "sls,*.sls"),
+            ("MKSLS_ALWAYS_SKIP", "1"),
+            ("MKSLS_STRICT", "true"),
+            ("MKSLS_JOBS", "4"),
+            ("MKSLS_LOG_LEVEL", "debug"),
+            ("MKSLS_BACKUP_DIR", "/tmp/backups"),
+            ("MKSLS_MAX_AGE_DAYS", "30"),
+        ]);
+        let cfg = Config::default()
+            .with_vars(|name| vars.get(name).map(|value| value.to_string()))
+            .unwrap();
+
+        assert_eq!(
+            cfg.filename,
+            vec![String::from("sls"), String::from("*.sls")]
+        );
+        assert!(cfg.always_skip);
+        assert!(cfg.strict);
+        assert!(!cfg.sorted);
+        assert_eq!(cfg.jobs, 4);
+        assert_eq!(cfg.log_level, LogLevel::Debug);
+        assert_eq!(cfg.backup_dir, PathBuf::from("/tmp/backups"));
+        assert_eq!(cfg.max_age_days, Some(30));
+        assert_eq!(cfg.max_backups_per_link, None);
+
+        for (name, value) in [("MKSLS_SORTED", "yes"), ("MKSLS_JOBS", "many")] {
+            assert!(Config::default()
+                .with_vars(|var| (var == name).then(|| value.to_string()))
+                .is_err());
         }
     }
 }
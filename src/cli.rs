@@ -1,16 +1,24 @@
 //! Everything related to the app's CLI.
 
-use clap::{crate_name, Parser};
-use crossterm::style::Stylize;
+use crate::log::LogLevel;
+use crate::operation::OperationKind;
+use crate::prompt::NoninteractiveAction;
+use crate::report::ReportFormat;
+use crate::style::{ColorChoice, Paint};
+use clap::{crate_name, ArgAction, Parser, Subcommand};
 use std::fmt::Debug;
 use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(version)]
+#[command(subcommand_negates_reqs = true)]
 #[clap(about = "Make symlinks specified in files.")]
 #[clap(long_about = "Make symlinks specified in files.
 
-This program makes the symlinks specified in files within DIR having the base FILENAME.
+This program makes the symlinks specified in files within DIR whose base matches one of the FILENAME patterns.
+If DIR is a file, only that file is processed, whatever its name.
+Paths listed in .slsignore files (or .gitignore files, within a git repository) are not scanned,
+with the same syntax as .gitignore files.
 A given file contains zero or more symlink specifications, where a symlink specification is a line with the following format:
     <TARGET_PATH> <SYMLINK_PATH>
 Notice the space in between.
@@ -19,7 +27,66 @@ There can be multiple spaces, but there needs to be at least one.
 If a path contains a space, wrap it in double quotes.
 For example, if <TARGET_PATH> contains a space, write this instead:
      \"<TARGET_PATH>\" <SYMLINK_PATH>
-If you have a double quote in one of the paths... Change it!
+A backslash makes the character following it part of the path as is, quoted or not,
+so double quotes, spaces and backslashes can be written as \\\", \\  and \\\\.
+A backslash ending a line (but not a comment) continues it on the next one, e.g. to split a long line:
+    <TARGET_PATH> \\
+        <SYMLINK_PATH>
+Lines starting with // or # are comments, and a # starting a word outside double quotes
+starts a comment until the end of the line:
+    <TARGET_PATH> <SYMLINK_PATH> # A comment.
+A path starting with ~ (or ~user) is relative to your home directory (or the one of user).
+The built-in variables {hostname}, {os}, {arch} and {user} can be used in paths, e.g. to pick
+a file per machine: /dotfiles/gitconfig.{hostname} ~/.gitconfig
+Other variables can be defined in the config file (vars table), and for the lines following
+a set directive, in the same file only (not in the files it includes), all overridden with --var:
+    set <NAME> <VALUE>
+A line referencing a variable that is not defined is invalid. Write {{ and }} for literal braces
+(e.g. {{name}} for a file named {name}). Other braces, e.g. in {a,b}, are taken as is.
+Environment variables can be used in paths, as $VAR or ${VAR}. Their values are taken as is, so that
+paths that are not valid UTF-8 (which lines can't contain) can be given through them.
+A relative target is relative to the directory containing the file it is written in.
+
+If <TARGET_PATH> is a glob pattern (e.g. /dotfiles/bin/*), one symlink is made per file matched,
+with the same name, inside the directory <SYMLINK_PATH>.
+
+A symlink specification prefixed by hard makes a hard link instead of a symlink:
+    hard <TARGET_PATH> <LINK_PATH>
+one prefixed by copy copies the target instead:
+    copy <SOURCE_PATH> <DEST_PATH>
+and one prefixed by template renders the target into the destination file instead:
+    template <TEMPLATE_PATH> <DEST_PATH>
+(the target can't be a directory then). Conflicts are handled the same way as for symlinks.
+Rendering a template replaces each {{ NAME }} by the value of the variable NAME, looked up in order
+among the variables defined in the config file (vars table), the built-in variables (hostname, os, arch and user),
+and the environment variables.
+
+A line can also be a directory specification, with the following format:
+    dir <TARGET_DIR> <LINK_DIR>
+In that case, instead of linking <TARGET_DIR> itself, one symlink is made per file within <TARGET_DIR>
+(recursively), mirroring its tree inside <LINK_DIR>. Intermediate directories are created as needed.
+
+Finally, a line can include another file, processed as if it had been found in DIR:
+    include <PATH>
+A file is processed only once, even if included multiple times. Include cycles are reported as errors.
+
+A line can be guarded so that it is processed only if a file exists, or if a program is found in PATH,
+e.g. so that the config of a tool that isn't installed doesn't make a dangling or useless link:
+    ifexists <PATH>
+    ifcmd <NAME>
+Such a guard applies to the next line (comments and empty lines aside), or, if followed by {, to every
+line up to the matching } (a block left open at the end of the file being invalid):
+    ifcmd tmux {
+        tmux.conf ~/.tmux.conf
+    }
+
+A file with a .toml extension (e.g. one included, or matching FILENAME) is read as TOML instead,
+each [[link]] entry being equivalent to a line:
+    include = [\"<PATH>\", ...]
+    [[link]]
+    kind = \"copy\" # Optional, one of symlink (default), hard, copy, template or dir.
+    target = \"<TARGET_PATH>\"
+    link = \"<LINK_PATH>\"
 
 By default, the program is interactive.
 If no file is found where a given symlink is about to be made, the symlink will be made.
@@ -33,7 +100,9 @@ However, if a file is found, you will be asked to choose between:
 However it can be made uninteractive by using one (and only one) of these options:
     --always-skip (equivalent to always selecting 's')
     --always-backup (equivalent to always selecting 'b')
-There is no --always-overwrite for you to not regret it.
+    --always-overwrite (equivalent to always selecting 'o')
+For you to not regret it, --always-overwrite asks for confirmation once at startup,
+unless --i-know-what-i-am-doing is also given.
 
 For each processed symlink specification, a line with the following format is printed:
     (<action>) <link> -> <target>
@@ -42,24 +111,128 @@ where <action> encodes what has been done for that symlink:
     d : Done. The symlink was successfully created.
     s : There was a conflict between the link and an existing file, and choose to [s]kip.
     b : There was a conflict between the link and an existing file, and choose to [b]ackup.
+    b= : Same as b, but the existing file was identical to a backup of it already made,
+         so it has been replaced without being backed up again.
     o : There was a conflict between the link and an existing file, and choose to [o]verwrite.
-(<link> and <target> are respectively the link and target of the symlink specification)")]
+    r : There was a conflict between the link and an existing symlink, and choose to [r]elink.
+    a : There was a conflict between the link and an existing file, and choose to [a]dopt.
+(<link> and <target> are respectively the link and target of the symlink specification)
+With --dry-run, nothing is changed, and these lines tell what would have been done.
+
+Once all files have been processed, a summary counting the symlinks per <action>,
+as well as the invalid lines encountered, is printed.
+
+The exit status is:
+    0 : Every symlink specified has been made, or already existed.
+    1 : An error occurred.
+    2 : Some symlinks conflicting with an existing file have been skipped.
+    3 : Some invalid lines have been encountered (takes precedence over 2).
+    4 : You quit before all the symlinks specified were processed (takes precedence over 2, 3 and 5).
+    5 : Some symlinks failed to be made, with --keep-going or as their path is protected (takes precedence over 2 and 3).
+
+The symlinks made (or already existing) are recorded in a manifest, so that
+`mksls prune DIR` can later remove the ones whose specification has since been deleted.
+The ones skipped are recorded as well, so that `mksls resolve DIR` can later make them again.")]
 // NOTE: The path of the config file depends on `confy`, which uses `directories`.
 // To keep up to date!
 #[command(after_help = format!("{}
 You can provide other default values for the options:
     --filename
+    --extension
+    --exclude
+    --follow-links
+    --sorted
+    --strict
+    --jobs
+    --transactional
+    --keep-going
+    --mkdirs
+    --allow-missing-targets
+    --relative
+    --relink
+    --replace-broken
+    --exact-targets
+    --log-file
+    --log-level
+    --noninteractive-action
     --backup-dir
     --always-skip
     --always-backup
+    --always-adopt
 in a TOML configuration file located at:
     (Linux) $XDG_CONFIG_HOME/<project_path> or .config/<project_path> if $XDG_CONFIG_HOME is not set
     (Mac) $HOME/Library/Application Support/<project_path>
+    (Windows) %APPDATA%\\mksls\\config\\mksls.toml
 where <project_path> is '{}/{}.toml'.
 
+It is overlaid on the system-wide configuration file /etc/mksls/config.toml (%PROGRAMDATA%\\mksls\\config.toml
+on Windows), if there is one: the entries set in the former replace the ones of the latter.
+
+The config file can also set the directory to scan when DIR is not given, e.g. your dotfiles repository:
+    default_dir = \"~/.dotfiles\"
+
+The config file can also define variables used in paths and when rendering templates, in a [vars] table.
+
+It can also define policies, i.e. actions to take without prompting for the links conflicting
+with an existing file, depending on their path. The first policy whose pattern matches applies:
+    policy = [
+        {{ pattern = \"~/.cache/**\", action = \"overwrite\" }},
+        {{ pattern = \"~/.ssh/**\", action = \"skip\" }},
+    ]
+where action is one of skip, backup, overwrite, adopt or relink.
+
+It can protect paths, that no link ever replaces (whatever the options, policies or answers to the
+prompts), the links conflicting with them failing instead (the run going on, even without --keep-going):
+    protected = [\"~/.ssh/id_*\", \"/etc/**\"]
+
+It can set how many backups to keep when running `mksls backups prune`:
+    max_backups_per_link : The maximum number of backups to keep per backed up file (the most recent ones).
+    max_age_days         : The maximum age of the backups to keep, in days.
+And it can archive the backups of each run into a single <run start time>.tar.gz in the backup directory,
+to keep backups small, with archive_backups = true.
+
+It can set what to append to the name of a conflicting file renamed in place at the prompt (re[n]ame),
+with rename_suffix (\".orig\" by default).
+
+It can set what to do when stdin is not a terminal (e.g. under cron or in CI),
+with noninteractive_action = \"skip\" or \"fail\" (the default), like --noninteractive-action.
+
+Finally, it can define hooks, i.e. shell commands to run at some points of a run, in a [hooks] table:
+    pre_run   : Before processing the files.
+    post_run  : After processing the files successfully.
+    post_link : After each link made, with $LINK and $TARGET set to the paths of the link and its target,
+                and $ACTION to one of created, backed_up, overwritten, renamed, relinked, repaired or adopted.
+
+It can also define profiles, selected with --profile NAME, each one overriding some of the values above:
+    [profiles.work]
+    filename = \"work.sls\"
+    backup_dir = \"/home/me/work/backups\"
+    policy = [{{ pattern = \"~/.ssh/**\", action = \"skip\" }}]
+where filename, exclude, backup_dir and policy can be set.
+
+A directory to scan can also ship its own settings in a .mksls.toml file, directly within it,
+overriding the ones of the config file (and of the profile), e.g.:
+    filename = \"*.sls\"
+    action = \"backup\"
+    relative = true
+where filename, exclude, action (skip, backup, adopt or relink), relative and mkdirs can be set.
+
+Each value of the config file (except vars, policy, protected, hooks and profiles) can be overridden by an environment variable
+named after it in uppercase, prefixed with MKSLS_ (e.g. MKSLS_BACKUP_DIR, MKSLS_ALWAYS_SKIP=true).
+Lists are comma-separated (e.g. MKSLS_FILENAME=sls,*.sls). Command-line options override them in turn.
+
 Note:
-    - If you didn't write a config file yourself, one with the default values will automatically be written.
+    - If you didn't write a config file yourself, the default values are used. `mksls config edit` writes one
+      with the default values to start from.
     - Paths in the config file should be absolute.
+    - The version entry tells which version of the format the config file is written in: files of an older
+      version are upgraded when read (in memory, the file being left as is).
+    - Another config file can be used with --config FILE, or none at all with --no-config (not even the system-wide one).
+    - `mksls config path|show|edit|set KEY VALUE` prints where the config file is, prints it, edits it or sets one of its values.
+    - `mksls import stow PACKAGE_DIR` and `mksls import dotbot CONFIG` print the file specifying the symlinks GNU Stow
+      makes for a package, or dotbot makes for a configuration file, to migrate from them.
+    - `mksls scan DIR --into FILE` appends to FILE the specifications of the symlinks within DIR pointing within the
+      directory of FILE, to bootstrap it from the symlinks in place.
 ", "Configuration file:".bold().underlined(), crate_name!(), crate_name!()))]
 /// Defines the command-line interface of the app.
 ///
@@ -74,23 +247,409 @@ Note:
 /// let args = Cli::parse();
 /// ```
 pub struct Cli {
+    /// Do something else than making symlinks.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// The directory in which to scan for files specifying symlinks.
+    ///
+    /// It can also be a single file specifying symlinks, in which case only that file is processed
+    /// (whatever its name).
+    /// If not given, default_dir of the configuration file is used.
     #[clap(verbatim_doc_comment)]
-    pub dir: PathBuf,
+    pub dir: Option<PathBuf>,
 
     /// The base (name + extension) of the file(s) specifying symlinks to make.
     ///
+    /// It can be a glob pattern (e.g. '*.sls'), and the option can be given
+    /// multiple times, in which case files matching any of them are picked up.
     /// By default, the name is "sls".
-    /// If one is specified in the config file, it will be used instead.
+    /// If some are specified in the config file, they will be used instead.
+    #[clap(verbatim_doc_comment)]
+    #[arg(short, long, global = true)]
+    pub filename: Vec<String>,
+
+    /// An extension of the file(s) specifying symlinks to make, whatever their name
+    /// (e.g. sls for nvim.sls, zsh.sls, etc.).
+    ///
+    /// The files with it are picked up besides the ones matching --filename, and the
+    /// option can be given multiple times.
+    /// If some are specified in the config file, they will be used instead.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, value_name = "EXT", global = true)]
+    pub extension: Vec<String>,
+
+    /// A glob pattern of files/directories to exclude from the scan of DIR (e.g. '.git').
+    ///
+    /// It is matched against both the name of an entry and its path relative to DIR.
+    /// An excluded directory is not descended into at all.
+    /// The option can be given multiple times.
+    /// If some are specified in the config file, they will be used instead.
+    #[clap(verbatim_doc_comment)]
+    #[arg(short, long, global = true)]
+    pub exclude: Vec<String>,
+
+    /// Only make the links matching this glob pattern (e.g. '~/.config/nvim/**'), ignoring the other ones.
+    ///
+    /// A link matches when the pattern matches its path, or the path of one of its parent
+    /// directories (so that '~/.config/nvim' matches every link within it). '*' doesn't match '/'.
+    /// The option can be given multiple times, in which case links matching any of them are made.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, value_name = "PATTERN")]
+    pub only: Vec<String>,
+
+    /// Ignore the links matching this glob pattern (e.g. '~/.ssh/**'), even if matched by --only.
+    ///
+    /// A link matches like with --only.
+    /// The option can be given multiple times, in which case links matching any of them are ignored.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, value_name = "PATTERN")]
+    pub skip: Vec<String>,
+
+    /// Only make the links specified by lines tagged with one of these comma-separated tags
+    /// (e.g. gui,laptop), and by lines not tagged at all.
+    ///
+    /// A line is tagged by the words starting with @ in its comment (e.g. `xinitrc ~/.xinitrc # @gui`),
+    /// and by the last tags directive above it in its file (e.g. `tags: gui laptop`, or `tags:` to
+    /// tag no more). A line of an included file is not tagged by the including file, but the
+    /// include line itself is.
+    /// Without it, every line is processed, whatever its tags.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// Define the variable NAME, referenced as {NAME} in paths, overriding its definitions by set
+    /// directives and in the config file (e.g. --var host=laptop).
+    ///
+    /// It is also given to the templates, like the variables of the config file.
+    /// The option can be given multiple times.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long = "var", value_name = "NAME=VALUE")]
+    pub vars: Vec<String>,
+
+    /// Follow symlinks to directories, when scanning DIR as well as directories of dir specifications.
+    ///
+    /// Symlink loops are detected, so that no directory is scanned endlessly.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, global = true)]
+    pub follow_links: bool,
+
+    /// Process files in a deterministic order, so that runs (and their output) are reproducible.
+    ///
+    /// The entries of each directory are then visited by ascending file name (compared byte-wise),
+    /// each subdirectory being scanned entirely before moving on to the next entry.
+    /// By default, the order is the one in which the file system lists the entries.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, global = true)]
+    pub sorted: bool,
+
+    /// Fail as soon as an entry can't be read while scanning (e.g. because of a lack of permissions),
+    /// or an invalid line is encountered.
+    ///
+    /// By default, a warning is printed and the entry is skipped, and you are asked to acknowledge
+    /// each invalid line (or, when stdin is not a terminal, it is only reported).
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, global = true)]
+    pub strict: bool,
+
+    /// The number of files to process in parallel, when the program is uninteractive
+    /// (i.e. with --always-skip, --always-backup, --always-overwrite or --always-adopt).
+    ///
+    /// By default, files are processed one at a time, or as many as jobs in the config file.
+    /// Files are processed one at a time all the same with --group-by-file or --transactional.
+    /// When processing files in parallel, the lines printed for a file are in order, but they may
+    /// be interleaved with the ones of the other files. The summary counts the links of all files.
     #[clap(verbatim_doc_comment)]
     #[arg(short, long)]
-    pub filename: Option<String>,
+    pub jobs: Option<usize>,
+
+    /// Undo all the changes made during the run if an error occurs.
+    ///
+    /// The links made are removed, and the backed up or overwritten files are put back in place.
+    /// To make that possible, overwritten files are kept in BACKUP_DIR until the end of the run.
+    /// Files are then processed one at a time, regardless of --jobs.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub transactional: bool,
+
+    /// Go on with the other links specified when one fails to be made (e.g. because of a
+    /// permission denied), instead of stopping the run.
+    ///
+    /// Each failure is reported when it happens, and all of them again at the end of the run,
+    /// which then ends with exit status 5.
+    /// It can't be used with --transactional.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, conflicts_with = "transactional")]
+    pub keep_going: bool,
+
+    /// Create the missing parent directories of the links before making them.
+    ///
+    /// Without it, a link whose parent directory doesn't exist can't be made.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub mkdirs: bool,
+
+    /// Make symlinks whose target doesn't exist (yet), e.g. a file generated after the run.
+    ///
+    /// Without it, a symlink specification whose target doesn't exist is invalid.
+    /// Only symlinks are concerned, not hard links, copies, rendered templates nor directory
+    /// specifications, and a glob pattern still needs to match files.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub allow_missing_targets: bool,
+
+    /// Make the links denied for lack of permissions (e.g. in /etc) by running the command making
+    /// them (ln or cp) through COMMAND, e.g. sudo, doas or pkexec, instead of failing.
+    ///
+    /// Only links made where there is no file yet are concerned.
+    /// It can't be used with --transactional nor --root-script.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "COMMAND", conflicts_with_all = ["transactional", "root_script"])]
+    pub escalate: Option<String>,
+
+    /// Write the commands making the links denied for lack of permissions (e.g. in /etc) to the
+    /// shell script FILE, to run as root after the run (e.g. with sudo sh FILE), instead of
+    /// failing.
+    ///
+    /// The links are then counted as deferred. Only links made where there is no file yet are
+    /// concerned. It can't be used with --transactional.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "FILE", conflicts_with = "transactional")]
+    pub root_script: Option<PathBuf>,
+
+    /// Make symlinks whose target is relative to the directory containing them (like ln -sr).
+    ///
+    /// Useful when the symlink-specification files live on a mount whose absolute path changes.
+    /// Only symlinks are concerned, not hard links, copies or rendered templates.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub relative: bool,
+
+    /// How to make the links of plain specifications (<TARGET_PATH> <LINK_PATH>, as well as
+    /// glob and directory specifications, and TOML entries without a kind).
+    ///
+    /// One of symlink (the default), hard (hard links, like the hard prefix) or copy (copies of
+    /// the targets, like the copy prefix). Conflicts are handled the same way whatever the
+    /// operation. A directory is still symlinked, as it can't be hard linked nor copied.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "OPERATION", default_value = "symlink")]
+    pub operation: OperationKind,
+
+    /// Replace the symlinks pointing somewhere else found at link paths, without asking.
+    ///
+    /// The replacement is atomic, and involves no backup.
+    /// Takes precedence over --always-skip, --always-backup and --always-overwrite
+    /// for such symlinks.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub relink: bool,
+
+    /// Replace the broken symlinks found at link paths (i.e. pointing to nothing), without asking.
+    ///
+    /// The replacement is atomic, involves no backup, and is reported with (f).
+    /// Takes precedence over --relink, --always-skip, --always-backup and --always-overwrite
+    /// for such symlinks, but not over policies.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub replace_broken: bool,
+
+    /// Take a symlink at a link path as the one to make only if it points to the target path as
+    /// written, not to an equivalent one.
+    ///
+    /// By default, a symlink pointing to the target through a differently written path (e.g. with
+    /// a trailing slash or .. components, or relative instead of absolute) is taken as already made,
+    /// the paths being compared once canonicalized. With this flag, it is a symlink pointing
+    /// somewhere else.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub exact_targets: bool,
+
+    /// Only print what would be done, without changing anything.
+    ///
+    /// Conflicts are still resolved (prompting you if needed), but nothing is made,
+    /// backed up, overwritten, renamed, relinked, repaired or adopted, no directory is created, no hook
+    /// is run, and neither the manifest nor the index of the backups is updated.
+    #[clap(verbatim_doc_comment)]
+    #[clap(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Resolve the conflicts with existing files in an interactive list, all at once, instead of
+    /// being prompted for each one in turn.
+    ///
+    /// The links specified are looked at first, without changing anything, and the ones
+    /// conflicting with an existing file (except the ones a policy applies to) are listed.
+    /// You move through them with the arrow keys (or k and j), see the details of the existing
+    /// file and the differences with the target, and choose what to do for each one (skip by
+    /// default) with the keys of the prompts (uppercase for all of them).
+    /// Enter applies everything, q quits without changing anything.
+    /// Confirmations to adopt a file for a missing target are still prompted for.
+    /// It can't be used with --always-skip, --always-backup, --always-overwrite or --always-adopt.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, conflicts_with_all = ["always_skip", "always_backup", "always_overwrite", "always_adopt"])]
+    pub tui: bool,
+
+    /// Resolve the conflicts with existing files at the end of the run, all at once, instead of
+    /// being prompted for each one as it comes.
+    ///
+    /// The links not conflicting with an existing file are made first, the other ones (except
+    /// the ones a policy or an --always-* option applies to) being collected along the way.
+    /// They are then listed in the same interactive list as with --tui, q skipping them all.
+    /// When stdin or stdout is not a terminal, each one is prompted for in turn instead, the
+    /// answers coming from stdin or --answers (see --noninteractive-action).
+    /// It can't be used with --tui.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, conflicts_with = "tui")]
+    pub resolve_at_end: bool,
+
+    /// Answer the prompts with the answers given, in order, instead of reading them from stdin.
+    ///
+    /// The answers are the inputs of the prompts, separated by commas, e.g. s,b,O to skip the
+    /// first conflict, backup the second and overwrite all the other ones. They are printed as
+    /// if entered, errors are only reported, and the run fails if there is no answer left for
+    /// a prompt. Useful to test interactive runs, or to make one again the same way.
+    /// With --always-overwrite, the first answer confirms it.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "ANSWERS", value_delimiter = ',')]
+    pub answers: Option<Vec<String>>,
+
+    /// Same as --answers, with the answers read from FILE, one per line.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "FILE", conflicts_with = "answers")]
+    pub answers_file: Option<PathBuf>,
+
+    /// Record every prompt answered during the run, and the answer given, to FILE (in JSON).
+    ///
+    /// The file is written once the run ends, even if it fails. Give it to --replay to take the
+    /// same decisions again, e.g. on another machine.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Answer the prompts as recorded in FILE by --record, instead of prompting.
+    ///
+    /// A prompt is answered the same way if it was answered for the same link (the links in the
+    /// home directory being recorded relative to it, as ~/...), otherwise it is asked as usual (or
+    /// answered with --answers), as it is when the answer recorded is not valid for the prompt.
+    /// The replayed answers are printed as if entered.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Resume the run interrupted (e.g. by a crash, Ctrl-C or quitting at a prompt) in DIR, skipping
+    /// the lines it has processed instead of prompting for them again.
+    ///
+    /// The progress of a run is saved in the data directory of the app once each file has been
+    /// processed, and when the run fails or is quit, so a run killed (e.g. with Ctrl-C) resumes from
+    /// the file it was processing. It is forgotten once the run ends. Without an interrupted run in DIR (with the same --only and --tags), the run
+    /// starts from the top.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub resume: bool,
+
+    /// Skip the files specifying symlinks unchanged since the last run processing them, if every
+    /// link they specify has been made then, to speed up frequent runs on big directories.
+    /// A file processed with other variables or options changing the links made is not skipped.
+    ///
+    /// The files processed are cached in the data directory of the app, along with the files they
+    /// include and what has been done for them. Beware that the links removed since, and the
+    /// targets changed since (e.g. copied or rendered), are not noticed in the files skipped.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub changed_only: bool,
+
+    /// Overwrite the directories conflicting with links without confirmation.
+    ///
+    /// Overwriting a directory deletes it with all its entries, so by default it has to be
+    /// confirmed each time, even with --always-overwrite or a policy, the number of entries
+    /// being shown. Refusing skips the link.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub allow_dir_overwrite: bool,
+
+    /// Print what is done in a stable format for scripts, instead of the human-readable output.
+    ///
+    /// Each link specified, failure and invalid line gives a line of tab-separated fields:
+    ///     ACTION<TAB>LINK<TAB>TARGET<TAB>SLS_FILE<TAB>LINE
+    /// where ACTION is one of created, already_existing, skipped, backed_up, overwritten,
+    /// renamed, relinked, repaired, adopted, deferred, failed or invalid (LINK and TARGET being
+    /// empty for an invalid line or a failure not about a given link, SLS_FILE and LINE for a
+    /// failure not about a given line, and LINE for a whole invalid file).
+    /// Backslashes, tabs, newlines and carriage returns in the fields are escaped as \\, \t, \n
+    /// and \r.
+    /// The output is never styled, and no summary is printed. Prompts are still shown.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub porcelain: bool,
+
+    /// Print the lines of each file specifying symlinks under a header, followed by its counts.
+    ///
+    /// For each file found in DIR (the files it includes being part of it), the output is:
+    ///     == <file, relative to DIR> ==
+    ///     <a line per link specified, as usual>
+    ///     -- <what has been done for the file, e.g. 2 created, 1 skipped>
+    /// Files are then processed one at a time, even with --jobs.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long)]
+    pub group_by_file: bool,
+
+    /// A file to which to write a complete report of the run once it ends (even if it fails),
+    /// whatever is printed.
+    ///
+    /// The report lists what has been done for each link specified, the invalid lines and the
+    /// errors, then the summary, in the format given by --report-format.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// The format of the report (see --report).
+    #[clap(verbatim_doc_comment)]
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        default_value = "text",
+        requires = "report"
+    )]
+    pub report_format: ReportFormat,
+
+    /// A file to which to append a timestamped line for every decision taken
+    /// (links made, conflicts resolved, invalid lines...).
+    ///
+    /// This is independent of what is printed to the terminal, and meant to audit
+    /// uninteractive runs.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// What to do when stdin is not a terminal (e.g. under cron or in CI), and the prompts are
+    /// not answered with --answers.
+    ///
+    /// With skip, the conflicting links are skipped (and broken symlinks kept), without reading stdin.
+    /// With fail, the answers are read from stdin all the same (e.g. piped to it), the run
+    /// failing at the first prompt left without answer.
+    /// By default, it is fail.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "ACTION", global = true)]
+    pub noninteractive_action: Option<NoninteractiveAction>,
+
+    /// How much is logged to the log file (see --log-file).
+    ///
+    /// By default, it is info.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "LEVEL", global = true)]
+    pub log_level: Option<LogLevel>,
 
     /// The backup directory in which to store the backed up files during execution.
     ///
-    /// By default, it is set to:
+    /// The backups of a run are grouped in a directory named after the time the run started.
+    /// Within it, a file is backed up under its absolute path, with the time of the backup as name
+    /// (e.g. <BACKUP_DIR>/2024-01-02T03:04:00+01:00/home/me/.bashrc/2024-01-02T03:04:05.123+01:00).
+    /// Each backup is recorded in <BACKUP_DIR>/index.toml, along with the path of the backed up file,
+    /// the time of the backup, the run it has been made during, and the target of the link that replaced it.
+    ///
+    /// By default, it is the backups/ directory next to the configuration file, i.e.:
     ///     (Linux) $XDG_CONFIG_HOME/mksls/backups/ or .config/mksls/backups/ if $XDG_CONFIG_HOME is not set
     ///     (Mac) $HOME/Library/Application Support/mksls/backups/
+    ///     (Windows) %APPDATA%\mksls\config\backups\
     #[clap(verbatim_doc_comment)]
     #[arg(short, long)]
     pub backup_dir: Option<PathBuf>,
@@ -98,16 +657,281 @@ pub struct Cli {
     /// Always skip the symlinks conflicting with an existing file.
     ///
     /// This makes the program uninteractive.
-    /// Of course, it can't be combined with --always-backup.
+    /// Of course, it can't be combined with --always-backup, --always-overwrite or --always-adopt.
     #[clap(verbatim_doc_comment)]
-    #[clap(long, conflicts_with = "always_backup")]
+    #[clap(long, conflicts_with_all = ["always_backup", "always_overwrite", "always_adopt"])]
     pub always_skip: bool,
 
     /// Always backup the conflicting file before replacing it by the symlink.
     ///
     /// This makes the program uninteractive.
-    /// Of course, it can't be combined with --always-skip.
+    /// Of course, it can't be combined with --always-skip, --always-overwrite or --always-adopt.
     #[clap(verbatim_doc_comment)]
-    #[clap(long, conflicts_with = "always_skip")]
+    #[clap(long, conflicts_with_all = ["always_skip", "always_overwrite", "always_adopt"])]
     pub always_backup: bool,
+
+    /// Always overwrite the conflicting file with the symlink, without backup.
+    ///
+    /// This makes the program uninteractive, once you confirmed at startup that
+    /// you really want it (beware data loss!).
+    /// Of course, it can't be combined with --always-skip, --always-backup or --always-adopt.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, conflicts_with_all = ["always_skip", "always_backup", "always_adopt"])]
+    pub always_overwrite: bool,
+
+    /// Always adopt the conflicting file, i.e. move it to the target path
+    /// (replacing the target), then make the symlink.
+    ///
+    /// This also applies when the target doesn't exist but a file exists at link path,
+    /// which is handy to bootstrap a dotfiles repository from the files in place.
+    /// This makes the program uninteractive.
+    /// Of course, it can't be combined with --always-skip, --always-backup or --always-overwrite.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, conflicts_with_all = ["always_skip", "always_backup", "always_overwrite"])]
+    pub always_adopt: bool,
+
+    /// Skip the confirmation asked at startup by --always-overwrite.
+    ///
+    /// Meant for scripts, where nobody is there to confirm.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, requires = "always_overwrite")]
+    pub i_know_what_i_am_doing: bool,
+
+    /// The configuration file to use, instead of the one at the default location
+    /// (see the "Configuration file" section below).
+    ///
+    /// Unlike the default one, it is not written if it doesn't exist.
+    /// If it doesn't set backup_dir, the backups/ directory next to it is used.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "FILE", global = true, conflicts_with = "no_config")]
+    pub config: Option<PathBuf>,
+
+    /// The profile of the configuration file to use (see the "Configuration file" section below).
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "NAME", global = true)]
+    pub profile: Option<String>,
+
+    /// Ignore the configuration files, and use the default values instead.
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, global = true)]
+    pub no_config: bool,
+
+    /// When to color the output.
+    ///
+    /// With auto, the output is colored only when stdout is a terminal, unless
+    /// NO_COLOR is set (never colored), CLICOLOR_FORCE is set (always colored),
+    /// or CLICOLOR is 0 (never colored).
+    #[clap(verbatim_doc_comment)]
+    #[clap(long, value_name = "WHEN", global = true, default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Print diagnostics to stderr, more with each occurrence.
+    ///
+    /// -v prints the symlink-specification files found and opened, the hooks run, warnings and
+    /// errors, -vv also prints every line parsed, what is decided for each link (conflicting
+    /// files, stale and broken symlinks included) and what is done for it.
+    #[clap(verbatim_doc_comment)]
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+}
+
+/// The subcommands of the app, doing something else than making symlinks.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Remove the symlinks made by previous runs whose specifications no longer exist.
+    ///
+    /// Every symlink made (or found already made) by a run is recorded in a manifest, along with
+    /// the DIR of the run, in the data directory of the app:
+    ///     (Linux) $XDG_DATA_HOME/mksls/manifest.toml or .local/share/mksls/manifest.toml if $XDG_DATA_HOME is not set
+    ///     (Mac) $HOME/Library/Application Support/rs.mksls/manifest.toml
+    ///     (Windows) %APPDATA%\mksls\data\manifest.toml
+    /// The symlinks recorded for DIR that are not specified anymore in the files within DIR
+    /// are removed, provided they still point to the same target. The ones recorded for other
+    /// directories are left untouched.
+    /// For each, a line with the following format is printed:
+    ///     (p) <link> -> <target>
+    #[clap(verbatim_doc_comment)]
+    Prune {
+        /// The directory in which to scan for files specifying symlinks.
+        dir: PathBuf,
+    },
+    /// Remove broken symlinks, i.e. whose target doesn't exist.
+    ///
+    /// The symlinks directly within the directories of the links specified
+    /// in the files within DIR are looked at, or every symlink within UNDER if given.
+    /// For each broken symlink, you are asked whether to remove it or not.
+    /// For each one removed, a line with the following format is printed:
+    ///     (c) <link> -> <target>
+    #[clap(verbatim_doc_comment)]
+    CleanBroken {
+        /// The directory in which to scan for files specifying symlinks.
+        dir: PathBuf,
+
+        /// The directory in which to look for broken symlinks (recursively),
+        /// instead of the directories of the links specified.
+        #[clap(long, value_name = "UNDER")]
+        under: Option<PathBuf>,
+    },
+    /// Make the symlinks, then make them again whenever a file specifying symlinks changes.
+    ///
+    /// The runs are uninteractive: unless --always-backup, --always-overwrite or
+    /// --always-adopt is given, the symlinks conflicting with an existing file are skipped.
+    /// Nothing is ever prompted: the invalid lines are only reported, and the other prompts are
+    /// answered as cautiously as possible (as with --noninteractive-action skip).
+    /// Watches until interrupted (e.g. with Ctrl-C).
+    #[clap(verbatim_doc_comment)]
+    Watch {
+        /// The directory in which to scan for files specifying symlinks.
+        dir: PathBuf,
+    },
+    /// Make again only the symlinks skipped by previous runs, to resolve their conflicts this time.
+    ///
+    /// Every symlink skipped because of a conflict with an existing file (except by a policy)
+    /// is recorded, in the data directory of the app:
+    ///     (Linux) $XDG_DATA_HOME/mksls/skipped.toml or .local/share/mksls/skipped.toml if $XDG_DATA_HOME is not set
+    ///     (Mac) $HOME/Library/Application Support/rs.mksls/skipped.toml
+    ///     (Windows) %APPDATA%\mksls\data\skipped.toml
+    /// until a run makes it. The ones specified in the files within DIR are made like a usual
+    /// run would, prompting you for their conflicts (--only being replaced by them).
+    #[clap(verbatim_doc_comment)]
+    Resolve {
+        /// The directory in which to scan for files specifying symlinks.
+        dir: PathBuf,
+    },
+    /// Check the files specifying symlinks within DIR, without prompting nor changing anything.
+    ///
+    /// Every invalid line (including the ones whose target doesn't exist), and every link
+    /// specified more than once, is reported on a line with the following format:
+    ///     (!) Invalid line in <file>, line number <line number>.
+    ///         <reason>
+    /// The exit status is 3 if any problem is found, 0 otherwise.
+    #[clap(verbatim_doc_comment)]
+    Lint {
+        /// The directory in which to scan for files specifying symlinks.
+        dir: PathBuf,
+    },
+    /// Append to FILE the symlink specifications of the symlinks within DIR pointing within the
+    /// directory of FILE (e.g. your dotfiles repository), to bootstrap FILE from the symlinks in place.
+    ///
+    /// DIR is scanned recursively, except the directory of FILE.
+    /// The targets are written relative to the directory of FILE, and the symlinks already
+    /// specified the same way in FILE are left out, so that scanning again only adds the new ones.
+    /// FILE is created if it doesn't exist. For example:
+    ///     mksls scan ~ --into ~/dotfiles/sls
+    #[clap(verbatim_doc_comment)]
+    Scan {
+        /// The directory in which to look for symlinks (e.g. your home directory).
+        dir: PathBuf,
+
+        /// The file specifying symlinks to append to.
+        #[clap(long, value_name = "FILE")]
+        into: PathBuf,
+    },
+    /// Manage the backups made in the backup directory.
+    Backups {
+        /// What to do with the backups.
+        #[command(subcommand)]
+        command: BackupsCommand,
+    },
+    /// Manage the configuration file (the one given by --config, if any).
+    Config {
+        /// What to do with the configuration file.
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Print a file specifying symlinks converted from the configuration of another dotfiles manager.
+    Import {
+        /// What to convert.
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
+}
+
+/// The subcommands of `mksls backups`.
+#[derive(Subcommand, Debug)]
+pub enum BackupsCommand {
+    /// Delete the old backups, as configured in the configuration file.
+    ///
+    /// Backups older than max_age_days days, or with max_backups_per_link more recent
+    /// backups of the same file, are deleted.
+    /// Backups are grouped by the file they are a backup of.
+    /// Without max_backups_per_link nor max_age_days in the configuration file, nothing is deleted.
+    /// For each backup deleted, a line with the following format is printed:
+    ///     (p) <backup>
+    #[clap(verbatim_doc_comment)]
+    Prune,
+}
+
+/// The subcommands of `mksls config`.
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the path of the configuration file.
+    Path,
+    /// Print the configuration, i.e. the value of every entry of the configuration file,
+    /// the default ones included.
+    #[clap(verbatim_doc_comment)]
+    Show,
+    /// Open the configuration file in $VISUAL, or $EDITOR, or vi, then check that it is still valid.
+    ///
+    /// The configuration file is written with the default values first, if it doesn't exist.
+    #[clap(verbatim_doc_comment)]
+    Edit,
+    /// Set an entry of the configuration file to a value, after checking that it is valid.
+    ///
+    /// Booleans are given as true/1 or false/0, and lists (filename, extension and exclude) as
+    /// comma-separated values. vars, policy, protected, hooks and profiles can't be set this way.
+    /// Beware that the configuration file is rewritten entirely, so its comments are lost.
+    #[clap(verbatim_doc_comment)]
+    Set {
+        /// The entry to set (e.g. backup_dir).
+        key: String,
+
+        /// The value to set it to.
+        value: String,
+    },
+}
+
+/// The subcommands of `mksls import`.
+#[derive(Subcommand, Debug)]
+pub enum ImportCommand {
+    /// Print the file specifying the symlinks GNU Stow makes for a package, to save in the stow directory.
+    ///
+    /// One symlink is specified per file within PACKAGE_DIR (recursively), except the ones
+    /// GNU Stow ignores (see the .stow-local-ignore file of the package, or the default ignore
+    /// list of GNU Stow without one).
+    /// The targets are relative to the stow directory (the parent of PACKAGE_DIR), so that the printed
+    /// file is meant to be saved in it, along with the ones of the other packages, e.g.:
+    ///     mksls import stow ~/dotfiles/vim >> ~/dotfiles/sls
+    /// Unlike GNU Stow, which can link whole directories, every file is linked on its own,
+    /// so make the symlinks with --mkdirs to create the missing directories, like GNU Stow does.
+    #[clap(verbatim_doc_comment)]
+    Stow {
+        /// The directory of the package (e.g. ~/dotfiles/vim).
+        package_dir: PathBuf,
+
+        /// The directory in which the package is stowed, as given to stow --target.
+        /// Defaults to the parent of the parent of PACKAGE_DIR, like GNU Stow.
+        #[clap(long, value_name = "DIR", verbatim_doc_comment)]
+        target: Option<PathBuf>,
+
+        /// Replace the dot- prefix of the names of files and directories by a dot in the links,
+        /// like stow --dotfiles.
+        #[clap(long, verbatim_doc_comment)]
+        dotfiles: bool,
+    },
+    /// Print the file specifying the symlinks of the link directives of a dotbot configuration file,
+    /// to save in the directory of CONFIG.
+    ///
+    /// The options of the links (create, relink, force, relative and ignore-missing) are converted
+    /// to the matching options of mksls (--mkdirs, --relink, --always-overwrite, --relative and
+    /// --allow-missing-targets), to make the symlinks with, when given for every link.
+    /// Otherwise, they are written as comments above the links given them.
+    /// The links made by dotbot only under an if condition are written commented out, and the
+    /// other directives (e.g. shell) are ignored. For example:
+    ///     mksls import dotbot ~/dotfiles/install.conf.yaml > ~/dotfiles/sls
+    #[clap(verbatim_doc_comment)]
+    Dotbot {
+        /// The dotbot configuration file (e.g. ~/dotfiles/install.conf.yaml).
+        config: PathBuf,
+    },
 }
@@ -2,9 +2,15 @@
 
 pub mod error;
 
-use std::path::PathBuf;
+use glob::Pattern;
+use ignore::{DirEntry, WalkBuilder};
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// The name of the files listing, in the `.gitignore` format, paths to ignore
+/// when looking for "symlink-specification" files.
+pub const SLS_IGNORE_FILENAME: &str = ".slsignore";
+
 /// A wrapper around [`std::path::PathBuf`] that represents a valid directory.
 ///
 /// Different iterators over the files of that directory are made available.
@@ -21,25 +27,41 @@ use walkdir::WalkDir;
 ///               .expect("Expected path to point to an existing directory.");
 ///
 /// for file in dir.iter_on_files() {
-///     println!("{}", file.to_string_lossy());
+///     match file {
+///         Ok(file) => println!("{}", file.to_string_lossy()),
+///         Err(err) => eprintln!("{}", err),
+///     }
 /// }
 /// ```
 ///
 /// Iterate over "symlink-specification" files in a directory:
 ///
 /// ```rust,no_run
+/// # use glob::Pattern;
 /// # use mksls::dir::Dir;
 /// # use std::path::PathBuf;
 /// #
 /// let dir = Dir::build(PathBuf::from("/my/dir/path"))
 ///               .expect("Expected path to point to an existing directory.");
 ///
-/// for sls_file in dir.iter_on_sls_files("sls") {
-///     println!("{}", sls_file.to_string_lossy());
+/// for sls_file in dir.iter_on_sls_files(&[Pattern::new("sls").unwrap()], &[]) {
+///     match sls_file {
+///         Ok(sls_file) => println!("{}", sls_file.to_string_lossy()),
+///         Err(err) => eprintln!("{}", err),
+///     }
 /// }
 /// ```
 #[derive(Debug, Clone)]
-pub struct Dir(PathBuf);
+pub struct Dir {
+    path: PathBuf,
+    /// Whether to follow symlinks to directories when iterating.
+    follow_links: bool,
+    /// Whether to iterate in a deterministic order.
+    sorted: bool,
+    /// The extensions of the "symlink-specification" files, besides their
+    /// filename patterns.
+    extensions: Vec<String>,
+}
 
 impl Dir {
     /// Creates a new [`Dir`], but can fail.
@@ -64,7 +86,96 @@ impl Dir {
         if !dir.is_dir() {
             return Err(error::DirDoesNotExist(dir));
         }
-        Ok(Dir(dir))
+        Ok(Dir {
+            path: dir,
+            follow_links: false,
+            sorted: false,
+            extensions: vec![],
+        })
+    }
+
+    /// Makes the iterators follow symlinks (or not), so that the files within
+    /// symlinked directories are iterated over too.
+    ///
+    /// Symlink loops are detected, so that no directory is iterated over
+    /// endlessly.
+    /// By default, symlinks are not followed.
+    ///
+    /// # Parameters
+    ///
+    /// - `follow_links`: Whether to follow symlinks.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use mksls::dir::Dir;
+    /// # use std::path::PathBuf;
+    /// #
+    /// let dir = Dir::build(PathBuf::from("/my/dir/path"))
+    ///               .expect("Expected path to point to an existing directory.")
+    ///               .follow_links(true);
+    /// ```
+    pub fn follow_links(mut self, follow_links: bool) -> Self {
+        self.follow_links = follow_links;
+        self
+    }
+
+    /// Makes the iterators go through the directory in a deterministic order
+    /// (or not).
+    ///
+    /// When sorted, the entries of each directory are visited by ascending
+    /// file name (compared byte-wise, so `B` comes before `a`), files and
+    /// subdirectories alike, each subdirectory being walked through entirely
+    /// before moving on to the next entry.
+    /// By default, the order is the one in which the file system lists the
+    /// entries, which can differ from one machine to another.
+    ///
+    /// # Parameters
+    ///
+    /// - `sorted`: Whether to sort the entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use mksls::dir::Dir;
+    /// # use std::path::PathBuf;
+    /// #
+    /// let dir = Dir::build(PathBuf::from("/my/dir/path"))
+    ///               .expect("Expected path to point to an existing directory.")
+    ///               .sorted(true);
+    /// ```
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Makes the files with one of the extensions `extensions` (with or
+    /// without the leading dot) "symlink-specification" files too, whatever
+    /// their stem, besides the ones whose filename matches the patterns given
+    /// to [`Dir::iter_on_sls_files`].
+    /// By default, there are none.
+    ///
+    /// # Parameters
+    ///
+    /// - `extensions`: The extensions of the "symlink-specification" files.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use mksls::dir::Dir;
+    /// # use std::path::PathBuf;
+    /// #
+    /// // Picks up nvim.sls, zsh.sls, etc.
+    /// let dir = Dir::build(PathBuf::from("/my/dir/path"))
+    ///               .expect("Expected path to point to an existing directory.")
+    ///               .extensions(vec![String::from("sls")]);
+    /// ```
+    pub fn extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions
+            .into_iter()
+            .map(|extension| extension.trim_start_matches('.').to_string())
+            .collect();
+        self
     }
 
     /// Creates an iterator over the directory's files ([`DirFilesIter`]).
@@ -79,7 +190,10 @@ impl Dir {
     ///               .expect("Expected path to point to an existing directory.");
     ///
     /// for file in dir.iter_on_files() {
-    ///     println!("{}", file.to_string_lossy());
+    ///     match file {
+    ///         Ok(file) => println!("{}", file.to_string_lossy()),
+    ///         Err(err) => eprintln!("{}", err),
+    ///     }
     /// }
     /// ```
     pub fn iter_on_files(&self) -> DirFilesIter {
@@ -90,41 +204,74 @@ impl Dir {
     ///
     /// # Parameters
     ///
-    /// - `sls_filename`: The filename (name + extension) to look for.
+    /// - `sls_filenames`: The patterns of filenames (name + extension) to look for.
+    ///
+    ///   Files with a filename matching one of `sls_filenames` (or with one of
+    ///   the extensions given to [`Dir::extensions`]) will be considered
+    ///   "symlink-specification" files.
+    /// - `excluded`: The patterns of files/directories to exclude.
+    ///
+    ///   An entry whose name or path relative to the directory matches one of
+    ///   `excluded` is skipped, and if it is a directory, it is not descended into.
     ///
-    ///     Files with a filename equal to `sls_filename` will be considered
-    ///     "symlink-specification" files.
+    /// Paths ignored by a [`SLS_IGNORE_FILENAME`] file, or by a `.gitignore`
+    /// file within a git repository, are skipped as well.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
+    /// # use glob::Pattern;
     /// # use mksls::dir::Dir;
     /// # use std::path::PathBuf;
     /// #
     /// let dir = Dir::build(PathBuf::from("/my/dir/path"))
     ///               .expect("Expected path to point to an existing directory.");
+    /// let sls_filenames = [Pattern::new("sls").unwrap(), Pattern::new("*.sls").unwrap()];
+    /// let excluded = [Pattern::new(".git").unwrap()];
     ///
-    /// for sls_file in dir.iter_on_sls_files("sls") {
-    ///     println!("{}", sls_file.to_string_lossy());
+    /// for sls_file in dir.iter_on_sls_files(&sls_filenames, &excluded) {
+    ///     match sls_file {
+    ///         Ok(sls_file) => println!("{}", sls_file.to_string_lossy()),
+    ///         Err(err) => eprintln!("{}", err),
+    ///     }
     /// }
     /// ```
-    pub fn iter_on_sls_files(&self, sls_filename: &str) -> DirSlsFilesIter {
-        DirSlsFilesIter::new(self, sls_filename)
+    pub fn iter_on_sls_files(
+        &self,
+        sls_filenames: &[Pattern],
+        excluded: &[Pattern],
+    ) -> DirSlsFilesIter {
+        DirSlsFilesIter::new(self, sls_filenames, excluded)
     }
 }
 
 /// An iterator over a directory's files.
+///
+/// Entries that can't be read (e.g. because of a lack of permissions) are
+/// yielded as errors, after which the iteration goes on.
 pub struct DirFilesIter {
-    walk_dir: Box<dyn Iterator<Item = PathBuf>>,
+    walk_dir: Box<dyn Iterator<Item = Result<PathBuf, error::WalkError>>>,
 }
 
 impl DirFilesIter {
     fn new(dir: &Dir) -> DirFilesIter {
-        let walk_dir = WalkDir::new(&dir.0)
+        let mut walk_dir = WalkDir::new(&dir.path).follow_links(dir.follow_links);
+        if dir.sorted {
+            walk_dir = walk_dir.sort_by_file_name();
+        }
+        let walk_dir = walk_dir
             .into_iter()
-            .filter_map(Result::ok)
-            .filter(|entry| entry.file_type().is_file() || entry.file_type().is_symlink())
-            .map(|entry| entry.into_path());
+            .filter(|entry| match entry {
+                Ok(entry) => entry.file_type().is_file() || entry.file_type().is_symlink(),
+                Err(_) => true,
+            })
+            .map(|entry| match entry {
+                Ok(entry) => Ok(entry.into_path()),
+                Err(err) => Err(error::WalkError(
+                    err.path().map(Path::to_path_buf),
+                    Box::new(err),
+                )),
+            });
 
         DirFilesIter {
             walk_dir: Box::new(walk_dir),
@@ -133,7 +280,7 @@ impl DirFilesIter {
 }
 
 impl Iterator for DirFilesIter {
-    type Item = PathBuf;
+    type Item = Result<PathBuf, error::WalkError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.walk_dir.next()
@@ -141,22 +288,64 @@ impl Iterator for DirFilesIter {
 }
 
 /// An iterator over a directory's "symlink-specification" files.
+///
+/// Entries that can't be read (e.g. because of a lack of permissions) are
+/// yielded as errors, after which the iteration goes on.
 pub struct DirSlsFilesIter {
-    walk_dir: Box<dyn Iterator<Item = PathBuf>>,
+    walk_dir: Box<dyn Iterator<Item = Result<PathBuf, error::WalkError>>>,
 }
 
 impl DirSlsFilesIter {
-    fn new(dir: &Dir, sls_filename: &str) -> DirSlsFilesIter {
-        let sls_filename = String::from(sls_filename);
-
-        let walk_dir = WalkDir::new(&dir.0)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|entry| entry.file_type().is_file() || entry.file_type().is_symlink())
-            .map(|entry| entry.into_path())
-            .filter(move |file| match file.file_name() {
-                Some(os_str) => os_str == &sls_filename[..],
-                None => false,
+    fn new(dir: &Dir, sls_filenames: &[Pattern], excluded: &[Pattern]) -> DirSlsFilesIter {
+        let sls_filenames = sls_filenames.to_vec();
+        let extensions = dir.extensions.clone();
+        let excluded = excluded.to_vec();
+        let root = dir.path.clone();
+
+        let mut walk_builder = WalkBuilder::new(&dir.path);
+        walk_builder
+            .follow_links(dir.follow_links)
+            .standard_filters(false)
+            .parents(true)
+            .git_ignore(true)
+            .add_custom_ignore_filename(SLS_IGNORE_FILENAME)
+            .filter_entry(move |entry| entry.depth() == 0 || !is_excluded(entry, &root, &excluded));
+        if dir.sorted {
+            walk_builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+        let walk_dir = walk_builder
+            .build()
+            .filter(|entry| match entry {
+                Ok(entry) => entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file() || file_type.is_symlink()),
+                Err(_) => true,
+            })
+            .map(|entry| match entry {
+                Ok(entry) => Ok(entry.into_path()),
+                Err(err) => Err(error::WalkError(ignore_error_path(&err), Box::new(err))),
+            })
+            .filter(move |file| match file {
+                Ok(file) => {
+                    let has_extension = file.extension().is_some_and(|extension| {
+                        extensions
+                            .iter()
+                            .any(|sls_extension| extension == &**sls_extension)
+                    });
+                    has_extension
+                        || file.file_name().is_some_and(|filename| {
+                            let filename = filename.to_string_lossy();
+                            sls_filenames
+                                .iter()
+                                .any(|pattern| pattern.matches(&filename))
+                        })
+                }
+                Err(_) => true,
+            })
+            .inspect(|file| {
+                if let Ok(file) = file {
+                    tracing::info!("Found the symlink-specification file {}.", file.display());
+                }
             });
 
         DirSlsFilesIter {
@@ -165,8 +354,37 @@ impl DirSlsFilesIter {
     }
 }
 
+/// The path of the entry `err` is about, if any.
+fn ignore_error_path(err: &ignore::Error) -> Option<PathBuf> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path.clone()),
+        ignore::Error::Loop { child, .. } => Some(child.clone()),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            ignore_error_path(err)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `entry` (found under `root`) matches one of the `excluded` patterns,
+/// either by its name or by its path relative to `root`.
+fn is_excluded(entry: &DirEntry, root: &Path, excluded: &[Pattern]) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    let relative = entry
+        .path()
+        .strip_prefix(root)
+        .ok()
+        .map(|relative| relative.to_string_lossy());
+    excluded.iter().any(|pattern| {
+        pattern.matches(&name)
+            || relative
+                .as_ref()
+                .is_some_and(|relative| pattern.matches(relative))
+    })
+}
+
 impl Iterator for DirSlsFilesIter {
-    type Item = PathBuf;
+    type Item = Result<PathBuf, error::WalkError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.walk_dir.next()
@@ -178,10 +396,12 @@ mod tests {
     use serial_test::serial;
 
     use super::*;
+    use crate::platform;
     use crate::utils;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
     use core::panic;
     use std::fs;
-    use std::os::unix;
     use std::path::PathBuf;
 
     fn get_tmp_dir() -> PathBuf {
@@ -224,7 +444,7 @@ mod tests {
         sl_spec_link.push("s2");
         let sl_spec = format!("{} {}", sl_spec_target.display(), sl_spec_link.display());
         let wrong_sl_spec = String::from("/wrong/\"target /wrong/\"link");
-        let lines = vec![
+        let lines = [
             sl_spec,
             String::from(""),
             String::from("// a comment"),
@@ -245,7 +465,7 @@ mod tests {
             target.push(format!("f{}", i));
 
             if !sl.exists() {
-                if let Err(err) = unix::fs::symlink(target, &sl) {
+                if let Err(err) = platform::symlink(target, &sl) {
                     panic!("{:?}", err);
                 }
             }
@@ -324,7 +544,7 @@ mod tests {
         let tmp_dir = get_tmp_dir();
         let tmp_dir = Dir::build(tmp_dir).expect("tmp_dir should exist at this point");
         let files_it = tmp_dir.iter_on_files();
-        let files: Vec<PathBuf> = files_it.collect();
+        let files: Vec<PathBuf> = files_it.map(Result::unwrap).collect();
         assert!(utils::tests::vec_are_equal(&files, &expected_files));
     }
 
@@ -344,8 +564,174 @@ mod tests {
 
         let tmp_dir = get_tmp_dir();
         let tmp_dir = Dir::build(tmp_dir).expect("tmp_dir should exist at this point");
-        let sls_files_it = tmp_dir.iter_on_sls_files(sls_filename);
-        let sls_files: Vec<PathBuf> = sls_files_it.collect();
+        let sls_files_it = tmp_dir.iter_on_sls_files(&[Pattern::new(sls_filename).unwrap()], &[]);
+        let sls_files: Vec<PathBuf> = sls_files_it.map(Result::unwrap).collect();
+        assert!(utils::tests::vec_are_equal(&sls_files, &expected_sls_files));
+    }
+
+    #[serial]
+    #[test]
+    fn dir_iter_on_sls_files_matches_any_pattern() {
+        let expected_sls_files: Vec<PathBuf> = mk_tmp_contents()
+            .into_iter()
+            .filter(|path| path.is_file() || path.is_symlink())
+            .filter(
+                |path| match path.file_name().and_then(|os_str| os_str.to_str()) {
+                    Some(filename) => ["sls", "f1", "f2"].contains(&filename),
+                    None => false,
+                },
+            )
+            .collect();
+
+        let tmp_dir = get_tmp_dir();
+        let tmp_dir = Dir::build(tmp_dir).expect("tmp_dir should exist at this point");
+        let patterns = [Pattern::new("sls").unwrap(), Pattern::new("f[12]").unwrap()];
+        let sls_files: Vec<PathBuf> = tmp_dir
+            .iter_on_sls_files(&patterns, &[])
+            .map(Result::unwrap)
+            .collect();
+        assert!(utils::tests::vec_are_equal(&sls_files, &expected_sls_files));
+    }
+
+    #[serial]
+    #[test]
+    fn dir_iter_on_sls_files_prunes_excluded_entries() {
+        let expected_sls_files: Vec<PathBuf> = mk_tmp_contents()
+            .into_iter()
+            .filter(|path| path.is_file() || path.is_symlink())
+            .filter(|path| match path.file_name() {
+                Some(os_str) => os_str == "sls",
+                None => false,
+            })
+            .filter(|path| !path.ends_with("d1/sls") && !path.ends_with("d2/sls"))
+            .collect();
+
+        let tmp_dir = get_tmp_dir();
+        let tmp_dir = Dir::build(tmp_dir).expect("tmp_dir should exist at this point");
+        let excluded = [Pattern::new("d1").unwrap(), Pattern::new("d2/*").unwrap()];
+        let sls_files: Vec<PathBuf> = tmp_dir
+            .iter_on_sls_files(&[Pattern::new("sls").unwrap()], &excluded)
+            .map(Result::unwrap)
+            .collect();
         assert!(utils::tests::vec_are_equal(&sls_files, &expected_sls_files));
     }
+
+    #[serial]
+    #[test]
+    fn dir_iter_on_sls_files_respects_sls_ignore_files() {
+        let expected_sls_files: Vec<PathBuf> = mk_tmp_contents()
+            .into_iter()
+            .filter(|path| path.is_file() || path.is_symlink())
+            .filter(|path| match path.file_name() {
+                Some(os_str) => os_str == "sls",
+                None => false,
+            })
+            .filter(|path| !path.ends_with("d1/sls"))
+            .collect();
+
+        let tmp_dir = get_tmp_dir();
+        fs::write(tmp_dir.join(SLS_IGNORE_FILENAME), "# Comment.\nd1/\n").unwrap();
+        let tmp_dir = Dir::build(tmp_dir).expect("tmp_dir should exist at this point");
+        let sls_files: Vec<PathBuf> = tmp_dir
+            .iter_on_sls_files(&[Pattern::new("sls").unwrap()], &[])
+            .map(Result::unwrap)
+            .collect();
+        assert!(utils::tests::vec_are_equal(&sls_files, &expected_sls_files));
+    }
+
+    #[test]
+    fn dir_iterators_follow_links_without_looping() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        tmp_dir.child("dots/sls").touch()?;
+        tmp_dir.child("elsewhere/sls").touch()?;
+        tmp_dir
+            .child("dots/linked")
+            .symlink_to_dir(tmp_dir.child("elsewhere"))?;
+        tmp_dir
+            .child("dots/loop")
+            .symlink_to_dir(tmp_dir.child("dots"))?;
+
+        let dir = Dir::build(tmp_dir.child("dots").to_path_buf())?;
+        let sls_filenames = [Pattern::new("sls").unwrap()];
+        let expected = [tmp_dir.child("dots/sls").to_path_buf()];
+        let sls_files: Vec<PathBuf> = dir
+            .iter_on_sls_files(&sls_filenames, &[])
+            .map(Result::unwrap)
+            .collect();
+        assert!(utils::tests::vec_are_equal(&sls_files, &expected));
+
+        // The loop is reported as an error, instead of being followed.
+        let dir = dir.follow_links(true);
+        let expected = [
+            tmp_dir.child("dots/sls").to_path_buf(),
+            tmp_dir.child("dots/linked/sls").to_path_buf(),
+        ];
+        let (sls_files, errors): (Vec<_>, Vec<_>) = dir
+            .iter_on_sls_files(&sls_filenames, &[])
+            .partition(Result::is_ok);
+        let sls_files: Vec<PathBuf> = sls_files.into_iter().map(Result::unwrap).collect();
+        assert!(utils::tests::vec_are_equal(&sls_files, &expected));
+        assert_eq!(errors.len(), 1);
+        let (files, errors): (Vec<_>, Vec<_>) = dir.iter_on_files().partition(Result::is_ok);
+        let files: Vec<PathBuf> = files.into_iter().map(Result::unwrap).collect();
+        assert!(utils::tests::vec_are_equal(&files, &expected));
+        assert_eq!(errors.len(), 1);
+
+        tmp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn sls_files_are_found_by_extension() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        for file in ["sls", "nvim.sls", "zsh/zsh.sls", "sls.txt", "notsls"] {
+            tmp_dir.child(file).touch()?;
+        }
+
+        let dir = Dir::build(tmp_dir.to_path_buf())?
+            .sorted(true)
+            .extensions(vec![String::from(".sls")]);
+        let expected: Vec<PathBuf> = ["nvim.sls", "sls", "zsh/zsh.sls"]
+            .iter()
+            .map(|file| tmp_dir.child(file).to_path_buf())
+            .collect();
+        let sls_files: Vec<PathBuf> = dir
+            .iter_on_sls_files(&[Pattern::new("sls").unwrap()], &[])
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sls_files, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dir_iterators_can_be_sorted() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        for file in ["b/sls", "a/c/sls", "a/sls", "B", "sls"] {
+            tmp_dir.child(file).touch()?;
+        }
+
+        let dir = Dir::build(tmp_dir.to_path_buf())?.sorted(true);
+        let expected: Vec<PathBuf> = ["a/c/sls", "a/sls", "b/sls", "sls"]
+            .iter()
+            .map(|file| tmp_dir.child(file).to_path_buf())
+            .collect();
+        let sls_files: Vec<PathBuf> = dir
+            .iter_on_sls_files(&[Pattern::new("sls").unwrap()], &[])
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sls_files, expected);
+
+        let expected: Vec<PathBuf> = ["B", "a/c/sls", "a/sls", "b/sls", "sls"]
+            .iter()
+            .map(|file| tmp_dir.child(file).to_path_buf())
+            .collect();
+        let files: Vec<PathBuf> = dir.iter_on_files().map(Result::unwrap).collect();
+        assert_eq!(files, expected);
+
+        tmp_dir.close()?;
+
+        Ok(())
+    }
 }
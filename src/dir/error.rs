@@ -9,13 +9,7 @@ pub struct DirDoesNotExist(pub PathBuf);
 
 impl fmt::Display for DirDoesNotExist {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "The directory {} does not exist.",
-            self.0
-                .to_str()
-                .expect("Expected only UTF-8 characters in the path.")
-        )
+        write!(f, "The directory {} does not exist.", self.0.display())
     }
 }
 
@@ -32,9 +26,7 @@ impl fmt::Display for DirCreationFailed {
             "The creation of directory {} failed.
 The underlying error is:
 {:4?}",
-            self.0
-                .to_str()
-                .expect("Expected only UTF-8 characters in the path."),
+            self.0.display(),
             self.1
         )
     }
@@ -48,3 +40,21 @@ impl error::Error for DirCreationFailed {
         Some(&self.1)
     }
 }
+
+#[derive(Debug)]
+/// An error for when an entry couldn't be read while iterating over a directory.
+///
+/// Holds the path of the entry (when known) and the underlying error.
+pub struct WalkError(pub Option<PathBuf>, pub Box<dyn error::Error + Send + Sync>);
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to read an entry while scanning: {}", self.1)
+    }
+}
+
+impl error::Error for WalkError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.1)
+    }
+}
@@ -1,24 +1,57 @@
 //! Where most of the app's logic resides.
 
+use crate::backups;
+use crate::cache::{self, Cache, CachedFile, FileState};
+use crate::cfg::{Config, PolicyAction};
+use crate::cli::Cli;
+use crate::dir::error::WalkError;
 use crate::dir::Dir;
+use crate::error::Context;
+use crate::event;
+use crate::event::{Event, Observer};
+use crate::filesystem::{Fs, RealFs};
+use crate::hook;
 use crate::line;
 use crate::line::{Invalid, LineType};
+use crate::log::{LogLevel, Logger};
+use crate::manifest::Manifest;
 use crate::params::Params;
-use crate::prompt;
-use crate::prompt::AlreadyExistPromptOptions;
+use crate::progress::Progress;
+use crate::prompt::{
+    AlreadyExistPromptOptions, BrokenSymlinkPromptOptions, Input, Prompter, Session,
+    StaleSymlinkPromptOptions, TerminalPrompter,
+};
+use crate::report;
+use crate::skipped::Skipped;
+use crate::sls_toml;
+use crate::style::Paint;
+use crate::template;
+use crate::tui;
+use crate::tui::Resolution;
 use crate::utils;
-use anyhow::Context;
-use crossterm::style::Stylize;
+use crate::utils::LinkKind;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fmt::Debug;
 use std::fs;
 use std::io;
 use std::io::BufRead;
-use std::os::unix;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::ops::AddAssign;
+use std::path;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 /// The possible actions to take when a symlink about to be made conflicts with an existing file.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Action {
     /// Don't make the symlink and move on.
     Skip,
@@ -26,6 +59,424 @@ enum Action {
     Backup,
     /// Make the symlink without backup, overwriting the existing file.
     Overwrite,
+    /// Rename the existing file in place, then make the symlink.
+    Rename,
+    /// Atomically replace the existing symlink, pointing somewhere else, by the symlink.
+    Relink,
+    /// Move the existing file to the target path, then make the symlink.
+    Adopt,
+}
+
+impl From<PolicyAction> for Action {
+    fn from(action: PolicyAction) -> Self {
+        match action {
+            PolicyAction::Skip => Action::Skip,
+            PolicyAction::Backup => Action::Backup,
+            PolicyAction::Overwrite => Action::Overwrite,
+            PolicyAction::Adopt => Action::Adopt,
+            PolicyAction::Relink => Action::Relink,
+        }
+    }
+}
+
+impl From<Resolution> for Action {
+    fn from(resolution: Resolution) -> Self {
+        match resolution {
+            Resolution::Skip => Action::Skip,
+            Resolution::Backup => Action::Backup,
+            Resolution::Overwrite => Action::Overwrite,
+            Resolution::Rename => Action::Rename,
+            Resolution::Relink => Action::Relink,
+            Resolution::Adopt => Action::Adopt,
+        }
+    }
+}
+
+/// A link conflicting with an existing file, whose resolution is postponed to
+/// the end of the run (see [`Params::resolve_at_end`]).
+#[derive(Debug)]
+struct Postponed {
+    /// The kind of the link.
+    kind: LinkKind,
+    /// The link, its target (as specified, i.e. not relative to the link
+    /// even with [`Params::relative`]) and the existing file.
+    conflict: tui::Conflict,
+    /// The line specifying the link (see [`Engine::spec_line`]).
+    spec_line: (PathBuf, u64),
+}
+
+/// A change made to the file system, recorded to be undone in transactional
+/// mode (see [`crate::cli::Cli::transactional`]).
+#[derive(Debug)]
+enum Operation {
+    /// The link `link` has been made where there was no file.
+    Made { link: PathBuf },
+    /// The existing file at `link` has been moved to `moved`, then the link
+    /// has been made in its place.
+    Moved { link: PathBuf, moved: PathBuf },
+    /// The directory has been created.
+    CreatedDir(PathBuf),
+    /// The existing symlink at `link`, pointing to `previous`, has been
+    /// replaced by the symlink.
+    Relinked { link: PathBuf, previous: PathBuf },
+    /// The existing file at `link` has been moved to `target`, then the link
+    /// has been made in its place. What was at `target` has been moved to
+    /// `saved`, if anything.
+    Adopted {
+        link: PathBuf,
+        target: PathBuf,
+        saved: Option<PathBuf>,
+    },
+}
+
+/// Where a link is specified, and what its target is (see
+/// [`Engine::specified`]).
+#[derive(Debug, Clone)]
+struct Spec {
+    /// The target of the link.
+    target: PathBuf,
+    /// The symlink-specification file specifying the link.
+    sls: PathBuf,
+    /// The line number of the specification in `sls`.
+    line_no: u64,
+}
+
+/// The state of a run in transactional mode.
+#[derive(Debug)]
+struct Transaction {
+    /// The changes made so far, in order.
+    operations: Vec<Operation>,
+    /// Where to move the overwritten files until the end of the run.
+    save_dir: PathBuf,
+}
+
+/// Where the [`Engine`] prints what it does, shared with the workers.
+///
+/// Lines written by different threads are not mixed up.
+#[derive(Clone)]
+struct Output(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Output {
+    fn new<W: Write + Send + 'static>(out: W) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(out))))
+    }
+}
+
+impl Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Output")
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments) -> io::Result<()> {
+        self.0.lock().unwrap().write_fmt(args)
+    }
+}
+
+/// The prompter of the workers processing files in parallel, asking one
+/// question at a time.
+///
+/// The output is held during each prompt, so that neither the prompts and
+/// their answers nor the lines printed by the other workers are mixed up.
+#[derive(Debug)]
+struct Exclusive {
+    prompter: Arc<dyn Prompter>,
+    out: Output,
+}
+
+impl Prompter for Exclusive {
+    fn resolve_conflict(
+        &self,
+        target: &Path,
+        link: &Path,
+        diff: &dyn Fn() -> Option<String>,
+    ) -> crate::Result<AlreadyExistPromptOptions> {
+        let _out = self.out.0.lock().unwrap();
+        self.prompter.resolve_conflict(target, link, diff)
+    }
+
+    fn resolve_stale_symlink(
+        &self,
+        target: &Path,
+        link: &Path,
+        current_target: &Path,
+    ) -> crate::Result<StaleSymlinkPromptOptions> {
+        let _out = self.out.0.lock().unwrap();
+        self.prompter
+            .resolve_stale_symlink(target, link, current_target)
+    }
+
+    fn confirm_adopt(&self, target: &Path, link: &Path) -> crate::Result<bool> {
+        let _out = self.out.0.lock().unwrap();
+        self.prompter.confirm_adopt(target, link)
+    }
+
+    fn confirm_dir_overwrite(&self, link: &Path, entries: u64) -> crate::Result<bool> {
+        let _out = self.out.0.lock().unwrap();
+        self.prompter.confirm_dir_overwrite(link, entries)
+    }
+
+    fn resolve_broken_symlink(
+        &self,
+        target: &Path,
+        link: &Path,
+    ) -> crate::Result<BrokenSymlinkPromptOptions> {
+        let _out = self.out.0.lock().unwrap();
+        self.prompter.resolve_broken_symlink(target, link)
+    }
+
+    fn acknowledge_error(&self, message: &str) -> crate::Result<()> {
+        let _out = self.out.0.lock().unwrap();
+        self.prompter.acknowledge_error(message)
+    }
+}
+
+/// Counts of what has been done during a run of the [`Engine`].
+///
+/// Returned by [`Engine::run`] so that library users can inspect the
+/// outcome of a run.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::engine::Summary;
+///
+/// let summary = Summary::default();
+/// assert_eq!(summary.created, 0);
+/// println!("{}", summary);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    /// Number of symlinks created without conflict (`d`).
+    pub created: u64,
+    /// Number of symlinks that already existed (`.`).
+    pub already_existing: u64,
+    /// Number of conflicting symlinks that were skipped (`s`).
+    pub skipped: u64,
+    /// Number of conflicting files that were backed up (`b`).
+    pub backed_up: u64,
+    /// Number of conflicting files that were overwritten (`o`).
+    pub overwritten: u64,
+    /// Number of conflicting files that were renamed in place (`n`).
+    pub renamed: u64,
+    /// Number of conflicting symlinks that were relinked (`r`).
+    pub relinked: u64,
+    /// Number of conflicting broken symlinks that were replaced (`f`, see
+    /// [`crate::cli::Cli::replace_broken`]).
+    pub repaired: u64,
+    /// Number of conflicting files that were adopted (`a`).
+    pub adopted: u64,
+    /// Number of links denied for lack of permissions, left to the root
+    /// script (`#`, see [`crate::cli::Cli::root_script`]).
+    pub deferred: u64,
+    /// Number of invalid lines encountered in symlink-specification files.
+    pub invalid: u64,
+    /// Number of links specified that failed to be made, the run having gone
+    /// on (see [`crate::cli::Cli::keep_going`]).
+    pub failed: u64,
+    /// Whether the user quit the run before all the links specified were
+    /// processed (`q`).
+    pub quit: bool,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Summary:
+    (d) created          : {}
+    (.) already existing : {}
+    (s) skipped          : {}
+    (b) backed up        : {}
+    (o) overwritten      : {}
+    (n) renamed          : {}
+    (r) relinked         : {}
+    (a) adopted          : {}",
+            self.created,
+            self.already_existing,
+            self.skipped,
+            self.backed_up,
+            self.overwritten,
+            self.renamed,
+            self.relinked,
+            self.adopted
+        )?;
+        if self.repaired > 0 {
+            write!(
+                f,
+                "
+    (f) repaired         : {}",
+                self.repaired
+            )?;
+        }
+        if self.deferred > 0 {
+            write!(
+                f,
+                "
+    (#) deferred         : {}",
+                self.deferred
+            )?;
+        }
+        write!(
+            f,
+            "
+    invalid lines        : {}",
+            self.invalid
+        )?;
+        if self.failed > 0 {
+            write!(
+                f,
+                "
+    failed               : {}",
+                self.failed
+            )?;
+        }
+        if self.quit {
+            write!(
+                f,
+                "
+Quit before all the links specified were processed."
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Summary {
+    /// Returns the status of the run this is the summary of.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::engine::{RunStatus, Summary};
+    ///
+    /// let mut summary = Summary::default();
+    /// assert_eq!(summary.status(), RunStatus::Success);
+    /// summary.skipped += 1;
+    /// assert_eq!(summary.status(), RunStatus::ConflictsSkipped);
+    /// summary.invalid += 1;
+    /// assert_eq!(summary.status(), RunStatus::InvalidLines);
+    /// summary.failed += 1;
+    /// assert_eq!(summary.status(), RunStatus::Failed);
+    /// summary.quit = true;
+    /// assert_eq!(summary.status(), RunStatus::Quit);
+    /// ```
+    pub fn status(&self) -> RunStatus {
+        if self.quit {
+            RunStatus::Quit
+        } else if self.failed > 0 {
+            RunStatus::Failed
+        } else if self.invalid > 0 {
+            RunStatus::InvalidLines
+        } else if self.skipped > 0 {
+            RunStatus::ConflictsSkipped
+        } else {
+            RunStatus::Success
+        }
+    }
+
+    /// Returns the non-zero counts, e.g. `2 created, 1 skipped`, or `nothing
+    /// done` if there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::engine::Summary;
+    ///
+    /// let mut summary = Summary::default();
+    /// assert_eq!(summary.counts(), "nothing done");
+    /// summary.created = 2;
+    /// summary.skipped = 1;
+    /// assert_eq!(summary.counts(), "2 created, 1 skipped");
+    /// ```
+    pub fn counts(&self) -> String {
+        let counts: Vec<String> = [
+            (self.created, "created"),
+            (self.already_existing, "already existing"),
+            (self.skipped, "skipped"),
+            (self.backed_up, "backed up"),
+            (self.overwritten, "overwritten"),
+            (self.renamed, "renamed"),
+            (self.relinked, "relinked"),
+            (self.repaired, "repaired"),
+            (self.adopted, "adopted"),
+            (self.deferred, "deferred"),
+            (self.invalid, "invalid lines"),
+            (self.failed, "failed"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, name)| format!("{} {}", count, name))
+        .collect();
+
+        if counts.is_empty() {
+            String::from("nothing done")
+        } else {
+            counts.join(", ")
+        }
+    }
+}
+
+impl AddAssign for Summary {
+    fn add_assign(&mut self, other: Self) {
+        self.created += other.created;
+        self.already_existing += other.already_existing;
+        self.skipped += other.skipped;
+        self.backed_up += other.backed_up;
+        self.overwritten += other.overwritten;
+        self.renamed += other.renamed;
+        self.relinked += other.relinked;
+        self.repaired += other.repaired;
+        self.adopted += other.adopted;
+        self.deferred += other.deferred;
+        self.invalid += other.invalid;
+        self.failed += other.failed;
+        self.quit |= other.quit;
+    }
+}
+
+/// How a run of the [`Engine`] went, when it didn't fail (see [`Summary::status`]).
+///
+/// When several apply, the one listed last takes precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// Every link specified has been made, or already existed.
+    Success,
+    /// Some links conflicting with an existing file have been skipped.
+    ConflictsSkipped,
+    /// Some invalid lines have been encountered.
+    InvalidLines,
+    /// Some links failed to be made, the run having gone on (see
+    /// [`crate::cli::Cli::keep_going`]).
+    Failed,
+    /// The user quit before all the links specified were processed.
+    Quit,
+}
+
+impl RunStatus {
+    /// Returns the exit code of the app for the status: 0 for
+    /// [`RunStatus::Success`], 2 for [`RunStatus::ConflictsSkipped`], 3 for
+    /// [`RunStatus::InvalidLines`], 4 for [`RunStatus::Quit`] and 5 for
+    /// [`RunStatus::Failed`] (1 being for errors).
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RunStatus::Success => 0,
+            RunStatus::ConflictsSkipped => 2,
+            RunStatus::InvalidLines => 3,
+            RunStatus::Quit => 4,
+            RunStatus::Failed => 5,
+        }
+    }
 }
 
 /// The engine of the program, where the app's pieces are glued together.
@@ -54,10 +505,271 @@ enum Action {
 pub struct Engine {
     /// The action to be taken at any given time.
     action: Option<Action>,
+    /// The action to be taken until the end of the symlink-specification
+    /// file being processed, if chosen for that file only.
+    file_action: Option<Action>,
+    /// The tags given by the last tags directive of the symlink-specification
+    /// file being processed (see [`line::LineType::Tags`]).
+    file_tags: Vec<String>,
+    /// The variables defined so far for the symlink-specification file being
+    /// processed (see [`line::LineType::Set`]), the ones of [`Params::vars`]
+    /// included.
+    file_vars: HashMap<String, String>,
+    /// Whether the guard of the next specification of the symlink-specification
+    /// file being processed holds, if guarded (see [`line::LineType::Guard`]).
+    file_guard: Option<bool>,
+    /// The line opening each block open in the symlink-specification file
+    /// being processed, and whether its guard holds, from the outermost one.
+    file_blocks: Vec<(u64, bool)>,
+    /// Whether to relink the conflicting symlinks without asking.
+    relink: bool,
     params: Params,
+    /// What has been done so far.
+    summary: Summary,
+    /// The (canonical) symlink-specification files already processed or being processed.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    processed: Arc<Mutex<HashSet<PathBuf>>>,
+    /// The (canonical) symlink-specification files being processed, each one
+    /// including the next.
+    include_stack: Vec<PathBuf>,
+    /// The (canonical) symlink-specification files included so far by the
+    /// top-level one being processed, to cache them along with it.
+    included: Vec<PathBuf>,
+    /// The state of the run, if in transactional mode.
+    transaction: Option<Transaction>,
+    /// The symlinks managed by the app.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    manifest: Arc<Mutex<Manifest>>,
+    /// The canonical path of [`Params::dir`], under which the symlinks made
+    /// are recorded in the manifest.
+    canonical_dir: PathBuf,
+    /// The links skipped and not made since, by this run or the previous
+    /// ones (see [`Skipped`]).
+    ///
+    /// Shared with the workers when processing files in parallel.
+    skipped: Arc<Mutex<Skipped>>,
+    /// The symlink-specification files processed by the previous runs, and
+    /// the ones processed by this run once it ends (see [`Cache`]).
+    ///
+    /// Shared with the workers when processing files in parallel.
+    cache: Arc<Mutex<Cache>>,
+    /// The fingerprint of the parameters of the run, cached along the files
+    /// processed (see [`cache::fingerprint`]).
+    fingerprint: String,
+    /// The progress of the run, kept so that it can be resumed if interrupted
+    /// (see [`Params::resume`]), if running (see [`Engine::run`]).
+    ///
+    /// Shared with the workers when processing files in parallel.
+    progress: Option<Arc<Mutex<Progress>>>,
+    /// The links specified, if only collecting them instead of making them
+    /// (see [`Engine::prune`], [`Engine::clean_broken`] and [`Engine::lint`]),
+    /// each one with its target, and the symlink-specification file and line
+    /// number where it is first specified.
+    specified: Option<HashMap<PathBuf, Spec>>,
+    /// The links skipped by previous runs, if only making them again (see
+    /// [`Engine::resolve`]).
+    resolving: Option<HashSet<PathBuf>>,
+    /// The symlink-specification file and line number of the specification
+    /// being processed.
+    spec_line: (PathBuf, u64),
+    /// Whether reporting the problems found in the symlink-specification
+    /// files instead of asking the user to acknowledge them (see
+    /// [`Engine::lint`]).
+    lint: bool,
+    /// The errors about links specified that have been reported instead of
+    /// failing the run (see [`Engine::keep_going`]).
+    failures: Vec<String>,
+    /// The links specified more than once with different targets, if only
+    /// collecting the links specified to look for them (see
+    /// [`Engine::check_conflicts`]).
+    conflicts: Option<Vec<String>>,
+    /// The links conflicting with an existing file that would be prompted
+    /// for, if only collecting them to resolve them in the terminal user
+    /// interface (see [`Params::tui`]), each one with the kind of the link.
+    pending: Option<Vec<(LinkKind, tui::Conflict)>>,
+    /// The actions chosen in the terminal user interface for the links
+    /// conflicting with an existing file (see [`Params::tui`]).
+    resolutions: HashMap<PathBuf, Action>,
+    /// The links conflicting with an existing file that would be prompted
+    /// for, if resolving them at the end of the run (see
+    /// [`Params::resolve_at_end`]).
+    postponed: Option<Vec<Postponed>>,
+    /// The commands making the links denied for lack of permissions, to
+    /// write to the root script (see [`Params::root_script`]).
+    ///
+    /// Shared with the workers when processing files in parallel.
+    root_commands: Arc<Mutex<Vec<Vec<OsString>>>>,
+    /// Where to log the decisions taken.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    logger: Arc<Logger>,
+    /// The identifier of the run, i.e. the time it started (RFC 3339), naming
+    /// the directory of its backups in the backup directory.
+    run_id: String,
+    /// The index of the backups made.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    backup_index: Arc<Mutex<backups::Index>>,
+    /// How to ask for the decisions to take.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    prompter: Arc<dyn Prompter>,
+    /// Where to print what is done.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    out: Output,
+    /// Where to print what is done in the porcelain format, instead of `out`
+    /// (see [`Params::porcelain`]).
+    ///
+    /// Shared with the workers when processing files in parallel.
+    porcelain: Option<Output>,
+    /// What to report once the run ends, if a report is to be written (see
+    /// [`Params::report`]).
+    ///
+    /// Shared with the workers when processing files in parallel.
+    report: Option<Arc<Mutex<Vec<report::Entry>>>>,
+    /// The session in which the prompts answered are recorded, if any (see
+    /// [`Params::record`]).
+    ///
+    /// Only the answers given to the default prompter are recorded (see
+    /// [`Engine::prompter`]).
+    recorded: Option<Arc<Mutex<Session>>>,
+    /// What to notify of the events of the run, if anything.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    observer: Option<Arc<dyn Observer>>,
+    /// The file system where to make the symlinks.
+    ///
+    /// Shared with the workers when processing files in parallel.
+    fs: Arc<dyn Fs>,
+}
+
+/// Builds an [`Engine`] option by option, without going through the
+/// [`Cli`] and the [`Config`] (see [`Engine::builder`]).
+///
+/// The options not set take the value they have when running the app
+/// without options nor configuration file.
+#[derive(Debug, Default)]
+pub struct EngineBuilder {
+    /// The options set so far, as if given on the command line.
+    cli: Cli,
+    /// Same as [`Engine::prompter`].
+    prompter: Option<Arc<dyn Prompter>>,
+    /// Same as [`Engine::output`].
+    out: Option<Output>,
+    /// Same as [`Engine::observer`].
+    observer: Option<Arc<dyn Observer>>,
+}
+
+impl EngineBuilder {
+    /// Sets the directory to scan for symlink-specification files (see
+    /// [`Cli::dir`]). Required.
+    pub fn dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.cli.dir = Some(dir.into());
+        self
+    }
+
+    /// Adds the glob pattern `pattern` to the ones the base of the
+    /// symlink-specification files must match (see [`Cli::filename`]).
+    pub fn filename<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.cli.filename.push(pattern.into());
+        self
+    }
+
+    /// Sets the directory where to back up the conflicting files (see
+    /// [`Cli::backup_dir`]).
+    pub fn backup_dir<P: Into<PathBuf>>(mut self, backup_dir: P) -> Self {
+        self.cli.backup_dir = Some(backup_dir.into());
+        self
+    }
+
+    /// Sets the action to take for every conflict, instead of asking (see
+    /// [`Cli::always_skip`], [`Cli::always_backup`],
+    /// [`Cli::always_overwrite`], [`Cli::always_adopt`] and [`Cli::relink`]).
+    pub fn default_action(mut self, action: PolicyAction) -> Self {
+        self.cli.always_skip = action == PolicyAction::Skip;
+        self.cli.always_backup = action == PolicyAction::Backup;
+        self.cli.always_overwrite = action == PolicyAction::Overwrite;
+        self.cli.always_adopt = action == PolicyAction::Adopt;
+        self.cli.relink = action == PolicyAction::Relink;
+        self
+    }
+
+    /// Sets whether to only print what would be done (see [`Cli::dry_run`]).
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.cli.dry_run = dry_run;
+        self
+    }
+
+    /// Same as [`Engine::prompter`].
+    pub fn prompter<P: Prompter + 'static>(mut self, prompter: P) -> Self {
+        self.prompter = Some(Arc::new(prompter));
+        self
+    }
+
+    /// Same as [`Engine::output`].
+    pub fn output<W: Write + Send + 'static>(mut self, out: W) -> Self {
+        self.out = Some(Output::new(out));
+        self
+    }
+
+    /// Same as [`Engine::observer`].
+    pub fn observer<O: Observer + 'static>(mut self, observer: O) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Builds the engine.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the options are invalid (see [`Params::new`]), e.g. when
+    /// the directory is not set.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn build(self) -> crate::Result<Engine> {
+        let mut engine = Engine::new(Params::new(self.cli, Config::default())?);
+        if let Some(prompter) = self.prompter {
+            engine.prompter = prompter;
+        }
+        if let Some(out) = self.out {
+            engine.out = out;
+        }
+        engine.observer = self.observer;
+
+        Ok(engine)
+    }
 }
 
 impl Engine {
+    /// Returns a builder to create an engine without going through the
+    /// [`Cli`] and the [`Config`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mksls::cfg::PolicyAction;
+    /// use mksls::engine::Engine;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let summary = Engine::builder()
+    ///     .dir("/home/me/.dotfiles")
+    ///     .filename("sls")
+    ///     .backup_dir("/tmp/backups")
+    ///     .default_action(PolicyAction::Backup)
+    ///     .dry_run(true)
+    ///     .build()?
+    ///     .run()?;
+    /// println!("{}", summary);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
     /// Creates an engine.
     ///
     /// # Parameters
@@ -89,85 +801,770 @@ impl Engine {
         if params.always_backup {
             action = Some(Action::Backup);
         }
+        if params.always_overwrite {
+            action = Some(Action::Overwrite);
+        }
+        if params.always_adopt {
+            action = Some(Action::Adopt);
+        }
+
+        let transaction = params.transactional.then(|| Transaction {
+            operations: vec![],
+            save_dir: params
+                .backup_dir
+                .join(format!(".transaction_{}", process::id())),
+        });
+
+        // In porcelain mode, only the porcelain format is printed.
+        let (out, porcelain) = if params.porcelain {
+            (Output::new(io::sink()), Some(Output::new(io::stdout())))
+        } else {
+            (Output::new(io::stdout()), None)
+        };
+        let report = params.report.is_some().then(Default::default);
+        let postponed = params.resolve_at_end.then(Vec::new);
+        let recorded = params
+            .record
+            .is_some()
+            .then(|| Arc::new(Mutex::new(Session::default())));
+        let mut prompter = match &params.answers {
+            Some(answers) => TerminalPrompter::new(Input::answers(answers.clone())),
+            None => TerminalPrompter::default(),
+        }
+        .noninteractive(params.noninteractive_action);
+        if let Some(session) = &params.replay {
+            prompter = prompter.replaying(session.clone());
+        }
+        if let Some(recorded) = &recorded {
+            prompter = prompter.recording(Arc::clone(recorded));
+        }
+        let prompter = Arc::new(prompter);
+        let canonical_dir = fs::canonicalize(&params.dir).unwrap_or_else(|_| params.dir.clone());
+        let fingerprint = cache::fingerprint(&params);
+        let run_id = backups::run_name(&params.backup_dir);
 
-        Self { action, params }
+        Self {
+            action,
+            file_action: None,
+            file_tags: vec![],
+            file_vars: params.vars.clone(),
+            file_guard: None,
+            file_blocks: vec![],
+            relink: params.relink,
+            params,
+            summary: Summary::default(),
+            processed: Arc::new(Mutex::new(HashSet::new())),
+            include_stack: vec![],
+            included: vec![],
+            transaction,
+            manifest: Arc::new(Mutex::new(Manifest::default())),
+            canonical_dir,
+            skipped: Arc::new(Mutex::new(Skipped::default())),
+            cache: Arc::new(Mutex::new(Cache::default())),
+            fingerprint,
+            progress: None,
+            specified: None,
+            resolving: None,
+            spec_line: (PathBuf::new(), 0),
+            lint: false,
+            failures: vec![],
+            conflicts: None,
+            pending: None,
+            resolutions: HashMap::new(),
+            postponed,
+            root_commands: Arc::new(Mutex::new(vec![])),
+            logger: Arc::new(Logger::default()),
+            run_id,
+            backup_index: Arc::new(Mutex::new(backups::Index::default())),
+            prompter,
+            out,
+            porcelain,
+            report,
+            recorded,
+            observer: None,
+            fs: Arc::new(RealFs),
+        }
     }
 
-    /// Processes a symlink-specification file (`sls`).
+    /// Makes the engine ask for decisions with `prompter` instead of on the
+    /// terminal.
     ///
-    /// Reads `sls` line-by-line, creates the symlinks corresponding
-    /// to the symlink specifications found.
+    /// # Parameters
+    ///
+    /// - `prompter`: How to ask for decisions (e.g. what to do with a file
+    ///   that is in the way of a symlink).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    /// use mksls::prompt::TerminalPrompter;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params).prompter(TerminalPrompter::default());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prompter<P: Prompter + 'static>(mut self, prompter: P) -> Self {
+        self.prompter = Arc::new(prompter);
+        self
+    }
+
+    /// Makes the engine print what it does to `out` instead of stdout (the
+    /// prompts are left to the prompter, see [`Engine::prompter`]).
+    ///
+    /// With [`Params::porcelain`], it is the porcelain format that is printed
+    /// to `out`.
     ///
     /// # Parameters
     ///
-    /// - `sls`: Path to the symlink-specification file.
+    /// - `out`: Where to print what is done (e.g. a file, or a buffer to
+    ///   capture the output).
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// Fails when:
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    /// use std::fs::File;
     ///
-    /// - Opening for read of `sls` fails.
-    /// - Reading a line fails.
-    /// - Processing a line fails (see [`Engine::process_line`]).
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params).output(File::create("/tmp/mksls.out")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn output<W: Write + Send + 'static>(mut self, out: W) -> Self {
+        match &mut self.porcelain {
+            Some(porcelain) => *porcelain = Output::new(out),
+            None => self.out = Output::new(out),
+        }
+        self
+    }
+
+    /// Makes the engine notify `observer` of the events of the run (see
+    /// [`crate::event`]), in addition to printing what it does.
     ///
-    /// These are `anyhow` errors, so most of the time, you just want to
-    /// propagate them.
-    fn process_file(&mut self, sls: PathBuf) -> anyhow::Result<()> {
-        let file = fs::File::open(&sls).with_context(|| {
-            format!("Tried to open {}, but unexpectedly failed.", sls.display())
-        })?;
-        let reader = io::BufReader::new(file);
+    /// # Parameters
+    ///
+    /// - `observer`: What to notify of the events.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::event::{Event, Observer};
+    /// use mksls::params::Params;
+    ///
+    /// #[derive(Debug)]
+    /// struct Printer;
+    ///
+    /// impl Observer for Printer {
+    ///     fn notify(&self, event: &Event) {
+    ///         eprintln!("{:?}", event);
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params).observer(Printer);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn observer<O: Observer + 'static>(mut self, observer: O) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
 
-        for (i, line) in reader.lines().enumerate() {
-            let line_no = (i + 1) as u64;
-            let line = line.with_context(|| {
-                format!("Error reading line {} of file {}.", line_no, sls.display())
-            })?;
+    /// Makes the engine make the symlinks in `fs` instead of the real file
+    /// system (see [`crate::filesystem`] for what goes through it).
+    #[cfg(test)]
+    pub(crate) fn filesystem<F: Fs + 'static>(mut self, fs: F) -> Self {
+        self.fs = Arc::new(fs);
+        self
+    }
 
-            self.process_line(&sls, line_no, line)?;
+    /// Creates a worker, i.e. an engine sharing the processed files with
+    /// `self`, to process files in another thread.
+    fn worker(&self) -> Self {
+        Self {
+            action: self.action,
+            file_action: None,
+            file_tags: vec![],
+            file_vars: self.params.vars.clone(),
+            file_guard: None,
+            file_blocks: vec![],
+            relink: self.relink,
+            params: self.params.clone(),
+            summary: Summary::default(),
+            processed: Arc::clone(&self.processed),
+            include_stack: vec![],
+            included: vec![],
+            transaction: None,
+            manifest: Arc::clone(&self.manifest),
+            canonical_dir: self.canonical_dir.clone(),
+            skipped: Arc::clone(&self.skipped),
+            cache: Arc::clone(&self.cache),
+            fingerprint: self.fingerprint.clone(),
+            progress: self.progress.clone(),
+            specified: None,
+            resolving: self.resolving.clone(),
+            spec_line: (PathBuf::new(), 0),
+            lint: self.lint,
+            failures: vec![],
+            conflicts: None,
+            pending: None,
+            resolutions: self.resolutions.clone(),
+            postponed: None,
+            root_commands: Arc::clone(&self.root_commands),
+            logger: Arc::clone(&self.logger),
+            run_id: self.run_id.clone(),
+            backup_index: Arc::clone(&self.backup_index),
+            prompter: Arc::clone(&self.prompter),
+            out: self.out.clone(),
+            porcelain: self.porcelain.clone(),
+            report: self.report.clone(),
+            recorded: self.recorded.clone(),
+            observer: self.observer.clone(),
+            fs: Arc::clone(&self.fs),
         }
-
-        Ok(())
     }
 
-    /// Processes a `line` from a symlink-specification file.
+    /// Processes a symlink-specification file (`sls`).
     ///
-    /// The processing depends on the [`line::LineType`] of `line`.
+    /// Reads `sls` line-by-line (or as TOML if it has a `.toml` extension, see
+    /// [`Engine::process_toml_file`]), creates the symlinks corresponding
+    /// to the symlink specifications found.
     ///
-    /// - If [`line::LineType::Invalid`], errors with an informative message
-    ///   for the user.
-    /// - If [`line::LineType::Empty`], does nothing and returns.
-    /// - If [`line::LineType::Comment`], does nothing and returns.
-    /// - If [`line::LineType::SlsSpec`], tries to make the symlink specified,
-    ///   or runs the interactive machinery in case there exists a conflicting file.
-    ///   Finally, reports to the user what has been done.
+    /// Does nothing if `sls` has already been processed (e.g. because it has
+    /// been included by another file), or if it is unchanged since the last
+    /// run processing it and only the changed files are to be processed (see
+    /// [`Params::changed_only`]). Otherwise, once a file that is not included
+    /// has been processed, it is cached with what has been done for it (see
+    /// [`Engine::cache`]), and the progress of the run is saved (see
+    /// [`Engine::save_progress`]).
     ///
     /// # Parameters
     ///
-    /// - `sls`: Path to the symlink-specification file where `line` lives.
-    /// - `line_no`: The line number of `line` in `sls`.
-    /// - `line`: Contents of the line to process.
+    /// - `sls`: Path to the symlink-specification file.
     ///
     /// # Errors
     ///
     /// Fails when:
     ///
-    /// - `line` is of type [`line::LineType::Invalid`].
-    /// - Symlink creation faiis.
-    /// - Reading conflicting file/symlink fails.
-    /// - Reading/writing from/to stdin/stdout fails.
+    /// - Getting the canonical path of `sls` fails.
+    /// - Processing its contents fails (see [`Engine::process_text_file`]
+    ///   and [`Engine::process_toml_file`]).
+    /// - Saving the progress of the run fails.
     ///
-    /// These are `anyhow` errors, so most of the time, you just want to
-    /// propagate them.
-    fn process_line(&mut self, sls: &Path, line_no: u64, line: String) -> anyhow::Result<()> {
-        let stdout = io::stdout();
-        match line::line_type(&line) {
-            LineType::Empty | LineType::Comment => {
-                return Ok(());
-            }
-
-            LineType::Invalid(invalid) => {
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_file(&mut self, sls: PathBuf) -> crate::Result<()> {
+        let canonical_sls = fs::canonicalize(&sls).io_context(|| {
+            format!(
+                "Failed to get the canonical path of {}, needed to not process it twice.",
+                sls.display()
+            )
+        })?;
+        let top_level = self.include_stack.is_empty();
+        if !top_level {
+            self.included.push(canonical_sls.clone());
+        }
+        if !self.processed.lock().unwrap().insert(canonical_sls.clone()) {
+            return Ok(());
+        }
+        // Every file is needed to collect the links specified.
+        if top_level
+            && self.params.changed_only
+            && self.specified.is_none()
+            && self
+                .cache
+                .lock()
+                .unwrap()
+                .is_unchanged(&canonical_sls, &self.fingerprint)
+        {
+            self.log(
+                LogLevel::Debug,
+                &format!(
+                    "Skipping {}, unchanged since the last run.",
+                    sls.to_string_lossy()
+                ),
+            );
+            return Ok(());
+        }
+        self.log(
+            LogLevel::Debug,
+            &format!("Processing {}.", sls.to_string_lossy()),
+        );
+        self.notify(Event::FileStarted { sls: sls.clone() });
+        // The files included are part of the including one.
+        let grouped = self.params.group_by_file && top_level;
+        if grouped {
+            let name = sls.strip_prefix(&self.params.dir).unwrap_or(&sls);
+            writeln!(self.out)?;
+            writeln!(self.out, "{}", format!("== {} ==", name.display()).bold())?;
+        }
+
+        let sls_dir = sls.parent().unwrap_or(Path::new("."));
+        let sls_dir = path::absolute(sls_dir).io_context(|| {
+            format!(
+                "Failed to get the absolute path of {}, needed to resolve relative paths in {}.",
+                sls_dir.display(),
+                sls.display()
+            )
+        })?;
+
+        self.include_stack.push(canonical_sls.clone());
+        if let Some(progress) = &self.progress {
+            let hash = utils::hash_file(&canonical_sls).io_context(|| {
+                format!(
+                    "Failed to hash {}, needed to keep the progress of the run.",
+                    sls.display()
+                )
+            })?;
+            progress.lock().unwrap().start(&canonical_sls, &hash);
+        }
+        // The action chosen for the including file doesn't apply to this one.
+        let including_file_action = self.file_action.take();
+        let including_file_tags = std::mem::take(&mut self.file_tags);
+        let including_file_vars = std::mem::replace(&mut self.file_vars, self.params.vars.clone());
+        let including_file_guard = self.file_guard.take();
+        let including_file_blocks = std::mem::take(&mut self.file_blocks);
+        let total = top_level.then(|| std::mem::take(&mut self.summary));
+        let result = if sls.extension().is_some_and(|ext| ext == "toml") {
+            self.process_toml_file(&sls, &sls_dir)
+        } else {
+            self.process_text_file(&sls, &sls_dir)
+        };
+        self.include_stack.pop();
+        self.file_action = including_file_action;
+        self.file_tags = including_file_tags;
+        self.file_vars = including_file_vars;
+        self.file_guard = including_file_guard;
+        self.file_blocks = including_file_blocks;
+        if let Some(total) = total {
+            let file_summary = std::mem::replace(&mut self.summary, total);
+            self.summary += file_summary;
+            if grouped {
+                writeln!(
+                    self.out,
+                    "{}",
+                    format!("-- {}", file_summary.counts()).dark_grey()
+                )?;
+            }
+            let included = std::mem::take(&mut self.included);
+            if result.is_ok() {
+                self.cache(&canonical_sls, &included, file_summary);
+                self.save_progress()?;
+            }
+        }
+
+        result
+    }
+
+    /// Caches the (canonical) symlink-specification file `sls` just
+    /// processed, along with the files it `included` and `outcome`, what has
+    /// been done for it (see [`Cache`]).
+    ///
+    /// Nothing is cached when the file has not been processed for making
+    /// every link it specifies, e.g. in dry-run mode or when only some links
+    /// are made (see [`Params::only`] and [`Params::tags`]). A file that can't
+    /// be read anymore is removed from the cache instead.
+    fn cache(&self, sls: &Path, included: &[PathBuf], outcome: Summary) {
+        if self.params.dry_run
+            || self.params.resume
+            || !self.params.only.is_empty()
+            || !self.params.tags.is_empty()
+            || self.resolving.is_some()
+            || self.specified.is_some()
+            || self.pending.is_some()
+        {
+            return;
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        match [sls.to_path_buf()]
+            .iter()
+            .chain(included)
+            .map(|path| FileState::of(path))
+            .collect()
+        {
+            Ok(files) => cache.add(
+                sls,
+                CachedFile {
+                    files,
+                    params: self.fingerprint.clone(),
+                    outcome,
+                },
+            ),
+            Err(_) => cache.remove(sls),
+        }
+    }
+
+    /// Processes a plain-text symlink-specification file, line by line (see
+    /// [`Engine::process_line`]).
+    ///
+    /// A line ending with a backslash is continued on the next one (see
+    /// [`line::is_continued`]), the whole being processed as one line,
+    /// reported with the number of the line it starts at. A line that is not
+    /// valid UTF-8 is handled as an invalid line.
+    ///
+    /// # Parameters
+    ///
+    /// - `sls`: Path to the symlink-specification file.
+    /// - `sls_dir`: Absolute path to the directory containing `sls`, against
+    ///   which relative paths in `sls` are resolved.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Opening for read of `sls` fails.
+    /// - Reading a line fails.
+    /// - Processing a line fails (see [`Engine::process_line`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_text_file(&mut self, sls: &Path, sls_dir: &Path) -> crate::Result<()> {
+        let file = fs::File::open(sls)
+            .io_context(|| format!("Tried to open {}, but unexpectedly failed.", sls.display()))?;
+        let reader = io::BufReader::new(file);
+
+        // The line being continued, with the number of the line it starts at.
+        let mut continued: Option<(u64, Vec<u8>)> = None;
+        for (i, line) in reader.split(b'\n').enumerate() {
+            let line_no = (i + 1) as u64;
+            let mut line = line.io_context(|| {
+                format!("Error reading line {} of file {}.", line_no, sls.display())
+            })?;
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            let (line_no, mut line) = match continued.take() {
+                Some((start_no, mut start)) => {
+                    start.append(&mut line);
+                    (start_no, start)
+                }
+                None => (line_no, line),
+            };
+            if line::is_continued(&line) {
+                line.pop();
+                continued = Some((line_no, line));
+                continue;
+            }
+
+            self.process_bytes(sls, sls_dir, line_no, line)?;
+        }
+        // The last line of the file ended with a backslash.
+        if let Some((line_no, line)) = continued {
+            self.process_bytes(sls, sls_dir, line_no, line)?;
+        }
+        for (line_no, _) in std::mem::take(&mut self.file_blocks) {
+            let result = self.process_spec(sls, line_no, LineType::Invalid(Invalid::UnclosedBlock));
+            self.keep_going(result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes a `line` from a plain-text symlink-specification file, as
+    /// read (see [`Engine::process_line`]), handling it as an invalid line if
+    /// it is not valid UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// Fails when processing the line fails and the processing can't go on
+    /// (see [`Engine::keep_going`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_bytes(
+        &mut self,
+        sls: &Path,
+        sls_dir: &Path,
+        line_no: u64,
+        line: Vec<u8>,
+    ) -> crate::Result<()> {
+        let result = match String::from_utf8(line) {
+            Ok(line) => self.process_line(sls, sls_dir, line_no, line),
+            Err(_) => {
+                self.process_line_of_type(sls, line_no, "", LineType::Invalid(Invalid::NotUtf8))
+            }
+        };
+        self.keep_going(result)?;
+        self.progressed(sls, line_no);
+
+        Ok(())
+    }
+
+    /// Processes a symlink-specification file written in TOML (see [`crate::sls_toml`]).
+    ///
+    /// Each specification in the file is processed like a line of a
+    /// plain-text symlink-specification file (see [`Engine::process_spec`]).
+    /// If the file can't be parsed, errors with an informative message for
+    /// the user.
+    ///
+    /// # Parameters
+    ///
+    /// - `sls`: Path to the symlink-specification file.
+    /// - `sls_dir`: Absolute path to the directory containing `sls`, against
+    ///   which relative paths in `sls` are resolved.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading `sls` fails.
+    /// - Processing one of its specifications fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_toml_file(&mut self, sls: &Path, sls_dir: &Path) -> crate::Result<()> {
+        let contents = fs::read_to_string(sls)
+            .io_context(|| format!("Tried to read {}, but unexpectedly failed.", sls.display()))?;
+
+        match sls_toml::specs(
+            &contents,
+            sls_dir,
+            self.params.allow_missing_targets,
+            &self.file_vars,
+        ) {
+            Ok(specs) => {
+                for (line_no, spec) in specs {
+                    let result = self.process_spec(sls, line_no, spec);
+                    self.keep_going(result)?;
+                    self.progressed(sls, line_no);
+                }
+            }
+            Err(err) => {
+                let err_mess = format!(
+                    "Invalid TOML symlink-specification file {}.
+    {}",
+                    sls.to_string_lossy(),
+                    err.to_string().trim_end().replace('\n', "\n    ")
+                );
+                self.invalid(sls, None, err_mess)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Processes a `line` from a symlink-specification file.
+    ///
+    /// See [`Engine::process_spec`] for how it is processed depending on its
+    /// [`line::LineType`].
+    ///
+    /// A tags directive sets the tags of the following lines of the file, and
+    /// a set directive defines a variable for them, unless defined by
+    /// [`Params::cli_vars`]. A line tagged (see [`line::tags`]) with none of
+    /// [`Params::tags`] is ignored.
+    ///
+    /// A guard applies to the next line that is neither empty nor a comment,
+    /// or to the lines of the block it opens, which are ignored if it doesn't
+    /// hold (but checked anyway when linting, see [`Engine::lint`]). A block
+    /// left open at the end of the file is reported as invalid (see
+    /// [`Engine::process_text_file`]).
+    ///
+    /// # Parameters
+    ///
+    /// - `sls`: Path to the symlink-specification file where `line` lives.
+    /// - `sls_dir`: Absolute path to the directory containing `sls`, against
+    ///   which relative paths in `line` are resolved.
+    /// - `line_no`: The line number of `line` in `sls`.
+    /// - `line`: Contents of the line to process.
+    ///
+    /// # Errors
+    ///
+    /// Fails when processing the specification fails (see [`Engine::process_spec`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_line(
+        &mut self,
+        sls: &Path,
+        sls_dir: &Path,
+        line_no: u64,
+        line: String,
+    ) -> crate::Result<()> {
+        let spec = line::line_type(
+            &line,
+            sls_dir,
+            self.params.allow_missing_targets,
+            &self.file_vars,
+        );
+        self.process_line_of_type(sls, line_no, &line, spec)
+    }
+
+    /// Processes the line `line_no` of `sls`, of contents `line` (empty if not
+    /// valid UTF-8) and of type `spec` (see [`Engine::process_line`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when processing the specification fails (see [`Engine::process_spec`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_line_of_type(
+        &mut self,
+        sls: &Path,
+        line_no: u64,
+        line: &str,
+        spec: LineType,
+    ) -> crate::Result<()> {
+        tracing::debug!("{}, line {}: {:?}", sls.display(), line_no, spec);
+        match spec {
+            LineType::Empty | LineType::Comment => {}
+            LineType::Guard { holds, block } => {
+                // A guard guarding another one guards what the latter guards.
+                let holds = holds && self.file_guard.take().unwrap_or(true);
+                if block {
+                    self.file_blocks.push((line_no, holds));
+                } else {
+                    self.file_guard = Some(holds);
+                }
+                return Ok(());
+            }
+            LineType::BlockEnd => {
+                self.file_guard = None;
+                if self.file_blocks.pop().is_some() {
+                    return Ok(());
+                }
+                return self.process_spec(sls, line_no, LineType::Invalid(Invalid::UnopenedBlock));
+            }
+            _ => {
+                let holds = self.file_guard.take().unwrap_or(true)
+                    && self.file_blocks.iter().all(|(_, holds)| *holds);
+                // The guard may hold on another machine, so the line is
+                // checked anyway when linting.
+                let checked = self.lint && matches!(spec, LineType::Invalid(_));
+                if !holds && !checked {
+                    self.log(
+                        LogLevel::Debug,
+                        &format!(
+                            "Skipping line {} of {}, guarded by a condition that doesn't hold.",
+                            line_no,
+                            sls.to_string_lossy()
+                        ),
+                    );
+                    return Ok(());
+                }
+            }
+        }
+        match spec {
+            LineType::Tags(tags) => {
+                self.file_tags = tags;
+                return Ok(());
+            }
+            LineType::Set { name, value } => {
+                if !self.params.cli_vars.contains_key(&name) {
+                    self.file_vars.insert(name, value);
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        if !self.params.tags.is_empty() {
+            let mut tags = line::tags(line);
+            tags.extend(self.file_tags.iter().cloned());
+            if !tags.is_empty() && !tags.iter().any(|tag| self.params.tags.contains(tag)) {
+                return Ok(());
+            }
+        }
+
+        self.process_spec(sls, line_no, spec)
+    }
+
+    /// Processes a specification from a symlink-specification file.
+    ///
+    /// The processing depends on the [`line::LineType`] of the specification.
+    ///
+    /// Does nothing if the specification has been processed by the
+    /// interrupted run being resumed (see [`Params::resume`]).
+    ///
+    /// - If [`line::LineType::Invalid`], errors with an informative message
+    ///   for the user.
+    /// - If [`line::LineType::Empty`], does nothing and returns.
+    /// - If [`line::LineType::Comment`], does nothing and returns.
+    /// - If [`line::LineType::Tags`], [`line::LineType::Set`],
+    ///   [`line::LineType::Guard`] or [`line::LineType::BlockEnd`], does
+    ///   nothing and returns (see [`Engine::process_line`]).
+    /// - If [`line::LineType::SlsSpec`], makes the symlink specified
+    ///   (see [`Engine::make_link`]).
+    /// - If [`line::LineType::HardlinkSpec`], makes the hard link specified
+    ///   (see [`Engine::make_link`]).
+    /// - If [`line::LineType::CopySpec`], makes the copy specified
+    ///   (see [`Engine::make_link`]).
+    /// - If [`line::LineType::TemplateSpec`], renders the template specified
+    ///   (see [`crate::template::render`]) and writes the result at the link path
+    ///   (see [`Engine::make_link`]). If rendering fails, errors with an
+    ///   informative message for the user.
+    /// - If [`line::LineType::SlsAdoptableSpec`], adopts the file at the link
+    ///   path (see [`Engine::adopt`]) if always adopting (or a policy says
+    ///   so, see [`Engine::policy`]), or if the user confirms. Otherwise, makes the symlink
+    ///   with [`Params::allow_missing_targets`], or handles it as an invalid line whose target
+    ///   doesn't exist.
+    /// - If [`line::LineType::SlsGlobSpec`], makes one symlink per target
+    ///   matched, in the link directory.
+    /// - If [`line::LineType::Include`], processes the included file (see
+    ///   [`Engine::process_file`]), unless that would make an include cycle,
+    ///   in which case errors with an informative message for the user.
+    /// - If [`line::LineType::SlsDirSpec`], makes one symlink per file in the
+    ///   target directory, mirroring its tree in the link directory (creating
+    ///   intermediate directories as needed).
+    ///
+    /// # Parameters
+    ///
+    /// - `sls`: Path to the symlink-specification file where the specification lives.
+    /// - `line_no`: The line number of the specification in `sls`.
+    /// - `spec`: The specification to process.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Symlink creation faiis.
+    /// - Reading conflicting file/symlink fails.
+    /// - Reading/writing from/to stdin/stdout fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_spec(&mut self, sls: &Path, line_no: u64, spec: LineType) -> crate::Result<()> {
+        if self.summary.quit || self.resumed(line_no) {
+            return Ok(());
+        }
+        self.spec_line = (sls.to_path_buf(), line_no);
+
+        match spec {
+            LineType::Empty
+            | LineType::Comment
+            | LineType::Tags(_)
+            | LineType::Set { .. }
+            | LineType::Guard { .. }
+            | LineType::BlockEnd => {
+                return Ok(());
+            }
+
+            LineType::Invalid(invalid) => {
                 let err_mess = match invalid {
+                    Invalid::Malformed(reason) => format!(
+                        "Invalid line in {}, line number {}.
+    {}",
+                        sls.to_string_lossy(),
+                        line_no,
+                        reason
+                    ),
                     Invalid::NoMatch => format!(
                         "Invalid line in {}, line number {}.
     Can't match up against the symlink specification format.",
@@ -180,67 +1577,229 @@ impl Engine {
                         sls.to_string_lossy(),
                         line_no
                     ),
+                    Invalid::TargetIsNotADirectory => format!(
+                        "Invalid line in {}, line number {}.
+    The target is not a directory, but a directory specification (dir) requires one.",
+                        sls.to_string_lossy(),
+                        line_no
+                    ),
+                    Invalid::IncludedFileDoesNotExist => format!(
+                        "Invalid line in {}, line number {}.
+    The file to include does not exist.",
+                        sls.to_string_lossy(),
+                        line_no
+                    ),
+                    Invalid::TargetIsADirectory => format!(
+                        "Invalid line in {}, line number {}.
+    The target is a directory, but hard link (hard), copy (copy) and template (template) specifications can't have one.",
+                        sls.to_string_lossy(),
+                        line_no
+                    ),
+                    Invalid::InvalidGlob(err) => format!(
+                        "Invalid line in {}, line number {}.
+    The target is an invalid glob pattern: {}.",
+                        sls.to_string_lossy(),
+                        line_no,
+                        err
+                    ),
+                    Invalid::UnsetEnvVar(var) => format!(
+                        "Invalid line in {}, line number {}.
+    The environment variable {} is not set.",
+                        sls.to_string_lossy(),
+                        line_no,
+                        var
+                    ),
+                    Invalid::UnavailableVar(var) => format!(
+                        "Invalid line in {}, line number {}.
+    The value of the built-in variable {} can't be found.",
+                        sls.to_string_lossy(),
+                        line_no,
+                        var
+                    ),
+                    Invalid::UndefinedVar(var) => format!(
+                        "Invalid line in {}, line number {}.
+    The variable {} is not defined (by a set directive, the config file or --var), nor built-in.",
+                        sls.to_string_lossy(),
+                        line_no,
+                        var
+                    ),
+                    Invalid::UnopenedBlock => format!(
+                        "Invalid line in {}, line number {}.
+    There is no block opened by a guard (ifexists or ifcmd) to close.",
+                        sls.to_string_lossy(),
+                        line_no
+                    ),
+                    Invalid::UnclosedBlock => format!(
+                        "Invalid line in {}, line number {}.
+    The block opened by this guard is never closed (with a }} line).",
+                        sls.to_string_lossy(),
+                        line_no
+                    ),
+                    Invalid::InvalidVarName(name) => format!(
+                        "Invalid line in {}, line number {}.
+    {} is not a valid variable name: it must be made of letters, digits and underscores, and not start with a digit.",
+                        sls.to_string_lossy(),
+                        line_no,
+                        name
+                    ),
+                    Invalid::NotUtf8 => format!(
+                        "Invalid line in {}, line number {}.
+    The line is not valid UTF-8. A path that is not can be given through an environment variable, or matched by a glob pattern or a directory specification.",
+                        sls.to_string_lossy(),
+                        line_no
+                    ),
                 };
-                prompt::error_prompt(&err_mess)?;
+                self.invalid(sls, Some(line_no), err_mess)?;
             }
 
             LineType::SlsSpec { target, link } => {
-                let link_str = link.to_string_lossy();
+                self.make_link(&self.plain_kind(&target), &target, &link)?;
+            }
 
-                if !link.is_symlink() && !link.exists() {
-                    unix::fs::symlink(&target, &link).with_context(|| {
-                        format!(
-                            "Failed to create {} -> {}",
-                            link_str,
-                            target.to_string_lossy()
-                        )
-                    })?;
-                    println!("(d) {} -> {}", link_str, target.to_string_lossy());
-                    return Ok(());
+            LineType::SlsAdoptableSpec { target, link } => {
+                if self.lint && !self.params.allow_missing_targets {
+                    return self.process_spec(
+                        sls,
+                        line_no,
+                        LineType::Invalid(Invalid::TargetDoesNotExist),
+                    );
                 }
-
-                if link.is_symlink()
-                    && fs::read_link(&link).with_context(|| format!("A symlink of path {} already exists, but failed to read it to check if it is the one you want to create or not.
-Nothing was done. Check for a problem and rerun this program.", link_str))?
-                        == target
-                {
-                    println!("{}", format!("(.) {} -> {}", link_str, target.to_string_lossy()).dark_grey());
+                if self.specified.is_some() {
+                    return self.make_link(&self.plain_kind(&target), &target, &link);
+                }
+                if self.pending.is_some() || !self.selected(&link) {
                     return Ok(());
                 }
-
-                if let Some(ref action) = self.action {
-                    match action {
-                        Action::Skip => utils::skip(stdout, &target, &link)?,
-                        Action::Backup => {
-                            utils::backup(stdout, &self.params.backup_dir, &target, &link)?
-                        }
-                        Action::Overwrite => utils::overwrite(stdout, &target, &link)?,
+                let adopt = match (self.policy(&link), self.file_action.or(self.action)) {
+                    (Some(action), _) => action == PolicyAction::Adopt,
+                    (None, Some(action)) => matches!(action, Action::Adopt),
+                    (None, None) => self.prompter.confirm_adopt(&target, &link)?,
+                };
+                if adopt {
+                    if self.refuse_protected(&target, &link)? {
+                        return Ok(());
                     }
-                    return Ok(());
+                    self.adopt(&self.plain_kind(&target), &target, &link)?;
+                } else if self.params.allow_missing_targets {
+                    self.make_link(&self.plain_kind(&target), &target, &link)?;
+                } else {
+                    self.process_spec(
+                        sls,
+                        line_no,
+                        LineType::Invalid(Invalid::TargetDoesNotExist),
+                    )?;
                 }
+            }
 
-                match prompt::already_exist_prompt(&target.to_string_lossy(), &link_str)? {
-                    AlreadyExistPromptOptions::Skip => {
-                        utils::skip(stdout, &target, &link)?;
-                    }
-                    AlreadyExistPromptOptions::AlwaysSkip => {
-                        utils::skip(stdout, &target, &link)?;
-                        self.action = Some(Action::Skip);
-                    }
-                    AlreadyExistPromptOptions::Backup => {
-                        utils::backup(stdout, &self.params.backup_dir, &target, &link)?
+            LineType::HardlinkSpec { target, link } => {
+                self.make_link(&LinkKind::Hardlink, &target, &link)?;
+            }
+
+            LineType::CopySpec { target, link } => {
+                self.make_link(&LinkKind::Copy, &target, &link)?;
+            }
+
+            LineType::TemplateSpec { target, link } => {
+                let template = fs::read_to_string(&target).io_context(|| {
+                    format!(
+                        "Failed to read template {}. Note that it should be valid UTF-8.",
+                        target.display()
+                    )
+                })?;
+                match template::render(&template, &self.file_vars) {
+                    Ok(rendered) => {
+                        self.make_link(&LinkKind::Rendered(rendered), &target, &link)?;
                     }
-                    AlreadyExistPromptOptions::AlwaysBackup => {
-                        utils::backup(stdout, &self.params.backup_dir, &target, &link)?;
-                        self.action = Some(Action::Backup);
+                    Err(err) => {
+                        let err_mess = format!(
+                            "Invalid line in {}, line number {}.
+    Failed to render template {}: {}",
+                            sls.to_string_lossy(),
+                            line_no,
+                            target.to_string_lossy(),
+                            err
+                        );
+                        self.invalid(sls, Some(line_no), err_mess)?;
                     }
-                    AlreadyExistPromptOptions::Overwrite => {
-                        utils::overwrite(stdout, &target, &link)?;
+                }
+            }
+
+            LineType::SlsGlobSpec { targets, link_dir } => {
+                for target in targets {
+                    // A glob match can't end with `..`, so there is always a file name.
+                    let link = link_dir.join(target.file_name().unwrap());
+                    let result = self.make_link(&self.plain_kind(&target), &target, &link);
+                    self.keep_going(result)?;
+                }
+            }
+
+            LineType::Include(included) => {
+                let canonical_included = fs::canonicalize(&included).io_context(|| {
+                    format!(
+                        "Failed to get the canonical path of {}, needed to detect include cycles.",
+                        included.display()
+                    )
+                })?;
+                match self
+                    .include_stack
+                    .iter()
+                    .position(|sls| *sls == canonical_included)
+                {
+                    Some(i) => {
+                        let cycle: Vec<String> = self.include_stack[i..]
+                            .iter()
+                            .chain([&canonical_included])
+                            .map(|sls| sls.to_string_lossy().into_owned())
+                            .collect();
+                        let err_mess = format!(
+                            "Invalid line in {}, line number {}.
+    Including {} would make an include cycle:
+        {}",
+                            sls.to_string_lossy(),
+                            line_no,
+                            included.to_string_lossy(),
+                            cycle.join("\n        -> ")
+                        );
+                        self.invalid(sls, Some(line_no), err_mess)?;
                     }
-                    AlreadyExistPromptOptions::AlwaysOverwrite => {
-                        utils::overwrite(stdout, &target, &link)?;
-                        self.action = Some(Action::Overwrite);
+                    None => self.process_file(included)?,
+                }
+            }
+
+            LineType::SlsDirSpec {
+                target_dir,
+                link_dir,
+            } => {
+                let dir = Dir::build(target_dir.clone())?
+                    .follow_links(self.params.follow_links)
+                    .sorted(self.params.sorted);
+                for target in dir.iter_on_files() {
+                    let target = match target {
+                        Ok(target) => target,
+                        Err(err) => {
+                            self.walk_error(err)?;
+                            continue;
+                        }
+                    };
+                    // Files iterated on are within `target_dir`.
+                    let link = link_dir.join(target.strip_prefix(&target_dir).unwrap());
+                    if !self.selected(&link) {
+                        continue;
                     }
+                    let kind = self.plain_kind(&target);
+                    // The symlink is always created in a directory, so there is a parent.
+                    let link_parent = link.parent().unwrap();
+                    let result = self
+                        .create_dir_all(link_parent)
+                        .link_context(|| {
+                            format!(
+                                "Failed to create directory {} to make symlink {} in it.",
+                                link_parent.display(),
+                                link.display()
+                            )
+                        })
+                        .and_then(|_| self.make_link(&kind, &target, &link));
+                    self.keep_going(result)?;
                 }
             }
         }
@@ -248,33 +1807,4131 @@ Nothing was done. Check for a problem and rerun this program.", link_str))?
         Ok(())
     }
 
-    /// Runs the engine.
+    /// Handles an entry that couldn't be read while scanning a directory.
     ///
-    /// # Examples
+    /// Warns the user and moves on, unless in strict mode (see
+    /// [`crate::cli::Cli::strict`]), in which case fails. Doesn't warn when
+    /// looking for conflicting specifications, the run warning afterwards.
     ///
-    /// ```rust,no_run
-    /// use clap::Parser;
-    /// use mksls::cfg::Config;
-    /// use mksls::cli::Cli;
-    /// use mksls::engine::Engine;
-    /// use mksls::params::Params;
+    /// # Parameters
     ///
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let cli = Cli::parse();
-    /// let cfg: Config = confy::load("my_crate", "config")?;
-    /// let params = Params::new(cli, cfg)?;
-    /// let engine = Engine::new(params);
+    /// - `err`: The error that occured reading the entry.
     ///
-    /// engine.run()?;
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn run(mut self) -> anyhow::Result<()> {
-        let dir = Dir::build(self.params.dir.clone())?;
-        for sls in dir.iter_on_sls_files(&self.params.filename[..]) {
-            self.process_file(sls)?;
+    /// # Errors
+    ///
+    /// Fails when in strict mode.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn walk_error(&mut self, err: WalkError) -> crate::Result<()> {
+        if self.params.strict {
+            return Err(err.into());
+        }
+        if self.conflicts.is_some() || self.pending.is_some() {
+            return Ok(());
         }
 
+        self.log(LogLevel::Warn, &err.to_string());
+        eprintln!("{}", format!("(!) Warning: {}", err).yellow());
+        Ok(())
+    }
+
+    /// Makes the link of kind `kind` at path `link`, pointing to `target`.
+    ///
+    /// Runs the interactive machinery in case there exists a conflicting file
+    /// (unless an action to take has already been chosen, or a policy applies
+    /// to `link`, see [`Engine::policy`]).
+    /// With [`Params::relative`], a symlink points to `target` through a path
+    /// relative to its directory.
+    /// Finally, reports to the user what has been done.
+    /// Does nothing once the user quit (see [`Engine::quit`]).
+    ///
+    /// # Parameters
+    ///
+    /// - `kind`: The kind of link to make.
+    /// - `target`: Path to the target of the link.
+    /// - `link`: Path to the link.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Link creation fails.
+    /// - Creating the missing parent directories of the link fails (with [`Params::mkdirs`]).
+    /// - Reading conflicting file/link fails.
+    /// - Reading/writing from/to stdin/stdout fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn make_link(&mut self, kind: &LinkKind, target: &Path, link: &Path) -> crate::Result<()> {
+        if self.summary.quit || !self.selected(link) {
+            return Ok(());
+        }
+        if let Some(specified) = &mut self.specified {
+            let (sls, line_no) = self.spec_line.clone();
+            let Some(first) = specified.get(link).cloned() else {
+                specified.insert(
+                    link.to_path_buf(),
+                    Spec {
+                        target: target.to_path_buf(),
+                        sls,
+                        line_no,
+                    },
+                );
+                return Ok(());
+            };
+            if let Some(conflicts) = &mut self.conflicts {
+                // The same target may be written differently (e.g. `a` and `dir/../a`).
+                let same_target = first.target == target
+                    || matches!(
+                        (fs::canonicalize(&first.target), fs::canonicalize(target)),
+                        (Ok(first_target), Ok(target)) if first_target == target
+                    );
+                if !same_target {
+                    conflicts.push(format!(
+                        "{} -> {} in {}, line number {}, but -> {} in {}, line number {}.",
+                        link.to_string_lossy(),
+                        target.to_string_lossy(),
+                        sls.to_string_lossy(),
+                        line_no,
+                        first.target.to_string_lossy(),
+                        first.sls.to_string_lossy(),
+                        first.line_no
+                    ));
+                }
+            } else if self.lint {
+                let err_mess = format!(
+                    "Invalid line in {}, line number {}.
+    The link {} is already specified in {}, line number {}.",
+                    sls.to_string_lossy(),
+                    line_no,
+                    link.to_string_lossy(),
+                    first.sls.to_string_lossy(),
+                    first.line_no
+                );
+                self.invalid(&sls, Some(line_no), err_mess)?;
+            }
+            return Ok(());
+        }
+
+        let specified_target = target;
+        let relative_target;
+        let target = if self.params.relative && *kind == LinkKind::Symlink {
+            relative_target = utils::relative_target(target, link);
+            relative_target.as_path()
+        } else {
+            target
+        };
+
+        if self.pending.is_some() {
+            return self.collect_pending(kind, target, link);
+        }
+
+        let link_str = link.to_string_lossy();
+
+        if !self.fs.exists(link) {
+            if self.params.dry_run {
+                return self.dry_run(event::Action::Created, target, link);
+            }
+            if self.params.mkdirs {
+                if let Some(link_parent) = link.parent() {
+                    self.create_dir_all(link_parent).link_context(|| {
+                        format!(
+                            "Failed to create directory {} to make {} in it.",
+                            link_parent.display(),
+                            link_str
+                        )
+                    })?;
+                }
+            }
+            let made = kind.make(&*self.fs, target, link);
+            if let Err(err) = &made {
+                if err.kind() == io::ErrorKind::PermissionDenied {
+                    if let Some(command) = kind.command(target, link) {
+                        if self.params.escalate.is_some() || self.params.root_script.is_some() {
+                            return self.make_privileged(kind, target, link, command);
+                        }
+                    }
+                }
+            }
+            made.link_context(|| {
+                format!(
+                    "Failed to create {} -> {}",
+                    link_str,
+                    target.to_string_lossy()
+                )
+            })?;
+            writeln!(self.out, "(d) {} -> {}", link_str, target.to_string_lossy())?;
+            self.summary.created += 1;
+            self.record(Operation::Made {
+                link: link.to_path_buf(),
+            });
+            self.linked(kind, target, link, event::Action::Created)?;
+            return Ok(());
+        }
+
+        if kind.is_made(&*self.fs, target, link, self.params.exact_targets).io_context(|| format!("A file of path {} already exists, but failed to read it to check if it is the link you want to create or not.
+Nothing was done. Check for a problem and rerun this program.", link_str))? {
+            writeln!(self.out, "{}", format!("(.) {} -> {}", link_str, target.to_string_lossy()).dark_grey())?;
+            self.log(
+                LogLevel::Info,
+                &format!("Already existing {} -> {}.", link_str, target.to_string_lossy()),
+            );
+            self.summary.already_existing += 1;
+            self.manage(kind, target, link);
+            self.spec_processed(event::Action::AlreadyExisting, target, link);
+            return Ok(());
+        }
+
+        let stale = *kind == LinkKind::Symlink && self.fs.is_symlink(link);
+        let policy = self
+            .policy(link)
+            .filter(|action| stale || *action != PolicyAction::Relink);
+        if policy.is_none()
+            && stale
+            && self.params.replace_broken
+            && utils::is_broken_symlink(&*self.fs, link)
+        {
+            tracing::debug!("{} is a broken symlink: repairing it.", link_str);
+            if self.refuse_protected(target, link)? {
+                return Ok(());
+            }
+            return self.repair(kind, target, link);
+        }
+        // A relink chosen for the file only applies to symlinks.
+        let file_action = self
+            .file_action
+            .filter(|action| stale || !matches!(action, Action::Relink));
+        let resolution = self.resolutions.get(link).copied();
+        let action = match (policy, resolution.or(file_action).or(self.action)) {
+            (Some(action), _) => action.into(),
+            (None, _) if stale && self.relink => Action::Relink,
+            (None, Some(action)) => action,
+            (None, None) if self.postponed.is_some() => {
+                return self.postpone(kind, specified_target, link, stale)
+            }
+            (None, None) if stale => {
+                let current_target = self.fs.read_link(link).link_context(|| {
+                    format!(
+                        "Failed to read symlink {} conflicting with the one you want to create.",
+                        link_str
+                    )
+                })?;
+                match self
+                    .prompter
+                    .resolve_stale_symlink(target, link, &current_target)?
+                {
+                    StaleSymlinkPromptOptions::Relink => Action::Relink,
+                    StaleSymlinkPromptOptions::AlwaysRelink => {
+                        self.relink = true;
+                        Action::Relink
+                    }
+                    StaleSymlinkPromptOptions::Skip => Action::Skip,
+                    StaleSymlinkPromptOptions::AlwaysSkip => *self.action.insert(Action::Skip),
+                    StaleSymlinkPromptOptions::Backup => Action::Backup,
+                    StaleSymlinkPromptOptions::AlwaysBackup => *self.action.insert(Action::Backup),
+                    StaleSymlinkPromptOptions::FileRelink => {
+                        *self.file_action.insert(Action::Relink)
+                    }
+                    StaleSymlinkPromptOptions::FileSkip => *self.file_action.insert(Action::Skip),
+                    StaleSymlinkPromptOptions::FileBackup => {
+                        *self.file_action.insert(Action::Backup)
+                    }
+                    StaleSymlinkPromptOptions::Quit => return self.quit(),
+                }
+            }
+            (None, None) => match self
+                .prompter
+                .resolve_conflict(target, link, &|| utils::diff(kind, target, link))?
+            {
+                AlreadyExistPromptOptions::Skip => Action::Skip,
+                AlreadyExistPromptOptions::AlwaysSkip => *self.action.insert(Action::Skip),
+                AlreadyExistPromptOptions::Backup => Action::Backup,
+                AlreadyExistPromptOptions::AlwaysBackup => *self.action.insert(Action::Backup),
+                AlreadyExistPromptOptions::Overwrite => Action::Overwrite,
+                AlreadyExistPromptOptions::AlwaysOverwrite => {
+                    *self.action.insert(Action::Overwrite)
+                }
+                AlreadyExistPromptOptions::Rename => Action::Rename,
+                AlreadyExistPromptOptions::AlwaysRename => *self.action.insert(Action::Rename),
+                AlreadyExistPromptOptions::Adopt => Action::Adopt,
+                AlreadyExistPromptOptions::AlwaysAdopt => *self.action.insert(Action::Adopt),
+                AlreadyExistPromptOptions::FileSkip => *self.file_action.insert(Action::Skip),
+                AlreadyExistPromptOptions::FileBackup => *self.file_action.insert(Action::Backup),
+                AlreadyExistPromptOptions::FileOverwrite => {
+                    *self.file_action.insert(Action::Overwrite)
+                }
+                AlreadyExistPromptOptions::FileRename => *self.file_action.insert(Action::Rename),
+                AlreadyExistPromptOptions::FileAdopt => *self.file_action.insert(Action::Adopt),
+                AlreadyExistPromptOptions::Quit => return self.quit(),
+            },
+        };
+        tracing::debug!(
+            "{} conflicts with an existing {}: {:?} (policy: {:?}).",
+            link_str,
+            if stale { "symlink" } else { "file" },
+            action,
+            policy
+        );
+        if !matches!(action, Action::Skip) && self.refuse_protected(target, link)? {
+            return Ok(());
+        }
+        // Overwriting a directory deletes all its entries, so it is confirmed.
+        let action = match action {
+            Action::Overwrite
+                if !self.params.allow_dir_overwrite
+                    && self.fs.is_dir(link)
+                    && !self.fs.is_symlink(link) =>
+            {
+                if self
+                    .prompter
+                    .confirm_dir_overwrite(link, utils::count_entries(link))?
+                {
+                    Action::Overwrite
+                } else {
+                    Action::Skip
+                }
+            }
+            action => action,
+        };
+
+        if self.params.dry_run {
+            let action = match action {
+                Action::Skip => event::Action::Skipped,
+                Action::Backup => event::Action::BackedUp,
+                Action::Overwrite => event::Action::Overwritten,
+                Action::Rename => event::Action::Renamed,
+                Action::Relink => event::Action::Relinked,
+                Action::Adopt => event::Action::Adopted,
+            };
+            return self.dry_run(action, target, link);
+        }
+
+        match action {
+            Action::Skip => {
+                utils::skip(&mut self.out, target, link)?;
+                self.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Skipped {} -> {}, conflicting with an existing file.",
+                        link.to_string_lossy(),
+                        target.to_string_lossy()
+                    ),
+                );
+                self.summary.skipped += 1;
+                // Skipped as chosen, so to be resolved later.
+                if policy.is_none() {
+                    self.skipped.lock().unwrap().add(link, specified_target);
+                }
+                self.spec_processed(event::Action::Skipped, target, link);
+            }
+            Action::Backup => self.backup(kind, target, link)?,
+            Action::Overwrite => {
+                match self.transaction_dir()? {
+                    Some(save_dir) => {
+                        let saved = utils::overwrite_saving(
+                            &mut self.out,
+                            &*self.fs,
+                            &save_dir,
+                            kind,
+                            target,
+                            link,
+                        )?;
+                        self.record(Operation::Moved {
+                            link: link.to_path_buf(),
+                            moved: saved,
+                        });
+                    }
+                    None => utils::overwrite(&mut self.out, &*self.fs, kind, target, link)?,
+                }
+                self.summary.overwritten += 1;
+                self.linked(kind, target, link, event::Action::Overwritten)?;
+            }
+            Action::Rename => {
+                let renamed = utils::rename(
+                    &mut self.out,
+                    &*self.fs,
+                    &self.params.rename_suffix,
+                    kind,
+                    target,
+                    link,
+                )?;
+                self.summary.renamed += 1;
+                self.record(Operation::Moved {
+                    link: link.to_path_buf(),
+                    moved: renamed,
+                });
+                self.linked(kind, target, link, event::Action::Renamed)?;
+            }
+            Action::Adopt => self.adopt(kind, target, link)?,
+            Action::Relink => {
+                let previous = utils::relink(&mut self.out, &*self.fs, target, link)?;
+                self.summary.relinked += 1;
+                self.record(Operation::Relinked {
+                    link: link.to_path_buf(),
+                    previous,
+                });
+                self.linked(kind, target, link, event::Action::Relinked)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the broken symlink at `link` by the link of kind `kind`
+    /// pointing to `target` (see [`Params::replace_broken`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Replacing the symlink fails (see [`utils::repair`]).
+    /// - The `post_link` hook fails (see [`hook::run`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn repair(&mut self, kind: &LinkKind, target: &Path, link: &Path) -> crate::Result<()> {
+        if self.params.dry_run {
+            return self.dry_run(event::Action::Repaired, target, link);
+        }
+
+        let previous = utils::repair(&mut self.out, &*self.fs, target, link)?;
+        self.summary.repaired += 1;
+        self.record(Operation::Relinked {
+            link: link.to_path_buf(),
+            previous,
+        });
+        self.linked(kind, target, link, event::Action::Repaired)
+    }
+
+    /// Makes the link of kind `kind` at path `link`, pointing to `target`, once
+    /// denied for lack of permissions, by running `command` (see
+    /// [`LinkKind::command`]) through [`Params::escalate`], or by leaving it
+    /// to the root script (see [`Params::root_script`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Running the command fails, or it exits with a non-zero status.
+    /// - Writing to stdout fails.
+    /// - The `post_link` hook fails (see [`hook::run`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn make_privileged(
+        &mut self,
+        kind: &LinkKind,
+        target: &Path,
+        link: &Path,
+        command: Vec<OsString>,
+    ) -> crate::Result<()> {
+        let Some(escalate) = &self.params.escalate else {
+            writeln!(
+                self.out,
+                "{}",
+                format!(
+                    "(#) {} -> {}",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                )
+                .yellow()
+            )?;
+            self.summary.deferred += 1;
+            self.root_commands.lock().unwrap().push(command);
+            self.log(
+                LogLevel::Info,
+                &format!(
+                    "Left {} -> {} to the root script, for lack of permissions.",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                ),
+            );
+            self.spec_processed(event::Action::Deferred, target, link);
+            return Ok(());
+        };
+
+        let mut escalate = escalate.split_whitespace();
+        // Not empty (see `Params::new`).
+        let program = escalate.next().unwrap();
+        let status = process::Command::new(program)
+            .args(escalate)
+            .args(&command)
+            .status()
+            .and_then(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!(
+                        "{} exited with {}",
+                        program, status
+                    )))
+                }
+            });
+        status.link_context(|| {
+            format!(
+                "Failed to create {} -> {} through {}",
+                link.to_string_lossy(),
+                target.to_string_lossy(),
+                program
+            )
+        })?;
+        writeln!(
+            self.out,
+            "(d) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )?;
+        self.summary.created += 1;
+        self.linked(kind, target, link, event::Action::Created)
+    }
+
+    /// Backs up the existing file at path `link` (see [`utils::backup`]), then
+    /// makes the link, recording the backup in the index of the backups.
+    ///
+    /// If `link` is a regular file identical to a backup of it already made,
+    /// it is replaced by the link without being backed up again (see
+    /// [`utils::already_backed_up`]).
+    ///
+    /// # Parameters
+    ///
+    /// - `kind`: The kind of link to make.
+    /// - `target`: Path to the target of the link.
+    /// - `link`: Path to the link.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading `link` to hash it fails.
+    /// - Backing up the file, or replacing it, fails.
+    /// - Writing to stdout fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn backup(&mut self, kind: &LinkKind, target: &Path, link: &Path) -> crate::Result<()> {
+        let hash = if self.fs.is_file(link) && !self.fs.is_symlink(link) {
+            Some(utils::hash_file(link).backup_context(|| {
+                format!(
+                    "Failed to read {} to know whether it has already been backed up.",
+                    link.to_string_lossy()
+                )
+            })?)
+        } else {
+            None
+        };
+        let identical = hash
+            .as_deref()
+            .is_some_and(|hash| self.backup_index.lock().unwrap().has_identical(link, hash));
+        if identical {
+            let save_dir = self.transaction_dir()?;
+            let saved = utils::already_backed_up(
+                &mut self.out,
+                &*self.fs,
+                save_dir.as_deref(),
+                kind,
+                target,
+                link,
+            )?;
+            self.log(
+                LogLevel::Info,
+                &format!(
+                    "Replaced {} by a link to {} without backing it up, an identical backup already existing.",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                ),
+            );
+            self.summary.backed_up += 1;
+            if let Some(saved) = saved {
+                self.record(Operation::Moved {
+                    link: link.to_path_buf(),
+                    moved: saved,
+                });
+            }
+            self.linked(kind, target, link, event::Action::BackedUp)?;
+            return Ok(());
+        }
+
+        let backup = utils::backup(
+            &mut self.out,
+            &*self.fs,
+            &self.params.backup_dir,
+            &self.run_id,
+            kind,
+            target,
+            link,
+        )?;
+        self.backup_index.lock().unwrap().add(backups::Record {
+            original: link.to_path_buf(),
+            time: backup
+                .file_name()
+                .map(|time| time.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            run: self.run_id.clone(),
+            target: target.to_path_buf(),
+            backup: backup.clone(),
+            archive: None,
+            hash,
+        });
+        self.summary.backed_up += 1;
+        self.record(Operation::Moved {
+            link: link.to_path_buf(),
+            moved: backup,
+        });
+        self.linked(kind, target, link, event::Action::BackedUp)?;
+
+        Ok(())
+    }
+
+    /// Adopts the existing file at path `link` (see [`utils::adopt`]), creating
+    /// the missing parent directories of `target` first, and backing up
+    /// `target` if it exists (recording the backup in the index of the
+    /// backups).
+    ///
+    /// A rendered template can't be adopted, nor a symlink (which is not the
+    /// file itself) or a special file (see [`utils::SpecialFile`]), in which
+    /// case the user is told, and the link is skipped.
+    ///
+    /// # Parameters
+    ///
+    /// - `kind`: The kind of link to make.
+    /// - `target`: Path to the target of the link.
+    /// - `link`: Path to the link.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Creating the missing parent directories of `target` fails.
+    /// - Backing up `target` fails.
+    /// - Adopting the file fails.
+    /// - Reading/writing from/to stdin/stdout fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn adopt(&mut self, kind: &LinkKind, target: &Path, link: &Path) -> crate::Result<()> {
+        if self.params.dry_run {
+            return self.dry_run(event::Action::Adopted, target, link);
+        }
+        let unadoptable = match (kind, utils::special_file(link)) {
+            (LinkKind::Rendered(_), _) => Some(format!(
+                "it is rendered from the template {}",
+                target.to_string_lossy()
+            )),
+            (_, Some(special)) => Some(format!("it is a {} (special file)", special)),
+            _ if self.fs.is_symlink(link) => {
+                Some(String::from("it is a symlink, not a file to adopt"))
+            }
+            _ => None,
+        };
+        if let Some(reason) = unadoptable {
+            self.prompter.acknowledge_error(&format!(
+                "Can't adopt {}, as {}. Skipping it.",
+                link.to_string_lossy(),
+                reason
+            ))?;
+            utils::skip(&mut self.out, target, link)?;
+            self.log(
+                LogLevel::Info,
+                &format!(
+                    "Skipped {} -> {}, conflicting with an existing file.",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                ),
+            );
+            self.summary.skipped += 1;
+            self.spec_processed(event::Action::Skipped, target, link);
+            return Ok(());
+        }
+
+        if let Some(target_parent) = target.parent() {
+            self.create_dir_all(target_parent).io_context(|| {
+                format!(
+                    "Failed to create directory {} to adopt {} in it.",
+                    target_parent.display(),
+                    link.to_string_lossy()
+                )
+            })?;
+        }
+        // Backed up rather than kept until the end of the run only, as it may
+        // be the real file while the adopted one is stale.
+        let saved = if self.fs.exists(target) {
+            let hash = if self.fs.is_file(target) && !self.fs.is_symlink(target) {
+                Some(utils::hash_file(target).backup_context(|| {
+                    format!(
+                        "Failed to read {} to back it up before adopting {}.",
+                        target.to_string_lossy(),
+                        link.to_string_lossy()
+                    )
+                })?)
+            } else {
+                None
+            };
+            let backup = utils::move_into(
+                &*self.fs,
+                &self.params.backup_dir.join(&self.run_id),
+                target,
+            )?;
+            self.backup_index.lock().unwrap().add(backups::Record {
+                original: target.to_path_buf(),
+                time: backup
+                    .file_name()
+                    .map(|time| time.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                run: self.run_id.clone(),
+                target: link.to_path_buf(),
+                backup: backup.clone(),
+                archive: None,
+                hash,
+            });
+            self.log(
+                LogLevel::Info,
+                &format!(
+                    "Backed up {} to {}, to adopt {} in its place.",
+                    target.to_string_lossy(),
+                    backup.to_string_lossy(),
+                    link.to_string_lossy()
+                ),
+            );
+            Some(backup)
+        } else {
+            None
+        };
+        utils::adopt(&mut self.out, &*self.fs, kind, target, link)?;
+        self.summary.adopted += 1;
+        self.record(Operation::Adopted {
+            link: link.to_path_buf(),
+            target: target.to_path_buf(),
+            saved,
+        });
+        self.linked(kind, target, link, event::Action::Adopted)?;
+
+        Ok(())
+    }
+
+    /// Processes the symlink-specification files `sls_files` in parallel,
+    /// using [`Params::jobs`] worker threads.
+    ///
+    /// Only meant to be used when no prompt can occur for conflicts (i.e. an
+    /// action to take has already been chosen).
+    /// The lines printed by the workers are not mixed up, but their order is
+    /// not deterministic. The prompts that can still occur (e.g. to
+    /// acknowledge an invalid line, or to confirm overwriting a directory)
+    /// are asked one at a time (see [`Exclusive`]).
+    ///
+    /// # Parameters
+    ///
+    /// - `sls_files`: Paths to the symlink-specification files to process.
+    ///
+    /// # Errors
+    ///
+    /// Fails when processing one of the files fails (see
+    /// [`Engine::process_file`]), in which case the workers stop as soon as
+    /// they are done with the file they are processing.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_files_in_parallel(&mut self, sls_files: Vec<PathBuf>) -> crate::Result<()> {
+        let sls_files = Mutex::new(sls_files.into_iter());
+        let failed = AtomicBool::new(false);
+        let prompter: Arc<dyn Prompter> = Arc::new(Exclusive {
+            prompter: Arc::clone(&self.prompter),
+            out: self.out.clone(),
+        });
+
+        let results: Vec<crate::Result<(Summary, Vec<String>)>> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.params.jobs)
+                .map(|_| {
+                    let mut worker = self.worker();
+                    worker.prompter = Arc::clone(&prompter);
+                    let sls_files = &sls_files;
+                    let failed = &failed;
+                    scope.spawn(move || {
+                        while !failed.load(Ordering::Relaxed) {
+                            let Some(sls) = sls_files.lock().unwrap().next() else {
+                                break;
+                            };
+                            if let Err(err) = worker.process_file(sls) {
+                                failed.store(true, Ordering::Relaxed);
+                                return Err(err);
+                            }
+                        }
+
+                        Ok((worker.summary, worker.failures))
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("A worker thread panicked."))
+                .collect()
+        });
+
+        for result in results {
+            let (summary, failures) = result?;
+            self.summary += summary;
+            self.failures.extend(failures);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the engine.
+    ///
+    /// Before any change is made, fails if links are specified more than once
+    /// with different targets (see [`Engine::check_conflicts`]).
+    /// Once every symlink-specification file has been processed, prints a
+    /// summary of what has been done.
+    /// The `pre_run` and `post_run` hooks, if any, are run before and after
+    /// processing the files (see [`crate::cfg::Hooks`]).
+    ///
+    /// # Returns
+    ///
+    /// The [`Summary`] of the run.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading/writing the manifest fails.
+    /// - Reading/writing the index of the backups, or archiving the backups,
+    ///   fails.
+    /// - Links are specified more than once with different targets.
+    /// - Processing the symlink-specification files fails (see [`Engine::process_file`]).
+    /// - A hook fails (see [`hook::run`]).
+    /// - Reading/writing/removing the progress of the run fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params);
+    ///
+    /// let summary = engine.run()?;
+    /// println!("{} symlinks created.", summary.created);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run(mut self) -> crate::Result<Summary> {
+        let manifest_path = Manifest::default_path()?;
+        *self.manifest.lock().unwrap() = Manifest::load(&manifest_path)?;
+        let skipped_path = Skipped::default_path()?;
+        *self.skipped.lock().unwrap() = Skipped::load(&skipped_path)?;
+        let cache_path = Cache::default_path()?;
+        *self.cache.lock().unwrap() = Cache::load(&cache_path)?;
+        *self.backup_index.lock().unwrap() = backups::Index::load(&self.params.backup_dir)?;
+        let progress_path = Progress::default_path()?;
+        self.start_progress(&progress_path)?;
+
+        self.check_conflicts()?;
+        if self.params.tui {
+            self.resolve_in_tui()?;
+        }
+
+        self.open_log()?;
+        self.log(
+            LogLevel::Info,
+            &format!("Run started in {}.", self.params.dir.to_string_lossy()),
+        );
+        if let Some(pre_run) = self.hook(&self.params.hooks.pre_run) {
+            self.log(LogLevel::Debug, "Running the pre_run hook.");
+            hook::run("pre_run", pre_run, &[("ACTION", OsStr::new("pre_run"))])?;
+        }
+        if let Err(err) = self.process_dir().and_then(|()| self.resolve_postponed()) {
+            self.log(LogLevel::Error, &format!("Run failed: {:#}", err));
+            self.report_failure(format!("Run failed: {:#}", err));
+            // The run failed, whether the report and the session can be written or not.
+            let _ = self.write_report();
+            let _ = self.save_session();
+            if self.transaction.is_some() {
+                self.rollback();
+                // Nothing is left to resume.
+                let _ = Progress::remove(&progress_path);
+                return Err(Error::RolledBack(Box::new(err)));
+            }
+            if !self.params.dry_run {
+                // The links made and skipped before the failure are recorded all
+                // the same, but not the cache, so that the files are processed
+                // again by the next run.
+                self.manifest.lock().unwrap().save(&manifest_path)?;
+                self.skipped.lock().unwrap().save(&skipped_path)?;
+                // So that the run can be resumed from the failing line.
+                self.save_progress()?;
+            }
+            self.finish_backups()?;
+            return Err(err);
+        }
+        self.commit()?;
+        if !self.params.dry_run {
+            self.manifest.lock().unwrap().save(&manifest_path)?;
+            self.skipped.lock().unwrap().save(&skipped_path)?;
+            self.cache.lock().unwrap().save(&cache_path)?;
+            // A run quit by the user can be resumed.
+            if !self.summary.quit {
+                Progress::remove(&progress_path)?;
+            }
+        }
+        let root_commands = self.root_commands.lock().unwrap().clone();
+        if let (Some(root_script), false) = (&self.params.root_script, root_commands.is_empty()) {
+            utils::write_root_script(root_script, &root_commands)?;
+        }
+        self.finish_backups()?;
+        if self.summary.quit {
+            self.log(LogLevel::Info, "Run quit by the user.");
+        } else if let Some(post_run) = self.hook(&self.params.hooks.post_run) {
+            self.log(LogLevel::Debug, "Running the post_run hook.");
+            hook::run("post_run", post_run, &[("ACTION", OsStr::new("post_run"))])?;
+        }
+
+        self.log(
+            LogLevel::Info,
+            &format!(
+                "Run finished: {} created, {} already existing, {} skipped, {} backed up, {} overwritten, {} renamed, {} relinked, {} repaired, {} adopted, {} deferred, {} invalid lines.",
+                self.summary.created,
+                self.summary.already_existing,
+                self.summary.skipped,
+                self.summary.backed_up,
+                self.summary.overwritten,
+                self.summary.renamed,
+                self.summary.relinked,
+                self.summary.repaired,
+                self.summary.adopted,
+                self.summary.deferred,
+                self.summary.invalid
+            ),
+        );
+
+        if !self.failures.is_empty() {
+            writeln!(self.out)?;
+            writeln!(self.out, "Failures:")?;
+            for failure in &self.failures {
+                writeln!(self.out, "{}", format!("    (!) {}", failure).red())?;
+            }
+        }
+        if let (Some(root_script), false) = (&self.params.root_script, root_commands.is_empty()) {
+            writeln!(self.out)?;
+            writeln!(
+                self.out,
+                "{}",
+                format!(
+                    "Some links have been denied for lack of permissions. Make them by running as root: sh {}",
+                    root_script.display()
+                )
+                .yellow()
+            )?;
+        }
+        writeln!(self.out)?;
+        writeln!(self.out, "{}", self.summary)?;
+        self.write_report()?;
+        self.save_session()?;
+
+        Ok(self.summary)
+    }
+
+    /// Makes again only the links skipped by the previous runs (see
+    /// [`Skipped`]) specified in the symlink-specification files found in
+    /// [`Params::dir`], for the user to resolve their conflicts this time.
+    ///
+    /// Otherwise the same as [`Engine::run`], only the skipped links being
+    /// made instead of those matched by [`Params::only`], themselves and not
+    /// what is under them. Does nothing but telling it if there are none.
+    ///
+    /// # Returns
+    ///
+    /// The summary of what has been done (see [`Engine::run`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading the skipped links fails.
+    /// - The run fails (see [`Engine::run`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params);
+    ///
+    /// let summary = engine.resolve()?;
+    /// println!("{} skipped again.", summary.skipped);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve(mut self) -> crate::Result<Summary> {
+        let skipped = Skipped::load(&Skipped::default_path()?)?;
+        if skipped.is_empty() {
+            writeln!(self.out, "No skipped links to resolve.")?;
+            return Ok(Summary::default());
+        }
+
+        self.resolving = Some(skipped.iter().map(|(link, _)| link.to_path_buf()).collect());
+        self.run()
+    }
+
+    /// Removes the symlinks of the manifest (i.e. made by previous runs, see
+    /// [`Manifest`]) recorded for [`Params::dir`], whose specifications no
+    /// longer exist in the symlink-specification files found in it. The
+    /// symlinks recorded for other directories are left untouched.
+    ///
+    /// A symlink that doesn't point to its recorded target anymore is left
+    /// untouched. Either way, it is removed from the manifest.
+    /// For each symlink removed, prints a line of the form:
+    ///
+    /// ```text
+    /// (p) <link> -> <target>
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The number of symlinks removed.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading/writing the manifest fails.
+    /// - Processing the symlink-specification files fails (see [`Engine::process_file`]).
+    /// - Reading or removing a symlink fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params);
+    ///
+    /// let pruned = engine.prune()?;
+    /// println!("{} symlinks removed.", pruned);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prune(mut self) -> crate::Result<u64> {
+        let manifest_path = Manifest::default_path()?;
+        self.prune_manifest(&manifest_path)
+    }
+
+    /// Does what [`Engine::prune`] does, with the manifest at `manifest_path`.
+    fn prune_manifest(&mut self, manifest_path: &Path) -> crate::Result<u64> {
+        self.open_log()?;
+        let mut manifest = Manifest::load(manifest_path)?;
+
+        self.specified = Some(HashMap::new());
+        self.process_dir()?;
+        let specified = self.specified.take().unwrap_or_default();
+
+        let unspecified: Vec<(PathBuf, PathBuf)> = manifest
+            .iter(&self.canonical_dir)
+            .filter(|(link, _)| !specified.contains_key(*link))
+            .map(|(link, target)| (link.to_path_buf(), target.to_path_buf()))
+            .collect();
+        let mut pruned = 0;
+        for (link, target) in unspecified {
+            let points_to_target = link.is_symlink()
+                && fs::read_link(&link).link_context(|| {
+                    format!("Failed to read symlink {} to prune it.", link.display())
+                })? == target;
+            if points_to_target {
+                fs::remove_file(&link)
+                    .link_context(|| format!("Failed to remove symlink {}.", link.display()))?;
+                writeln!(
+                    self.out,
+                    "{}",
+                    format!(
+                        "(p) {} -> {}",
+                        link.to_string_lossy(),
+                        target.to_string_lossy()
+                    )
+                    .dark_magenta()
+                )?;
+                self.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Pruned {} -> {}.",
+                        link.to_string_lossy(),
+                        target.to_string_lossy()
+                    ),
+                );
+                pruned += 1;
+            }
+            manifest.remove(&self.canonical_dir, &link);
+        }
+        manifest.save(manifest_path)?;
+
+        writeln!(self.out)?;
+        writeln!(self.out, "{} symlink(s) pruned.", pruned)?;
+
+        Ok(pruned)
+    }
+
+    /// Looks for broken symlinks (i.e. whose target doesn't exist) and offers
+    /// to remove them.
+    ///
+    /// If `under` is given, every symlink within it (recursively) is
+    /// considered. Otherwise, only the symlinks directly within the
+    /// directories of the links specified by the symlink-specification files
+    /// found in [`Params::dir`] are.
+    ///
+    /// For each broken symlink, the user is prompted to remove or keep it
+    /// (see [`Prompter::resolve_broken_symlink`]), and for each one removed,
+    /// prints a line of the form:
+    ///
+    /// ```text
+    /// (c) <link> -> <target>
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `under`: The directory in which to look for broken symlinks, if not
+    ///   the directories of the links specified.
+    ///
+    /// # Returns
+    ///
+    /// The number of broken symlinks removed.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - `under` is not a directory.
+    /// - Processing the symlink-specification files fails (see [`Engine::process_file`]).
+    /// - Reading a directory or symlink, or removing a symlink fails.
+    /// - Reading/writing from/to stdin/stdout fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params);
+    ///
+    /// let removed = engine.clean_broken(None)?;
+    /// println!("{} broken symlinks removed.", removed);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clean_broken(mut self, under: Option<&Path>) -> crate::Result<u64> {
+        self.open_log()?;
+        let mut symlinks = BTreeSet::new();
+        match under {
+            Some(under) => {
+                for file in Dir::build(under.to_path_buf())?.iter_on_files() {
+                    match file {
+                        Ok(file) if file.is_symlink() => {
+                            symlinks.insert(file);
+                        }
+                        Ok(_) => {}
+                        Err(err) => self.walk_error(err)?,
+                    }
+                }
+            }
+            None => {
+                self.specified = Some(HashMap::new());
+                self.process_dir()?;
+                let specified = self.specified.take().unwrap_or_default();
+
+                let dirs: BTreeSet<&Path> = specified
+                    .keys()
+                    .filter_map(|link| link.parent())
+                    .filter(|dir| dir.is_dir())
+                    .collect();
+                for dir in dirs {
+                    let entries = fs::read_dir(dir).io_context(|| {
+                        format!(
+                            "Failed to read directory {} to look for broken symlinks.",
+                            dir.display()
+                        )
+                    })?;
+                    for entry in entries {
+                        let path = entry
+                            .io_context(|| {
+                                format!("Failed to read an entry of directory {}.", dir.display())
+                            })?
+                            .path();
+                        if path.is_symlink() {
+                            symlinks.insert(path);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut always_remove: Option<bool> = None;
+        let mut removed = 0;
+        for link in symlinks.into_iter().filter(|link| !link.exists()) {
+            let target = fs::read_link(&link)
+                .link_context(|| format!("Failed to read symlink {}.", link.display()))?;
+            let remove = match always_remove {
+                Some(remove) => remove,
+                None => match self.prompter.resolve_broken_symlink(&target, &link)? {
+                    BrokenSymlinkPromptOptions::Remove => true,
+                    BrokenSymlinkPromptOptions::AlwaysRemove => *always_remove.insert(true),
+                    BrokenSymlinkPromptOptions::Keep => false,
+                    BrokenSymlinkPromptOptions::AlwaysKeep => *always_remove.insert(false),
+                },
+            };
+            tracing::debug!(
+                "{} is a broken symlink: {}.",
+                link.display(),
+                if remove { "removing it" } else { "keeping it" }
+            );
+            if remove {
+                fs::remove_file(&link)
+                    .link_context(|| format!("Failed to remove symlink {}.", link.display()))?;
+                writeln!(
+                    self.out,
+                    "{}",
+                    format!(
+                        "(c) {} -> {}",
+                        link.to_string_lossy(),
+                        target.to_string_lossy()
+                    )
+                    .dark_magenta()
+                )?;
+                self.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Removed broken symlink {} -> {}.",
+                        link.to_string_lossy(),
+                        target.to_string_lossy()
+                    ),
+                );
+                removed += 1;
+            }
+        }
+
+        writeln!(self.out)?;
+        writeln!(self.out, "{} broken symlink(s) removed.", removed)?;
+
+        Ok(removed)
+    }
+
+    /// Checks the symlink-specification files found in [`Params::dir`],
+    /// without prompting nor changing anything.
+    ///
+    /// Every problem found is reported on a line of the form:
+    ///
+    /// ```text
+    /// (!) Invalid line in <sls>, line number <line number>.
+    ///     <reason>
+    /// ```
+    ///
+    /// The problems are the invalid lines (including the ones whose target
+    /// doesn't exist, and the ones guarded by a condition that doesn't hold
+    /// here), and the links specified more than once.
+    ///
+    /// # Returns
+    ///
+    /// The number of problems found.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Processing the symlink-specification files fails (see [`Engine::process_file`]).
+    /// - Writing the output fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use clap::Parser;
+    /// use mksls::cfg::Config;
+    /// use mksls::cli::Cli;
+    /// use mksls::engine::Engine;
+    /// use mksls::params::Params;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cli = Cli::parse();
+    /// let cfg: Config = confy::load("my_crate", "config")?;
+    /// let params = Params::new(cli, cfg)?;
+    /// let engine = Engine::new(params);
+    ///
+    /// let problems = engine.lint()?;
+    /// println!("{} problems found.", problems);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lint(mut self) -> crate::Result<u64> {
+        self.lint = true;
+        self.specified = Some(HashMap::new());
+        self.process_dir()?;
+
+        writeln!(self.out)?;
+        writeln!(self.out, "{} problem(s) found.", self.summary.invalid)?;
+
+        Ok(self.summary.invalid)
+    }
+
+    /// Looks for the links specified more than once with different targets,
+    /// before any change is made, instead of letting the link point to the
+    /// target of whichever specification is processed last.
+    ///
+    /// Problems other than these (e.g. invalid lines) are left to the run to
+    /// report.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Links are specified more than once with different targets.
+    /// - Processing the symlink-specification files fails (see [`Engine::process_file`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn check_conflicts(&self) -> crate::Result<()> {
+        let mut checker = self.worker();
+        checker.processed = Arc::new(Mutex::new(HashSet::new()));
+        checker.specified = Some(HashMap::new());
+        checker.conflicts = Some(vec![]);
+        checker.observer = None;
+        // Even the specifications processed by the interrupted run being
+        // resumed are checked.
+        checker.progress = None;
+        checker.process_dir()?;
+
+        let conflicts = checker.conflicts.unwrap_or_default();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        Err(Error::ConflictingSpecs {
+            message: format!(
+                "Links are specified more than once, with different targets. Nothing has been changed.
+    {}",
+                conflicts.join("\n    ")
+            ),
+        })
+    }
+
+    /// Lets the user resolve the links conflicting with an existing file in
+    /// the terminal user interface, before any is made (see [`Params::tui`]).
+    ///
+    /// The links are collected by processing the symlink-specification files
+    /// without changing anything, then the actions chosen are taken for them
+    /// during the run, instead of prompting. If the user quits, the run
+    /// doesn't make any link.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Processing the symlink-specification files fails (see [`Engine::process_file`]).
+    /// - Showing the interface fails (see [`tui::resolve`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn resolve_in_tui(&mut self) -> crate::Result<()> {
+        let mut collector = self.worker();
+        collector.processed = Arc::new(Mutex::new(HashSet::new()));
+        collector.pending = Some(vec![]);
+        collector.observer = None;
+        collector.process_dir()?;
+
+        let (kinds, conflicts): (Vec<LinkKind>, Vec<tui::Conflict>) =
+            collector.pending.unwrap_or_default().into_iter().unzip();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        let diff = |i: usize| utils::diff(&kinds[i], &conflicts[i].target, &conflicts[i].link);
+        match tui::resolve(&conflicts, diff)? {
+            Some(resolutions) => {
+                self.resolutions = conflicts
+                    .into_iter()
+                    .map(|conflict| conflict.link)
+                    .zip(resolutions.into_iter().map(Action::from))
+                    .collect();
+                Ok(())
+            }
+            None => self.quit(),
+        }
+    }
+
+    /// Postpones the resolution of the link of kind `kind` at path `link`,
+    /// pointing to `target`, conflicting with an existing file (a symlink if
+    /// `stale`), to the end of the run (see [`Engine::resolve_postponed`]).
+    fn postpone(
+        &mut self,
+        kind: &LinkKind,
+        target: &Path,
+        link: &Path,
+        stale: bool,
+    ) -> crate::Result<()> {
+        writeln!(
+            self.out,
+            "{}",
+            format!(
+                "(?) {} -> {}, resolved at the end of the run",
+                link.to_string_lossy(),
+                target.to_string_lossy()
+            )
+            .dark_grey()
+        )?;
+        let conflict = tui::Conflict {
+            target: target.to_path_buf(),
+            link: link.to_path_buf(),
+            stale,
+        };
+        if let Some(postponed) = &mut self.postponed {
+            postponed.push(Postponed {
+                kind: kind.clone(),
+                conflict,
+                spec_line: self.spec_line.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Lets the user resolve the links whose resolution has been postponed
+    /// to the end of the run, all at once (see [`Params::resolve_at_end`]).
+    ///
+    /// They are shown in the terminal user interface (see [`tui::resolve`]),
+    /// quitting it skipping them all. When stdin or stdout is not a terminal,
+    /// the user is prompted for each one in turn instead.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Showing the interface fails (see [`tui::resolve`]).
+    /// - Making a link fails and the run can't go on (see [`Engine::keep_going`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn resolve_postponed(&mut self) -> crate::Result<()> {
+        let postponed = self.postponed.take().unwrap_or_default();
+        if postponed.is_empty() || self.summary.quit {
+            return Ok(());
+        }
+
+        writeln!(self.out)?;
+        writeln!(self.out, "{} conflict(s) to resolve.", postponed.len())?;
+        let resolutions: Vec<Option<Action>> =
+            if io::stdin().is_terminal() && io::stdout().is_terminal() {
+                let conflicts: Vec<tui::Conflict> = postponed
+                    .iter()
+                    .map(|postponed| postponed.conflict.clone())
+                    .collect();
+                let diff = |i: usize| {
+                    let Postponed { kind, conflict, .. } = &postponed[i];
+                    utils::diff(kind, &conflict.target, &conflict.link)
+                };
+                match tui::resolve(&conflicts, diff)? {
+                    Some(resolutions) => resolutions
+                        .into_iter()
+                        .map(|resolution| Some(resolution.into()))
+                        .collect(),
+                    None => vec![Some(Action::Skip); conflicts.len()],
+                }
+            } else {
+                vec![None; postponed.len()]
+            };
+        self.apply_postponed(postponed, resolutions)
+    }
+
+    /// Makes the links whose resolution has been postponed (see
+    /// [`Engine::resolve_postponed`]), taking the action resolved for each
+    /// one, or prompting the user for it if `None`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when making a link fails and the run can't go on (see
+    /// [`Engine::keep_going`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn apply_postponed(
+        &mut self,
+        postponed: Vec<Postponed>,
+        resolutions: Vec<Option<Action>>,
+    ) -> crate::Result<()> {
+        // The choices made for the last file processed don't apply.
+        self.file_action = None;
+        for (postponed, resolution) in postponed.into_iter().zip(resolutions) {
+            let Postponed {
+                kind,
+                conflict,
+                spec_line,
+            } = postponed;
+            self.spec_line = spec_line;
+            if let Some(action) = resolution {
+                self.resolutions.insert(conflict.link.clone(), action);
+            }
+            let result = self.make_link(&kind, &conflict.target, &conflict.link);
+            self.keep_going(result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the link of kind `kind` at path `link`, pointing to `target`,
+    /// if it conflicts with an existing file and the user would be prompted
+    /// for it, to resolve it in the terminal user interface (see
+    /// [`Engine::resolve_in_tui`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when the existing file can't be read to check if it is the link.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn collect_pending(
+        &mut self,
+        kind: &LinkKind,
+        target: &Path,
+        link: &Path,
+    ) -> crate::Result<()> {
+        if !self.fs.exists(link)
+            || kind.is_made(&*self.fs, target, link, self.params.exact_targets).io_context(|| {
+                format!(
+                    "A file of path {} already exists, but failed to read it to check if it is the link you want to create or not.",
+                    link.display()
+                )
+            })?
+        {
+            return Ok(());
+        }
+
+        let stale = *kind == LinkKind::Symlink && self.fs.is_symlink(link);
+        let policy = self
+            .policy(link)
+            .filter(|action| stale || *action != PolicyAction::Relink);
+        let repaired =
+            stale && self.params.replace_broken && utils::is_broken_symlink(&*self.fs, link);
+        if policy.is_some() || self.action.is_some() || (stale && self.relink) || repaired {
+            return Ok(());
+        }
+        if let Some(pending) = &mut self.pending {
+            pending.push((
+                kind.clone(),
+                tui::Conflict {
+                    target: target.to_path_buf(),
+                    link: link.to_path_buf(),
+                    stale,
+                },
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Opens the log file, if any (see [`Params::log_file`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when the log file can't be opened.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn open_log(&mut self) -> crate::Result<()> {
+        self.logger = Arc::new(Logger::open(
+            self.params.log_file.as_deref(),
+            self.params.log_level,
+        )?);
+
+        Ok(())
+    }
+
+    /// Writes `message` to the log file, if logging at `level` (see [`Logger::log`]),
+    /// and emits it as a tracing event, printed with `-v` (see [`Cli::verbose`]).
+    ///
+    /// `-v` is about the files processed and `-vv` about each link, the other
+    /// way around from the log levels, so the details about the run are traced
+    /// at the info level and what is done for each link at the debug level.
+    fn log(&self, level: LogLevel, message: &str) {
+        self.logger.log(level, message);
+        match level {
+            LogLevel::Error => tracing::error!("{}", message),
+            LogLevel::Warn => tracing::warn!("{}", message),
+            LogLevel::Info => tracing::debug!("{}", message),
+            LogLevel::Debug => tracing::info!("{}", message),
+        }
+    }
+
+    /// Returns the action of the first policy whose pattern matches `link`
+    /// (see [`Params::policy`]), if any.
+    fn policy(&self, link: &Path) -> Option<PolicyAction> {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.params
+            .policy
+            .iter()
+            .find(|(pattern, _)| pattern.matches_path_with(link, options))
+            .map(|(_, action)| *action)
+    }
+
+    /// Returns whether the existing file at `link` is protected (see
+    /// [`Params::protected`]), in which case it is not replaced by the link
+    /// pointing to `target`, which fails (see [`Engine::failed`]) without
+    /// stopping the run.
+    ///
+    /// # Errors
+    ///
+    /// Fails when writing the output fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn refuse_protected(&mut self, target: &Path, link: &Path) -> crate::Result<bool> {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let Some(pattern) = self
+            .params
+            .protected
+            .iter()
+            .find(|pattern| pattern.matches_path_with(link, options))
+        else {
+            return Ok(false);
+        };
+
+        let err_mess = format!(
+            "Refused to replace {} by {} -> {}, as it is protected (by {} in the configuration file).",
+            link.display(),
+            link.display(),
+            target.display(),
+            pattern
+        );
+        self.failed(Some((target, link)), err_mess)?;
+
+        Ok(true)
+    }
+
+    /// Returns the kind of link to make for a plain specification (or a glob
+    /// or directory one) whose target is `target` (see
+    /// [`Params::operation`]).
+    ///
+    /// A directory is always symlinked, as it can't be hard linked nor copied.
+    fn plain_kind(&self, target: &Path) -> LinkKind {
+        if target.is_dir() {
+            LinkKind::Symlink
+        } else {
+            self.params.operation.into()
+        }
+    }
+
+    /// Returns whether the link `link` is to be made, i.e. matched by
+    /// [`Params::only`] (if any) but not by [`Params::skip`], either itself or
+    /// through one of its parent directories.
+    ///
+    /// Every link is, when only collecting the links specified. When making
+    /// the skipped links again (see [`Engine::resolve`]), only those links
+    /// are, themselves and not what is under them.
+    fn selected(&self, link: &Path) -> bool {
+        if self.specified.is_some() {
+            return true;
+        }
+
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let matched = |patterns: &[glob::Pattern]| {
+            link.ancestors().any(|path| {
+                patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_path_with(path, options))
+            })
+        };
+        let only = match &self.resolving {
+            Some(links) => links.contains(link),
+            None => self.params.only.is_empty() || matched(&self.params.only),
+        };
+        only && !matched(&self.params.skip)
+    }
+
+    /// Handles an invalid line of `sls`: counts it, logs it, notifies the
+    /// observer and tells the user why it is invalid with `err_mess`.
+    ///
+    /// In strict mode (see [`crate::cli::Cli::strict`]), fails instead, unless
+    /// linting. Otherwise, does nothing when looking for conflicting
+    /// specifications, the run reporting it afterwards.
+    ///
+    /// # Parameters
+    ///
+    /// - `sls`: Path to the symlink-specification file where the line lives.
+    /// - `line_no`: The line number of the line in `sls`, or `None` when the
+    ///   whole file is invalid.
+    /// - `err_mess`: Why the line is invalid.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - In strict mode.
+    /// - Telling the user fails (see [`Prompter::acknowledge_error`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn invalid(&mut self, sls: &Path, line_no: Option<u64>, err_mess: String) -> crate::Result<()> {
+        if self.params.strict && !self.lint {
+            return Err(Error::InvalidLine { message: err_mess });
+        }
+        if self.conflicts.is_some() || self.pending.is_some() {
+            return Ok(());
+        }
+
+        self.summary.invalid += 1;
+        if self.lint {
+            writeln!(self.out, "{}", format!("(!) {}", err_mess).red())?;
+        } else {
+            self.log(LogLevel::Warn, &err_mess);
+            self.prompter.acknowledge_error(&err_mess)?;
+        }
+        self.porcelain(
+            "invalid",
+            None,
+            &sls.to_string_lossy(),
+            &line_no
+                .map(|line_no| line_no.to_string())
+                .unwrap_or_default(),
+        );
+        self.report(report::Entry {
+            action: String::from("invalid"),
+            sls: Some(sls.to_string_lossy().into_owned()),
+            line: line_no,
+            message: Some(err_mess.clone()),
+            ..report::Entry::default()
+        });
+        self.notify(Event::InvalidLine {
+            sls: sls.to_path_buf(),
+            line_no,
+            message: err_mess,
+        });
+
+        Ok(())
+    }
+
+    /// Returns `result`, unless it is an error about a single link specified
+    /// and [`Params::keep_going`] is set, in which case the error is reported
+    /// and recorded (see [`Summary::failed`]) so that the run goes on.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `result` is an error not about a single link (or
+    /// [`Params::keep_going`] is not set), or when writing the output fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn keep_going(&mut self, result: crate::Result<()>) -> crate::Result<()> {
+        match result {
+            Err(
+                err @ (Error::Link { .. }
+                | Error::Backup { .. }
+                | Error::Parse { .. }
+                | Error::Hook { .. }
+                | Error::Io { .. }),
+            ) if self.params.keep_going => self.failed(None, format!("{:#}", err)),
+            result => result,
+        }
+    }
+
+    /// Reports that making a link specified failed because of `err_mess`,
+    /// and records it (see [`Summary::failed`]).
+    ///
+    /// `link` is the link that failed with its target, when known.
+    ///
+    /// # Errors
+    ///
+    /// Fails when writing the output fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn failed(&mut self, link: Option<(&Path, &Path)>, err_mess: String) -> crate::Result<()> {
+        self.summary.failed += 1;
+        self.log(LogLevel::Error, &format!("Failed: {}", err_mess));
+        let (sls, line_no) = &self.spec_line;
+        let (sls, line_no) = if *line_no > 0 {
+            (sls.to_string_lossy(), line_no.to_string())
+        } else {
+            Default::default()
+        };
+        self.porcelain("failed", link, &sls, &line_no);
+        self.report_failure(err_mess.clone());
+        writeln!(self.out, "{}", format!("(!) Failed: {}", err_mess).red())?;
+        self.failures.push(err_mess);
+
+        Ok(())
+    }
+
+    /// Stops processing the links specified, as the user asked for it.
+    ///
+    /// The remaining specifications are ignored, and the run ends as usual,
+    /// except for the `post_run` hook, which is not run.
+    fn quit(&mut self) -> crate::Result<()> {
+        self.summary.quit = true;
+        Ok(())
+    }
+
+    /// Reports that `action` would have been done for the link `link`,
+    /// pointing to `target`, without doing it (see [`Params::dry_run`]).
+    ///
+    /// The line printed, the summary, the log and the observer are the same
+    /// as if it had been done.
+    ///
+    /// # Errors
+    ///
+    /// Fails when writing the output fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn dry_run(&mut self, action: event::Action, target: &Path, link: &Path) -> crate::Result<()> {
+        let (letter, count) = match action {
+            event::Action::Created => ("d", &mut self.summary.created),
+            event::Action::AlreadyExisting => (".", &mut self.summary.already_existing),
+            event::Action::Skipped => ("s", &mut self.summary.skipped),
+            event::Action::BackedUp => ("b", &mut self.summary.backed_up),
+            event::Action::Overwritten => ("o", &mut self.summary.overwritten),
+            event::Action::Renamed => ("n", &mut self.summary.renamed),
+            event::Action::Relinked => ("r", &mut self.summary.relinked),
+            event::Action::Repaired => ("f", &mut self.summary.repaired),
+            event::Action::Adopted => ("a", &mut self.summary.adopted),
+            event::Action::Deferred => ("#", &mut self.summary.deferred),
+        };
+        *count += 1;
+        let line = format!(
+            "({}) {} -> {}",
+            letter,
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        );
+        let line = match action {
+            event::Action::Created => line,
+            event::Action::AlreadyExisting => line.dark_grey().to_string(),
+            event::Action::Skipped => line.dark_blue().to_string(),
+            event::Action::BackedUp => line.dark_green().to_string(),
+            event::Action::Overwritten => line.dark_red().to_string(),
+            event::Action::Renamed => line.green().to_string(),
+            event::Action::Relinked | event::Action::Repaired => line.dark_cyan().to_string(),
+            event::Action::Adopted => line.dark_yellow().to_string(),
+            event::Action::Deferred => line.yellow().to_string(),
+        };
+        writeln!(self.out, "{}", line)?;
+        self.log(
+            LogLevel::Info,
+            &format!(
+                "Would have made {} -> {} ({}).",
+                link.to_string_lossy(),
+                target.to_string_lossy(),
+                action
+            ),
+        );
+        self.spec_processed(action, target, link);
+
+        Ok(())
+    }
+
+    /// Returns the command of `hook`, if any and not in dry-run mode (see
+    /// [`Params::dry_run`]).
+    fn hook<'a>(&self, hook: &'a Option<String>) -> Option<&'a String> {
+        hook.as_ref().filter(|_| !self.params.dry_run)
+    }
+
+    /// Notifies the observer, if any, of `event`.
+    fn notify(&self, event: Event) {
+        if let Some(observer) = &self.observer {
+            observer.notify(&event);
+        }
+    }
+
+    /// Adds `entry` to the report, if one is to be written (see
+    /// [`Params::report`]).
+    fn report(&self, entry: report::Entry) {
+        if let Some(report) = &self.report {
+            report.lock().unwrap().push(entry);
+        }
+    }
+
+    /// Adds the failure `message`, about the specification being processed,
+    /// to the report (see [`Engine::report`]).
+    fn report_failure(&self, message: String) {
+        let (sls, line_no) = &self.spec_line;
+        self.report(report::Entry {
+            action: String::from("failed"),
+            sls: (*line_no > 0).then(|| sls.to_string_lossy().into_owned()),
+            line: (*line_no > 0).then_some(*line_no),
+            message: Some(message),
+            ..report::Entry::default()
+        });
+    }
+
+    /// Writes the report of the run, if asked for (see [`Params::report`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when writing the report fails (see [`report::write`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn write_report(&self) -> crate::Result<()> {
+        let (Some(path), Some(report)) = (&self.params.report, &self.report) else {
+            return Ok(());
+        };
+        report::write(
+            path,
+            self.params.report_format,
+            &report.lock().unwrap(),
+            &self.summary,
+        )
+    }
+
+    /// Saves the session of the prompts answered, if recorded (see
+    /// [`Params::record`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when saving the session fails (see [`Session::save`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn save_session(&self) -> crate::Result<()> {
+        let (Some(path), Some(recorded)) = (&self.params.record, &self.recorded) else {
+            return Ok(());
+        };
+        recorded.lock().unwrap().save(path)
+    }
+
+    /// Prints the line `ACTION<TAB>LINK<TAB>TARGET<TAB>SLS_FILE<TAB>LINE` in
+    /// porcelain mode (see [`Params::porcelain`]), `LINK` and `TARGET` being
+    /// empty without `link`.
+    ///
+    /// The fields are escaped (see [`utils::escape_field`]), so that a path
+    /// can't break the line.
+    ///
+    /// Failing to print is ignored, like failing to log.
+    fn porcelain(&self, action: &str, link: Option<(&Path, &Path)>, sls: &str, line_no: &str) {
+        let Some(mut out) = self.porcelain.clone() else {
+            return;
+        };
+        let (target, link) = link
+            .map(|(target, link)| (target.to_string_lossy(), link.to_string_lossy()))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            action,
+            utils::escape_field(&link),
+            utils::escape_field(&target),
+            utils::escape_field(sls),
+            line_no
+        );
+    }
+
+    /// Notifies the observer, if any, that `action` has been done for the
+    /// link `link`, pointing to `target`.
+    ///
+    /// The link is no longer skipped once made (see [`Engine::skipped`]).
+    fn spec_processed(&self, action: event::Action, target: &Path, link: &Path) {
+        if !matches!(action, event::Action::Skipped | event::Action::Deferred) {
+            self.skipped.lock().unwrap().remove(link);
+        }
+        let (sls, line_no) = &self.spec_line;
+        self.porcelain(
+            &action.to_string(),
+            Some((target, link)),
+            &sls.to_string_lossy(),
+            &line_no.to_string(),
+        );
+        self.report(report::Entry {
+            action: action.to_string(),
+            link: Some(link.to_string_lossy().into_owned()),
+            target: Some(target.to_string_lossy().into_owned()),
+            sls: Some(sls.to_string_lossy().into_owned()),
+            line: Some(*line_no),
+            message: None,
+        });
+        self.notify(Event::SpecProcessed {
+            action,
+            link: link.to_path_buf(),
+            target: target.to_path_buf(),
+        });
+    }
+
+    /// To call once the link `link`, pointing to `target`, has been made, by
+    /// doing `action`.
+    ///
+    /// Records it in the manifest (see [`Engine::manage`]), notifies the
+    /// observer, then runs the `post_link` hook, if any.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the `post_link` hook fails (see [`hook::run`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn linked(
+        &self,
+        kind: &LinkKind,
+        target: &Path,
+        link: &Path,
+        action: event::Action,
+    ) -> crate::Result<()> {
+        self.manage(kind, target, link);
+        self.spec_processed(action, target, link);
+        self.log(
+            LogLevel::Info,
+            &format!(
+                "Made {} -> {} ({}).",
+                link.to_string_lossy(),
+                target.to_string_lossy(),
+                action
+            ),
+        );
+        if let Some(post_link) = &self.params.hooks.post_link {
+            self.log(
+                LogLevel::Debug,
+                &format!("Running the post_link hook for {}.", link.to_string_lossy()),
+            );
+            hook::run(
+                "post_link",
+                post_link,
+                &[
+                    ("LINK", link.as_os_str()),
+                    ("TARGET", target.as_os_str()),
+                    ("ACTION", OsStr::new(&action.to_string())),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the link `link`, pointing to `target`, in the manifest if it is
+    /// a symlink.
+    fn manage(&self, kind: &LinkKind, target: &Path, link: &Path) {
+        if *kind == LinkKind::Symlink {
+            self.manifest
+                .lock()
+                .unwrap()
+                .add(&self.canonical_dir, link, target);
+        }
+    }
+
+    /// Processes every symlink-specification file found in [`Params::dir`],
+    /// or just [`Params::dir`] if it is a file.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - [`Params::dir`] is neither a directory nor a file.
+    /// - An entry can't be read while scanning, in strict mode (see [`Engine::walk_error`]).
+    /// - Processing one of the files fails (see [`Engine::process_file`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn process_dir(&mut self) -> crate::Result<()> {
+        if self.params.dir.is_file() {
+            return self.process_file(self.params.dir.clone());
+        }
+
+        let dir = Dir::build(self.params.dir.clone())?
+            .follow_links(self.params.follow_links)
+            .sorted(self.params.sorted)
+            .extensions(self.params.extension.clone());
+        let sls_files = dir.iter_on_sls_files(&self.params.filename, &self.params.exclude);
+        if self.params.jobs > 1
+            && !self.params.group_by_file
+            && self.action.is_some()
+            && self.transaction.is_none()
+            && self.specified.is_none()
+        {
+            let mut files = vec![];
+            for sls in sls_files {
+                match sls {
+                    Ok(sls) => files.push(sls),
+                    Err(err) => self.walk_error(err)?,
+                }
+            }
+            self.process_files_in_parallel(files)?;
+        } else {
+            for sls in sls_files {
+                if self.summary.quit {
+                    break;
+                }
+                match sls {
+                    Ok(sls) => self.process_file(sls)?,
+                    Err(err) => self.walk_error(err)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts keeping the progress of the run, from the one of the
+    /// interrupted run saved at `path` if resuming it (see
+    /// [`Params::resume`]), telling so.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading the progress at `path` fails (see [`Progress::load`]).
+    /// - Printing fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn start_progress(&mut self, path: &Path) -> crate::Result<()> {
+        let dir = path::absolute(&self.params.dir).io_context(|| {
+            format!(
+                "Failed to get the absolute path of {}, needed to keep the progress of the run.",
+                self.params.dir.display()
+            )
+        })?;
+        let only: Vec<String> = self
+            .params
+            .only
+            .iter()
+            .map(|pattern| pattern.as_str().to_string())
+            .collect();
+        let mut progress = Progress::new(&dir, &only, &self.params.tags);
+        if self.params.resume {
+            match Progress::load(path)? {
+                Some(interrupted) if interrupted.is_of_same_run(&progress) => {
+                    writeln!(
+                        self.out,
+                        "Resuming the run interrupted in {}.",
+                        dir.display()
+                    )?;
+                    progress = interrupted;
+                }
+                _ => writeln!(
+                    self.out,
+                    "{}",
+                    format!(
+                        "No interrupted run to resume in {}, so starting from the top.",
+                        dir.display()
+                    )
+                    .yellow()
+                )?,
+            }
+        }
+        self.progress = Some(Arc::new(Mutex::new(progress)));
+
+        Ok(())
+    }
+
+    /// Whether the line `line_no` of the symlink-specification file being
+    /// processed has already been processed, by the interrupted run being
+    /// resumed (see [`Params::resume`]).
+    fn resumed(&self, line_no: u64) -> bool {
+        let (Some(progress), Some(sls)) = (&self.progress, self.include_stack.last()) else {
+            return false;
+        };
+        progress.lock().unwrap().is_done(sls, line_no)
+    }
+
+    /// Records that the line `line_no` of the symlink-specification file
+    /// `sls` being processed has been processed, in the progress of the run
+    /// (see [`Params::resume`]).
+    ///
+    /// The progress is only saved once a file has been processed, and when
+    /// the run fails (see [`Engine::save_progress`]), not to write it for
+    /// every line.
+    ///
+    /// The line is left to process again if the user quit the run at it, and
+    /// nothing is recorded while only collecting the conflicts to resolve in
+    /// the terminal user interface (see [`Params::tui`]). Once a conflict of
+    /// `sls` is postponed to the end of the run (see
+    /// [`Params::resolve_at_end`]), nothing more is recorded for it either,
+    /// as its lines are only done once the conflict is resolved.
+    fn progressed(&self, sls: &Path, line_no: u64) {
+        let (Some(progress), Some(canonical_sls)) = (&self.progress, self.include_stack.last())
+        else {
+            return;
+        };
+        let postponed = self.postponed.as_ref().is_some_and(|postponed| {
+            postponed
+                .iter()
+                .any(|postponed| postponed.spec_line.0 == sls)
+        });
+        if self.summary.quit || self.pending.is_some() || postponed {
+            return;
+        }
+
+        progress.lock().unwrap().done(canonical_sls, line_no);
+    }
+
+    /// Saves the progress of the run (see [`Params::resume`]), if kept,
+    /// unless in dry-run mode.
+    ///
+    /// # Errors
+    ///
+    /// Fails when saving the progress fails (see [`Progress::save`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn save_progress(&self) -> crate::Result<()> {
+        match &self.progress {
+            Some(progress) if !self.params.dry_run => {
+                progress.lock().unwrap().save(&Progress::default_path()?)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records `operation` if in transactional mode, so that it can be undone.
+    fn record(&mut self, operation: Operation) {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.operations.push(operation);
+        }
+    }
+
+    /// Returns the directory where to move the overwritten files if in
+    /// transactional mode, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the directory fails to be created.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn transaction_dir(&self) -> crate::Result<Option<PathBuf>> {
+        match &self.transaction {
+            Some(transaction) => {
+                fs::create_dir_all(&transaction.save_dir).io_context(|| {
+                    format!(
+                        "Failed to create directory {} to keep overwritten files until the end of the run.",
+                        transaction.save_dir.display()
+                    )
+                })?;
+                Ok(Some(transaction.save_dir.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Creates the directory `dir` and its missing parents, recording the
+    /// directories created (see [`Engine::record`]).
+    ///
+    /// Does nothing if only collecting the links specified (see [`Engine::prune`]
+    /// and [`Engine::clean_broken`]).
+    ///
+    /// # Errors
+    ///
+    /// Fails when a directory fails to be created.
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        if self.specified.is_some() || self.pending.is_some() || self.params.dry_run {
+            return Ok(());
+        }
+
+        let mut missing: Vec<PathBuf> = dir
+            .ancestors()
+            .take_while(|dir| !self.fs.exists(dir))
+            .map(Path::to_path_buf)
+            .collect();
+        self.fs.create_dir_all(dir)?;
+        while let Some(created) = missing.pop() {
+            self.record(Operation::CreatedDir(created));
+        }
+
+        Ok(())
+    }
+
+    /// Ends the run's transaction, if in transactional mode, removing the
+    /// files overwritten during the run for good.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the overwritten files fail to be removed.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn commit(&mut self) -> crate::Result<()> {
+        if let Some(transaction) = self.transaction.take() {
+            if transaction.save_dir.exists() {
+                fs::remove_dir_all(&transaction.save_dir).io_context(|| {
+                    format!(
+                        "Failed to remove directory {}, where overwritten files were kept until the end of the run.",
+                        transaction.save_dir.display()
+                    )
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Archives the backups made during the run if
+    /// [`Params::archive_backups`] (see [`backups::archive_run`]), then saves
+    /// the index of the backups, unless it is empty.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Archiving the backups fails (see [`backups::archive_run`]).
+    /// - Writing the index fails (see [`backups::Index::save`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn finish_backups(&self) -> crate::Result<()> {
+        if self.params.dry_run {
+            return Ok(());
+        }
+
+        let mut backup_index = self.backup_index.lock().unwrap();
+        if self.params.archive_backups {
+            if let Some(archive) = backups::archive_run(&self.params.backup_dir, &self.run_id)? {
+                backup_index.archived(&self.run_id, &archive);
+                self.log(
+                    LogLevel::Info,
+                    &format!(
+                        "Archived the backups of the run into {}.",
+                        archive.to_string_lossy()
+                    ),
+                );
+            }
+        }
+        if backup_index.iter().next().is_none() {
+            return Ok(());
+        }
+
+        backup_index.save(&self.params.backup_dir)
+    }
+
+    /// Undoes the changes recorded during the run's transaction, in reverse
+    /// order, reporting what has been undone.
+    ///
+    /// A change that fails to be undone is reported with a warning, and the
+    /// rollback goes on.
+    fn rollback(&mut self) {
+        let Some(transaction) = self.transaction.take() else {
+            return;
+        };
+
+        let _ = writeln!(self.out);
+        let _ = writeln!(
+            self.out,
+            "{}",
+            "An error occurred, rolling back...".yellow()
+        );
+        for operation in transaction.operations.into_iter().rev() {
+            let (undone, result) = match operation {
+                Operation::Made { link } => (
+                    format!("Removed {}", link.to_string_lossy()),
+                    self.fs.remove(&link),
+                ),
+                Operation::Moved { link, moved } => (
+                    format!(
+                        "Restored {} from {}",
+                        link.to_string_lossy(),
+                        moved.to_string_lossy()
+                    ),
+                    self.fs
+                        .remove(&link)
+                        .and_then(|_| utils::move_path(&*self.fs, &moved, &link)),
+                ),
+                Operation::Relinked { link, previous } => (
+                    format!(
+                        "Relinked {} to {}",
+                        link.to_string_lossy(),
+                        previous.to_string_lossy()
+                    ),
+                    utils::replace_symlink(&*self.fs, &previous, &link),
+                ),
+                Operation::Adopted {
+                    link,
+                    target,
+                    saved,
+                } => (
+                    format!(
+                        "Moved {} back from {}",
+                        link.to_string_lossy(),
+                        target.to_string_lossy()
+                    ),
+                    self.fs
+                        .remove(&link)
+                        .and_then(|_| utils::move_path(&*self.fs, &target, &link))
+                        .and_then(|_| match &saved {
+                            Some(saved) => utils::move_path(&*self.fs, saved, &target),
+                            None => Ok(()),
+                        }),
+                ),
+                Operation::CreatedDir(dir) => (
+                    format!("Removed directory {}", dir.to_string_lossy()),
+                    fs::remove_dir(&dir),
+                ),
+            };
+            match result {
+                Ok(()) => {
+                    self.log(LogLevel::Warn, &format!("Rolled back: {}.", undone));
+                    let _ = writeln!(self.out, "{}", format!("(u) {}", undone).yellow());
+                }
+                Err(err) => eprintln!(
+                    "{}",
+                    format!("(!) Warning: Failed to undo \"{}\": {}", undone, err).yellow()
+                ),
+            }
+        }
+        let _ = fs::remove_dir(&transaction.save_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::Config;
+    use crate::cli::Cli;
+    use crate::filesystem::{MemFs, Node};
+    use crate::prompt::Prompt;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use clap::Parser;
+
+    /// Returns an engine run with the command-line arguments `args`, making
+    /// the symlinks in `fs`.
+    fn engine(args: &[&str], fs: &Arc<MemFs>) -> Result<Engine, Box<dyn std::error::Error>> {
+        let cli = Cli::try_parse_from([&["mksls"], args, &["/dots"]].concat())?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        engine.fs = Arc::clone(fs) as Arc<dyn Fs>;
+        Ok(engine)
+    }
+
+    /// A buffer capturing the output.
+    #[derive(Debug, Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A prompter answering the conflicts and the stale symlinks as
+    /// scripted, refusing the rest, and recording what it is prompted for.
+    #[derive(Debug, Clone)]
+    struct Scripted {
+        conflict: AlreadyExistPromptOptions,
+        stale_symlink: StaleSymlinkPromptOptions,
+        prompted: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Scripted {
+        fn new(
+            conflict: AlreadyExistPromptOptions,
+            stale_symlink: StaleSymlinkPromptOptions,
+        ) -> Self {
+            Self {
+                conflict,
+                stale_symlink,
+                prompted: Arc::default(),
+            }
+        }
+
+        fn prompted(&self, prompt: String) {
+            self.prompted.lock().unwrap().push(prompt);
+        }
+    }
+
+    impl Prompter for Scripted {
+        fn resolve_conflict(
+            &self,
+            _: &Path,
+            link: &Path,
+            _: &dyn Fn() -> Option<String>,
+        ) -> crate::Result<AlreadyExistPromptOptions> {
+            self.prompted(format!("conflict {}", link.display()));
+            Ok(self.conflict)
+        }
+
+        fn resolve_stale_symlink(
+            &self,
+            _: &Path,
+            link: &Path,
+            _: &Path,
+        ) -> crate::Result<StaleSymlinkPromptOptions> {
+            self.prompted(format!("stale symlink {}", link.display()));
+            Ok(self.stale_symlink)
+        }
+
+        fn confirm_adopt(&self, _: &Path, link: &Path) -> crate::Result<bool> {
+            self.prompted(format!("adopt {}", link.display()));
+            Ok(false)
+        }
+
+        fn confirm_dir_overwrite(&self, link: &Path, entries: u64) -> crate::Result<bool> {
+            self.prompted(format!(
+                "dir overwrite {} ({} entries)",
+                link.display(),
+                entries
+            ));
+            Ok(false)
+        }
+
+        fn resolve_broken_symlink(
+            &self,
+            _: &Path,
+            link: &Path,
+        ) -> crate::Result<BrokenSymlinkPromptOptions> {
+            self.prompted(format!("broken symlink {}", link.display()));
+            Ok(BrokenSymlinkPromptOptions::Keep)
+        }
+
+        fn acknowledge_error(&self, _: &str) -> crate::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn make_link_handles_conflicts_in_the_given_fs() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/file"), Node::File)
+                .with(Path::new("/home/stale"), Node::Symlink("/old".into()))
+                .with(Path::new("/home/made"), Node::Symlink("/dots/made".into())),
+        );
+        let mut engine = engine(&["--always-overwrite", "--relink"], &fs)?;
+
+        for name in ["file", "stale", "made", "new"] {
+            let target = Path::new("/dots").join(name);
+            let link = Path::new("/home").join(name);
+            engine.make_link(&LinkKind::Symlink, &target, &link)?;
+            assert_eq!(fs.get(&link), Some(Node::Symlink(target)));
+        }
+        assert_eq!(engine.summary.overwritten, 1);
+        assert_eq!(engine.summary.relinked, 1);
+        assert_eq!(engine.summary.already_existing, 1);
+        assert_eq!(engine.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backups_are_made_in_the_given_fs() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/dir"), Node::Dir)
+                .with(Path::new("/home/dir/file"), Node::File)
+                .with(Path::new("/home/stale"), Node::Symlink("/old".into())),
+        );
+        let mut engine = engine(&["--always-backup", "--backup-dir", "/backups"], &fs)?;
+
+        for name in ["dir", "stale"] {
+            let target = Path::new("/dots").join(name);
+            let link = Path::new("/home").join(name);
+            engine.make_link(&LinkKind::Symlink, &target, &link)?;
+            assert_eq!(fs.get(&link), Some(Node::Symlink(target)));
+        }
+        assert_eq!(engine.summary.backed_up, 2);
+        assert_eq!(fs.get(Path::new("/home/dir/file")), None);
+        let run = Path::new("/backups").join(&engine.run_id);
+        let backups: Vec<PathBuf> = engine
+            .backup_index
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|record| record.backup.clone())
+            .collect();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].parent(), Some(run.join("home/dir").as_path()));
+        assert_eq!(fs.get(&backups[0]), Some(Node::Dir));
+        assert_eq!(fs.get(&backups[0].join("file")), Some(Node::File));
+        assert_eq!(backups[1].parent(), Some(run.join("home/stale").as_path()));
+        assert_eq!(fs.get(&backups[1]), Some(Node::Symlink("/old".into())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn directories_are_overwritten_in_the_given_fs() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/dir"), Node::Dir)
+                .with(Path::new("/home/dir/file"), Node::File)
+                .with(Path::new("/home/kept"), Node::Dir),
+        );
+        let refuser = Scripted::new(
+            AlreadyExistPromptOptions::Skip,
+            StaleSymlinkPromptOptions::Skip,
+        );
+        let mut engine = engine(&["--always-overwrite"], &fs)?.prompter(refuser.clone());
+
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/kept"),
+            Path::new("/home/kept"),
+        )?;
+        assert_eq!(fs.get(Path::new("/home/kept")), Some(Node::Dir));
+        assert_eq!(engine.summary.skipped, 1);
+        engine.params.allow_dir_overwrite = true;
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/dir"),
+            Path::new("/home/dir"),
+        )?;
+
+        assert_eq!(
+            fs.get(Path::new("/home/dir")),
+            Some(Node::Symlink("/dots/dir".into()))
+        );
+        assert_eq!(fs.get(Path::new("/home/dir/file")), None);
+        assert_eq!(engine.summary.overwritten, 1);
+        assert_eq!(
+            *refuser.prompted.lock().unwrap(),
+            ["dir overwrite /home/kept (0 entries)"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn make_link_changes_nothing_in_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default().with(Path::new("/home/file"), Node::File));
+        let mut engine = Engine::builder()
+            .dir("/dots")
+            .default_action(PolicyAction::Overwrite)
+            .dry_run(true)
+            .output(io::sink())
+            .build()?;
+        engine.fs = Arc::clone(&fs) as Arc<dyn Fs>;
+
+        for name in ["file", "new"] {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+
+        assert_eq!(fs.get(Path::new("/home/file")), Some(Node::File));
+        assert_eq!(fs.get(Path::new("/home/new")), None);
+        assert_eq!(engine.summary.overwritten, 1);
+        assert_eq!(engine.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts_are_collected_then_resolved_as_chosen() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/file"), Node::File)
+                .with(Path::new("/home/stale"), Node::Symlink("/old".into()))
+                .with(Path::new("/home/made"), Node::Symlink("/dots/made".into())),
+        );
+        let mut engine = engine(&["--tui"], &fs)?;
+        let names = ["file", "stale", "made", "new"];
+
+        engine.pending = Some(vec![]);
+        for name in names {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+        let pending = engine.pending.take().unwrap_or_default();
+        let links: Vec<&Path> = pending
+            .iter()
+            .map(|(_, conflict)| conflict.link.as_path())
+            .collect();
+        assert_eq!(links, [Path::new("/home/file"), Path::new("/home/stale")]);
+        assert!(!pending[0].1.stale && pending[1].1.stale);
+        assert_eq!(fs.get(Path::new("/home/new")), None);
+
+        engine.resolutions = HashMap::from([
+            (PathBuf::from("/home/file"), Action::Overwrite),
+            (PathBuf::from("/home/stale"), Action::Skip),
+        ]);
+        for name in names {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+        assert_eq!(
+            fs.get(Path::new("/home/file")),
+            Some(Node::Symlink("/dots/file".into()))
+        );
+        assert_eq!(
+            fs.get(Path::new("/home/stale")),
+            Some(Node::Symlink("/old".into()))
+        );
+        assert_eq!(engine.summary.overwritten, 1);
+        assert_eq!(engine.summary.skipped, 1);
+        assert_eq!(engine.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn broken_symlinks_are_replaced_without_asking() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/broken"), Node::Symlink("/nowhere".into()))
+                .with(Path::new("/home/stale"), Node::Symlink("/elsewhere".into()))
+                .with(Path::new("/elsewhere"), Node::File),
+        );
+        let mut engine = engine(&["--replace-broken", "--always-skip"], &fs)?;
+
+        for name in ["broken", "stale"] {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+
+        assert_eq!(
+            fs.get(Path::new("/home/broken")),
+            Some(Node::Symlink("/dots/broken".into()))
+        );
+        assert_eq!(
+            fs.get(Path::new("/home/stale")),
+            Some(Node::Symlink("/elsewhere".into()))
+        );
+        assert_eq!(engine.summary.repaired, 1);
+        assert_eq!(engine.summary.skipped, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_links_selected_are_made() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default());
+        let mut engine = engine(
+            &["--only", "/home/nvim", "--skip", "/home/nvim/secret*"],
+            &fs,
+        )?;
+
+        for name in ["nvim/init.lua", "nvim/secret.lua", "zshrc"] {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+
+        assert_eq!(
+            fs.get(Path::new("/home/nvim/init.lua")),
+            Some(Node::Symlink("/dots/nvim/init.lua".into()))
+        );
+        assert_eq!(fs.get(Path::new("/home/nvim/secret.lua")), None);
+        assert_eq!(fs.get(Path::new("/home/zshrc")), None);
+        assert_eq!(engine.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_lines_tagged_as_given_are_processed() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default());
+        let mut engine = engine(&["--tags", "gui,laptop", "--allow-missing-targets"], &fs)?;
+        let lines = [
+            "xinitrc /home/xinitrc # @gui",
+            "server /home/server # for the @server",
+            "zshrc /home/zshrc",
+            "tags: @desktop",
+            "monitors /home/monitors",
+            "battery /home/battery # @laptop",
+            "tags:",
+            "vimrc /home/vimrc",
+        ];
+
+        for (i, line) in lines.into_iter().enumerate() {
+            engine.process_line(
+                Path::new("/dots/sls"),
+                Path::new("/dots"),
+                i as u64 + 1,
+                String::from(line),
+            )?;
+        }
+
+        let made: Vec<_> = ["xinitrc", "server", "zshrc", "monitors", "battery", "vimrc"]
+            .into_iter()
+            .filter(|name| fs.get(&Path::new("/home").join(name)).is_some())
+            .collect();
+        assert_eq!(made, ["xinitrc", "zshrc", "battery", "vimrc"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lint_reports_invalid_lines_and_duplicate_links() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        dir.child("sls")
+            .write_str("a /home/a\na /home/b\nmissing /home/c\na\na /home/a\n")?;
+        let engine = Engine::builder()
+            .dir(dir.path())
+            .output(io::sink())
+            .build()?;
+
+        assert_eq!(engine.lint()?, 3);
+        assert!(!Path::new("/home/a").is_symlink());
+
+        Ok(())
+    }
+
+    #[test]
+    fn lines_ending_with_a_backslash_are_continued() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a b").touch()?;
+        dir.child("c").touch()?;
+        let home = dir.child("home");
+        home.create_dir_all()?;
+        dir.child("sls").write_str(&format!(
+            "./a\\\n\\ b \\\n    {0}/ab\nc {0}/c\\\\\nc {0}/d \\\n",
+            home.path().display()
+        ))?;
+        let mut engine = Engine::builder()
+            .dir(dir.path())
+            .output(io::sink())
+            .build()?;
+
+        engine.process_file(dir.child("sls").path().to_path_buf())?;
+
+        assert_eq!(fs::read_link(home.child("ab"))?, dir.child("a b").path());
+        assert_eq!(fs::read_link(home.child("c\\"))?, dir.child("c").path());
+        // The last line is continued by nothing.
+        assert_eq!(fs::read_link(home.child("d"))?, dir.child("c").path());
+        assert_eq!(engine.summary.created, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn paths_that_are_not_utf8_are_linked() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"a\xff");
+        let dir = TempDir::new()?;
+        dir.child("dots").child(name).touch()?;
+        dir.child("sls").write_str(&format!(
+            "dir dots {}\n",
+            dir.child("home").path().display()
+        ))?;
+        dir.child("bad")
+            .child("sls")
+            .write_binary(b"a\xff /home/a\n")?;
+        let mut engine = Engine::builder()
+            .dir(dir.path())
+            .output(io::sink())
+            .build()?;
+
+        engine.process_file(dir.child("sls").path().to_path_buf())?;
+
+        assert_eq!(
+            fs::read_link(dir.child("home").child(name))?,
+            dir.child("dots").child(name).path()
+        );
+        assert_eq!(engine.summary.created, 1);
+
+        let engine = Engine::builder()
+            .dir(dir.child("bad").path())
+            .output(io::sink())
+            .build()?;
+        assert_eq!(engine.lint()?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keep_going_records_failures_and_goes_on() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        let link = |name| dir.child(name).path().display().to_string();
+        dir.child("sls")
+            .write_str(&format!("a {}\na {}\n", link("missing/a"), link("b")))?;
+        let cli = Cli::try_parse_from(["mksls", "--keep-going", &link("")])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        engine.process_file(dir.child("sls").path().to_path_buf())?;
+
+        assert!(dir.child("b").path().is_symlink());
+        assert_eq!(engine.summary.created, 1);
+        assert_eq!(engine.summary.failed, 1);
+        assert_eq!(engine.failures.len(), 1);
+        assert_eq!(engine.summary.status(), RunStatus::Failed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_fails_at_the_first_invalid_line() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        let link = |name| dir.child(name).path().display().to_string();
+        dir.child("sls")
+            .write_str(&format!("not a line\na {}\n", link("b")))?;
+        let cli = Cli::try_parse_from(["mksls", "--strict", &link("")])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        let err = engine
+            .process_file(dir.child("sls").path().to_path_buf())
+            .unwrap_err();
+
+        assert!(matches!(err, Error::InvalidLine { .. }));
+        assert!(!dir.child("b").path().exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn links_specified_with_different_targets_conflict() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        dir.child("b").touch()?;
+        let link = dir.child("link").path().display().to_string();
+        dir.child("sls").write_str(&format!("a {}\n", link))?;
+        dir.child("other/sls")
+            .write_str(&format!("../a {}\n", link))?;
+        let engine = Engine::builder()
+            .dir(dir.path())
+            .output(io::sink())
+            .build()?;
+
+        engine.check_conflicts()?;
+
+        dir.child("other/sls")
+            .write_str(&format!("../b {}\n", link))?;
+        let err = engine.check_conflicts().unwrap_err();
+
+        assert!(matches!(err, Error::ConflictingSpecs { .. }));
+        assert!(err.to_string().contains(&link));
+        assert!(!dir.child("link").path().exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn links_denied_are_left_to_the_root_script() -> Result<(), Box<dyn std::error::Error>> {
+        /// Denies making symlinks, as in a directory owned by root.
+        #[derive(Debug)]
+        struct DeniedFs;
+
+        impl Fs for DeniedFs {
+            fn symlink(&self, _: &Path, _: &Path) -> io::Result<()> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+
+            fn rename(&self, _: &Path, _: &Path) -> io::Result<()> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+
+            fn remove(&self, _: &Path) -> io::Result<()> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+
+            fn read_link(&self, _: &Path) -> io::Result<PathBuf> {
+                Err(io::ErrorKind::NotFound.into())
+            }
+
+            fn canonicalize(&self, _: &Path) -> io::Result<PathBuf> {
+                Err(io::ErrorKind::NotFound.into())
+            }
+
+            fn exists(&self, _: &Path) -> bool {
+                false
+            }
+
+            fn is_symlink(&self, _: &Path) -> bool {
+                false
+            }
+
+            fn is_dir(&self, _: &Path) -> bool {
+                false
+            }
+
+            fn is_file(&self, _: &Path) -> bool {
+                false
+            }
+
+            fn create_dir_all(&self, _: &Path) -> io::Result<()> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+
+            fn copy(&self, _: &Path, _: &Path) -> io::Result<()> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+        }
+
+        let link = Path::new("/etc/app.conf");
+        let target = Path::new("/dots/app.conf");
+        let cli = Cli::try_parse_from(["mksls", "--root-script", "/tmp/root.sh", "/dots"])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?)
+            .output(io::sink())
+            .filesystem(DeniedFs);
+
+        engine.make_link(&LinkKind::Symlink, target, link)?;
+
+        assert_eq!(engine.summary.deferred, 1);
+        assert_eq!(
+            *engine.root_commands.lock().unwrap(),
+            vec![LinkKind::Symlink.command(target, link).unwrap()]
+        );
+
+        let cli = Cli::try_parse_from(["mksls", "/dots"])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?)
+            .output(io::sink())
+            .filesystem(DeniedFs);
+
+        assert!(matches!(
+            engine.make_link(&LinkKind::Symlink, target, link),
+            Err(Error::Link { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn make_link_stops_once_the_user_quit() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default().with(Path::new("/home/file"), Node::File));
+        let quitter = Scripted::new(
+            AlreadyExistPromptOptions::Quit,
+            StaleSymlinkPromptOptions::Quit,
+        );
+        let mut engine = engine(&[], &fs)?.prompter(quitter.clone());
+
+        for name in ["file", "new"] {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+
+        assert_eq!(fs.get(Path::new("/home/file")), Some(Node::File));
+        assert_eq!(fs.get(Path::new("/home/new")), None);
+        assert_eq!(engine.summary.status(), RunStatus::Quit);
+        assert_eq!(*quitter.prompted.lock().unwrap(), ["conflict /home/file"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn choices_for_the_file_only_apply_to_the_rest_of_the_file(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        for name in ["b", "c", "d", "e"] {
+            dir.child(name).write_str("x")?;
+        }
+        let link = |name| dir.child(name).path().display().to_string();
+        dir.child("one.sls")
+            .write_str(&format!("a {}\na {}\n", link("b"), link("c")))?;
+        dir.child("two.sls")
+            .write_str(&format!("a {}\na {}\n", link("d"), link("e")))?;
+        let file_skipper = Scripted::new(
+            AlreadyExistPromptOptions::FileSkip,
+            StaleSymlinkPromptOptions::FileSkip,
+        );
+        let mut engine = Engine::builder()
+            .dir(dir.path())
+            .output(io::sink())
+            .build()?
+            .prompter(file_skipper.clone());
+
+        engine.process_file(dir.child("one.sls").path().to_path_buf())?;
+        engine.process_file(dir.child("two.sls").path().to_path_buf())?;
+
+        assert_eq!(
+            *file_skipper.prompted.lock().unwrap(),
+            [
+                format!("conflict {}", link("b")),
+                format!("conflict {}", link("d"))
+            ]
+        );
+        assert_eq!(engine.summary.skipped, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn porcelain_prints_a_line_per_link() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/b"), Node::File)
+                .with(Path::new("/etc/c"), Node::File),
+        );
+        let buffer = Buffer::default();
+        let mut engine = engine(
+            &[
+                "--porcelain",
+                "--allow-missing-targets",
+                "--always-overwrite",
+            ],
+            &fs,
+        )?
+        .output(buffer.clone());
+        engine.params.protected = vec![glob::Pattern::new("/etc/*")?];
+
+        for (i, line) in ["a /home/a", "", "b /home/b", "c /etc/c"]
+            .into_iter()
+            .enumerate()
+        {
+            engine.process_line(
+                Path::new("/dots/s\tls"),
+                Path::new("/dots"),
+                i as u64 + 1,
+                String::from(line),
+            )?;
+        }
+
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone())?,
+            "created\t/home/a\t/dots/a\t/dots/s\\tls\t1\n\
+             overwritten\t/home/b\t/dots/b\t/dots/s\\tls\t3\n\
+             failed\t/etc/c\t/dots/c\t/dots/s\\tls\t4\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn report_lists_every_link() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let report = dir.child("report.json");
+        let fs = Arc::new(MemFs::default().with(Path::new("/home/b"), Node::File));
+        let mut engine = engine(
+            &[
+                "--report",
+                report.to_str().unwrap(),
+                "--report-format",
+                "json",
+                "--allow-missing-targets",
+                "--always-skip",
+            ],
+            &fs,
+        )?;
+
+        for (i, line) in ["a /home/a", "b /home/b"].into_iter().enumerate() {
+            engine.process_line(
+                Path::new("/dots/sls"),
+                Path::new("/dots"),
+                i as u64 + 1,
+                String::from(line),
+            )?;
+        }
+        engine.write_report()?;
+
+        let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(report)?)?;
+        assert_eq!(report["entries"][0]["action"], "created");
+        assert_eq!(report["entries"][0]["link"], "/home/a");
+        assert_eq!(report["entries"][1]["action"], "skipped");
+        assert_eq!(report["entries"][1]["line"], 2);
+        assert_eq!(report["summary"]["created"], 1);
+        assert_eq!(report["summary"]["skipped"], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts_are_resolved_at_the_end() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/file"), Node::File)
+                .with(Path::new("/home/stale"), Node::Symlink("/old".into())),
+        );
+        let mut engine = engine(&["--resolve-at-end"], &fs)?;
+
+        for name in ["file", "stale", "new"] {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+        assert_eq!(
+            fs.get(Path::new("/home/new")),
+            Some(Node::Symlink("/dots/new".into()))
+        );
+        let postponed = engine.postponed.take().unwrap_or_default();
+        let links: Vec<&Path> = postponed
+            .iter()
+            .map(|postponed| postponed.conflict.link.as_path())
+            .collect();
+        assert_eq!(links, [Path::new("/home/file"), Path::new("/home/stale")]);
+        assert_eq!(fs.get(Path::new("/home/file")), Some(Node::File));
+
+        engine.apply_postponed(postponed, vec![Some(Action::Overwrite), Some(Action::Skip)])?;
+        assert_eq!(
+            fs.get(Path::new("/home/file")),
+            Some(Node::Symlink("/dots/file".into()))
+        );
+        assert_eq!(
+            fs.get(Path::new("/home/stale")),
+            Some(Node::Symlink("/old".into()))
+        );
+        assert_eq!(
+            (
+                engine.summary.created,
+                engine.summary.overwritten,
+                engine.summary.skipped
+            ),
+            (1, 1, 1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn skipped_links_are_recorded_until_made() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default().with(Path::new("/home/a"), Node::File));
+        let mut engine = engine(&["--always-skip"], &fs)?;
+        let (target, link) = (Path::new("/dots/a"), Path::new("/home/a"));
+
+        engine.make_link(&LinkKind::Symlink, target, link)?;
+        assert_eq!(
+            engine.skipped.lock().unwrap().iter().collect::<Vec<_>>(),
+            [(link, target)]
+        );
+
+        engine.action = Some(Action::Overwrite);
+        engine.make_link(&LinkKind::Symlink, target, link)?;
+        assert!(engine.skipped.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_skipped_links_themselves_are_made_again() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let fs = Arc::new(MemFs::default());
+        let mut engine = engine(&[], &fs)?;
+        engine.resolving = Some(HashSet::from([PathBuf::from("/home/a")]));
+
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/a/b"),
+            Path::new("/home/a/b"),
+        )?;
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/a"),
+            Path::new("/home/a"),
+        )?;
+
+        assert!(!fs.exists(Path::new("/home/a/b")));
+        assert_eq!(fs.read_link(Path::new("/home/a"))?, Path::new("/dots/a"));
+        assert_eq!(engine.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_is_grouped_by_file() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let link = |name: &str| dir.child("home").child(name).path().display().to_string();
+        dir.child("dots/a/sls")
+            .write_str(&format!("file {}\nfile {}\n", link("a"), link("b")))?;
+        dir.child("dots/b/sls")
+            .write_str(&format!("file {}\n", link("b")))?;
+        dir.child("dots/a/file").touch()?;
+        dir.child("dots/b/file").touch()?;
+        dir.child("home").create_dir_all()?;
+        let cli = Cli::try_parse_from([
+            "mksls",
+            "--group-by-file",
+            "--sorted",
+            "--always-skip",
+            dir.child("dots").path().to_str().unwrap(),
+        ])?;
+        let buffer = Buffer::default();
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(buffer.clone());
+
+        engine.process_dir()?;
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone())?;
+        let lines: Vec<&str> = output
+            .lines()
+            .filter(|line| line.contains("==") || line.contains("--"))
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("== a/sls =="));
+        assert!(lines[1].contains("-- 2 created"));
+        assert!(lines[2].contains("== b/sls =="));
+        assert!(lines[3].contains("-- 1 skipped"));
+        assert_eq!((engine.summary.created, engine.summary.skipped), (2, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn plain_specifications_use_the_operation_chosen() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("dots/file").write_str("contents")?;
+        dir.child("dots/nvim").create_dir_all()?;
+        dir.child("home").create_dir_all()?;
+        let link = |name: &str| dir.child("home").child(name).path().display().to_string();
+        dir.child("dots/sls").write_str(&format!(
+            "file {}\nnvim {}\n",
+            link("file"),
+            link("nvim")
+        ))?;
+        let cli = Cli::try_parse_from([
+            "mksls",
+            "--operation",
+            "copy",
+            dir.child("dots").path().to_str().unwrap(),
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        engine.process_dir()?;
+
+        let copy = dir.child("home/file");
+        assert!(!copy.path().is_symlink());
+        assert_eq!(fs::read_to_string(copy)?, "contents");
+        assert_eq!(
+            fs::read_link(dir.child("home/nvim"))?,
+            dir.child("dots/nvim").path()
+        );
+        assert_eq!(engine.summary.created, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prompts_are_answered_as_given() -> Result<(), Box<dyn std::error::Error>> {
+        let names = ["a", "b", "c"];
+        let fs = Arc::new(names.iter().fold(MemFs::default(), |fs, name| {
+            fs.with(&Path::new("/home").join(name), Node::File)
+        }));
+        let mut engine = engine(&["--answers", "x,s,O"], &fs)?;
+
+        for name in names {
+            engine.make_link(
+                &LinkKind::Symlink,
+                &Path::new("/dots").join(name),
+                &Path::new("/home").join(name),
+            )?;
+        }
+
+        // The wrong answer is rejected, as if entered.
+        assert_eq!(fs.get(Path::new("/home/a")), Some(Node::File));
+        assert_eq!((engine.summary.skipped, engine.summary.overwritten), (1, 2));
+
+        let err = engine
+            .prompter
+            .confirm_adopt(Path::new("/dots/d"), Path::new("/home/d"))
+            .unwrap_err();
+        assert!(err.to_string().contains("No answer left"));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_files_are_backed_up_but_not_adopted() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let target = dir.child("dots/sock");
+        target.touch()?;
+        let link = dir.child("home/sock");
+        dir.child("home").create_dir_all()?;
+        let _listener = std::os::unix::net::UnixListener::bind(&link)?;
+        let backup_dir = dir.child("backups");
+        let cli = Cli::try_parse_from([
+            "mksls".as_ref(),
+            "--answers".as_ref(),
+            "a,b".as_ref(),
+            "--backup-dir".as_ref(),
+            backup_dir.as_os_str(),
+            dir.as_os_str(),
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        engine.make_link(&LinkKind::Symlink, &target, &link)?;
+        assert_eq!(utils::special_file(&link), Some(utils::SpecialFile::Socket));
+        assert_eq!(engine.summary.skipped, 1);
+
+        engine.make_link(&LinkKind::Symlink, &target, &link)?;
+        assert_eq!(fs::read_link(&link)?, target.path());
+        assert_eq!(engine.summary.backed_up, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn symlinks_are_not_adopted_and_existing_targets_are_backed_up(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let target = dir.child("dots/vimrc");
+        target.write_str("real")?;
+        let link = dir.child("home/.vimrc");
+        dir.child("home").create_dir_all()?;
+        crate::platform::symlink(dir.child("old/vimrc"), &link)?;
+        let backup_dir = dir.child("backups");
+        let cli = Cli::try_parse_from([
+            "mksls".as_ref(),
+            "--always-adopt".as_ref(),
+            "--backup-dir".as_ref(),
+            backup_dir.as_os_str(),
+            dir.as_os_str(),
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        engine.adopt(&LinkKind::Symlink, &target, &link)?;
+        assert_eq!(fs::read_to_string(&target)?, "real");
+        assert_eq!(fs::read_link(&link)?, dir.child("old/vimrc").path());
+        assert_eq!(engine.summary.skipped, 1);
+        assert!(utils::adopt(io::sink(), &RealFs, &LinkKind::Symlink, &target, &link).is_err());
+        assert_eq!(fs::read_to_string(&target)?, "real");
+
+        fs::remove_file(&link)?;
+        link.write_str("local")?;
+        engine.adopt(&LinkKind::Symlink, &target, &link)?;
+        assert_eq!(fs::read_to_string(&target)?, "local");
+        assert_eq!(fs::read_link(&link)?, target.path());
+        let index = engine.backup_index.lock().unwrap();
+        let backup = &index.iter().next().unwrap().backup;
+        assert_eq!(fs::read_to_string(backup)?, "real");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sessions_are_recorded_then_replayed() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let session = dir.child("session.json");
+        let session = session.to_str().unwrap();
+        let (target, link) = (Path::new("/dots/a"), Path::new("/home/a"));
+
+        let fs = Arc::new(MemFs::default().with(link, Node::File));
+        let mut recording = engine(&["--answers", "o", "--record", session], &fs)?;
+        recording.make_link(&LinkKind::Symlink, target, link)?;
+        recording.save_session()?;
+        assert_eq!(
+            Session::load(Path::new(session))?.answer(Prompt::Conflict, link),
+            Some("o")
+        );
+
+        // Without the replay, the prompt would fail for lack of a valid answer.
+        let fs = Arc::new(MemFs::default().with(link, Node::File));
+        let mut replaying = engine(&["--answers", "", "--replay", session], &fs)?;
+        replaying.make_link(&LinkKind::Symlink, target, link)?;
+        assert_eq!(fs.get(link), Some(Node::Symlink(target.into())));
+
+        Ok(())
+    }
+
+    #[test]
+    fn protected_files_are_never_replaced() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(
+            MemFs::default()
+                .with(Path::new("/home/.ssh/id_rsa"), Node::File)
+                .with(Path::new("/home/.ssh/config"), Node::File),
+        );
+        let cli = Cli::try_parse_from(["mksls", "--always-overwrite", "/dots"])?;
+        let cfg = Config {
+            protected: vec![String::from("/home/.ssh/id_*")],
+            ..Config::default()
+        };
+        let mut engine = Engine::new(Params::new(cli, cfg)?).output(io::sink());
+        engine.fs = Arc::clone(&fs) as Arc<dyn Fs>;
+
+        // Without --keep-going, the run goes on all the same.
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/id_rsa"),
+            Path::new("/home/.ssh/id_rsa"),
+        )?;
+        assert_eq!(engine.summary.failed, 1);
+        assert_eq!(engine.failures.len(), 1);
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/config"),
+            Path::new("/home/.ssh/config"),
+        )?;
+
+        assert_eq!(fs.get(Path::new("/home/.ssh/id_rsa")), Some(Node::File));
+        assert_eq!(
+            fs.get(Path::new("/home/.ssh/config")),
+            Some(Node::Symlink("/dots/config".into()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn directories_are_overwritten_only_if_allowed() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("target").touch()?;
+        for name in ["a", "b"] {
+            dir.child(name).child("entry").touch()?;
+        }
+        let target = dir.child("target");
+
+        let cli = Cli::try_parse_from([
+            OsStr::new("mksls"),
+            OsStr::new("--always-overwrite"),
+            dir.path().as_os_str(),
+        ])?;
+        let refuser = Scripted::new(
+            AlreadyExistPromptOptions::Skip,
+            StaleSymlinkPromptOptions::Skip,
+        );
+        let mut engine = Engine::new(Params::new(cli, Config::default())?)
+            .output(io::sink())
+            .prompter(refuser.clone());
+        engine.make_link(&LinkKind::Symlink, &target, &dir.child("a"))?;
+        assert_eq!(
+            *refuser.prompted.lock().unwrap(),
+            [format!(
+                "dir overwrite {} (1 entries)",
+                dir.child("a").display()
+            )]
+        );
+        assert!(dir.child("a").child("entry").exists());
+        assert_eq!(engine.summary.skipped, 1);
+
+        let cli = Cli::try_parse_from([
+            OsStr::new("mksls"),
+            OsStr::new("--always-overwrite"),
+            OsStr::new("--allow-dir-overwrite"),
+            dir.path().as_os_str(),
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        engine.make_link(&LinkKind::Symlink, &target, &dir.child("b"))?;
+        assert_eq!(fs::read_link(dir.child("b"))?, target.path());
+        assert_eq!(engine.summary.overwritten, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_parent_directories_are_created_only_if_allowed(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("target").touch()?;
+        let target = dir.child("target");
+        let link = dir.child("a/b/link");
+
+        let cli = Cli::try_parse_from([OsStr::new("mksls"), dir.path().as_os_str()])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        assert!(engine
+            .make_link(&LinkKind::Symlink, &target, &link)
+            .is_err());
+        assert!(!dir.child("a").exists());
+
+        let cli = Cli::try_parse_from([
+            OsStr::new("mksls"),
+            OsStr::new("--mkdirs"),
+            dir.path().as_os_str(),
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        engine.make_link(&LinkKind::Symlink, &target, &link)?;
+        assert_eq!(fs::read_link(&link)?, target.path());
+        assert_eq!(engine.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn make_link_skips_conflicts_when_always_skipping() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default().with(Path::new("/home/file"), Node::File));
+        let mut engine = engine(&["--always-skip"], &fs)?;
+
+        engine.make_link(
+            &LinkKind::Symlink,
+            Path::new("/dots/file"),
+            Path::new("/home/file"),
+        )?;
+
+        assert_eq!(fs.get(Path::new("/home/file")), Some(Node::File));
+        assert_eq!(engine.summary.skipped, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resumed_runs_skip_the_lines_already_processed() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        dir.child("b").touch()?;
+        let sls = dir.child("sls");
+        sls.write_str("a /home/a\nb /home/b\n")?;
+        let cli = Cli::try_parse_from(["mksls".as_ref(), "--dry-run".as_ref(), dir.as_os_str()])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        let canonical_sls = fs::canonicalize(&sls)?;
+        let mut interrupted = Progress::new(dir.path(), &[], &[]);
+        interrupted.start(&canonical_sls, &utils::hash_file(&sls)?);
+        interrupted.done(&canonical_sls, 1);
+        engine.progress = Some(Arc::new(Mutex::new(interrupted.clone())));
+
+        engine.process_file(sls.to_path_buf())?;
+        assert_eq!(engine.summary.created, 1);
+        let progress = engine.progress.unwrap();
+        assert!(progress.lock().unwrap().is_done(&canonical_sls, 2));
+
+        // The progress of a file edited since is dropped.
+        sls.write_str("b /home/b\na /home/a\n")?;
+        let cli = Cli::try_parse_from(["mksls".as_ref(), "--dry-run".as_ref(), dir.as_os_str()])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        engine.progress = Some(Arc::new(Mutex::new(interrupted)));
+        engine.process_file(sls.to_path_buf())?;
+        assert_eq!(engine.summary.created, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lines_with_postponed_conflicts_are_not_done() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("dots/a").touch()?;
+        dir.child("dots/b").touch()?;
+        dir.child("home/a").write_str("Existing.")?;
+        let sls = dir.child("dots/sls");
+        sls.write_str(&format!(
+            "a {}\nb {}\n",
+            dir.child("home/a").display(),
+            dir.child("home/b").display()
+        ))?;
+        let cli = Cli::try_parse_from([
+            "mksls".as_ref(),
+            "--dry-run".as_ref(),
+            "--resolve-at-end".as_ref(),
+            dir.child("dots").as_os_str(),
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+        let canonical_sls = fs::canonicalize(&sls)?;
+        engine.progress = Some(Arc::new(Mutex::new(Progress::new(dir.path(), &[], &[]))));
+
+        engine.process_file(sls.to_path_buf())?;
+        assert_eq!(engine.postponed.as_ref().map(Vec::len), Some(1));
+        let progress = engine.progress.unwrap();
+        assert!(!progress.lock().unwrap().is_done(&canonical_sls, 1));
+        assert!(!progress.lock().unwrap().is_done(&canonical_sls, 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unchanged_files_are_skipped_if_asked() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("dots/a").touch()?;
+        dir.child("dots/b").touch()?;
+        dir.child("home").create_dir_all()?;
+        let sls = dir.child("dots/sls");
+        sls.write_str(&format!("a {}\n", dir.child("home/a").display()))?;
+        let cache = Arc::new(Mutex::new(Cache::default()));
+        let dots = dir.child("dots").display().to_string();
+        let engine = |args: &[&str]| -> Result<Engine, Box<dyn std::error::Error>> {
+            let cli = Cli::try_parse_from(
+                ["mksls", "--changed-only"]
+                    .iter()
+                    .chain(args)
+                    .chain([&dots.as_str()]),
+            )?;
+            let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+            engine.cache = Arc::clone(&cache);
+            Ok(engine)
+        };
+
+        let mut first = engine(&[])?;
+        first.process_file(sls.to_path_buf())?;
+        assert_eq!(first.summary.created, 1);
+
+        let mut unchanged = engine(&[])?;
+        unchanged.process_file(sls.to_path_buf())?;
+        assert_eq!(unchanged.summary, Summary::default());
+
+        // Nor is a file processed with other parameters.
+        let mut other_params = engine(&["--var", "theme=dark"])?;
+        other_params.process_file(sls.to_path_buf())?;
+        assert_eq!(other_params.summary.already_existing, 1);
+
+        sls.write_str(&format!(
+            "a {}\nb {}\n",
+            dir.child("home/a").display(),
+            dir.child("home/b").display()
+        ))?;
+        let mut changed = engine(&[])?;
+        changed.process_file(sls.to_path_buf())?;
+        assert_eq!(changed.summary.already_existing, 1);
+        assert_eq!(changed.summary.created, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn variables_are_scoped_to_their_file_and_overridden_by_the_cli(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("dark/vimrc").touch()?;
+        let home = dir.child("home").path().display().to_string();
+        dir.child("greeting")
+            .write_str("{{ theme }} on {{ host }}")?;
+        dir.child("sls").write_str(&format!(
+            "set theme dark\nset host laptop\n{{theme}}/vimrc {home}/vimrc.{{host}}\ntemplate greeting {home}/greeting\ninclude included\n"
+        ))?;
+        dir.child("included").write_str(&format!(
+            "dark/vimrc {home}/{{theme}}\ndark/vimrc {home}/{{host}}.{{os}}.{{editor}}\n"
+        ))?;
+        dir.child("home").create_dir_all()?;
+        let dir_arg = dir.path().display().to_string();
+        let cli = Cli::try_parse_from(["mksls", "--var", "host=work", &dir_arg])?;
+        let cfg = Config {
+            vars: HashMap::from([(String::from("theme"), String::from("light"))]),
+            ..Config::default()
+        };
+        let mut engine = Engine::new(Params::new(cli, cfg)?).output(io::sink());
+
+        engine.process_file(dir.child("sls").path().to_path_buf())?;
+
+        assert_eq!(
+            fs::read_link(dir.child("home/vimrc.work"))?,
+            dir.child("dark/vimrc").path()
+        );
+        assert!(!dir.child("home/vimrc.laptop").path().exists());
+        dir.child("home/greeting").assert("dark on work");
+        // The included file only gets the variables of the config file.
+        assert!(dir.child("home/light").path().is_symlink());
+        assert_eq!(engine.summary.invalid, 1);
+        assert_eq!(
+            engine.file_vars.get("theme").map(String::as_str),
+            Some("light")
+        );
+
+        let cli = Cli::try_parse_from(["mksls", "--var", "2nd=x", &dir_arg])?;
+        assert!(Params::new(cli, Config::default()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn guarded_lines_are_processed_only_if_their_guard_holds(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default());
+        let mut engine = engine(&["--allow-missing-targets"], &fs)?;
+        let lines = [
+            "ifexists /",
+            "# The root always exists.",
+            "root /home/root",
+            "ifcmd mksls_test_no_such_command",
+            "missing /home/missing",
+            "after /home/after",
+            "ifexists / {",
+            "in /home/in",
+            "ifexists /mksls_test_does_not_exist {",
+            "nested /home/nested",
+            "}",
+            "also_in /home/also_in",
+            "}",
+            "}",
+        ];
+
+        for (i, line) in lines.into_iter().enumerate() {
+            engine.process_line(
+                Path::new("/dots/sls"),
+                Path::new("/dots"),
+                i as u64 + 1,
+                String::from(line),
+            )?;
+        }
+
+        let made: Vec<_> = ["root", "missing", "after", "in", "nested", "also_in"]
+            .into_iter()
+            .filter(|name| fs.get(&Path::new("/home").join(name)).is_some())
+            .collect();
+        assert_eq!(made, ["root", "after", "in", "also_in"]);
+        assert_eq!(engine.summary.invalid, 1);
+
+        // A line that is not valid UTF-8 is guarded as well.
+        let sls = Path::new("/dots/sls");
+        engine.process_line(
+            sls,
+            Path::new("/dots"),
+            15,
+            String::from("ifexists /mksls_test_does_not_exist"),
+        )?;
+        engine.process_bytes(sls, Path::new("/dots"), 16, b"\xff /home/invalid".to_vec())?;
+        engine.process_line(sls, Path::new("/dots"), 17, String::from("last /home/last"))?;
+        assert!(fs.get(Path::new("/home/last")).is_some());
+        assert_eq!(engine.summary.invalid, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn guarded_lines_are_checked_when_linting() -> Result<(), Box<dyn std::error::Error>> {
+        let fs = Arc::new(MemFs::default());
+        let mut engine = engine(&[], &fs)?;
+        engine.lint = true;
+        let lines = [
+            "ifcmd mksls_test_no_such_command",
+            "missing /home/missing",
+            "ifcmd mksls_test_no_such_command",
+            "include missing.sls",
+        ];
+
+        for (i, line) in lines.into_iter().enumerate() {
+            engine.process_line(
+                Path::new("/dots/sls"),
+                Path::new("/dots"),
+                i as u64 + 1,
+                String::from(line),
+            )?;
+        }
+
+        assert_eq!(engine.summary.invalid, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn blocks_left_open_are_invalid() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").touch()?;
+        let sls = dir.child("sls");
+        sls.write_str("ifexists / {\na /home/a\n")?;
+        let cli = Cli::try_parse_from(["mksls".as_ref(), "--dry-run".as_ref(), dir.as_os_str()])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        engine.process_file(sls.to_path_buf())?;
+        assert_eq!(engine.summary.created, 1);
+        assert_eq!(engine.summary.invalid, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prune_only_removes_the_symlinks_of_the_dir_given() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let tmp = TempDir::new()?;
+        let manifest_path = tmp.child("data/manifest.toml");
+        let mut manifest = Manifest::default();
+        for name in ["a", "b"] {
+            let dir = tmp.child(name);
+            dir.child("sls").touch()?;
+            dir.child("target").touch()?;
+            let link = tmp.child(format!("link_{}", name));
+            crate::platform::symlink(dir.child("target"), &link)?;
+            manifest.add(&fs::canonicalize(&dir)?, &link, dir.child("target").path());
+        }
+        manifest.save(&manifest_path)?;
+        let cli = Cli::try_parse_from(["mksls".as_ref(), tmp.child("a").as_os_str()])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        assert_eq!(engine.prune_manifest(&manifest_path)?, 1);
+        assert!(!tmp.child("link_a").path().is_symlink());
+        assert!(tmp.child("link_b").path().is_symlink());
+        let manifest = Manifest::load(&manifest_path)?;
+        assert_eq!(manifest.iter(&fs::canonicalize(tmp.child("b"))?).count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_transactions_are_rolled_back() -> Result<(), Box<dyn std::error::Error>> {
+        /// A [`MemFs`] failing to make the symlink at a given path.
+        #[derive(Debug)]
+        struct FailingFs {
+            fs: Arc<MemFs>,
+            failing: PathBuf,
+        }
+
+        impl Fs for FailingFs {
+            fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+                if link == self.failing {
+                    return Err(io::Error::other("disk full"));
+                }
+                self.fs.symlink(target, link)
+            }
+
+            fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+                self.fs.rename(from, to)
+            }
+
+            fn remove(&self, path: &Path) -> io::Result<()> {
+                self.fs.remove(path)
+            }
+
+            fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+                self.fs.read_link(path)
+            }
+
+            fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+                self.fs.canonicalize(path)
+            }
+
+            fn exists(&self, path: &Path) -> bool {
+                self.fs.exists(path)
+            }
+
+            fn is_symlink(&self, path: &Path) -> bool {
+                self.fs.is_symlink(path)
+            }
+
+            fn is_dir(&self, path: &Path) -> bool {
+                self.fs.is_dir(path)
+            }
+
+            fn is_file(&self, path: &Path) -> bool {
+                self.fs.is_file(path)
+            }
+
+            fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+                self.fs.create_dir_all(path)
+            }
+
+            fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+                self.fs.copy(from, to)
+            }
+        }
+
+        let fs =
+            Arc::new(MemFs::default().with(Path::new("/home/b"), Node::Symlink("/old".into())));
+        let cli = Cli::try_parse_from([
+            "mksls",
+            "--transactional",
+            "--relink",
+            "--allow-missing-targets",
+            "/dots",
+        ])?;
+        let mut engine = Engine::new(Params::new(cli, Config::default())?)
+            .output(io::sink())
+            .filesystem(FailingFs {
+                fs: Arc::clone(&fs),
+                failing: PathBuf::from("/home/c"),
+            });
+
+        let mut result = Ok(());
+        for (i, line) in ["a /home/a", "b /home/b", "c /home/c", "d /home/d"]
+            .into_iter()
+            .enumerate()
+        {
+            result = engine.process_line(
+                Path::new("/dots/sls"),
+                Path::new("/dots"),
+                i as u64 + 1,
+                String::from(line),
+            );
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(Error::Link { .. })));
+        assert_eq!(
+            fs.get(Path::new("/home/a")),
+            Some(Node::Symlink("/dots/a".into()))
+        );
+        assert_eq!(
+            fs.get(Path::new("/home/b")),
+            Some(Node::Symlink("/dots/b".into()))
+        );
+
+        engine.rollback();
+
+        assert_eq!(fs.get(Path::new("/home/a")), None);
+        assert_eq!(
+            fs.get(Path::new("/home/b")),
+            Some(Node::Symlink("/old".into()))
+        );
+        assert_eq!(fs.get(Path::new("/home/c")), None);
+        assert_eq!(fs.get(Path::new("/home/d")), None);
+        assert!(engine.transaction.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_processed_in_parallel_are_summed_up() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let home = dir.child("home");
+        home.create_dir_all()?;
+        let files = ["a", "b", "c", "d", "e"];
+        for file in files {
+            let mut lines = String::new();
+            for i in 0..3 {
+                let target = dir.child(format!("dots/{file}/{i}"));
+                target.touch()?;
+                lines.push_str(&format!(
+                    "{i} {}\n",
+                    home.child(format!("{file}{i}")).display()
+                ));
+            }
+            dir.child(format!("dots/{file}/sls")).write_str(&lines)?;
+        }
+        // Already made, and failing for lack of a parent directory.
+        crate::platform::symlink(dir.child("dots/a/0"), home.child("a0"))?;
+        dir.child("dots/e/sls")
+            .write_str(&format!("0 {}\n", home.child("missing/e0").display()))?;
+        let dots = dir.child("dots").path().display().to_string();
+        let cli = Cli::try_parse_from([
+            "mksls",
+            "--jobs",
+            "4",
+            "--always-skip",
+            "--keep-going",
+            &dots,
+        ])?;
+        let buffer = Buffer::default();
+        let mut engine = Engine::new(Params::new(cli, Config::default())?).output(buffer.clone());
+
+        engine.process_dir()?;
+
+        assert_eq!(engine.summary.created, 11);
+        assert_eq!(engine.summary.already_existing, 1);
+        assert_eq!(engine.summary.failed, 1);
+        assert_eq!(engine.failures.len(), 1);
+        // Each line is printed whole, and the lines of a file in order.
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone())?;
+        let links: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.split(' ').nth(1))
+            .collect();
+        for file in &files[..4] {
+            let made: Vec<String> = (0..3)
+                .map(|i| home.child(format!("{file}{i}")).display().to_string())
+                .collect();
+            let printed: Vec<String> = links
+                .iter()
+                .filter(|link| made.iter().any(|made| made == *link))
+                .map(|link| link.to_string())
+                .collect();
+            assert_eq!(printed, made);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_lines_are_acknowledged_one_at_a_time_in_parallel(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        /// A prompter acknowledging errors slowly, telling whether two
+        /// acknowledgements overlapped.
+        #[derive(Debug, Default, Clone)]
+        struct Slow {
+            acknowledging: Arc<AtomicBool>,
+            overlapped: Arc<AtomicBool>,
+            acknowledged: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl Prompter for Slow {
+            fn resolve_conflict(
+                &self,
+                _: &Path,
+                _: &Path,
+                _: &dyn Fn() -> Option<String>,
+            ) -> crate::Result<AlreadyExistPromptOptions> {
+                unreachable!()
+            }
+
+            fn resolve_stale_symlink(
+                &self,
+                _: &Path,
+                _: &Path,
+                _: &Path,
+            ) -> crate::Result<StaleSymlinkPromptOptions> {
+                unreachable!()
+            }
+
+            fn confirm_adopt(&self, _: &Path, _: &Path) -> crate::Result<bool> {
+                unreachable!()
+            }
+
+            fn confirm_dir_overwrite(&self, _: &Path, _: u64) -> crate::Result<bool> {
+                unreachable!()
+            }
+
+            fn resolve_broken_symlink(
+                &self,
+                _: &Path,
+                _: &Path,
+            ) -> crate::Result<BrokenSymlinkPromptOptions> {
+                unreachable!()
+            }
+
+            fn acknowledge_error(&self, message: &str) -> crate::Result<()> {
+                if self.acknowledging.swap(true, Ordering::SeqCst) {
+                    self.overlapped.store(true, Ordering::SeqCst);
+                }
+                thread::sleep(std::time::Duration::from_millis(20));
+                self.acknowledged.lock().unwrap().push(message.to_string());
+                self.acknowledging.store(false, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let dir = TempDir::new()?;
+        for file in ["a", "b", "c", "d"] {
+            dir.child(format!("dots/{file}/sls"))
+                .write_str("not a valid line\n")?;
+        }
+        let dots = dir.child("dots").path().display().to_string();
+        let cli = Cli::try_parse_from(["mksls", "--jobs", "4", "--always-skip", &dots])?;
+        let slow = Slow::default();
+        let mut engine = Engine::new(Params::new(cli, Config::default())?)
+            .output(Buffer::default())
+            .prompter(slow.clone());
+
+        engine.process_dir()?;
+
+        assert_eq!(engine.summary.invalid, 4);
+        assert_eq!(slow.acknowledged.lock().unwrap().len(), 4);
+        assert!(!slow.overlapped.load(Ordering::SeqCst));
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_runs_record_the_links_made_before_the_failure(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        // Where the manifest and the skipped links are kept.
+        std::env::set_var("XDG_DATA_HOME", dir.child("data").path());
+        let home = dir.child("home");
+        home.child("b").write_str("existing")?;
+        dir.child("dots/a").write_str("a")?;
+        dir.child("dots/b").write_str("b")?;
+        dir.child("dots/sls").write_str(&format!(
+            "a {}\nb {}\na {}\n",
+            home.child("a").display(),
+            home.child("b").display(),
+            // Failing for lack of a parent directory.
+            home.child("missing/a").display()
+        ))?;
+        let dots = dir.child("dots");
+        let cli = Cli::try_parse_from([
+            "mksls".as_ref(),
+            "--always-skip".as_ref(),
+            "--backup-dir".as_ref(),
+            dir.child("backups").as_os_str(),
+            dots.as_os_str(),
+        ])?;
+        let engine = Engine::new(Params::new(cli, Config::default())?).output(io::sink());
+
+        assert!(engine.run().is_err());
+
+        assert_eq!(fs::read_link(home.child("a"))?, dots.child("a").path());
+        let manifest = Manifest::load(&Manifest::default_path()?)?;
+        let made: Vec<_> = manifest.iter(&fs::canonicalize(&dots)?).collect();
+        assert_eq!(made.len(), 1);
+        let skipped = Skipped::load(&Skipped::default_path()?)?;
+        assert_eq!(skipped.iter().count(), 1);
+        // The run can be resumed from the failing line.
+        let progress = Progress::load(&Progress::default_path()?)?.unwrap();
+        let sls = fs::canonicalize(dots.child("sls"))?;
+        assert!(progress.is_done(&sls, 2));
+        assert!(!progress.is_done(&sls, 3));
+
         Ok(())
     }
 }
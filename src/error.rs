@@ -0,0 +1,303 @@
+//! The errors of the library, so that callers can tell failures apart.
+
+use crate::dir::error::{DirDoesNotExist, WalkError};
+use std::error;
+use std::fmt;
+use std::io;
+
+/// A boxed error, as the source of errors coming from other crates.
+type BoxError = Box<dyn error::Error + Send + Sync>;
+
+/// Same as [`std::result::Result`], with [`Error`] as the error.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error of the library.
+///
+/// Each variant holds a message telling what failed, and most of the time
+/// the underlying error (see [`error::Error::source`]). The alternate format
+/// (`{:#}`) prints the whole chain, separated by `: `.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+/// use mksls::cfg::Config;
+/// use mksls::cli::Cli;
+/// use mksls::engine::Engine;
+/// use mksls::params::Params;
+/// use mksls::Error;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let cli = Cli::parse();
+/// let cfg: Config = confy::load("my_crate", "config")?;
+/// let params = Params::new(cli, cfg)?;
+/// match Engine::new(params).run() {
+///     Ok(summary) => println!("{}", summary),
+///     Err(Error::Backup { message, .. }) => eprintln!("Backup failed: {}", message),
+///     Err(err) => return Err(err.into()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// Making, replacing or removing a link failed.
+    Link {
+        /// What failed.
+        message: String,
+        /// The underlying error.
+        source: io::Error,
+    },
+    /// Backing up a conflicting file (or managing the backups) failed.
+    Backup {
+        /// What failed.
+        message: String,
+        /// The underlying error.
+        source: io::Error,
+    },
+    /// Parsing or serializing a file (e.g. the manifest, or a TOML
+    /// symlink-specification file) failed.
+    Parse {
+        /// What failed.
+        message: String,
+        /// The underlying error.
+        source: BoxError,
+    },
+    /// The parameters are invalid (see [`crate::params::Params::new`]).
+    Params {
+        /// What is invalid.
+        message: String,
+        /// The underlying error, if any.
+        source: Option<BoxError>,
+    },
+    /// A hook failed (see [`crate::hook::run`]).
+    Hook {
+        /// What failed.
+        message: String,
+        /// The underlying error, if the hook couldn't be run at all.
+        source: Option<io::Error>,
+    },
+    /// Watching for changes failed (see [`crate::watch::watch`]).
+    Watch {
+        /// What failed.
+        message: String,
+        /// The underlying error.
+        source: BoxError,
+    },
+    /// The directory to scan does not exist.
+    DirDoesNotExist(DirDoesNotExist),
+    /// An entry couldn't be read while scanning, in strict mode (see
+    /// [`crate::cli::Cli::strict`]).
+    Walk(WalkError),
+    /// An invalid line has been encountered, in strict mode (see
+    /// [`crate::cli::Cli::strict`]).
+    InvalidLine {
+        /// Where the line is, and why it is invalid.
+        message: String,
+    },
+    /// Links are specified more than once, with different targets (see
+    /// [`crate::engine::Engine::run`]).
+    ConflictingSpecs {
+        /// Which links, and where they are specified.
+        message: String,
+    },
+    /// The run failed, and the changes made have been rolled back (see
+    /// [`crate::cli::Cli::transactional`]).
+    RolledBack(Box<Error>),
+    /// Another input/output operation failed (e.g. reading a file, or
+    /// writing the output).
+    Io {
+        /// What failed.
+        message: String,
+        /// The underlying error.
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Link { message, .. }
+            | Error::Backup { message, .. }
+            | Error::Parse { message, .. }
+            | Error::Params { message, .. }
+            | Error::Hook { message, .. }
+            | Error::Watch { message, .. }
+            | Error::InvalidLine { message }
+            | Error::ConflictingSpecs { message }
+            | Error::Io { message, .. } => write!(f, "{}", message)?,
+            Error::DirDoesNotExist(err) => write!(f, "{}", err)?,
+            Error::Walk(err) => write!(f, "{}", err)?,
+            Error::RolledBack(_) => write!(f, "Rolled back the changes made during the run.")?,
+        }
+
+        if f.alternate() {
+            let mut source = error::Error::source(self);
+            while let Some(err) = source {
+                write!(f, ": {}", err)?;
+                source = err.source();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Link { source, .. }
+            | Error::Backup { source, .. }
+            | Error::Io { source, .. } => Some(source),
+            Error::Parse { source, .. } | Error::Watch { source, .. } => Some(source.as_ref()),
+            Error::Params { source, .. } => source
+                .as_ref()
+                .map(|source| source.as_ref() as &(dyn error::Error + 'static)),
+            Error::Hook { source, .. } => source
+                .as_ref()
+                .map(|source| source as &(dyn error::Error + 'static)),
+            Error::DirDoesNotExist(_)
+            | Error::InvalidLine { .. }
+            | Error::ConflictingSpecs { .. } => None,
+            Error::Walk(err) => error::Error::source(err),
+            Error::RolledBack(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Writing the output is the only input/output operation whose error is
+    /// not given a message of its own.
+    fn from(source: io::Error) -> Self {
+        Error::Io {
+            message: String::from("Failed to write the output."),
+            source,
+        }
+    }
+}
+
+impl From<DirDoesNotExist> for Error {
+    fn from(err: DirDoesNotExist) -> Self {
+        Error::DirDoesNotExist(err)
+    }
+}
+
+impl From<WalkError> for Error {
+    fn from(err: WalkError) -> Self {
+        Error::Walk(err)
+    }
+}
+
+/// Turns the error of a result into an [`Error`] of the variant given by the
+/// method, with the message returned by `message`.
+pub(crate) trait Context<T, E> {
+    /// Into an [`Error::Io`].
+    fn io_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<io::Error>;
+
+    /// Into an [`Error::Link`].
+    fn link_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<io::Error>;
+
+    /// Into an [`Error::Backup`].
+    fn backup_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<io::Error>;
+
+    /// Into an [`Error::Parse`].
+    fn parse_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<BoxError>;
+
+    /// Into an [`Error::Params`].
+    fn params_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<BoxError>;
+
+    /// Into an [`Error::Watch`].
+    fn watch_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<BoxError>;
+}
+
+impl<T, E> Context<T, E> for std::result::Result<T, E> {
+    fn io_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<io::Error>,
+    {
+        self.map_err(|source| Error::Io {
+            message: message().into(),
+            source: source.into(),
+        })
+    }
+
+    fn link_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<io::Error>,
+    {
+        self.map_err(|source| Error::Link {
+            message: message().into(),
+            source: source.into(),
+        })
+    }
+
+    fn backup_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<io::Error>,
+    {
+        self.map_err(|source| Error::Backup {
+            message: message().into(),
+            source: source.into(),
+        })
+    }
+
+    fn parse_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<BoxError>,
+    {
+        self.map_err(|source| Error::Parse {
+            message: message().into(),
+            source: source.into(),
+        })
+    }
+
+    fn params_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<BoxError>,
+    {
+        self.map_err(|source| Error::Params {
+            message: message().into(),
+            source: Some(source.into()),
+        })
+    }
+
+    fn watch_context<M: Into<String>, F: FnOnce() -> M>(self, message: F) -> Result<T>
+    where
+        E: Into<BoxError>,
+    {
+        self.map_err(|source| Error::Watch {
+            message: message().into(),
+            source: source.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_format_prints_the_chain() {
+        let source = io::Error::from(io::ErrorKind::PermissionDenied);
+        let expected = format!("Failed to create /link -> /target: {}", source);
+        let err = Error::Link {
+            message: String::from("Failed to create /link -> /target"),
+            source,
+        };
+
+        assert_eq!(err.to_string(), "Failed to create /link -> /target");
+        assert_eq!(format!("{:#}", err), expected);
+    }
+}
@@ -0,0 +1,117 @@
+//! What happens during a run of the [`crate::engine::Engine`], reported to an
+//! [`Observer`] as typed events, independently of what is printed to the
+//! terminal.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+/// What has been done for a link specified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The link has been made without conflict (`d`).
+    Created,
+    /// The link already existed (`.`).
+    AlreadyExisting,
+    /// The link conflicted with an existing file and has been skipped (`s`).
+    Skipped,
+    /// The conflicting file has been backed up, then the link made (`b`).
+    BackedUp,
+    /// The conflicting file has been overwritten by the link (`o`).
+    Overwritten,
+    /// The conflicting file has been renamed in place, then the link made
+    /// (`n`).
+    Renamed,
+    /// The conflicting symlink has been replaced by the link (`r`).
+    Relinked,
+    /// The conflicting symlink, broken, has been replaced by the link (`f`,
+    /// see [`crate::cli::Cli::replace_broken`]).
+    Repaired,
+    /// The conflicting file has been moved to the target, then the link made
+    /// (`a`).
+    Adopted,
+    /// The link has been denied for lack of permissions, and left to the
+    /// root script (`#`, see [`crate::cli::Cli::root_script`]).
+    Deferred,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self {
+            Action::Created => "created",
+            Action::AlreadyExisting => "already_existing",
+            Action::Skipped => "skipped",
+            Action::BackedUp => "backed_up",
+            Action::Overwritten => "overwritten",
+            Action::Renamed => "renamed",
+            Action::Relinked => "relinked",
+            Action::Repaired => "repaired",
+            Action::Adopted => "adopted",
+            Action::Deferred => "deferred",
+        };
+        write!(f, "{}", action)
+    }
+}
+
+/// Something that happened during a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The symlink-specification file `sls` is about to be processed.
+    FileStarted {
+        /// Path to the symlink-specification file.
+        sls: PathBuf,
+    },
+    /// A link specified has been processed.
+    SpecProcessed {
+        /// What has been done.
+        action: Action,
+        /// Path to the link.
+        link: PathBuf,
+        /// Path to the target of the link.
+        target: PathBuf,
+    },
+    /// An invalid line has been encountered.
+    InvalidLine {
+        /// Path to the symlink-specification file where the line lives.
+        sls: PathBuf,
+        /// The line number of the line in `sls`, or `None` when the whole file
+        /// is invalid.
+        line_no: Option<u64>,
+        /// Why the line is invalid, as told to the user.
+        message: String,
+    },
+}
+
+/// Gets notified of the [`Event`]s of a run (see
+/// [`crate::engine::Engine::observer`]).
+///
+/// It is shared with the threads processing files in parallel, hence the
+/// `Send + Sync` bounds.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::event::{Action, Event, Observer};
+/// use std::sync::Mutex;
+///
+/// /// Collects the links created.
+/// #[derive(Debug, Default)]
+/// struct Created(Mutex<Vec<std::path::PathBuf>>);
+///
+/// impl Observer for Created {
+///     fn notify(&self, event: &Event) {
+///         if let Event::SpecProcessed {
+///             action: Action::Created,
+///             link,
+///             ..
+///         } = event
+///         {
+///             self.0.lock().unwrap().push(link.clone());
+///         }
+///     }
+/// }
+/// ```
+pub trait Observer: Debug + Send + Sync {
+    /// Called for each event, in the thread where it happened.
+    fn notify(&self, event: &Event);
+}
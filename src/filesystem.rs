@@ -0,0 +1,332 @@
+//! The file system operations the [`crate::engine::Engine`] relies on.
+//!
+//! The handling of conflicts (backups, overwriting, renaming, adoptions) and
+//! its undoing go through the crate-private `Fs` trait, so that it can be
+//! exercised in tests without touching the real file system.
+
+use crate::platform;
+#[cfg(test)]
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+#[cfg(test)]
+use std::path::Component;
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::sync::Mutex;
+
+/// File system operations.
+///
+/// It is shared with the threads processing files in parallel, hence the
+/// `Send + Sync` bounds.
+pub(crate) trait Fs: Debug + Send + Sync {
+    /// Makes the symlink `link` pointing to `target`.
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+
+    /// Renames the file at `from` to `to`, replacing the file at `to` if any.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Removes the file at `path`, recursively if it is a directory (but not
+    /// through a symlink).
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns the path the symlink at `path` points to.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Returns the canonical form of the absolute `path`, to compare it with
+    /// another path.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Whether there is a file at `path`, a broken symlink included.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Whether there is a symlink at `path`.
+    fn is_symlink(&self, path: &Path) -> bool;
+
+    /// Whether there is a directory at `path`, following symlinks.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Whether there is a regular file at `path`, following symlinks.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Makes the directory `path`, and its missing parents.
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Copies the contents of the file at `from` to `to`, replacing the file
+    /// at `to` if any.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+/// The real file system.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RealFs;
+
+impl Fs for RealFs {
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        platform::symlink(target, link)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_symlink() || path.exists()
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// A file in a [`MemFs`].
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Node {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// A symlink pointing to the path.
+    Symlink(PathBuf),
+}
+
+/// An in-memory file system, for tests.
+///
+/// It is a flat map from paths to [`Node`]s: paths are taken as is (neither
+/// normalized nor resolved), and parent directories don't need to exist.
+/// Only [`Fs::canonicalize`] normalizes paths, lexically, and only
+/// [`Fs::is_dir`], [`Fs::is_file`] and [`Fs::copy`] follow symlinks, their
+/// targets being taken as is as well.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct MemFs {
+    /// The files, by path.
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+#[cfg(test)]
+impl MemFs {
+    /// Returns the file system with `node` added at `path`.
+    pub(crate) fn with(self, path: &Path, node: Node) -> Self {
+        self.nodes.lock().unwrap().insert(path.to_path_buf(), node);
+        self
+    }
+
+    /// Returns the file at `path`, if any.
+    pub(crate) fn get(&self, path: &Path) -> Option<Node> {
+        self.nodes.lock().unwrap().get(path).cloned()
+    }
+
+    /// Returns the file at `path`, following symlinks, if any.
+    fn resolve(&self, path: &Path) -> Option<Node> {
+        let mut node = self.get(path);
+        // As many symlinks as Linux follows at most.
+        for _ in 0..40 {
+            match node {
+                Some(Node::Symlink(target)) => node = self.get(&target),
+                node => return node,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+impl Fs for MemFs {
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        if nodes.contains_key(link) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        nodes.insert(link.to_path_buf(), Node::Symlink(target.to_path_buf()));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = nodes.remove(from).ok_or(io::ErrorKind::NotFound)?;
+        if node == Node::Dir {
+            let entries: Vec<PathBuf> = nodes
+                .keys()
+                .filter(|path| path.starts_with(from))
+                .cloned()
+                .collect();
+            for entry in entries {
+                let moved = to.join(entry.strip_prefix(from).unwrap());
+                let node = nodes.remove(&entry).unwrap();
+                nodes.insert(moved, node);
+            }
+        }
+        nodes.insert(to.to_path_buf(), node);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = nodes.remove(path).ok_or(io::ErrorKind::NotFound)?;
+        if node == Node::Dir {
+            nodes.retain(|other, _| !other.starts_with(path));
+        }
+        Ok(())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.nodes.lock().unwrap().get(path) {
+            Some(Node::Symlink(target)) => Ok(target.clone()),
+            Some(_) => Err(io::ErrorKind::InvalidInput.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut canonical = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    canonical.pop();
+                }
+                component => canonical.push(component),
+            }
+        }
+        Ok(canonical)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.nodes.lock().unwrap().contains_key(path)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        matches!(self.get(path), Some(Node::Symlink(_)))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.resolve(path) == Some(Node::Dir)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.resolve(path) == Some(Node::File)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        for dir in path.ancestors().filter(|dir| dir.parent().is_some()) {
+            match self.resolve(dir) {
+                Some(Node::Dir) => {}
+                None if self.get(dir).is_none() => {
+                    self.nodes
+                        .lock()
+                        .unwrap()
+                        .insert(dir.to_path_buf(), Node::Dir);
+                }
+                _ => return Err(io::ErrorKind::AlreadyExists.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match self.resolve(from) {
+            Some(Node::File) => {
+                self.nodes
+                    .lock()
+                    .unwrap()
+                    .insert(to.to_path_buf(), Node::File);
+                Ok(())
+            }
+            Some(_) => Err(io::ErrorKind::InvalidInput.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mem_fs_replaces_files_with_symlinks() {
+        let fs = MemFs::default().with(Path::new("/home/me/.vimrc"), Node::File);
+
+        assert!(fs.exists(Path::new("/home/me/.vimrc")));
+        fs.remove(Path::new("/home/me/.vimrc")).unwrap();
+        fs.symlink(Path::new("/dots/vimrc"), Path::new("/home/me/.vimrc"))
+            .unwrap();
+
+        assert_eq!(
+            fs.get(Path::new("/home/me/.vimrc")),
+            Some(Node::Symlink("/dots/vimrc".into()))
+        );
+    }
+
+    #[test]
+    fn mem_fs_removes_directories_recursively() {
+        let fs = MemFs::default()
+            .with(Path::new("/a"), Node::Dir)
+            .with(Path::new("/a/b"), Node::File)
+            .with(Path::new("/ab"), Node::File);
+
+        fs.remove(Path::new("/a")).unwrap();
+
+        assert!(!fs.exists(Path::new("/a")));
+        assert!(!fs.exists(Path::new("/a/b")));
+        assert!(fs.exists(Path::new("/ab")));
+    }
+
+    #[test]
+    fn mem_fs_normalizes_paths_to_canonicalize_them() {
+        let fs = MemFs::default();
+
+        assert_eq!(
+            fs.canonicalize(Path::new("/a/./b/../c/")).unwrap(),
+            Path::new("/a/c")
+        );
+    }
+
+    #[test]
+    fn mem_fs_renames_over_existing_file() {
+        let fs = MemFs::default()
+            .with(Path::new("/tmp"), Node::Symlink("/new".into()))
+            .with(Path::new("/link"), Node::Symlink("/old".into()));
+
+        fs.rename(Path::new("/tmp"), Path::new("/link")).unwrap();
+
+        assert!(!fs.exists(Path::new("/tmp")));
+        assert_eq!(fs.read_link(Path::new("/link")).unwrap(), Path::new("/new"));
+        assert!(fs.rename(Path::new("/tmp"), Path::new("/link")).is_err());
+    }
+}
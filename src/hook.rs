@@ -0,0 +1,76 @@
+//! Running the hook commands defined in the configuration file (see [`crate::cfg::Hooks`]).
+
+use crate::platform;
+use crate::Error;
+use std::ffi::OsStr;
+
+/// Runs the hook `name`, i.e. the shell command `command`, with the
+/// environment variables `env` set in addition to the ones of the app.
+///
+/// The hook inherits the standard input, output and error of the app.
+///
+/// # Parameters
+///
+/// - `name`: The name of the hook (e.g. `post_link`), for error messages.
+/// - `command`: The command to run, with `sh -c` (or `cmd /C` on Windows).
+/// - `env`: The environment variables describing what triggered the hook.
+///
+/// # Errors
+///
+/// Fails when the command fails to be run, or exits with a non-zero status.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::hook;
+/// use std::ffi::OsStr;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// hook::run(
+///     "post_link",
+///     "echo \"$LINK\"",
+///     &[("LINK", OsStr::new("/home/me/.vimrc"))],
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn run(name: &str, command: &str, env: &[(&str, &OsStr)]) -> crate::Result<()> {
+    let status = platform::shell(command)
+        .envs(env.iter().copied())
+        .status()
+        .map_err(|err| Error::Hook {
+            message: format!("Failed to run the {} hook: {}", name, command),
+            source: Some(err),
+        })?;
+    if !status.success() {
+        return Err(Error::Hook {
+            message: format!("The {} hook failed ({}): {}", name, status, command),
+            source: None,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn run_passes_env_and_reports_failure() {
+        let env = [
+            ("LINK", OsStr::new("/home/me/.vimrc")),
+            ("ACTION", OsStr::new("created")),
+        ];
+        assert!(run(
+            "post_link",
+            "test \"$LINK\" = /home/me/.vimrc && test \"$ACTION\" = created",
+            &env
+        )
+        .is_ok());
+        assert!(run("post_link", "test \"$ACTION\" = skipped", &env).is_err());
+    }
+}
@@ -0,0 +1,795 @@
+//! Converting the configuration of other dotfiles managers into
+//! symlink-specification files, to migrate from them.
+
+use crate::dir::error::WalkError;
+use crate::error::Context;
+use crate::line;
+use crate::platform;
+use crate::style::Paint;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The regexes of the files GNU Stow ignores in a package without a
+/// `.stow-local-ignore` file.
+const STOW_DEFAULT_IGNORE: [&str; 16] = [
+    r"RCS",
+    r".+,v",
+    r"CVS",
+    r"\.\#.+",
+    r"\.cvsignore",
+    r"\.svn",
+    r"_darcs",
+    r"\.hg",
+    r"\.git",
+    r"\.gitignore",
+    r"\.gitmodules",
+    r".+~",
+    r"\#.*\#",
+    r"^/README.*",
+    r"^/LICENSE.*",
+    r"^/COPYING",
+];
+
+/// The name of the file listing the files GNU Stow ignores in a package.
+const STOW_LOCAL_IGNORE: &str = ".stow-local-ignore";
+
+/// The options of the links of dotbot that mksls has an option for, and
+/// that option.
+const DOTBOT_OPTIONS: [(&str, &str); 5] = [
+    ("create", "--mkdirs"),
+    ("relink", "--relink"),
+    ("force", "--always-overwrite"),
+    ("relative", "--relative"),
+    ("ignore-missing", "--allow-missing-targets"),
+];
+
+/// What has been converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Converted {
+    /// The number of symlink specifications written.
+    pub specs: u64,
+    /// The options to make the symlinks with, to do as the converted
+    /// configuration does (e.g. `--mkdirs`).
+    pub flags: Vec<&'static str>,
+}
+
+/// Writes into `writer` the symlink-specification file equivalent to the GNU
+/// Stow package `package_dir` stowed in `target_dir` (i.e. `stow --target
+/// <target_dir> <package>`).
+///
+/// One symlink specification is written per file within `package_dir`
+/// (recursively), in alphabetical order, its target being relative to the
+/// parent of `package_dir` (the stow directory), so that the file is meant
+/// to be saved in the stow directory, for all its packages. The link is
+/// written relative to the home directory (`~`) when within it.
+///
+/// Like GNU Stow, the files matched by the regexes of the
+/// `.stow-local-ignore` file of the package are ignored, or, without one,
+/// the usual version-control files, backup files, and the `README.*`,
+/// `LICENSE.*` and `COPYING` files at the root of the package. With
+/// `dotfiles` (like `stow --dotfiles`), the `dot-` prefix of file and
+/// directory names is replaced by a `.` in links.
+///
+/// A path that is not valid UTF-8, which a symlink-specification file can't
+/// contain, is written as a comment instead.
+///
+/// # Returns
+///
+/// The number of symlink specifications written, and the options to make
+/// them with: `--mkdirs`, as GNU Stow creates the missing directories.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - Reading `package_dir`, or its `.stow-local-ignore` file, fails.
+/// - A regex of the `.stow-local-ignore` file is invalid.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::import;
+/// use std::io;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// import::stow(
+///     io::stdout(),
+///     Path::new("/home/me/dotfiles/vim"),
+///     Path::new("/home/me"),
+///     false,
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn stow<W: Write>(
+    mut writer: W,
+    package_dir: &Path,
+    target_dir: &Path,
+    dotfiles: bool,
+) -> crate::Result<Converted> {
+    let ignore = stow_ignore(package_dir)?;
+    let home = platform::home_dir().map(PathBuf::from);
+    let flags = vec!["--mkdirs"];
+
+    write_header(
+        &mut writer,
+        &format!(
+            "the GNU Stow package {}, stowed in {}",
+            package_dir.display(),
+            target_dir.display()
+        ),
+        &flags,
+    )?;
+    let mut specs = 0;
+    let entries = WalkDir::new(package_dir)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            // Within `package_dir`, as walked from it.
+            let relative = entry.path().strip_prefix(package_dir).unwrap();
+            !ignore.iter().any(|re| re.is_ignored(relative))
+        });
+    for entry in entries {
+        let entry = entry.io_context(|| format!("Failed to read {}", package_dir.display()))?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(package_dir).unwrap();
+        let target = match package_dir.file_name() {
+            Some(package) => Path::new(".").join(package).join(relative),
+            None => entry.path().to_path_buf(),
+        };
+        let link = target_dir.join(stowed_path(relative, dotfiles));
+        match (target.to_str(), spec_path(&link, home.as_deref())) {
+            (Some(target), Some(link)) => {
+                writeln!(writer, "{} {}", line::quote(target), link)?;
+                specs += 1;
+            }
+            _ => writeln!(
+                writer,
+                "# Not valid UTF-8, to link by hand: {} -> {}",
+                link.display(),
+                entry.path().display()
+            )?,
+        }
+    }
+
+    Ok(Converted { specs, flags })
+}
+
+/// Writes into `writer` the symlink-specification file equivalent to the
+/// `link` directives of the dotbot configuration file at `config` (e.g.
+/// `install.conf.yaml`).
+///
+/// One symlink specification is written per link, in order, as is, its
+/// target being relative to the directory of `config` (like in dotbot), so
+/// that the file is meant to be saved in that directory. A link without a
+/// path gets the one dotbot infers, i.e. its file name without its leading
+/// dot. The options of the links (`create`, `relink`, `force`, `relative`
+/// and `ignore-missing`, given for the link or in the `defaults` directive)
+/// are converted to the matching options of mksls (see [`Converted`]) when
+/// given for every link, and written as a comment above the links given them
+/// otherwise. A link made only under an `if` condition is written commented
+/// out, the other options and the other directives (e.g. `shell`) as
+/// comments.
+///
+/// # Returns
+///
+/// The number of symlink specifications written, and the options to make
+/// them with.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - Reading `config` fails.
+/// - `config` is not a valid dotbot configuration file.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::import;
+/// use std::io;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let converted = import::dotbot(io::stdout(), Path::new("/home/me/dotfiles/install.conf.yaml"))?;
+/// eprintln!("Make the symlinks with: mksls {}", converted.flags.join(" "));
+/// # Ok(())
+/// # }
+/// ```
+pub fn dotbot<W: Write>(mut writer: W, config: &Path) -> crate::Result<Converted> {
+    let contents =
+        fs::read_to_string(config).io_context(|| format!("Failed to read {}", config.display()))?;
+    let message = || {
+        format!(
+            "Failed to parse the dotbot configuration {}.",
+            config.display()
+        )
+    };
+    let directives: Vec<serde_yaml::Mapping> = serde_yaml::from_str::<Option<_>>(&contents)
+        .parse_context(message)?
+        .unwrap_or_default();
+
+    let mut defaults = DotbotLink::default();
+    let mut links = vec![];
+    let mut ignored = vec![];
+    for (name, value) in directives.into_iter().flatten() {
+        match name.as_str() {
+            Some("defaults") => {
+                let directive: DotbotDefaults =
+                    serde_yaml::from_value(value).parse_context(message)?;
+                if let Some(link) = directive.link {
+                    defaults = link;
+                }
+            }
+            Some("link") => {
+                let directive: serde_yaml::Mapping =
+                    serde_yaml::from_value(value).parse_context(message)?;
+                for (link, options) in directive {
+                    let link: String = serde_yaml::from_value(link).parse_context(message)?;
+                    let options = match serde_yaml::from_value(options).parse_context(message)? {
+                        None => DotbotLink::default(),
+                        Some(DotbotEntry::Path(path)) => DotbotLink {
+                            path: Some(path),
+                            ..Default::default()
+                        },
+                        Some(DotbotEntry::Options(options)) => options,
+                    };
+                    links.push((link, options.or(&defaults)));
+                }
+            }
+            _ => ignored.push(serde_yaml::to_string(&name).unwrap_or_default()),
+        }
+    }
+
+    // The options given for every link.
+    let flags: Vec<&'static str> = DOTBOT_OPTIONS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !links.is_empty() && links.iter().all(|(_, link)| link.enabled()[*i]))
+        .map(|(_, (_, flag))| *flag)
+        .collect();
+    write_header(
+        &mut writer,
+        &format!("the dotbot configuration {}", config.display()),
+        &flags,
+    )?;
+    for name in ignored {
+        writeln!(writer, "# Ignored the {} directive of dotbot.", name.trim())?;
+    }
+
+    let mut specs = 0;
+    for (link, options) in links {
+        for (enabled, (option, flag)) in options.enabled().into_iter().zip(DOTBOT_OPTIONS) {
+            if enabled && !flags.contains(&flag) {
+                writeln!(writer, "# Given {} in dotbot (see {}).", option, flag)?;
+            }
+        }
+        for option in options.other.keys() {
+            writeln!(writer, "# Ignored the {} option of dotbot.", option)?;
+        }
+        let target = options.path.unwrap_or_else(|| {
+            let name = link
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or(&link);
+            name.strip_prefix('.').unwrap_or(name).to_string()
+        });
+        let target = if target.starts_with(['/', '~', '$']) {
+            target
+        } else {
+            format!("./{}", target)
+        };
+        let spec = format!("{} {}", line::quote(&target), line::quote(&link));
+        match options.condition {
+            Some(condition) => {
+                writeln!(writer, "# Made by dotbot only if {} succeeds:", condition)?;
+                writeln!(writer, "# {}", spec)?;
+            }
+            None => {
+                writeln!(writer, "{}", spec)?;
+                specs += 1;
+            }
+        }
+    }
+
+    Ok(Converted { specs, flags })
+}
+
+/// Writes into `writer` the symlink specifications of the symlinks within
+/// `dir` (recursively) pointing within the directory of the
+/// symlink-specification file `sls_file`, e.g. the dotfiles repository, to
+/// append them to `sls_file`.
+///
+/// The targets are written relative to the directory of `sls_file`, and the
+/// links relative to the home directory (`~`) when within it, in
+/// alphabetical order. Neither the symlinks within the directory of
+/// `sls_file` nor the ones already specified the same way in `sls_file` are
+/// written, so that scanning again only adds the new ones. The directories
+/// within `dir` are not followed if symlinks, and the entries that can't be
+/// read are reported with a warning.
+///
+/// # Returns
+///
+/// The number of symlink specifications written, and the options to make
+/// them with: `--relative` if every symlink written points to a relative
+/// path.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - `dir` or the directory of `sls_file` can't be read.
+/// - Reading `sls_file`, if it exists, fails.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::import;
+/// use std::fs::OpenOptions;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let sls_file = Path::new("/home/me/dotfiles/sls");
+/// let file = OpenOptions::new().create(true).append(true).open(sls_file)?;
+/// import::symlinks(file, Path::new("/home/me"), sls_file)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn symlinks<W: Write>(mut writer: W, dir: &Path, sls_file: &Path) -> crate::Result<Converted> {
+    let dir = fs::canonicalize(dir).io_context(|| format!("Failed to read {}", dir.display()))?;
+    let sls_dir = match sls_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let sls_dir =
+        fs::canonicalize(sls_dir).io_context(|| format!("Failed to read {}", sls_dir.display()))?;
+    let contents = match fs::read_to_string(sls_file) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).io_context(|| format!("Failed to read {}", sls_file.display()));
+        }
+    };
+    let specified: HashSet<&str> = contents.lines().map(str::trim).collect();
+    let home = platform::home_dir().map(PathBuf::from);
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        writeln!(writer)?;
+    }
+    if contents.trim().is_empty() {
+        write_header(
+            &mut writer,
+            &format!("the symlinks within {}", dir.display()),
+            &[],
+        )?;
+    }
+    let mut specs = 0;
+    let mut relative = true;
+    let entries = WalkDir::new(&dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.path() != sls_dir);
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let err = WalkError(err.path().map(Path::to_path_buf), Box::new(err));
+                eprintln!("{}", format!("(!) Warning: {}", err).yellow());
+                continue;
+            }
+        };
+        if !entry.path_is_symlink() {
+            continue;
+        }
+        let link = entry.path();
+        let Ok(pointed) = fs::read_link(link) else {
+            continue;
+        };
+        // Through the canonical path of its directory, so that `..` and the
+        // symlinks to directories on the way are resolved, but not the
+        // target itself.
+        let target = link.parent().unwrap_or(Path::new("/")).join(&pointed);
+        let target = match (target.parent(), target.file_name()) {
+            (Some(parent), Some(name)) => fs::canonicalize(parent)
+                .map(|parent| parent.join(name))
+                .unwrap_or(target),
+            _ => target,
+        };
+        let Ok(within) = target.strip_prefix(&sls_dir) else {
+            continue;
+        };
+        let spec = match (within.to_str(), spec_path(link, home.as_deref())) {
+            (Some(within), Some(link)) => {
+                format!("{} {}", line::quote(&format!("./{}", within)), link)
+            }
+            _ => {
+                writeln!(
+                    writer,
+                    "# Not valid UTF-8, to link by hand: {} -> {}",
+                    link.display(),
+                    target.display()
+                )?;
+                continue;
+            }
+        };
+        if specified.contains(spec.as_str()) {
+            continue;
+        }
+
+        writeln!(writer, "{}", spec)?;
+        specs += 1;
+        relative &= pointed.is_relative();
+    }
+
+    let flags = if specs > 0 && relative {
+        vec!["--relative"]
+    } else {
+        vec![]
+    };
+    Ok(Converted { specs, flags })
+}
+
+/// Writes into `writer` the header of a converted file: where it has been
+/// converted from (`from`), and which options to make the symlinks with
+/// (`flags`).
+fn write_header<W: Write>(writer: &mut W, from: &str, flags: &[&str]) -> crate::Result<()> {
+    writeln!(writer, "# Converted from {}.", from)?;
+    if !flags.is_empty() {
+        writeln!(
+            writer,
+            "# Make the symlinks with: mksls {} <DIR>",
+            flags.join(" ")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns `path` as written in a symlink specification (see
+/// [`line::quote`]), relative to `home` (i.e. starting with `~`) if within
+/// it, or `None` if it is not valid UTF-8.
+pub(crate) fn spec_path(path: &Path, home: Option<&Path>) -> Option<String> {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(relative) if relative.as_os_str().is_empty() => Some(String::from("~")),
+        Some(relative) => Some(line::quote(&format!("~/{}", relative.to_str()?)).into_owned()),
+        None => Some(line::quote(path.to_str()?).into_owned()),
+    }
+}
+
+/// The `defaults` directive of dotbot.
+#[derive(Debug, Deserialize)]
+struct DotbotDefaults {
+    /// The default options of the links.
+    link: Option<DotbotLink>,
+}
+
+/// A link of the `link` directive of dotbot, with only a path or with
+/// options.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DotbotEntry {
+    Path(String),
+    Options(DotbotLink),
+}
+
+/// The options of a link of dotbot.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DotbotLink {
+    path: Option<String>,
+    create: Option<bool>,
+    relink: Option<bool>,
+    force: Option<bool>,
+    relative: Option<bool>,
+    ignore_missing: Option<bool>,
+    /// Glob patterns are recognized as such anyway.
+    glob: Option<bool>,
+    #[serde(rename = "if")]
+    condition: Option<String>,
+    /// The options mksls has nothing for.
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl DotbotLink {
+    /// Returns the options, the ones not given taken from `defaults`.
+    fn or(self, defaults: &DotbotLink) -> DotbotLink {
+        let mut other = defaults.other.clone();
+        other.extend(self.other);
+        DotbotLink {
+            path: self.path,
+            create: self.create.or(defaults.create),
+            relink: self.relink.or(defaults.relink),
+            force: self.force.or(defaults.force),
+            relative: self.relative.or(defaults.relative),
+            ignore_missing: self.ignore_missing.or(defaults.ignore_missing),
+            glob: self.glob.or(defaults.glob),
+            condition: self.condition.or_else(|| defaults.condition.clone()),
+            other,
+        }
+    }
+
+    /// Returns whether each option of [`DOTBOT_OPTIONS`] is enabled.
+    fn enabled(&self) -> [bool; DOTBOT_OPTIONS.len()] {
+        [
+            self.create,
+            self.relink,
+            self.force,
+            self.relative,
+            self.ignore_missing,
+        ]
+        .map(|option| option.unwrap_or(false))
+    }
+}
+
+/// A regex of the files GNU Stow ignores.
+#[derive(Debug)]
+struct StowIgnore {
+    /// The regex, anchored at both ends.
+    re: Regex,
+    /// Whether it is matched against the path of the file relative to the
+    /// package (starting with a `/`), as it contains a `/`, rather than
+    /// against the name of the file.
+    path: bool,
+}
+
+impl StowIgnore {
+    /// Whether the file at the path `relative` within the package is ignored.
+    fn is_ignored(&self, relative: &Path) -> bool {
+        let relative = relative.to_string_lossy();
+        if self.path {
+            self.re.is_match(&format!("/{}", relative))
+        } else {
+            let name = relative.rsplit(['/', '\\']).next().unwrap_or(&relative);
+            self.re.is_match(name)
+        }
+    }
+}
+
+/// Returns the regexes of the files GNU Stow ignores in `package_dir`, the
+/// ones of its `.stow-local-ignore` file (which is ignored too) if any.
+///
+/// # Errors
+///
+/// Fails when reading the `.stow-local-ignore` file fails, or when one of
+/// its regexes is invalid.
+///
+/// See [`crate::Error`] for the kinds of errors.
+fn stow_ignore(package_dir: &Path) -> crate::Result<Vec<StowIgnore>> {
+    let local_ignore = package_dir.join(STOW_LOCAL_IGNORE);
+    let contents;
+    let patterns: Vec<&str> = if local_ignore.is_file() {
+        contents = fs::read_to_string(&local_ignore)
+            .io_context(|| format!("Failed to read {}", local_ignore.display()))?;
+        contents
+            .lines()
+            // Like GNU Stow, a `#` starts a comment only at the start of the
+            // line or after whitespace, so that it can be part of a regex.
+            .map(|line| match line.find(" #").or_else(|| line.find("\t#")) {
+                Some(i) => &line[..i],
+                None if line.starts_with('#') => "",
+                None => line,
+            })
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .chain([r"\.stow-local-ignore"])
+            .collect()
+    } else {
+        STOW_DEFAULT_IGNORE.to_vec()
+    };
+
+    patterns
+        .into_iter()
+        .map(|pattern| {
+            let re = Regex::new(&format!("^(?:{})$", pattern)).parse_context(|| {
+                format!(
+                    "Got an invalid regex in {}: {}",
+                    local_ignore.display(),
+                    pattern
+                )
+            })?;
+            Ok(StowIgnore {
+                re,
+                path: pattern.contains('/'),
+            })
+        })
+        .collect()
+}
+
+/// Returns the path where the file at the path `relative` within a package
+/// is stowed, relative to the target directory: `relative` itself, or, with
+/// `dotfiles`, `relative` with the `dot-` prefix of its components replaced
+/// by a `.`.
+fn stowed_path(relative: &Path, dotfiles: bool) -> PathBuf {
+    if !dotfiles {
+        return relative.to_path_buf();
+    }
+
+    relative
+        .iter()
+        .map(|component| match component.to_str() {
+            Some(name) if name.len() > "dot-".len() && name.starts_with("dot-") => {
+                OsString::from(format!(".{}", &name["dot-".len()..]))
+            }
+            _ => component.to_os_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn stow_writes_one_spec_per_file_not_ignored() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let package = dir.child("vim");
+        package.child("dot-vimrc").touch()?;
+        package.child("dot-vim/colors/my theme.vim").touch()?;
+        package.child("README.md").touch()?;
+        package.child(".git/HEAD").touch()?;
+        package.child("dot-vim/README.md").touch()?;
+
+        let mut out = vec![];
+        let converted = stow(&mut out, &package, Path::new("/target"), true)?;
+
+        assert_eq!(converted.specs, 3);
+        let out = String::from_utf8(out)?;
+        let specs: Vec<&str> = out.lines().skip(2).collect();
+        assert_eq!(
+            specs,
+            [
+                "./vim/dot-vim/README.md /target/.vim/README.md",
+                r#""./vim/dot-vim/colors/my theme.vim" "/target/.vim/colors/my theme.vim""#,
+                "./vim/dot-vimrc /target/.vimrc",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn stow_honors_the_local_ignore_file() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let package = dir.child("zsh");
+        package.child(".zshrc").touch()?;
+        package.child(".zshenv").touch()?;
+        package.child("README.md").touch()?;
+        package
+            .child(STOW_LOCAL_IGNORE)
+            .write_str("# Only the environment.\n\\.zshenv # Another comment.\n")?;
+
+        let mut out = vec![];
+        let converted = stow(&mut out, &package, Path::new("/target"), false)?;
+
+        assert_eq!(converted.specs, 2);
+        let out = String::from_utf8(out)?;
+        assert!(out.contains("./zsh/README.md /target/README.md"));
+        assert!(out.contains("./zsh/.zshrc /target/.zshrc"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dotbot_converts_the_links_and_their_options() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let config = dir.child("install.conf.yaml");
+        config.write_str(
+            "- defaults:
+    link:
+      create: true
+      relink: true
+- clean: ['~']
+- link:
+    ~/.vimrc:
+    ~/.config/my app: app
+    ~/.zshrc:
+      path: zsh/zshrc
+      force: true
+    ~/.work:
+      if: '[ `hostname` = work ]'
+      path: work
+- shell:
+  - git submodule sync
+",
+        )?;
+
+        let mut out = vec![];
+        let converted = dotbot(&mut out, &config)?;
+
+        assert_eq!(
+            converted,
+            Converted {
+                specs: 3,
+                flags: vec!["--mkdirs", "--relink"],
+            }
+        );
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().skip(1).collect();
+        assert_eq!(
+            lines,
+            [
+                "# Make the symlinks with: mksls --mkdirs --relink <DIR>",
+                "# Ignored the clean directive of dotbot.",
+                "# Ignored the shell directive of dotbot.",
+                "./vimrc ~/.vimrc",
+                r#"./app "~/.config/my app""#,
+                "# Given force in dotbot (see --always-overwrite).",
+                "./zsh/zshrc ~/.zshrc",
+                "# Made by dotbot only if [ `hostname` = work ] succeeds:",
+                "# ./work ~/.work",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_writes_the_ones_pointing_within_the_directory_of_the_file(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new()?;
+        let repo = dir.child("dotfiles");
+        repo.child("vimrc").touch()?;
+        repo.child("git/config").touch()?;
+        repo.child("vim").create_dir_all()?;
+        symlink("vim", repo.child(".vim"))?;
+        let home = dir.child("home");
+        home.child(".config/git").create_dir_all()?;
+        home.child("notes").touch()?;
+        symlink(repo.child("vimrc"), home.child(".vimrc"))?;
+        symlink(
+            "../../../dotfiles/git/config",
+            home.child(".config/git/config"),
+        )?;
+        symlink(home.child("notes"), home.child("notes link"))?;
+        let sls_file = repo.child("sls");
+        sls_file.write_str(&format!("./vimrc {}\n", home.child(".vimrc").display()))?;
+
+        let mut out = vec![];
+        let converted = symlinks(&mut out, &home, &sls_file)?;
+
+        assert_eq!(
+            converted,
+            Converted {
+                specs: 1,
+                flags: vec!["--relative"],
+            }
+        );
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!(
+                "./git/config {}\n",
+                fs::canonicalize(&home)?
+                    .join(".config/git/config")
+                    .display()
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -1,11 +1,33 @@
 #![warn(missing_docs)]
 #![doc = include_str!("../README.md")]
 
+pub mod backups;
+pub mod cache;
 pub mod cfg;
 pub mod cli;
 pub mod dir;
 pub mod engine;
+pub mod error;
+pub mod event;
+pub mod filesystem;
+pub mod hook;
+pub mod import;
 pub mod line;
+pub mod log;
+pub mod manifest;
+pub mod operation;
 pub mod params;
+mod path_repr;
+mod platform;
+pub mod progress;
 pub mod prompt;
+pub mod report;
+pub mod skipped;
+pub mod sls_toml;
+pub mod style;
+pub mod template;
+pub mod tui;
 mod utils;
+pub mod watch;
+
+pub use error::{Error, Result};
@@ -1,23 +1,65 @@
 //! Types and functions for parsing a line in a symlink-specification file and extracting
 //! the relevant contents.
 
+use crate::platform;
+use crate::template;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::path::{self, Path, PathBuf};
 
 lazy_static! {
-    /// A regex to parse a line expected to contain a symlink specification.
-    pub static ref SLS_SPEC_RE: Regex =
-        Regex::new(r#"^\s*(?<target>[^\s"]+|"[^"]+")\s+(?<link>[^\s"]+|"[^"]+")\s*$"#).unwrap();
+    /// A regex to find references to variables (`{NAME}`) in a path, the
+    /// user-defined ones (see [`LineType::Set`]) and the built-in ones (see
+    /// [`template::builtin_var`]) being expanded, the others being invalid.
+    /// It also finds `{{` and `}}`, standing for literal braces.
+    pub static ref PATH_VAR_RE: Regex =
+        Regex::new(r"\{\{|\}\}|\{(?<name>[A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+    /// A regex to find references to environment variables (`$VAR` or `${VAR}`) in a path.
+    pub static ref ENV_VAR_RE: Regex =
+        Regex::new(r"\$(?:\{(?<braced>[A-Za-z_][A-Za-z0-9_]*)\}|(?<bare>[A-Za-z_][A-Za-z0-9_]*))").unwrap();
 }
 
 /// Ways a line expected to contain a symlink specification can be invalid.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Invalid {
-    /// When the line doesn't match [`struct@SLS_SPEC_RE`].
+    /// When the line can't be split into tokens (see [`tokenize`]), for the given reason.
+    Malformed(String),
+    /// When the tokens of the line don't match any of the specification formats.
     NoMatch,
-    /// When the line matches [`struct@SLS_SPEC_RE`] but the target of the symlink doesn't exist.
+    /// When the target of the specification doesn't exist.
     TargetDoesNotExist,
+    /// When the line is a hard link, copy or template specification but the
+    /// target is a directory.
+    TargetIsADirectory,
+    /// When the line is a directory specification but the target is not a directory.
+    TargetIsNotADirectory,
+    /// When the target of the symlink looks like a glob pattern, but an invalid one.
+    InvalidGlob(String),
+    /// When the line is an include directive but the file to include doesn't exist.
+    IncludedFileDoesNotExist,
+    /// When a path references an environment variable (see [`struct@ENV_VAR_RE`]) that is not set.
+    UnsetEnvVar(String),
+    /// When a path references a built-in variable (see [`struct@PATH_VAR_RE`])
+    /// whose value can't be found.
+    UnavailableVar(String),
+    /// When a path references a variable (see [`struct@PATH_VAR_RE`]) that is
+    /// neither defined nor built-in.
+    UndefinedVar(String),
+    /// When the line is a set directive but the name of the variable is not
+    /// valid (see [`struct@PATH_VAR_RE`]).
+    InvalidVarName(String),
+    /// When the line closes a guarded block (see [`LineType::Guard`]), but
+    /// none is open.
+    UnopenedBlock,
+    /// When the line opens a guarded block (see [`LineType::Guard`]) that is
+    /// never closed.
+    UnclosedBlock,
+    /// When the line is not valid UTF-8.
+    NotUtf8,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -36,13 +78,135 @@ pub enum LineType {
         /// The path of the symlink.
         link: PathBuf,
     },
+    /// A line containing a symlink specification whose target doesn't exist,
+    /// but whose link is an existing file, that can be adopted (i.e. moved to
+    /// the target path before making the symlink).
+    SlsAdoptableSpec {
+        /// The path of the symlink's target.
+        target: PathBuf,
+        /// The path of the symlink.
+        link: PathBuf,
+    },
+    /// A line containing a valid hard link specification.
+    HardlinkSpec {
+        /// The path of the hard link's target.
+        target: PathBuf,
+        /// The path of the hard link.
+        link: PathBuf,
+    },
+    /// A line containing a valid copy specification.
+    CopySpec {
+        /// The path of the file to copy.
+        target: PathBuf,
+        /// The path of the copy.
+        link: PathBuf,
+    },
+    /// A line containing a valid template specification.
+    TemplateSpec {
+        /// The path of the template file.
+        target: PathBuf,
+        /// The path of the file in which to render the template.
+        link: PathBuf,
+    },
+    /// A line containing a valid symlink specification whose target is a glob
+    /// pattern, specifying one symlink per file matched.
+    SlsGlobSpec {
+        /// The paths matched by the glob pattern, i.e. the targets of the symlinks.
+        targets: Vec<PathBuf>,
+        /// The path of the directory in which to make the symlinks.
+        ///
+        /// Each symlink has the same file name as its target.
+        link_dir: PathBuf,
+    },
+    /// A line containing a valid directory specification, specifying one symlink
+    /// per file within the target directory (recursively), mirroring its tree
+    /// inside the link directory.
+    SlsDirSpec {
+        /// The path of the directory whose files are the targets of the symlinks.
+        target_dir: PathBuf,
+        /// The path of the directory in which to mirror the tree of `target_dir`.
+        link_dir: PathBuf,
+    },
+    /// A line containing an include directive, i.e. asking for another
+    /// symlink-specification file to be processed.
+    Include(PathBuf),
+    /// A tags directive (`tags: <TAG>...`), tagging the specifications
+    /// following it in the file, with the tags given (see [`tags`] and
+    /// [`crate::cli::Cli::tags`]).
+    Tags(Vec<String>),
+    /// A set directive (`set <NAME> <VALUE>`), defining the variable `NAME`
+    /// for the paths of the specifications following it in the file, as
+    /// `{NAME}`.
+    Set {
+        /// The name of the variable.
+        name: String,
+        /// The value of the variable, with the variables it references
+        /// expanded, but not the environment variables nor a leading `~`
+        /// (expanded in the paths it is substituted in).
+        value: String,
+    },
+    /// A guard (`ifexists <PATH>` or `ifcmd <NAME>`), applying the
+    /// specification following it in the file only if its condition holds.
+    ///
+    /// With a trailing `{`, it guards the block of lines up to the matching
+    /// [`LineType::BlockEnd`] instead.
+    Guard {
+        /// Whether the condition holds, i.e. whether `PATH` exists (for
+        /// `ifexists`) or the program `NAME` is found (for `ifcmd`).
+        holds: bool,
+        /// Whether it guards a block instead of a single specification.
+        block: bool,
+    },
+    /// The end of a block of lines guarded by a [`LineType::Guard`] (`}`).
+    BlockEnd,
 }
 
 /// Returns the type of a line.
 ///
+/// The line is first split into tokens (see [`tokenize`]).
+/// A line containing only whitespace is empty.
+/// A line starting with `//`, or having no token before a `#` comment, is a comment.
+///
+/// Then, the type depends on the tokens:
+///
+/// - `<TARGET> <LINK>` is a [`LineType::SlsSpec`]. If the target doesn't
+///   exist but looks like a glob pattern (contains one of `*`, `?` or `[`), it
+///   is a [`LineType::SlsGlobSpec`] instead, the link being taken as the
+///   directory in which to make the symlinks. Otherwise, if the link is an
+///   existing file (not a symlink), it is a [`LineType::SlsAdoptableSpec`].
+///   Otherwise, it is a [`LineType::SlsSpec`] anyway with
+///   `allow_missing_targets`, the symlink being dangling until its target
+///   exists.
+/// - `hard <TARGET> <LINK>` is a [`LineType::HardlinkSpec`].
+/// - `copy <TARGET> <LINK>` is a [`LineType::CopySpec`].
+/// - `template <TARGET> <LINK>` is a [`LineType::TemplateSpec`].
+/// - `dir <TARGET> <LINK>` is a [`LineType::SlsDirSpec`].
+/// - `include <PATH>` is a [`LineType::Include`].
+/// - `tags: <TAG>...` is a [`LineType::Tags`], the tags being separated by
+///   whitespace or commas, with an optional leading `@`.
+/// - `set <NAME> <VALUE>` is a [`LineType::Set`].
+/// - `ifexists <PATH>` and `ifcmd <NAME>`, optionally followed by `{`, are a
+///   [`LineType::Guard`], `NAME` being looked up in the directories of `PATH`
+///   when not a path.
+/// - `}` is a [`LineType::BlockEnd`].
+///
+/// In all cases, references to variables (`{NAME}`) in paths are replaced by
+/// their values, taken from `vars` or else among the built-in variables (see
+/// [`template::builtin_var`]), a reference to another variable being left as
+/// is. Then, references to environment variables (`$VAR` or `${VAR}`)
+/// in paths are replaced by their values, and a leading `~` (or `~user`) is
+/// expanded to the home directory of the current user (or of `user`).
+/// Finally, a relative path, unless it is the one of a link, is made relative
+/// to `sls_dir`.
+///
 /// # Parameters
 ///
 /// * `line` - The line for which to figure out the type.
+/// * `sls_dir` - The directory containing the symlink-specification file
+///   where `line` lives. Should be absolute.
+/// * `allow_missing_targets` - Whether a symlink specification whose target
+///   doesn't exist is valid (see [`crate::cli::Cli::allow_missing_targets`]).
+/// * `vars` - The variables defined so far (see [`LineType::Set`]), by name.
 ///
 /// # Examples
 ///
@@ -50,46 +214,1047 @@ pub enum LineType {
 /// use mksls::line;
 /// use mksls::line::LineType;
 /// use mksls::line::Invalid;
+/// use std::collections::HashMap;
+/// use std::path::Path;
+///
+/// let sls_dir = Path::new("/home/my_user/.dotfiles");
+/// let vars = HashMap::new();
 ///
-/// let invalid_line = "/wrong/\"target /wrong/\"link";
-/// assert_eq!(line::line_type(invalid_line), LineType::Invalid(Invalid::NoMatch));
+/// let invalid_line = "/wrong/target /wrong/link /extra/link";
+/// assert_eq!(line::line_type(invalid_line, sls_dir, false, &vars), LineType::Invalid(Invalid::NoMatch));
+///
+/// let malformed_line = "/wrong/\"target /wrong/\"link";
+/// assert!(matches!(
+///     line::line_type(malformed_line, sls_dir, false, &vars),
+///     LineType::Invalid(Invalid::Malformed(_))
+/// ));
 ///
 /// let empty_line = "";
-/// assert_eq!(line::line_type(empty_line), LineType::Empty);
+/// assert_eq!(line::line_type(empty_line, sls_dir, false, &vars), LineType::Empty);
 ///
 /// let comment_line = "// A comment.";
-/// assert_eq!(line::line_type(comment_line), LineType::Comment);
+/// assert_eq!(line::line_type(comment_line, sls_dir, false, &vars), LineType::Comment);
+///
+/// let comment_line = "  # Another comment.";
+/// assert_eq!(line::line_type(comment_line, sls_dir, false, &vars), LineType::Comment);
 ///
 /// let valid_line = "/home/my_user/.dotfiles/my_program/config /home/my_user/.config/my_program_config";
 /// // It actually isn't quite valid because the target does not exist.
 /// // The format is correct however.
-/// assert_eq!(line::line_type(valid_line), LineType::Invalid(Invalid::TargetDoesNotExist));
+/// assert_eq!(line::line_type(valid_line, sls_dir, false, &vars), LineType::Invalid(Invalid::TargetDoesNotExist));
+/// // Unless allowing missing targets.
+/// assert!(matches!(line::line_type(valid_line, sls_dir, true, &vars), LineType::SlsSpec { .. }));
+///
+/// let set_line = "set host laptop";
+/// assert_eq!(
+///     line::line_type(set_line, sls_dir, false, &vars),
+///     LineType::Set { name: String::from("host"), value: String::from("laptop") }
+/// );
+/// let vars = HashMap::from([(String::from("host"), String::from("laptop"))]);
+/// assert!(matches!(
+///     line::line_type("./zshrc.{host} ~/.zshrc", sls_dir, true, &vars),
+///     LineType::SlsSpec { target, .. } if target == Path::new("/home/my_user/.dotfiles/zshrc.laptop")
+/// ));
 /// ```
-pub fn line_type(line: &str) -> LineType {
+pub fn line_type(
+    line: &str,
+    sls_dir: &Path,
+    allow_missing_targets: bool,
+    vars: &HashMap<String, String>,
+) -> LineType {
+    if line.trim().is_empty() {
+        return LineType::Empty;
+    }
     if line.starts_with("//") {
-        LineType::Comment
-    } else if line.is_empty() {
-        LineType::Empty
-    } else {
-        match SLS_SPEC_RE.captures(line) {
-            Some(caps) => {
-                let mut target = PathBuf::new();
-                target.push(&caps["target"]);
-                if !target.exists() {
-                    return LineType::Invalid(Invalid::TargetDoesNotExist);
+        return LineType::Comment;
+    }
+
+    let tokens = match tokenize(line) {
+        Ok(tokens) => tokens,
+        Err(reason) => return LineType::Invalid(Invalid::Malformed(reason)),
+    };
+    if tokens.is_empty() {
+        return LineType::Comment;
+    }
+    let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+    // Guards are only written as lines, e.g. the target of a TOML
+    // specification may well be named `ifcmd`.
+    match tokens[..] {
+        ["ifexists", path] | ["ifexists", path, "{"] => match expand_path(path, sls_dir, vars) {
+            Ok(path) => LineType::Guard {
+                holds: path.exists(),
+                block: tokens.len() == 3,
+            },
+            Err(invalid) => LineType::Invalid(invalid),
+        },
+        ["ifcmd", name] | ["ifcmd", name, "{"] => LineType::Guard {
+            holds: platform::is_command(name),
+            block: tokens.len() == 3,
+        },
+        ["}"] => LineType::BlockEnd,
+        _ => spec_type(&tokens, sls_dir, allow_missing_targets, vars),
+    }
+}
+
+/// Returns the type of a specification made of `tokens` (see [`line_type`]
+/// for the formats recognized, but the guards and the ends of blocks, which
+/// are only written as lines).
+///
+/// This allows specifications not written as lines of text (e.g. in a TOML
+/// file, see [`crate::sls_toml`]) to be interpreted the same way.
+///
+/// # Parameters
+///
+/// * `tokens` - The tokens making up the specification, already unquoted and unescaped.
+/// * `sls_dir` - The directory containing the symlink-specification file
+///   where the specification lives. Should be absolute.
+/// * `allow_missing_targets` - Whether a symlink specification whose target
+///   doesn't exist is valid.
+/// * `vars` - The variables defined so far, by name.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::line;
+/// use mksls::line::{Invalid, LineType};
+/// use std::collections::HashMap;
+/// use std::path::Path;
+///
+/// let sls_dir = Path::new("/home/my_user/.dotfiles");
+/// let vars = HashMap::new();
+///
+/// assert_eq!(
+///     line::spec_type(&["copy", "/does/not/exist", "/some/link"], sls_dir, true, &vars),
+///     LineType::Invalid(Invalid::TargetDoesNotExist)
+/// );
+/// assert_eq!(
+///     line::spec_type(&["/some/target"], sls_dir, false, &vars),
+///     LineType::Invalid(Invalid::NoMatch)
+/// );
+/// ```
+pub fn spec_type(
+    tokens: &[&str],
+    sls_dir: &Path,
+    allow_missing_targets: bool,
+    vars: &HashMap<String, String>,
+) -> LineType {
+    match *tokens {
+        ["tags:", ref tags @ ..] => LineType::Tags(
+            tags.iter()
+                .flat_map(|tags| tags.split(','))
+                .map(|tag| tag.trim().trim_start_matches('@'))
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect(),
+        ),
+        ["set", name, value] if is_var_name(name) => match expand_vars(value, vars) {
+            Ok(value) => LineType::Set {
+                name: String::from(name),
+                value: value.into_owned(),
+            },
+            Err(invalid) => LineType::Invalid(invalid),
+        },
+        ["set", name, _] => LineType::Invalid(Invalid::InvalidVarName(String::from(name))),
+        ["include", path] => match expand_path(path, sls_dir, vars) {
+            Ok(path) if path.is_file() => LineType::Include(path),
+            Ok(_) => LineType::Invalid(Invalid::IncludedFileDoesNotExist),
+            Err(invalid) => LineType::Invalid(invalid),
+        },
+        ["hard", target, link] => file_spec(target, link, sls_dir, vars, |target, link| {
+            LineType::HardlinkSpec { target, link }
+        }),
+        ["copy", target, link] => file_spec(target, link, sls_dir, vars, |target, link| {
+            LineType::CopySpec { target, link }
+        }),
+        ["template", target, link] => file_spec(target, link, sls_dir, vars, |target, link| {
+            LineType::TemplateSpec { target, link }
+        }),
+        ["dir", target, link] => match expand_target_and_link(target, link, sls_dir, vars) {
+            Ok((target_dir, link_dir)) if target_dir.is_dir() => LineType::SlsDirSpec {
+                target_dir,
+                link_dir,
+            },
+            Ok((target_dir, _)) if target_dir.exists() => {
+                LineType::Invalid(Invalid::TargetIsNotADirectory)
+            }
+            Ok(_) => LineType::Invalid(Invalid::TargetDoesNotExist),
+            Err(invalid) => LineType::Invalid(invalid),
+        },
+        [target, link] => {
+            let (target, link) = match expand_target_and_link(target, link, sls_dir, vars) {
+                Ok(target_and_link) => target_and_link,
+                Err(invalid) => return LineType::Invalid(invalid),
+            };
+            if target.exists() {
+                return LineType::SlsSpec { target, link };
+            }
+
+            match target.to_str().filter(|target| is_glob(target)) {
+                Some(pattern) => match expand_glob(pattern) {
+                    Ok(targets) if targets.is_empty() => {
+                        LineType::Invalid(Invalid::TargetDoesNotExist)
+                    }
+                    Ok(targets) => LineType::SlsGlobSpec {
+                        targets,
+                        link_dir: link,
+                    },
+                    Err(invalid) => LineType::Invalid(invalid),
+                },
+                None if link.exists() && !link.is_symlink() => {
+                    LineType::SlsAdoptableSpec { target, link }
+                }
+                None if allow_missing_targets => LineType::SlsSpec { target, link },
+                None => LineType::Invalid(Invalid::TargetDoesNotExist),
+            }
+        }
+        _ => LineType::Invalid(Invalid::NoMatch),
+    }
+}
+
+/// Returns whether `line` is continued on the next line, i.e. whether it ends
+/// with a backslash escaping nothing (and not with an escaped backslash).
+///
+/// The backslash is then removed, and the next line appended, before getting
+/// the type of the line (see [`line_type`]). A backslash ending a comment is
+/// part of the comment, so a comment line, or a line ending with a comment,
+/// is never continued.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::line;
+///
+/// assert!(line::is_continued(br"./a/very/long/\"));
+/// assert!(!line::is_continued(br"./dir\\ ~/dir\\"));
+/// assert!(!line::is_continued(b"./vimrc ~/.vimrc"));
+/// assert!(!line::is_continued(br"# C:\Users\"));
+/// assert!(!line::is_continued(br"./vimrc ~/.vimrc # From C:\Users\"));
+/// ```
+pub fn is_continued(line: &[u8]) -> bool {
+    let line = String::from_utf8_lossy(line);
+    !line.starts_with("//")
+        && comment(&line).is_none()
+        && line.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
+}
+
+/// Splits `line` into whitespace-separated tokens, up to a comment.
+///
+/// A token is either unquoted, or wrapped in double quotes, in which case it
+/// can contain whitespace. In both cases, a backslash escapes the character
+/// following it, which is then taken literally (e.g. `\"`, `\ ` or `\\`).
+/// A `#` starting an unquoted token starts a comment, up to the end of the line.
+///
+/// # Errors
+///
+/// Fails with the reason why `line` can't be split into tokens, which is one of:
+///
+/// - There is an unescaped double quote within an unquoted token.
+/// - There is no closing double quote for an opening one.
+/// - A closing double quote is not followed by whitespace.
+/// - The line ends with a backslash escaping nothing.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::line;
+///
+/// assert_eq!(
+///     line::tokenize(r#"  "/a path/with spaces" /a\ path/with\ \"quotes\"  # A comment."#),
+///     Ok(vec![
+///         String::from("/a path/with spaces"),
+///         String::from("/a path/with \"quotes\""),
+///     ])
+/// );
+/// assert!(line::tokenize(r#"/a/"path /b/path"#).is_err());
+/// ```
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let escapes_nothing = || String::from("The line ends with a backslash escaping nothing.");
+
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut token = String::new();
+        match chars.next() {
+            None | Some('#') => break,
+            Some('"') => {
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(String::from(
+                                "There is no closing double quote for an opening one.",
+                            ))
+                        }
+                        Some('"') => break,
+                        Some('\\') => token.push(chars.next().ok_or_else(escapes_nothing)?),
+                        Some(c) => token.push(c),
+                    }
+                }
+                if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                    return Err(String::from(
+                        "A closing double quote is not followed by whitespace.",
+                    ));
                 }
-                let mut link = PathBuf::new();
-                link.push(&caps["link"]);
-                LineType::SlsSpec { target, link }
             }
-            None => LineType::Invalid(Invalid::NoMatch),
+            Some(c) => {
+                let mut c = Some(c);
+                while let Some(current) = c {
+                    match current {
+                        '"' => {
+                            return Err(String::from(
+                                "There is an unescaped double quote within an unquoted path.",
+                            ))
+                        }
+                        '\\' => token.push(chars.next().ok_or_else(escapes_nothing)?),
+                        current => token.push(current),
+                    }
+                    c = chars.next_if(|c| !c.is_whitespace());
+                }
+            }
+        }
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Returns the tags of `line` given in its comment, i.e. the words of the
+/// comment starting with `@` (e.g. `~/.xinitrc # @gui @laptop`), without the
+/// `@`.
+///
+/// The tags given by a tags directive (see [`LineType::Tags`]) apply too, but
+/// are not part of the line.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::line;
+///
+/// assert_eq!(
+///     line::tags(r#"xinitrc "~/.xinitrc" # For the @gui, on my @laptop."#),
+///     ["gui", "laptop"]
+/// );
+/// assert!(line::tags("a/@b c/@d").is_empty());
+/// ```
+pub fn tags(line: &str) -> Vec<String> {
+    let Some(comment) = comment(line) else {
+        return vec![];
+    };
+
+    comment
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|tag| tag.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the comment of `line` (without the `#`), if any, i.e. what
+/// follows a `#` starting an unquoted token (see [`tokenize`]).
+fn comment(line: &str) -> Option<&str> {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut token_start = true;
+    for (i, c) in line.char_indices() {
+        let literal = escaped;
+        escaped = false;
+        match c {
+            _ if literal => {}
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted && token_start => return Some(&line[i + 1..]),
+            _ => {}
+        }
+        token_start = !quoted && !literal && c.is_whitespace();
+    }
+
+    None
+}
+
+/// Returns `token` as written in a line, so that [`tokenize`] gives it back.
+///
+/// It is left as is, unless it is empty, contains whitespace, a double quote
+/// or a backslash, or starts with `#` or `//`. It is then wrapped in double
+/// quotes, its double quotes and backslashes being escaped.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::line;
+///
+/// assert_eq!(line::quote("/a/path"), "/a/path");
+/// assert_eq!(line::quote("/a path/with \"quotes\""), r#""/a path/with \"quotes\"""#);
+/// assert_eq!(
+///     line::tokenize(&line::quote("/a path/with \"quotes\"")),
+///     Ok(vec![String::from("/a path/with \"quotes\"")])
+/// );
+/// ```
+pub fn quote(token: &str) -> Cow<'_, str> {
+    if !token.is_empty()
+        && !token.starts_with('#')
+        && !token.starts_with("//")
+        && !token.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\')
+    {
+        return Cow::Borrowed(token);
+    }
+
+    let mut quoted = String::with_capacity(token.len() + 2);
+    quoted.push('"');
+    for c in token.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Expands the paths `target` and `link` (see [`expand_path`] and
+/// [`expand_link`]).
+fn expand_target_and_link(
+    target: &str,
+    link: &str,
+    sls_dir: &Path,
+    vars: &HashMap<String, String>,
+) -> Result<(PathBuf, PathBuf), Invalid> {
+    let target = expand_path(target, sls_dir, vars)?;
+    let link = expand_link(link, vars)?;
+    Ok((target, link))
+}
+
+/// Returns the line type built by `spec` out of the expanded `target` and
+/// `link`, after checking that `target` is an existing file (not a directory).
+fn file_spec(
+    target: &str,
+    link: &str,
+    sls_dir: &Path,
+    vars: &HashMap<String, String>,
+    spec: impl FnOnce(PathBuf, PathBuf) -> LineType,
+) -> LineType {
+    match expand_target_and_link(target, link, sls_dir, vars) {
+        Ok((target, _)) if target.is_dir() => LineType::Invalid(Invalid::TargetIsADirectory),
+        Ok((target, link)) if target.exists() => spec(target, link),
+        Ok(_) => LineType::Invalid(Invalid::TargetDoesNotExist),
+        Err(invalid) => LineType::Invalid(invalid),
+    }
+}
+
+/// Whether `name` is a valid variable name, i.e. made of ASCII letters,
+/// digits and underscores, and not starting with a digit.
+pub(crate) fn is_var_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether `path` looks like a glob pattern.
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Returns the paths matched by the glob `pattern`, in alphabetical order.
+///
+/// Fails with [`Invalid::InvalidGlob`] if `pattern` is invalid.
+fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, Invalid> {
+    let paths = glob::glob(pattern).map_err(|err| Invalid::InvalidGlob(err.to_string()))?;
+    // Unreadable paths can't be targets anyway.
+    Ok(paths.filter_map(Result::ok).collect())
+}
+
+/// Turns a path as written in a symlink specification into a [`PathBuf`].
+///
+/// If `path` is relative, it is made relative to `sls_dir`.
+///
+/// The variables (`vars`, then the built-in ones like `{hostname}`) are
+/// expanded first, then the environment variables, whose values are taken as is, even if not valid
+/// UTF-8. A leading `~` is then left as is, though.
+///
+/// Fails with [`Invalid::UndefinedVar`], [`Invalid::UnavailableVar`] or
+/// [`Invalid::UnsetEnvVar`] if `path` references a variable that is not
+/// defined, a built-in variable without value or an environment variable that
+/// is not set.
+fn expand_path(
+    path: &str,
+    sls_dir: &Path,
+    vars: &HashMap<String, String>,
+) -> Result<PathBuf, Invalid> {
+    let path = expand_link(path, vars)?;
+    // Joining with an absolute path replaces `sls_dir`, and collecting the
+    // components gets rid of the `.` ones.
+    Ok(sls_dir.join(path).components().collect())
+}
+
+/// Turns the path of a link as written in a symlink specification into a
+/// [`PathBuf`], like [`expand_path`], except that a relative path is left as
+/// is, i.e. relative to the current directory.
+fn expand_link(path: &str, vars: &HashMap<String, String>) -> Result<PathBuf, Invalid> {
+    let path = expand_vars(path, vars)?;
+    Ok(match expand_env_vars(&path)?.into_string() {
+        Ok(path) => expand_tilde(&path),
+        Err(path) => PathBuf::from(path),
+    })
+}
+
+/// Replaces the references to variables in `path` by their values, the
+/// ones of `vars` taking precedence over the built-in ones (see
+/// [`template::builtin_var`]), and `{{` and `}}` by literal braces.
+///
+/// Braces around anything else than a variable name (e.g. `{a,b}`) are left
+/// as is, as well as the references to environment variables (`${VAR}`).
+///
+/// Fails with [`Invalid::UndefinedVar`] at the first referenced variable that
+/// is neither in `vars` nor built-in, and with [`Invalid::UnavailableVar`] at
+/// the first referenced built-in variable whose value can't be found.
+fn expand_vars<'a>(path: &'a str, vars: &HashMap<String, String>) -> Result<Cow<'a, str>, Invalid> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut last = 0;
+    for caps in PATH_VAR_RE.captures_iter(path) {
+        let reference = caps.get(0).unwrap();
+        let Some(name) = caps.name("name").map(|name| name.as_str()) else {
+            // An escaped brace.
+            expanded.push_str(&path[last..reference.start() + 1]);
+            last = reference.end();
+            continue;
+        };
+        if path[..reference.start()].ends_with('$') {
+            // An environment variable (see [`struct@ENV_VAR_RE`]).
+            continue;
+        }
+        let value = match vars.get(name) {
+            Some(value) => value.clone(),
+            None if template::BUILTIN_VARS.contains(&name) => template::builtin_var(name)
+                .ok_or_else(|| Invalid::UnavailableVar(String::from(name)))?,
+            None => return Err(Invalid::UndefinedVar(String::from(name))),
+        };
+
+        expanded.push_str(&path[last..reference.start()]);
+        expanded.push_str(&value);
+        last = reference.end();
+    }
+    if last == 0 {
+        return Ok(Cow::Borrowed(path));
+    }
+    expanded.push_str(&path[last..]);
+
+    Ok(Cow::Owned(expanded))
+}
+
+/// Replaces the references to environment variables in `path` by their values.
+///
+/// Fails with [`Invalid::UnsetEnvVar`] at the first referenced variable that is not set.
+fn expand_env_vars(path: &str) -> Result<OsString, Invalid> {
+    let mut expanded = OsString::with_capacity(path.len());
+    let mut last = 0;
+    for caps in ENV_VAR_RE.captures_iter(path) {
+        let reference = caps.get(0).unwrap();
+        let name = caps
+            .name("braced")
+            .or_else(|| caps.name("bare"))
+            .unwrap()
+            .as_str();
+        let value = env::var_os(name).ok_or_else(|| Invalid::UnsetEnvVar(String::from(name)))?;
+
+        expanded.push(&path[last..reference.start()]);
+        expanded.push(value);
+        last = reference.end();
+    }
+    expanded.push(&path[last..]);
+
+    Ok(expanded)
+}
+
+/// Expands a leading `~` or `~user` in `path` to the corresponding home directory.
+///
+/// `path` is left as is when it doesn't start with `~`, or when the home
+/// directory can't be figured out.
+pub(crate) fn expand_tilde(path: &str) -> PathBuf {
+    let Some(rest) = path.strip_prefix('~') else {
+        return PathBuf::from(path);
+    };
+    let (user, rest) = match rest.find(['/', path::MAIN_SEPARATOR]) {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+
+    let home = if user.is_empty() {
+        platform::home_dir()
+    } else {
+        platform::home_of(user)
+    };
+
+    match home {
+        Some(mut home) => {
+            home.push(rest);
+            PathBuf::from(home)
         }
+        None => PathBuf::from(path),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::SLS_SPEC_RE;
+    use super::{
+        expand_env_vars, expand_link, expand_path, expand_tilde, expand_vars, line_type, tags,
+        tokenize, Invalid, LineType,
+    };
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::collections::HashMap;
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn tokenize_stops_at_comments_only() {
+        assert_eq!(tokenize("# a comment"), Ok(vec![]));
+        assert_eq!(
+            tokenize("/a /b  # my note"),
+            Ok(vec!["/a".into(), "/b".into()])
+        );
+        assert_eq!(
+            tokenize("/a /b\t# my note"),
+            Ok(vec!["/a".into(), "/b".into()])
+        );
+        assert_eq!(
+            tokenize("/a#1 /b#2"),
+            Ok(vec!["/a#1".into(), "/b#2".into()])
+        );
+        assert_eq!(
+            tokenize("\"/a #1\" \"/b #2\" # my note"),
+            Ok(vec!["/a #1".into(), "/b #2".into()])
+        );
+        assert_eq!(tokenize("\\#a /b"), Ok(vec!["#a".into(), "/b".into()]));
+    }
+
+    #[test]
+    fn line_type_handles_comments_and_empty_lines() {
+        let vars = HashMap::new();
+        let sls_dir = Path::new("/");
+
+        assert_eq!(line_type("", sls_dir, false, &vars), LineType::Empty);
+        assert_eq!(line_type("  \t ", sls_dir, false, &vars), LineType::Empty);
+        assert_eq!(
+            line_type("// a comment", sls_dir, false, &vars),
+            LineType::Comment
+        );
+        assert_eq!(
+            line_type("# a comment", sls_dir, false, &vars),
+            LineType::Comment
+        );
+        assert_eq!(
+            line_type("   # a comment", sls_dir, false, &vars),
+            LineType::Comment
+        );
+        assert_eq!(
+            line_type("/ /some/link # a comment", sls_dir, false, &vars),
+            LineType::SlsSpec {
+                target: PathBuf::from("/"),
+                link: PathBuf::from("/some/link"),
+            }
+        );
+    }
+
+    #[test]
+    fn tags_are_read_from_comments_and_directives() {
+        let vars = HashMap::new();
+        let sls_dir = Path::new("/");
+
+        assert_eq!(tags("/ /a # @gui @laptop."), ["gui", "laptop"]);
+        assert_eq!(tags("/ /a # @ not@tag"), Vec::<String>::new());
+        assert_eq!(tags(r#""/ # @quoted" /a"#), Vec::<String>::new());
+        assert_eq!(tags(r"/ /a\\ # @escaped"), ["escaped"]);
+        assert_eq!(tags(r"/ /a\ #@not_a_comment"), Vec::<String>::new());
+        assert_eq!(
+            line_type("tags: @gui, laptop  desktop", sls_dir, false, &vars),
+            LineType::Tags(vec!["gui".into(), "laptop".into(), "desktop".into()])
+        );
+        assert_eq!(
+            line_type("tags:", sls_dir, false, &vars),
+            LineType::Tags(vec![])
+        );
+    }
+
+    #[test]
+    fn line_type_recognizes_dir_specs() -> Result<(), Box<dyn std::error::Error>> {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("config/nvim/init.lua").touch()?;
+
+        assert_eq!(
+            line_type("dir ./config ~/.config", &dir, false, &vars),
+            LineType::SlsDirSpec {
+                target_dir: dir.child("config").to_path_buf(),
+                link_dir: expand_tilde("~/.config"),
+            }
+        );
+        assert_eq!(
+            line_type(
+                "  dir  \"./config/nvim/init.lua\" ~/.config  ",
+                &dir,
+                false,
+                &vars
+            ),
+            LineType::Invalid(Invalid::TargetIsNotADirectory)
+        );
+        assert_eq!(
+            line_type("dir ./does_not_exist ~/.config", &dir, false, &vars),
+            LineType::Invalid(Invalid::TargetDoesNotExist)
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_type_recognizes_hardlink_copy_and_template_specs(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("config/app.conf").touch()?;
+
+        assert_eq!(
+            line_type("hard ./config/app.conf ~/.app.conf", &dir, false, &vars),
+            LineType::HardlinkSpec {
+                target: dir.child("config/app.conf").to_path_buf(),
+                link: expand_tilde("~/.app.conf"),
+            }
+        );
+        assert_eq!(
+            line_type("hard ./config ~/.config/app", &dir, false, &vars),
+            LineType::Invalid(Invalid::TargetIsADirectory)
+        );
+        assert_eq!(
+            line_type("copy ./config/app.conf ~/.app.conf", &dir, false, &vars),
+            LineType::CopySpec {
+                target: dir.child("config/app.conf").to_path_buf(),
+                link: expand_tilde("~/.app.conf"),
+            }
+        );
+        assert_eq!(
+            line_type("template ./config/app.conf ~/.app.conf", &dir, false, &vars),
+            LineType::TemplateSpec {
+                target: dir.child("config/app.conf").to_path_buf(),
+                link: expand_tilde("~/.app.conf"),
+            }
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_type_recognizes_includes() -> Result<(), Box<dyn std::error::Error>> {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("nvim/sls").touch()?;
+
+        assert_eq!(
+            line_type("include ./nvim/sls", &dir, false, &vars),
+            LineType::Include(dir.child("nvim/sls").to_path_buf())
+        );
+        assert_eq!(
+            line_type("include ./nvim", &dir, false, &vars),
+            LineType::Invalid(Invalid::IncludedFileDoesNotExist)
+        );
+        assert_eq!(
+            line_type("include ./zsh/sls", &dir, false, &vars),
+            LineType::Invalid(Invalid::IncludedFileDoesNotExist)
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_type_expands_glob_targets() -> Result<(), Box<dyn std::error::Error>> {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("bin/b").touch()?;
+        dir.child("bin/a").touch()?;
+        dir.child("bin/c.txt").touch()?;
+
+        assert_eq!(
+            line_type("./bin/[ab] ~/.local/bin", &dir, false, &vars),
+            LineType::SlsGlobSpec {
+                targets: vec![
+                    dir.child("bin/a").to_path_buf(),
+                    dir.child("bin/b").to_path_buf()
+                ],
+                link_dir: expand_tilde("~/.local/bin"),
+            }
+        );
+        assert_eq!(
+            line_type("./bin/*.md ~/.local/bin", &dir, false, &vars),
+            LineType::Invalid(Invalid::TargetDoesNotExist)
+        );
+        assert!(matches!(
+            line_type("./bin/[ ~/.local/bin", &dir, false, &vars),
+            LineType::Invalid(Invalid::InvalidGlob(_))
+        ));
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_type_recognizes_adoptable_specs() -> Result<(), Box<dyn std::error::Error>> {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("home/.vimrc").touch()?;
+        dir.child("home/.config/nvim").create_dir_all()?;
+        let home = dir.child("home").path().display().to_string();
+
+        assert_eq!(
+            line_type(&format!("./dots/vimrc {home}/.vimrc"), &dir, false, &vars),
+            LineType::SlsAdoptableSpec {
+                target: dir.child("dots/vimrc").to_path_buf(),
+                link: dir.child("home/.vimrc").to_path_buf(),
+            }
+        );
+        assert_eq!(
+            line_type(
+                &format!("./dots/nvim {home}/.config/nvim"),
+                &dir,
+                false,
+                &vars
+            ),
+            LineType::SlsAdoptableSpec {
+                target: dir.child("dots/nvim").to_path_buf(),
+                link: dir.child("home/.config/nvim").to_path_buf(),
+            }
+        );
+        assert_eq!(
+            line_type(&format!("./dots/zshrc {home}/.zshrc"), &dir, false, &vars),
+            LineType::Invalid(Invalid::TargetDoesNotExist)
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_type_allows_missing_targets_of_symlinks_only() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("home/.vimrc").touch()?;
+        let home = dir.child("home").path().display().to_string();
+
+        assert_eq!(
+            line_type(&format!("./secrets/token {home}/.token"), &dir, true, &vars),
+            LineType::SlsSpec {
+                target: dir.child("secrets/token").to_path_buf(),
+                link: dir.child("home/.token").to_path_buf(),
+            }
+        );
+        assert!(matches!(
+            line_type(&format!("./dots/vimrc {home}/.vimrc"), &dir, true, &vars),
+            LineType::SlsAdoptableSpec { .. }
+        ));
+        assert_eq!(
+            line_type("./bin/*.md ~/.local/bin", &dir, true, &vars),
+            LineType::Invalid(Invalid::TargetDoesNotExist)
+        );
+        assert_eq!(
+            line_type(
+                &format!("copy ./secrets/token {home}/.token"),
+                &dir,
+                true,
+                &vars
+            ),
+            LineType::Invalid(Invalid::TargetDoesNotExist)
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn expand_path_makes_relative_paths_relative_to_sls_dir() {
+        let vars = HashMap::new();
+        let sls_dir = Path::new("/dotfiles/nvim");
+
+        assert_eq!(
+            expand_path("./init.lua", sls_dir, &vars).unwrap(),
+            PathBuf::from("/dotfiles/nvim/init.lua")
+        );
+        assert_eq!(
+            expand_path("lua/plugins.lua", sls_dir, &vars).unwrap(),
+            PathBuf::from("/dotfiles/nvim/lua/plugins.lua")
+        );
+        assert_eq!(
+            expand_path("../zsh/.zshrc", sls_dir, &vars).unwrap(),
+            PathBuf::from("/dotfiles/nvim/../zsh/.zshrc")
+        );
+        assert_eq!(
+            expand_path("/absolute/path", sls_dir, &vars).unwrap(),
+            PathBuf::from("/absolute/path")
+        );
+        assert_eq!(
+            expand_link("./.config/nvim", &vars).unwrap(),
+            PathBuf::from("./.config/nvim")
+        );
+    }
+
+    #[test]
+    fn expand_env_vars_replaces_set_vars() {
+        env::set_var("MKSLS_TEST_SET_VAR", "/some/dir");
+
+        assert_eq!(
+            expand_env_vars("$MKSLS_TEST_SET_VAR/foo").unwrap(),
+            "/some/dir/foo"
+        );
+        assert_eq!(
+            expand_env_vars("${MKSLS_TEST_SET_VAR}_suffix/foo").unwrap(),
+            "/some/dir_suffix/foo"
+        );
+        assert_eq!(expand_env_vars("/no/var/$/foo").unwrap(), "/no/var/$/foo");
+    }
+
+    #[test]
+    fn expand_env_vars_errors_on_unset_var() {
+        env::remove_var("MKSLS_TEST_UNSET_VAR");
+
+        assert_eq!(
+            expand_env_vars("/foo/${MKSLS_TEST_UNSET_VAR}/bar"),
+            Err(Invalid::UnsetEnvVar(String::from("MKSLS_TEST_UNSET_VAR")))
+        );
+    }
+
+    #[test]
+    fn expand_vars_replaces_defined_vars_only() {
+        let vars = HashMap::new();
+        assert_eq!(
+            expand_vars("/dots/{os}-{arch}", &vars).unwrap(),
+            format!("/dots/{}-{}", env::consts::OS, env::consts::ARCH)
+        );
+        assert_eq!(
+            expand_vars("/dots/{os}/{name}", &vars),
+            Err(Invalid::UndefinedVar(String::from("name")))
+        );
+        assert_eq!(
+            expand_path("gitconfig.{os}", Path::new("/dots"), &vars).unwrap(),
+            PathBuf::from(format!("/dots/gitconfig.{}", env::consts::OS))
+        );
+    }
+
+    #[test]
+    fn expand_vars_keeps_literal_braces() {
+        let vars = HashMap::new();
+        assert_eq!(
+            expand_vars("/dots/{a,b}/{}", &vars).unwrap(),
+            "/dots/{a,b}/{}"
+        );
+        assert_eq!(
+            expand_vars("${HOME}/.vimrc", &vars).unwrap(),
+            "${HOME}/.vimrc"
+        );
+        assert_eq!(
+            expand_vars("/dots/{{name}}/{{{os}}}", &vars).unwrap(),
+            format!("/dots/{{name}}/{{{}}}", env::consts::OS)
+        );
+    }
+
+    #[test]
+    fn line_type_evaluates_guards() -> Result<(), Box<dyn std::error::Error>> {
+        let vars = HashMap::new();
+        let dir = TempDir::new()?;
+        dir.child("zshrc").touch()?;
+
+        assert_eq!(
+            line_type("ifexists ./zshrc", &dir, false, &vars),
+            LineType::Guard {
+                holds: true,
+                block: false,
+            }
+        );
+        assert_eq!(
+            line_type("ifexists ./bashrc {", &dir, false, &vars),
+            LineType::Guard {
+                holds: false,
+                block: true,
+            }
+        );
+        assert_eq!(
+            line_type("ifcmd mksls_test_no_such_command", &dir, false, &vars),
+            LineType::Guard {
+                holds: false,
+                block: false,
+            }
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            line_type("ifcmd sh", &dir, false, &vars),
+            LineType::Guard {
+                holds: true,
+                block: false,
+            }
+        );
+        assert_eq!(line_type("  }  ", &dir, false, &vars), LineType::BlockEnd);
+        assert_eq!(
+            line_type("ifexists ./zshrc {{", &dir, false, &vars),
+            LineType::Invalid(Invalid::NoMatch)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn line_type_recognizes_set_directives() {
+        let sls_dir = Path::new("/dots");
+        let vars = HashMap::from([
+            (String::from("os"), String::from("plan9")),
+            (String::from("theme"), String::from("dark")),
+        ]);
+
+        assert_eq!(
+            line_type("set theme \"solarized dark\"", sls_dir, false, &vars),
+            LineType::Set {
+                name: String::from("theme"),
+                value: String::from("solarized dark"),
+            }
+        );
+        assert_eq!(
+            line_type("set 2nd light", sls_dir, false, &vars),
+            LineType::Invalid(Invalid::InvalidVarName(String::from("2nd")))
+        );
+        assert_eq!(
+            line_type("./{theme}/{os}.{arch} ./{theme}", sls_dir, true, &vars),
+            LineType::SlsSpec {
+                target: PathBuf::from(format!("/dots/dark/plan9.{}", env::consts::ARCH)),
+                link: PathBuf::from("./dark"),
+            }
+        );
+        assert_eq!(
+            line_type("./{theme} ./{unknown}", sls_dir, true, &vars),
+            LineType::Invalid(Invalid::UndefinedVar(String::from("unknown")))
+        );
+    }
+
+    #[test]
+    fn expand_tilde_expands_home_dir() {
+        let home = PathBuf::from(env::var_os("HOME").expect("HOME should be set."));
+
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(expand_tilde("~/.config/foo"), home.join(".config/foo"));
+        assert_eq!(expand_tilde("~root/foo"), PathBuf::from("/root/foo"));
+    }
+
+    #[test]
+    fn expand_tilde_leaves_other_paths_untouched() {
+        assert_eq!(expand_tilde("/some/~/path"), PathBuf::from("/some/~/path"));
+        assert_eq!(expand_tilde("some/path"), PathBuf::from("some/path"));
+        assert_eq!(
+            expand_tilde("~no_such_user_hopefully/foo"),
+            PathBuf::from("~no_such_user_hopefully/foo")
+        );
+    }
 
     #[derive(Debug)]
     struct TestCase {
@@ -100,7 +1265,7 @@ mod tests {
     }
 
     #[test]
-    fn sls_spec_re_matches_when_it_should() {
+    fn tokenize_splits_spec_lines_when_it_should() {
         let test_cases = vec![
             // regular input
             TestCase {
@@ -141,7 +1306,7 @@ mod tests {
             TestCase {
                 input: String::from("\"/some/random/target\" /some/random/link"),
                 matches: true,
-                target: Some(String::from("\"/some/random/target\"")),
+                target: Some(String::from("/some/random/target")),
                 link: Some(String::from("/some/random/link")),
             },
             // link in quotes
@@ -149,14 +1314,14 @@ mod tests {
                 input: String::from("/some/random/target \"/some/random/link\""),
                 matches: true,
                 target: Some(String::from("/some/random/target")),
-                link: Some(String::from("\"/some/random/link\"")),
+                link: Some(String::from("/some/random/link")),
             },
             // both in quotes
             TestCase {
                 input: String::from("\"/some/random/target\" \"/some/random/link\""),
                 matches: true,
-                target: Some(String::from("\"/some/random/target\"")),
-                link: Some(String::from("\"/some/random/link\"")),
+                target: Some(String::from("/some/random/target")),
+                link: Some(String::from("/some/random/link")),
             },
             // both in quotes with spaces
             TestCase {
@@ -164,8 +1329,8 @@ mod tests {
                     "\"/some/random/target with spaces\" \"/some/random/link with spaces\"",
                 ),
                 matches: true,
-                target: Some(String::from("\"/some/random/target with spaces\"")),
-                link: Some(String::from("\"/some/random/link with spaces\"")),
+                target: Some(String::from("/some/random/target with spaces")),
+                link: Some(String::from("/some/random/link with spaces")),
             },
             // target contains double quote
             TestCase {
@@ -188,20 +1353,66 @@ mod tests {
                 target: None,
                 link: None,
             },
+            // escaped quotes within quotes
+            TestCase {
+                input: String::from("\"/some/random/\\\"target\" \"/some/random/\\\"link\""),
+                matches: true,
+                target: Some(String::from("/some/random/\"target")),
+                link: Some(String::from("/some/random/\"link")),
+            },
+            // escaped quotes and spaces without quotes
+            TestCase {
+                input: String::from("/some/random/\\\"target\\ 1 /some/random/link\\ 1"),
+                matches: true,
+                target: Some(String::from("/some/random/\"target 1")),
+                link: Some(String::from("/some/random/link 1")),
+            },
+            // escaped backslashes
+            TestCase {
+                input: String::from("/some/random\\\\target \"/some/random\\\\link\""),
+                matches: true,
+                target: Some(String::from("/some/random\\target")),
+                link: Some(String::from("/some/random\\link")),
+            },
+            // too many paths
+            TestCase {
+                input: String::from("/some/random/target /some/random/link /some/other/link"),
+                matches: false,
+                target: None,
+                link: None,
+            },
+            // backslash escaping nothing
+            TestCase {
+                input: String::from("/some/random/target /some/random/link\\"),
+                matches: false,
+                target: None,
+                link: None,
+            },
+            // unterminated quote
+            TestCase {
+                input: String::from("/some/random/target \"/some/random/link"),
+                matches: false,
+                target: None,
+                link: None,
+            },
         ];
 
         for test_case in test_cases {
-            let caps = SLS_SPEC_RE.captures(&test_case.input[..]);
+            let tokens = tokenize(&test_case.input[..]);
+            let target_and_link = match tokens.as_deref() {
+                Ok([target, link]) => Some((target.clone(), link.clone())),
+                _ => None,
+            };
             assert_eq!(
-                caps.is_some(),
+                target_and_link.is_some(),
                 test_case.matches,
-                "Didn't match as expected for input '{}'",
+                "Didn't tokenize as expected for input '{}'",
                 test_case.input
             );
 
-            if let Some(caps) = caps {
-                assert_eq!(&caps["target"], test_case.target.unwrap());
-                assert_eq!(&caps["link"], test_case.link.unwrap());
+            if let Some((target, link)) = target_and_link {
+                assert_eq!(target, test_case.target.unwrap());
+                assert_eq!(link, test_case.link.unwrap());
             }
         }
     }
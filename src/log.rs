@@ -0,0 +1,154 @@
+//! Logging of the decisions taken during a run to a file, independently of
+//! what is printed to the terminal.
+
+use crate::error::Context;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// How much is logged, each level including the ones before it.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Errors making the run fail.
+    Error,
+    /// Problems that don't make the run fail (e.g. invalid lines).
+    Warn,
+    /// What has been done for each link.
+    #[default]
+    Info,
+    /// Details about the run (e.g. files processed, hooks run).
+    Debug,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        };
+        write!(f, "{}", level)
+    }
+}
+
+/// Writes timestamped lines to a log file, of the form:
+///
+/// ```text
+/// <RFC 3339 timestamp> <LEVEL> <message>
+/// ```
+///
+/// Can be shared between threads, lines written concurrently are not mixed up.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::log::{LogLevel, Logger};
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let logger = Logger::open(Some(Path::new("/tmp/mksls.log")), LogLevel::Info)?;
+/// logger.log(LogLevel::Info, "Made /home/me/.vimrc -> /home/me/.dotfiles/vimrc");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Logger {
+    /// The log file, if logging.
+    file: Option<Mutex<File>>,
+    /// The most detailed level logged.
+    level: LogLevel,
+}
+
+impl Logger {
+    /// Opens a logger appending to the file at `path` (created if needed), or
+    /// one logging nothing if `path` is `None`.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: Path to the log file.
+    /// - `level`: The most detailed level to log.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the log file can't be opened.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn open(path: Option<&Path>, level: LogLevel) -> crate::Result<Self> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .io_context(|| format!("Failed to open log file {}.", path.display()))?,
+            )),
+            None => None,
+        };
+
+        Ok(Self { file, level })
+    }
+
+    /// Writes `message` to the log file, if logging at `level`.
+    ///
+    /// A multiline message is joined into a single line.
+    ///
+    /// Failing to write is not an error, so as not to interrupt a run, but
+    /// is reported to stderr.
+    pub fn log(&self, level: LogLevel, message: &str) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if level > self.level {
+            return;
+        }
+
+        // One line per entry, even for multiline messages.
+        let message = message.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+        let line = format!(
+            "{} {} {}\n",
+            chrono::Local::now().to_rfc3339(),
+            level,
+            message
+        );
+        if let Err(err) = file.lock().unwrap().write_all(line.as_bytes()) {
+            eprintln!("(!) Warning: Failed to write to the log file: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::NamedTempFile;
+    use std::fs;
+
+    #[test]
+    fn log_writes_lines_up_to_level() -> Result<(), Box<dyn std::error::Error>> {
+        let file = NamedTempFile::new("mksls.log")?;
+        file.touch()?;
+
+        let logger = Logger::open(Some(file.path()), LogLevel::Warn)?;
+        logger.log(LogLevel::Error, "An error.");
+        logger.log(LogLevel::Warn, "A warning.\n    On two lines.");
+        logger.log(LogLevel::Info, "Some info.");
+
+        let contents = fs::read_to_string(file.path())?;
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" ERROR An error."));
+        assert!(lines[1].ends_with(" WARN A warning. On two lines."));
+
+        file.close()?;
+
+        Ok(())
+    }
+}
@@ -1,24 +1,235 @@
 use clap::{crate_name, Parser};
+use mksls::backups;
 use mksls::cfg::Config;
-use mksls::cli::Cli;
+use mksls::cli::{BackupsCommand, Cli, Command, ConfigCommand, ImportCommand};
 use mksls::dir::error::{DirCreationFailed, DirDoesNotExist};
-use mksls::engine::Engine;
+use mksls::engine::{Engine, RunStatus};
+use mksls::import::{self, Converted};
 use mksls::params::Params;
+use mksls::prompt::{Input, TerminalPrompter};
+use mksls::style;
+use mksls::watch;
+use std::env;
 use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::process::ExitCode;
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    let cfg: Config = confy::load(crate_name!(), crate_name!())?;
+fn main() -> anyhow::Result<ExitCode> {
+    let mut cli = Cli::parse();
+    style::init(cli.color);
+    init_tracing(cli.verbose);
+    let command = cli.command.take();
+    if let Some(
+        Command::Prune { dir }
+        | Command::CleanBroken { dir, .. }
+        | Command::Watch { dir }
+        | Command::Resolve { dir }
+        | Command::Lint { dir },
+    ) = &command
+    {
+        cli.dir = Some(dir.clone());
+    }
+    let confirm_always_overwrite = cli.always_overwrite && !cli.i_know_what_i_am_doing;
+    if let Some(Command::Config { command }) = command {
+        let path = match &cli.config {
+            Some(path) => path.clone(),
+            None => Config::default_path()?,
+        };
+        config(command, &path)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(Command::Import { command }) = command {
+        import(command)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(Command::Scan { dir, into }) = command {
+        // Only written once the scan succeeded, so that a failed one doesn't
+        // leave half of the specifications in the file.
+        let mut specs = vec![];
+        let converted = import::symlinks(&mut specs, &dir, &into)?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&into)?
+            .write_all(&specs)?;
+        print_converted(&converted);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let cfg: Config = if cli.no_config {
+        Config::default()
+    } else {
+        Config::load_with_system(cli.config.as_deref(), &Config::system_path())?
+    };
 
-    let params = Params::new(cli, cfg)?;
-    if !params.dir.is_dir() {
+    if let Some(Command::Backups {
+        command: BackupsCommand::Prune,
+    }) = command
+    {
+        let cfg = match &cli.profile {
+            Some(profile) => cfg.with_profile(profile)?,
+            None => cfg,
+        };
+        let cfg = cfg.with_env()?;
+        let backup_dir = cli.backup_dir.unwrap_or(cfg.backup_dir);
+        if backup_dir.is_dir() {
+            let pruned = backups::prune(&backup_dir, cfg.max_backups_per_link, cfg.max_age_days)?;
+            println!();
+            println!("{} backup(s) pruned.", pruned);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut params = Params::new(cli, cfg)?;
+    if confirm_always_overwrite {
+        let input = match &mut params.answers {
+            // The first answer confirms, the other ones are for the run.
+            Some(answers) => Input::answers(answers.drain(..answers.len().min(1))),
+            None => Input::Stdin,
+        };
+        let prompter = TerminalPrompter::new(input).noninteractive(params.noninteractive_action);
+        if !prompter.confirm_always_overwrite()? {
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+    if !params.dir.is_dir() && !params.dir.is_file() {
         Err(DirDoesNotExist(params.dir.clone()))?;
     }
-    if !params.backup_dir.is_dir() {
+    if !params.backup_dir.is_dir() && !matches!(command, Some(Command::Lint { .. })) {
         if let Err(err) = fs::create_dir_all(params.backup_dir.as_path()) {
             Err(DirCreationFailed(params.backup_dir.clone(), err))?;
         }
     }
 
-    Engine::new(params).run()
+    match command {
+        Some(Command::Prune { .. }) => {
+            Engine::new(params).prune()?;
+        }
+        Some(Command::CleanBroken { under, .. }) => {
+            Engine::new(params).clean_broken(under.as_deref())?;
+        }
+        Some(Command::Watch { .. }) => {
+            watch::watch(params)?;
+        }
+        Some(Command::Resolve { .. }) => {
+            let summary = Engine::new(params).resolve()?;
+            return Ok(ExitCode::from(summary.status().exit_code()));
+        }
+        Some(Command::Lint { .. }) => {
+            if Engine::new(params).lint()? > 0 {
+                return Ok(ExitCode::from(RunStatus::InvalidLines.exit_code()));
+            }
+        }
+        Some(
+            Command::Scan { .. }
+            | Command::Backups { .. }
+            | Command::Config { .. }
+            | Command::Import { .. },
+        ) => {
+            unreachable!("Handled before building the parameters.")
+        }
+        None => {
+            let summary = Engine::new(params).run()?;
+            return Ok(ExitCode::from(summary.status().exit_code()));
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the diagnostics of the app to stderr, at the level given by the
+/// number of `-v` (see [`Cli::verbose`]), nothing without any. They are
+/// colored like the output (see [`style::enabled`]).
+fn init_tracing(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => tracing::Level::INFO,
+        _ => tracing::Level::DEBUG,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(style::enabled())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// Runs `mksls import <command>`, printing the converted file to stdout.
+fn import(command: ImportCommand) -> anyhow::Result<()> {
+    let converted = match command {
+        ImportCommand::Stow {
+            package_dir,
+            target,
+            dotfiles,
+        } => {
+            let package_dir = fs::canonicalize(&package_dir).map_err(|err| {
+                anyhow::anyhow!("Failed to read {}: {}", package_dir.display(), err)
+            })?;
+            let target = match target {
+                Some(target) => std::path::absolute(target)?,
+                None => match package_dir.parent().and_then(Path::parent) {
+                    Some(target) => target.to_path_buf(),
+                    None => anyhow::bail!(
+                        "{} has no default target directory, give one with --target.",
+                        package_dir.display()
+                    ),
+                },
+            };
+            import::stow(io::stdout().lock(), &package_dir, &target, dotfiles)?
+        }
+        ImportCommand::Dotbot { config } => import::dotbot(io::stdout().lock(), &config)?,
+    };
+
+    print_converted(&converted);
+
+    Ok(())
+}
+
+/// Prints how many symlinks have been specified by `mksls import` or `mksls
+/// scan`, and the options to make them with, if any.
+fn print_converted(converted: &Converted) {
+    eprintln!("{} symlink(s) specified.", converted.specs);
+    if !converted.flags.is_empty() {
+        eprintln!(
+            "Make them with: {} {} <DIR>",
+            crate_name!(),
+            converted.flags.join(" ")
+        );
+    }
+}
+
+/// Runs `mksls config <command>` on the configuration file at `path`.
+fn config(command: ConfigCommand, path: &Path) -> anyhow::Result<()> {
+    if !matches!(command, ConfigCommand::Path) && !path.exists() {
+        confy::store_path(path, Config::default())?;
+    }
+
+    match command {
+        ConfigCommand::Path => println!("{}", path.display()),
+        ConfigCommand::Show => print!("{}", toml::to_string(&Config::load(path)?)?),
+        ConfigCommand::Edit => {
+            let editor = env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| String::from("vi"));
+            let mut words = editor.split_whitespace();
+            let status = process::Command::new(words.next().unwrap_or("vi"))
+                .args(words)
+                .arg(path)
+                .status()?;
+            if !status.success() {
+                anyhow::bail!("The editor {} exited with {}.", editor, status);
+            }
+            Config::load(path)?.validate()?;
+        }
+        ConfigCommand::Set { key, value } => {
+            let cfg = Config::load(path)?.with_value(&key, &value)?;
+            confy::store_path(path, cfg)?;
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,284 @@
+//! The manifest of the symlinks made by the app, to keep track of them across runs.
+
+use crate::error::Context;
+use crate::path_repr::PathRepr;
+use crate::Error;
+use clap::crate_name;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The symlinks made by the app, i.e. the symlinks it manages, by the
+/// directory whose symlink-specification files specify them.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::manifest::Manifest;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = Manifest::default_path()?;
+/// let mut manifest = Manifest::load(&path)?;
+/// manifest.add(
+///     Path::new("/home/me/.dotfiles"),
+///     Path::new("/home/me/.vimrc"),
+///     Path::new("/home/me/.dotfiles/vimrc"),
+/// );
+/// manifest.save(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Stored", into = "Stored")]
+pub struct Manifest {
+    /// The managed symlinks of each (canonical) directory, each one
+    /// associated with its target.
+    dirs: BTreeMap<PathBuf, BTreeMap<PathBuf, PathBuf>>,
+}
+
+/// How the [`Manifest`] is written.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Stored {
+    dir: Vec<StoredDir>,
+}
+
+/// A directory in [`Stored::dir`].
+///
+/// The path of a symlink can only be a key of `links` if it is valid UTF-8,
+/// so the other symlinks are in `other_links` instead (see
+/// [`crate::path_repr`]).
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct StoredDir {
+    #[serde(with = "crate::path_repr")]
+    path: PathBuf,
+    links: BTreeMap<String, PathRepr>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    other_links: Vec<StoredLink>,
+}
+
+/// A symlink in [`StoredDir::other_links`].
+#[derive(Serialize, Deserialize)]
+struct StoredLink {
+    #[serde(with = "crate::path_repr")]
+    link: PathBuf,
+    #[serde(with = "crate::path_repr")]
+    target: PathBuf,
+}
+
+impl From<Stored> for Manifest {
+    fn from(stored: Stored) -> Self {
+        let dirs = stored
+            .dir
+            .into_iter()
+            .map(|dir| {
+                let links = dir
+                    .links
+                    .into_iter()
+                    .map(|(link, target)| (PathBuf::from(link), target.0))
+                    .chain(
+                        dir.other_links
+                            .into_iter()
+                            .map(|other| (other.link, other.target)),
+                    )
+                    .collect();
+                (dir.path, links)
+            })
+            .collect();
+
+        Manifest { dirs }
+    }
+}
+
+impl From<Manifest> for Stored {
+    fn from(manifest: Manifest) -> Self {
+        let mut stored = Stored::default();
+        for (path, links) in manifest.dirs {
+            let mut dir = StoredDir {
+                path,
+                ..StoredDir::default()
+            };
+            for (link, target) in links {
+                match link.into_os_string().into_string() {
+                    Ok(link) => {
+                        dir.links.insert(link, PathRepr(target));
+                    }
+                    Err(link) => dir.other_links.push(StoredLink {
+                        link: PathBuf::from(link),
+                        target,
+                    }),
+                }
+            }
+            stored.dir.push(dir);
+        }
+
+        stored
+    }
+}
+
+impl Manifest {
+    /// Returns the path of the manifest in the data directory of the app.
+    ///
+    /// # Errors
+    ///
+    /// Fails when no home directory is found for the current user.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn default_path() -> crate::Result<PathBuf> {
+        let project = ProjectDirs::from("rs", "", crate_name!()).ok_or_else(|| Error::Io {
+            message: String::from(
+                "Failed to find the data directory where to keep the manifest of the symlinks made.",
+            ),
+            source: io::ErrorKind::NotFound.into(),
+        })?;
+
+        Ok(project.data_dir().join("manifest.toml"))
+    }
+
+    /// Loads the manifest at `path`, or an empty one if there is no file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file at `path` can't be read or is not a valid manifest.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read the manifest {}.", path.display()))?;
+        toml::from_str(&contents)
+            .parse_context(|| format!("Failed to parse the manifest {}.", path.display()))
+    }
+
+    /// Saves the manifest at `path`, creating the parent directories if needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the manifest can't be written.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).io_context(|| {
+                format!(
+                    "Failed to create directory {} to save the manifest in it.",
+                    parent.display()
+                )
+            })?;
+        }
+        let contents = toml::to_string(self).parse_context(|| {
+            format!(
+                "Failed to serialize the manifest to save it at {}.",
+                path.display()
+            )
+        })?;
+        fs::write(path, contents)
+            .io_context(|| format!("Failed to write the manifest {}.", path.display()))
+    }
+
+    /// Adds the symlink `link`, pointing to `target`, to the managed symlinks
+    /// of the (canonical) directory `dir`.
+    pub fn add(&mut self, dir: &Path, link: &Path, target: &Path) {
+        self.dirs
+            .entry(dir.to_path_buf())
+            .or_default()
+            .insert(link.to_path_buf(), target.to_path_buf());
+    }
+
+    /// Removes the symlink `link` from the managed symlinks of the
+    /// (canonical) directory `dir`.
+    pub fn remove(&mut self, dir: &Path, link: &Path) {
+        if let Some(links) = self.dirs.get_mut(dir) {
+            links.remove(link);
+            if links.is_empty() {
+                self.dirs.remove(dir);
+            }
+        }
+    }
+
+    /// Returns an iterator over the managed symlinks of the (canonical)
+    /// directory `dir`, each one with its target.
+    pub fn iter(&self, dir: &Path) -> impl Iterator<Item = (&Path, &Path)> {
+        self.dirs
+            .get(dir)
+            .into_iter()
+            .flatten()
+            .map(|(link, target)| (link.as_path(), target.as_path()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn manifest_round_trips() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data/manifest.toml");
+
+        assert_eq!(Manifest::load(&path)?, Manifest::default());
+
+        let (dots, work) = (Path::new("/dots"), Path::new("/work"));
+        let mut manifest = Manifest::default();
+        manifest.add(dots, Path::new("/home/me/.vimrc"), Path::new("/dots/vimrc"));
+        manifest.add(
+            dots,
+            Path::new("/home/me/.bashrc"),
+            Path::new("/dots/bashrc"),
+        );
+        manifest.add(work, Path::new("/home/me/.vimrc"), Path::new("/work/vimrc"));
+        manifest.remove(dots, Path::new("/home/me/.vimrc"));
+        manifest.save(&path)?;
+
+        let loaded = Manifest::load(&path)?;
+        assert_eq!(loaded, manifest);
+        assert_eq!(
+            loaded.iter(dots).collect::<Vec<_>>(),
+            vec![(Path::new("/home/me/.bashrc"), Path::new("/dots/bashrc"))]
+        );
+        assert_eq!(
+            loaded.iter(work).collect::<Vec<_>>(),
+            vec![(Path::new("/home/me/.vimrc"), Path::new("/work/vimrc"))]
+        );
+        assert_eq!(loaded.iter(Path::new("/other")).count(), 0);
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn manifest_round_trips_paths_that_are_not_utf8() -> Result<(), Box<dyn std::error::Error>> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new()?;
+        let path = dir.path().join("manifest.toml");
+        let mut manifest = Manifest::default();
+        manifest.add(
+            Path::new("/dots"),
+            Path::new(OsStr::from_bytes(b"/home/me/\xff")),
+            Path::new("/dots/a"),
+        );
+        manifest.add(
+            Path::new("/dots"),
+            Path::new("/home/me/b"),
+            Path::new(OsStr::from_bytes(b"/dots/\xfe")),
+        );
+        manifest.save(&path)?;
+
+        assert_eq!(Manifest::load(&path)?, manifest);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! The operations making the links specified (symlinking, hard linking,
+//! copying or rendering a template), behind the crate-private `Operation`
+//! trait so that the handling of conflicts (prompts, backups, overwriting,
+//! ...) is the same whatever the operation.
+
+use crate::filesystem::Fs;
+use clap::ValueEnum;
+use std::ffi::OsString;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A way to make a link pointing to a target.
+pub(crate) trait Operation: Debug + Send + Sync {
+    /// Makes the link `link` pointing to `target`, through `fs` if the
+    /// operation supports it.
+    fn make(&self, fs: &dyn Fs, target: &Path, link: &Path) -> io::Result<()>;
+
+    /// Whether `link` already is the link pointing to `target`.
+    ///
+    /// `exact` asks for the link to point to `target` as written, not to an
+    /// equivalent path, when it makes a difference.
+    fn is_made(&self, fs: &dyn Fs, target: &Path, link: &Path, exact: bool) -> io::Result<bool>;
+
+    /// Returns the program (with its options) making the link, to which the
+    /// target and the link are given as arguments, or `None` if it can't be
+    /// made by a command.
+    fn program(&self) -> Option<&'static [&'static str]>;
+
+    /// Returns the command making the link `link` pointing to `target` (see
+    /// [`Operation::program`]), to run it with other permissions.
+    fn command(&self, target: &Path, link: &Path) -> Option<Vec<OsString>> {
+        let mut command: Vec<OsString> = self.program()?.iter().map(OsString::from).collect();
+        command.extend(["--".into(), target.into(), link.into()]);
+        Some(command)
+    }
+}
+
+/// Makes a symbolic link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SymlinkOp;
+
+impl Operation for SymlinkOp {
+    fn make(&self, fs: &dyn Fs, target: &Path, link: &Path) -> io::Result<()> {
+        fs.symlink(target, link)
+    }
+
+    /// `link` is a symlink whose target is `target`, or an equivalent path
+    /// unless `exact` (both being resolved against the directory containing
+    /// `link` if relative, then canonicalized).
+    fn is_made(&self, fs: &dyn Fs, target: &Path, link: &Path, exact: bool) -> io::Result<bool> {
+        if !fs.is_symlink(link) {
+            return Ok(false);
+        }
+        let pointed = fs.read_link(link)?;
+        if pointed == target || exact {
+            return Ok(pointed == target);
+        }
+
+        let link_dir = link.parent().unwrap_or(Path::new(""));
+        let canonical = |path: &Path| fs.canonicalize(&link_dir.join(path)).ok();
+        Ok(canonical(&pointed).is_some_and(|pointed| Some(pointed) == canonical(target)))
+    }
+
+    fn program(&self) -> Option<&'static [&'static str]> {
+        Some(&["ln", "-s"])
+    }
+}
+
+/// Makes a hard link.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct HardlinkOp;
+
+impl Operation for HardlinkOp {
+    fn make(&self, _: &dyn Fs, target: &Path, link: &Path) -> io::Result<()> {
+        fs::hard_link(target, link)
+    }
+
+    /// `link` and `target` are the same file.
+    fn is_made(&self, _: &dyn Fs, target: &Path, link: &Path, _: bool) -> io::Result<bool> {
+        if link.is_symlink() || !link.exists() {
+            return Ok(false);
+        }
+        same_file::is_same_file(link, target)
+    }
+
+    fn program(&self) -> Option<&'static [&'static str]> {
+        Some(&["ln"])
+    }
+}
+
+/// Copies the target, which is not making a link per se.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CopyOp;
+
+impl Operation for CopyOp {
+    fn make(&self, fs: &dyn Fs, target: &Path, link: &Path) -> io::Result<()> {
+        fs.copy(target, link)
+    }
+
+    /// `link` is a regular file with the same contents as `target`.
+    fn is_made(&self, _: &dyn Fs, target: &Path, link: &Path, _: bool) -> io::Result<bool> {
+        if link.is_symlink() || !link.is_file() {
+            return Ok(false);
+        }
+        Ok(fs::read(link)? == fs::read(target)?)
+    }
+
+    fn program(&self) -> Option<&'static [&'static str]> {
+        Some(&["cp"])
+    }
+}
+
+/// Writes `contents` (a rendered template, the target being the template),
+/// which is not making a link per se.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RenderOp<'a> {
+    /// The contents of the file to write.
+    pub(crate) contents: &'a str,
+}
+
+impl Operation for RenderOp<'_> {
+    fn make(&self, _: &dyn Fs, _: &Path, link: &Path) -> io::Result<()> {
+        fs::write(link, self.contents)
+    }
+
+    /// `link` is a regular file with the rendered contents.
+    fn is_made(&self, _: &dyn Fs, _: &Path, link: &Path, _: bool) -> io::Result<bool> {
+        if link.is_symlink() || !link.is_file() {
+            return Ok(false);
+        }
+        Ok(fs::read(link)? == self.contents.as_bytes())
+    }
+
+    /// A rendered template is not made by a command.
+    fn program(&self) -> Option<&'static [&'static str]> {
+        None
+    }
+}
+
+/// The operation making the links of plain specifications (see
+/// [`crate::cli::Cli::operation`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OperationKind {
+    /// Make symbolic links.
+    #[default]
+    Symlink,
+    /// Make hard links.
+    Hard,
+    /// Copy the targets instead of linking to them.
+    Copy,
+}
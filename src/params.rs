@@ -2,9 +2,18 @@
 //! are validated and united into a single data structure easy to use across the
 //! codebase.
 
-use crate::cfg::Config;
+use crate::cfg::{Config, Hooks, PolicyAction};
 use crate::cli::Cli;
-use anyhow::anyhow;
+use crate::error::Context;
+use crate::line;
+use crate::log::LogLevel;
+use crate::operation::OperationKind;
+use crate::prompt::{NoninteractiveAction, Session};
+use crate::report::ReportFormat;
+use crate::Error;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
 
 /// An aggregation of configurations coming from the CLI ([`Cli`]) and the configuration file
@@ -34,22 +43,154 @@ use std::path::PathBuf;
 ///     Ok(())
 /// }
 /// ```
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Params {
     /// Same as [`crate::cli::Cli::dir`].
     pub dir: PathBuf,
 
-    /// Same as [`crate::cli::Cli::filename`].
-    pub filename: String,
+    /// Same as [`crate::cli::Cli::filename`], as glob patterns.
+    pub filename: Vec<Pattern>,
+
+    /// Same as [`crate::cli::Cli::extension`].
+    pub extension: Vec<String>,
+
+    /// Same as [`crate::cli::Cli::exclude`], as glob patterns.
+    pub exclude: Vec<Pattern>,
+
+    /// Same as [`crate::cli::Cli::only`], as glob patterns (with a leading
+    /// `~` expanded).
+    pub only: Vec<Pattern>,
+
+    /// Same as [`crate::cli::Cli::skip`], as glob patterns (with a leading
+    /// `~` expanded).
+    pub skip: Vec<Pattern>,
+
+    /// Same as [`crate::cli::Cli::tags`].
+    pub tags: Vec<String>,
+
+    /// The variables defined by [`crate::cli::Cli::vars`], by name.
+    pub cli_vars: HashMap<String, String>,
+
+    /// Same as [`crate::cli::Cli::follow_links`].
+    pub follow_links: bool,
+
+    /// Same as [`crate::cli::Cli::sorted`].
+    pub sorted: bool,
+
+    /// Same as [`crate::cli::Cli::strict`].
+    pub strict: bool,
+
+    /// Same as [`crate::cli::Cli::jobs`].
+    pub jobs: usize,
+
+    /// Same as [`crate::cli::Cli::transactional`].
+    pub transactional: bool,
+
+    /// Same as [`crate::cli::Cli::keep_going`].
+    pub keep_going: bool,
+
+    /// Same as [`crate::cli::Cli::mkdirs`].
+    pub mkdirs: bool,
+
+    /// Same as [`crate::cli::Cli::allow_missing_targets`].
+    pub allow_missing_targets: bool,
+
+    /// Same as [`crate::cli::Cli::escalate`].
+    pub escalate: Option<String>,
+
+    /// Same as [`crate::cli::Cli::root_script`].
+    pub root_script: Option<PathBuf>,
+
+    /// Same as [`crate::cli::Cli::relative`].
+    pub relative: bool,
+    /// Same as [`crate::cli::Cli::operation`].
+    pub operation: OperationKind,
+
+    /// Same as [`crate::cli::Cli::relink`].
+    pub relink: bool,
+
+    /// Same as [`crate::cli::Cli::replace_broken`].
+    pub replace_broken: bool,
+
+    /// Same as [`crate::cli::Cli::exact_targets`].
+    pub exact_targets: bool,
+
+    /// Same as [`crate::cli::Cli::dry_run`].
+    pub dry_run: bool,
+
+    /// Same as [`crate::cli::Cli::tui`].
+    pub tui: bool,
+    /// Same as [`crate::cli::Cli::resolve_at_end`].
+    pub resolve_at_end: bool,
+    /// The answers to the prompts, given by [`crate::cli::Cli::answers`] or
+    /// read from [`crate::cli::Cli::answers_file`], if any.
+    pub answers: Option<Vec<String>>,
+    /// Same as [`crate::cli::Cli::record`].
+    pub record: Option<PathBuf>,
+    /// The session read from [`crate::cli::Cli::replay`], if any.
+    pub replay: Option<Session>,
+    /// Same as [`crate::cli::Cli::resume`].
+    pub resume: bool,
+    /// Same as [`crate::cli::Cli::changed_only`].
+    pub changed_only: bool,
+
+    /// Same as [`crate::cli::Cli::allow_dir_overwrite`].
+    pub allow_dir_overwrite: bool,
+
+    /// Same as [`crate::cli::Cli::porcelain`].
+    pub porcelain: bool,
+    /// Same as [`crate::cli::Cli::group_by_file`].
+    pub group_by_file: bool,
+
+    /// Same as [`crate::cli::Cli::report`].
+    pub report: Option<PathBuf>,
+
+    /// Same as [`crate::cli::Cli::report_format`].
+    pub report_format: ReportFormat,
+
+    /// Same as [`crate::cli::Cli::log_file`].
+    pub log_file: Option<PathBuf>,
+
+    /// Same as [`crate::cli::Cli::log_level`].
+    pub log_level: LogLevel,
+
+    /// Same as [`crate::cli::Cli::noninteractive_action`].
+    pub noninteractive_action: NoninteractiveAction,
 
     /// Same as [`crate::cli::Cli::backup_dir`].
     pub backup_dir: PathBuf,
 
+    /// Same as [`crate::cfg::Config::archive_backups`].
+    pub archive_backups: bool,
+
+    /// Same as [`crate::cfg::Config::rename_suffix`].
+    pub rename_suffix: String,
+
     /// Same as [`crate::cli::Cli::always_skip`].
     pub always_skip: bool,
 
     /// Same as [`crate::cli::Cli::always_backup`].
     pub always_backup: bool,
+
+    /// Same as [`crate::cli::Cli::always_overwrite`].
+    pub always_overwrite: bool,
+
+    /// Same as [`crate::cli::Cli::always_adopt`].
+    pub always_adopt: bool,
+
+    /// Same as [`crate::cfg::Config::vars`], overridden by [`Params::cli_vars`].
+    pub vars: HashMap<String, String>,
+
+    /// The policies of [`crate::cfg::Config::policy`], with their patterns
+    /// parsed (and a leading `~` expanded), in order.
+    pub policy: Vec<(Pattern, PolicyAction)>,
+
+    /// The patterns of [`crate::cfg::Config::protected`], parsed (and a
+    /// leading `~` expanded).
+    pub protected: Vec<Pattern>,
+
+    /// Same as [`crate::cfg::Config::hooks`].
+    pub hooks: Hooks,
 }
 
 impl Params {
@@ -57,6 +198,18 @@ impl Params {
     /// arguments/configurations, with verification of the validity of those
     /// arguments/configurations.
     ///
+    /// `cfg` is expected to be already overlaid on the system-wide
+    /// configuration file, if any (see [`Config::load_with_system`]).
+    ///
+    /// The entries of `cfg` are first overridden by the ones of the profile
+    /// selected, if any (see [`Config::with_profile`]), then by the ones of the
+    /// local configuration file of the directory to scan, if any (see
+    /// [`Config::with_local`]), then by the `MKSLS_*` environment variables set
+    /// (see [`Config::with_env`]), and finally by `cli`.
+    ///
+    /// The directory to scan is `cli.dir`, or [`Config::default_dir`] if not
+    /// given.
+    ///
     /// # Examples
     ///
     /// ```rust,no_run
@@ -74,43 +227,306 @@ impl Params {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new(cli: Cli, cfg: Config) -> anyhow::Result<Self> {
-        // backup_dir in Config should be absolute
-        if cfg.backup_dir.is_relative() {
-            return Err(anyhow!("Got a relative path for backup_dir in the configuration file, but backup_dir should be absolute."));
+    pub fn new(cli: Cli, cfg: Config) -> crate::Result<Self> {
+        let cfg = match &cli.profile {
+            Some(profile) => cfg.with_profile(profile)?,
+            None => cfg,
+        };
+        // The environment variables may set default_dir, needed to find the
+        // local configuration file, and are applied again over the latter.
+        let cfg = cfg.with_env()?;
+        let dir = match cli.dir {
+            Some(dir) => dir,
+            None => match &cfg.default_dir {
+                Some(dir) => line::expand_tilde(&dir.to_string_lossy()),
+                None => return Err(invalid("Got no directory in which to scan for files specifying symlinks: give one as argument, or set default_dir in the configuration file.")),
+            },
+        };
+        let cfg = cfg.with_local(&dir)?;
+        // Environment variables come between the configuration file and Cli
+        let cfg = cfg.with_env()?;
+
+        cfg.validate()?;
+
+        // Enforce mutual exclusivity of always_skip, always_backup, always_overwrite
+        // and always_adopt for Cli (already checked by `conflicts`).
+        assert!(
+            [
+                cli.always_skip,
+                cli.always_backup,
+                cli.always_overwrite,
+                cli.always_adopt
+            ]
+            .into_iter()
+            .filter(|b| *b)
+            .count()
+                <= 1
+        );
+
+        let filename = if cli.filename.is_empty() {
+            cfg.filename
+        } else {
+            cli.filename
+        };
+        if filename.is_empty() {
+            return Err(invalid("Got no value for filename in the configuration file, but at least one is needed to find files specifying symlinks."));
         }
+        let filename = filename
+            .iter()
+            .map(|filename| {
+                Pattern::new(filename).params_context(|| {
+                    format!("Got an invalid glob pattern for filename: {}", filename)
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let extension = if cli.extension.is_empty() {
+            cfg.extension
+        } else {
+            cli.extension
+        };
+
+        let exclude = if cli.exclude.is_empty() {
+            cfg.exclude
+        } else {
+            cli.exclude
+        };
+        let exclude = exclude
+            .iter()
+            .map(|exclude| {
+                Pattern::new(exclude).params_context(|| {
+                    format!("Got an invalid glob pattern to exclude: {}", exclude)
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let link_patterns = |patterns: &[String], option| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    let expanded = line::expand_tilde(pattern);
+                    Pattern::new(&expanded.to_string_lossy()).params_context(|| {
+                        format!("Got an invalid glob pattern for {}: {}", option, pattern)
+                    })
+                })
+                .collect::<crate::Result<Vec<_>>>()
+        };
+        let only = link_patterns(&cli.only, "only")?;
+        let skip = link_patterns(&cli.skip, "skip")?;
+
+        let cli_vars = cli
+            .vars
+            .iter()
+            .map(|var| match var.split_once('=') {
+                Some((name, value)) if line::is_var_name(name) => {
+                    Ok((String::from(name), String::from(value)))
+                }
+                _ => Err(invalid(&format!(
+                    "Got an invalid variable definition for var: {}. Expected NAME=VALUE, NAME being made of letters, digits and underscores, and not starting with a digit.",
+                    var
+                ))),
+            })
+            .collect::<crate::Result<HashMap<_, _>>>()?;
+        let mut vars = cfg.vars;
+        vars.extend(cli_vars.clone());
+
+        let follow_links = cli.follow_links || cfg.follow_links;
+
+        let sorted = cli.sorted || cfg.sorted;
+
+        let strict = cli.strict || cfg.strict;
+
+        let jobs = cli.jobs.unwrap_or(cfg.jobs);
+        if jobs == 0 {
+            return Err(invalid("Got 0 for jobs, but at least one job is needed."));
+        }
+
+        let transactional = cli.transactional || cfg.transactional;
 
-        // Enforce mutual exclusivity of always_skip and always_backup for Config
-        // (no need for Cli if `conflicts` is used)
-        assert!(!(cli.always_skip && cli.always_backup));
-        if cfg.always_skip && cfg.always_backup {
-            return Err(anyhow!("Got always_skip and always_backup set to true in the configuration file, but only one of them can be true."));
+        let keep_going = cli.keep_going || cfg.keep_going;
+        if transactional && keep_going {
+            return Err(invalid(
+                "Got both transactional and keep_going, but they can't be used together.",
+            ));
         }
 
-        let filename = cli.filename.unwrap_or(cfg.filename);
+        let mkdirs = cli.mkdirs || cfg.mkdirs;
+
+        let allow_missing_targets = cli.allow_missing_targets || cfg.allow_missing_targets;
+
+        let escalate = cli.escalate;
+        if escalate
+            .as_ref()
+            .is_some_and(|escalate| escalate.trim().is_empty())
+        {
+            return Err(invalid("Got an empty command for escalate."));
+        }
+        let root_script = cli.root_script;
+        if transactional && (escalate.is_some() || root_script.is_some()) {
+            return Err(invalid(
+                "Got transactional along with escalate or root_script, but they can't be used together.",
+            ));
+        }
+
+        let relative = cli.relative || cfg.relative;
+        let operation = cli.operation;
+
+        let relink = cli.relink || cfg.relink;
+
+        let replace_broken = cli.replace_broken || cfg.replace_broken;
+
+        let exact_targets = cli.exact_targets || cfg.exact_targets;
+
+        let dry_run = cli.dry_run;
+
+        let tui = cli.tui;
+        let resolve_at_end = cli.resolve_at_end;
+        let answers = match &cli.answers_file {
+            Some(path) => Some(
+                fs::read_to_string(path)
+                    .params_context(|| {
+                        format!("Failed to read the answers file {}.", path.display())
+                    })?
+                    .lines()
+                    .map(String::from)
+                    .collect(),
+            ),
+            None => cli.answers,
+        };
+        let record = cli.record;
+        let replay = cli.replay.as_deref().map(Session::load).transpose()?;
+        let resume = cli.resume;
+        let changed_only = cli.changed_only;
+
+        let allow_dir_overwrite = cli.allow_dir_overwrite;
+
+        let porcelain = cli.porcelain;
+        let group_by_file = cli.group_by_file;
+
+        let report = cli.report;
+
+        let report_format = cli.report_format;
+
+        let log_file = cli.log_file.or(cfg.log_file);
+
+        let log_level = cli.log_level.unwrap_or(cfg.log_level);
+
+        let noninteractive_action = cli
+            .noninteractive_action
+            .unwrap_or(cfg.noninteractive_action);
 
         let backup_dir = cli.backup_dir.unwrap_or(cfg.backup_dir);
 
+        let policy = cfg
+            .policy
+            .iter()
+            .map(|policy| {
+                let pattern = line::expand_tilde(&policy.pattern);
+                Pattern::new(&pattern.to_string_lossy())
+                    .params_context(|| {
+                        format!(
+                            "Got an invalid glob pattern for a policy in the configuration file: {}",
+                            policy.pattern
+                        )
+                    })
+                    .map(|pattern| (pattern, policy.action))
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let protected = cfg
+            .protected
+            .iter()
+            .map(|pattern| {
+                let expanded = line::expand_tilde(pattern);
+                Pattern::new(&expanded.to_string_lossy()).params_context(|| {
+                    format!(
+                        "Got an invalid glob pattern of protected paths in the configuration file: {}",
+                        pattern
+                    )
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
         let mut always_skip = cli.always_skip;
         let mut always_backup = cli.always_backup;
-        if !(always_skip || always_backup) {
+        let always_overwrite = cli.always_overwrite;
+        let mut always_adopt = cli.always_adopt;
+        if !(always_skip || always_backup || always_overwrite || always_adopt) {
             always_skip = cfg.always_skip;
             always_backup = cfg.always_backup;
+            always_adopt = cfg.always_adopt;
         }
 
         Ok(Params {
-            dir: cli.dir,
+            dir,
             filename,
+            extension,
+            exclude,
+            only,
+            skip,
+            tags: cli.tags,
+            cli_vars,
+            follow_links,
+            sorted,
+            strict,
+            jobs,
+            transactional,
+            keep_going,
+            mkdirs,
+            allow_missing_targets,
+            escalate,
+            root_script,
+            relative,
+            operation,
+            relink,
+            replace_broken,
+            exact_targets,
+            dry_run,
+            tui,
+            resolve_at_end,
+            answers,
+            record,
+            replay,
+            resume,
+            changed_only,
+            allow_dir_overwrite,
+            porcelain,
+            group_by_file,
+            report,
+            report_format,
+            log_file,
+            log_level,
+            noninteractive_action,
             backup_dir,
+            archive_backups: cfg.archive_backups,
+            rename_suffix: cfg.rename_suffix,
             always_skip,
             always_backup,
+            always_overwrite,
+            always_adopt,
+            vars,
+            policy,
+            protected,
+            hooks: cfg.hooks,
         })
     }
 }
 
+/// Returns the error telling that the parameters are invalid, because of
+/// `message`.
+fn invalid(message: &str) -> Error {
+    Error::Params {
+        message: String::from(message),
+        source: None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cfg::CONFIG_VERSION;
+    use crate::style::ColorChoice;
+    use clap::Parser;
 
     #[derive(Debug)]
     struct TestCase {
@@ -119,76 +535,462 @@ mod tests {
         params: Params,
     }
 
+    #[test]
+    fn dir_defaults_to_the_one_of_the_config() -> Result<(), Box<dyn std::error::Error>> {
+        let err = Params::new(Cli::try_parse_from(["mksls"])?, Config::default()).unwrap_err();
+        assert!(err.to_string().contains("set default_dir"));
+
+        let cfg = || Config {
+            default_dir: Some(PathBuf::from("/dots")),
+            ..Config::default()
+        };
+        let params = Params::new(Cli::try_parse_from(["mksls"])?, cfg())?;
+        assert_eq!(params.dir, PathBuf::from("/dots"));
+        let params = Params::new(Cli::try_parse_from(["mksls", "/other"])?, cfg())?;
+        assert_eq!(params.dir, PathBuf::from("/other"));
+
+        let cfg = Config {
+            default_dir: Some(PathBuf::from("dots")),
+            ..Config::default()
+        };
+        assert!(Params::new(Cli::try_parse_from(["mksls"])?, cfg).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn cli_takes_precedence_on_config() {
         let test_cases = vec![
             TestCase {
                 // Cli takes precedence
                 cli: Cli {
-                    dir: PathBuf::from("dir"),
-                    filename: Some(String::from("cli_filename")),
+                    command: None,
+                    dir: Some(PathBuf::from("dir")),
+                    filename: vec![String::from("cli_filename")],
+                    extension: vec![],
+                    exclude: vec![String::from(".git")],
+                    only: vec![],
+                    skip: vec![],
+                    tags: vec![],
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: Some(4),
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    escalate: None,
+                    root_script: None,
+                    relative: false,
+                    operation: OperationKind::Symlink,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    dry_run: false,
+                    tui: false,
+                    resolve_at_end: false,
+                    answers: None,
+                    record: None,
+                    replay: None,
+                    resume: false,
+                    changed_only: false,
+                    vars: vec![],
+                    answers_file: None,
+                    allow_dir_overwrite: false,
+                    porcelain: false,
+                    group_by_file: false,
+                    report: None,
+                    report_format: ReportFormat::Text,
+                    log_file: None,
+                    log_level: None,
+                    noninteractive_action: None,
                     backup_dir: Some(PathBuf::from("/cli/backup/dir")),
                     always_skip: false,
                     always_backup: true,
+                    always_overwrite: false,
+                    always_adopt: false,
+                    i_know_what_i_am_doing: false,
+                    config: None,
+                    no_config: false,
+                    color: ColorChoice::Auto,
+                    verbose: 0,
+                    profile: None,
                 },
                 cfg: Config {
-                    filename: String::from("cfg_filename"),
+                    filename: vec![String::from("cfg_filename")],
+                    extension: vec![],
+                    exclude: vec![String::from("node_modules")],
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: 1,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    relative: false,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    log_file: None,
+                    log_level: LogLevel::Info,
+                    noninteractive_action: NoninteractiveAction::Fail,
                     backup_dir: PathBuf::from("/cfg/backup/dir"),
+                    max_backups_per_link: None,
+                    max_age_days: None,
+                    version: CONFIG_VERSION,
+                    default_dir: None,
+                    archive_backups: false,
+                    rename_suffix: String::from(".orig"),
                     always_skip: true,
                     always_backup: false,
+                    always_adopt: false,
+                    vars: HashMap::new(),
+                    policy: vec![],
+                    protected: vec![],
+                    hooks: Hooks::default(),
+                    profiles: HashMap::new(),
                 },
                 params: Params {
                     dir: PathBuf::from("dir"),
-                    filename: String::from("cli_filename"),
+                    filename: vec![Pattern::new("cli_filename").unwrap()],
+                    extension: vec![],
+                    exclude: vec![Pattern::new(".git").unwrap()],
+                    only: vec![],
+                    skip: vec![],
+                    tags: vec![],
+                    cli_vars: HashMap::new(),
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: 4,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    escalate: None,
+                    root_script: None,
+                    relative: false,
+                    operation: OperationKind::Symlink,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    dry_run: false,
+                    tui: false,
+                    resolve_at_end: false,
+                    answers: None,
+                    record: None,
+                    replay: None,
+                    resume: false,
+                    changed_only: false,
+                    allow_dir_overwrite: false,
+                    porcelain: false,
+                    group_by_file: false,
+                    report: None,
+                    report_format: ReportFormat::Text,
+                    log_file: None,
+                    log_level: LogLevel::Info,
+                    noninteractive_action: NoninteractiveAction::Fail,
                     backup_dir: PathBuf::from("/cli/backup/dir"),
+                    archive_backups: false,
+                    rename_suffix: String::from(".orig"),
                     always_skip: false,
                     always_backup: true,
+                    always_overwrite: false,
+                    always_adopt: false,
+                    vars: HashMap::new(),
+                    policy: vec![],
+                    protected: vec![],
+                    hooks: Hooks::default(),
                 },
             },
             // When option not defined via Cli, backup to Config
             TestCase {
                 cli: Cli {
-                    dir: PathBuf::from("dir"),
-                    filename: None,
+                    command: None,
+                    dir: Some(PathBuf::from("dir")),
+                    filename: vec![],
+                    extension: vec![],
+                    exclude: vec![],
+                    only: vec![],
+                    skip: vec![],
+                    tags: vec![],
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: None,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    escalate: None,
+                    root_script: None,
+                    relative: false,
+                    operation: OperationKind::Symlink,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    dry_run: false,
+                    tui: false,
+                    resolve_at_end: false,
+                    answers: None,
+                    record: None,
+                    replay: None,
+                    resume: false,
+                    changed_only: false,
+                    vars: vec![],
+                    answers_file: None,
+                    allow_dir_overwrite: false,
+                    porcelain: false,
+                    group_by_file: false,
+                    report: None,
+                    report_format: ReportFormat::Text,
+                    log_file: None,
+                    log_level: None,
+                    noninteractive_action: None,
                     backup_dir: None,
                     always_skip: false,
                     always_backup: false,
+                    always_overwrite: false,
+                    always_adopt: false,
+                    i_know_what_i_am_doing: false,
+                    config: None,
+                    no_config: false,
+                    color: ColorChoice::Auto,
+                    verbose: 0,
+                    profile: None,
                 },
                 cfg: Config {
-                    filename: String::from("cfg_filename"),
+                    filename: vec![String::from("cfg_filename")],
+                    extension: vec![],
+                    exclude: vec![String::from("node_modules")],
+                    follow_links: true,
+                    sorted: false,
+                    strict: false,
+                    jobs: 1,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    relative: false,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    log_file: None,
+                    log_level: LogLevel::Info,
+                    noninteractive_action: NoninteractiveAction::Fail,
                     backup_dir: PathBuf::from("/cfg/backup/dir"),
+                    max_backups_per_link: None,
+                    max_age_days: None,
+                    version: CONFIG_VERSION,
+                    default_dir: None,
+                    archive_backups: false,
+                    rename_suffix: String::from(".orig"),
                     always_skip: true,
                     always_backup: false,
+                    always_adopt: false,
+                    vars: HashMap::new(),
+                    policy: vec![],
+                    protected: vec![],
+                    hooks: Hooks::default(),
+                    profiles: HashMap::new(),
                 },
                 params: Params {
                     dir: PathBuf::from("dir"),
-                    filename: String::from("cfg_filename"),
+                    filename: vec![Pattern::new("cfg_filename").unwrap()],
+                    extension: vec![],
+                    exclude: vec![Pattern::new("node_modules").unwrap()],
+                    only: vec![],
+                    skip: vec![],
+                    tags: vec![],
+                    cli_vars: HashMap::new(),
+                    follow_links: true,
+                    sorted: false,
+                    strict: false,
+                    jobs: 1,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    escalate: None,
+                    root_script: None,
+                    relative: false,
+                    operation: OperationKind::Symlink,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    dry_run: false,
+                    tui: false,
+                    resolve_at_end: false,
+                    answers: None,
+                    record: None,
+                    replay: None,
+                    resume: false,
+                    changed_only: false,
+                    allow_dir_overwrite: false,
+                    porcelain: false,
+                    group_by_file: false,
+                    report: None,
+                    report_format: ReportFormat::Text,
+                    log_file: None,
+                    log_level: LogLevel::Info,
+                    noninteractive_action: NoninteractiveAction::Fail,
                     backup_dir: PathBuf::from("/cfg/backup/dir"),
+                    archive_backups: false,
+                    rename_suffix: String::from(".orig"),
                     always_skip: true,
                     always_backup: false,
+                    always_overwrite: false,
+                    always_adopt: false,
+                    vars: HashMap::new(),
+                    policy: vec![],
+                    protected: vec![],
+                    hooks: Hooks::default(),
                 },
             },
             // A mix of options coming from Cli and others from Config
             TestCase {
                 cli: Cli {
-                    dir: PathBuf::from("dir"),
-                    filename: Some(String::from("cli_filename")),
+                    command: None,
+                    dir: Some(PathBuf::from("dir")),
+                    filename: vec![String::from("cli_filename")],
+                    extension: vec![],
+                    exclude: vec![],
+                    only: vec![],
+                    skip: vec![],
+                    tags: vec![],
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: None,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    escalate: None,
+                    root_script: None,
+                    relative: false,
+                    operation: OperationKind::Symlink,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    dry_run: false,
+                    tui: false,
+                    resolve_at_end: false,
+                    answers: None,
+                    record: None,
+                    replay: None,
+                    resume: false,
+                    changed_only: false,
+                    vars: vec![],
+                    answers_file: None,
+                    allow_dir_overwrite: false,
+                    porcelain: false,
+                    group_by_file: false,
+                    report: None,
+                    report_format: ReportFormat::Text,
+                    log_file: None,
+                    log_level: None,
+                    noninteractive_action: None,
                     backup_dir: None,
                     always_skip: false,
                     always_backup: false,
+                    always_overwrite: false,
+                    always_adopt: false,
+                    i_know_what_i_am_doing: false,
+                    config: None,
+                    no_config: false,
+                    color: ColorChoice::Auto,
+                    verbose: 0,
+                    profile: None,
                 },
                 cfg: Config {
-                    filename: String::from("cfg_filename"),
+                    filename: vec![String::from("cfg_filename")],
+                    extension: vec![],
+                    exclude: vec![],
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: 1,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    relative: false,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    log_file: None,
+                    log_level: LogLevel::Info,
+                    noninteractive_action: NoninteractiveAction::Fail,
                     backup_dir: PathBuf::from("/cfg/backup/dir"),
+                    max_backups_per_link: None,
+                    max_age_days: None,
+                    version: CONFIG_VERSION,
+                    default_dir: None,
+                    archive_backups: false,
+                    rename_suffix: String::from(".orig"),
                     always_skip: true,
                     always_backup: false,
+                    always_adopt: false,
+                    vars: HashMap::new(),
+                    policy: vec![],
+                    protected: vec![],
+                    hooks: Hooks::default(),
+                    profiles: HashMap::new(),
                 },
                 params: Params {
                     dir: PathBuf::from("dir"),
-                    filename: String::from("cli_filename"),
+                    filename: vec![Pattern::new("cli_filename").unwrap()],
+                    extension: vec![],
+                    exclude: vec![],
+                    only: vec![],
+                    skip: vec![],
+                    tags: vec![],
+                    cli_vars: HashMap::new(),
+                    follow_links: false,
+                    sorted: false,
+                    strict: false,
+                    jobs: 1,
+                    transactional: false,
+                    keep_going: false,
+                    mkdirs: false,
+                    allow_missing_targets: false,
+                    escalate: None,
+                    root_script: None,
+                    relative: false,
+                    operation: OperationKind::Symlink,
+                    relink: false,
+                    replace_broken: false,
+                    exact_targets: false,
+                    dry_run: false,
+                    tui: false,
+                    resolve_at_end: false,
+                    answers: None,
+                    record: None,
+                    replay: None,
+                    resume: false,
+                    changed_only: false,
+                    allow_dir_overwrite: false,
+                    porcelain: false,
+                    group_by_file: false,
+                    report: None,
+                    report_format: ReportFormat::Text,
+                    log_file: None,
+                    log_level: LogLevel::Info,
+                    noninteractive_action: NoninteractiveAction::Fail,
                     backup_dir: PathBuf::from("/cfg/backup/dir"),
+                    archive_backups: false,
+                    rename_suffix: String::from(".orig"),
                     always_skip: true,
                     always_backup: false,
+                    always_overwrite: false,
+                    always_adopt: false,
+                    vars: HashMap::new(),
+                    policy: vec![],
+                    protected: vec![],
+                    hooks: Hooks::default(),
                 },
             },
         ];
@@ -0,0 +1,94 @@
+//! The representation of paths in the files the app writes (e.g. the
+//! manifest), paths not having to be valid UTF-8.
+//!
+//! A path is written as a string when it is valid UTF-8, and as the array of
+//! its bytes otherwise (see [`platform::path_bytes`]). Both are read back.
+//!
+//! It is used with `#[serde(with = "crate::path_repr")]` on `PathBuf` fields,
+//! or through [`PathRepr`] where such an attribute can't be used (e.g. for the
+//! values of a map).
+
+use crate::platform;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Path, PathBuf};
+
+/// How a path is written.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    /// A path that is valid UTF-8.
+    Str(String),
+    /// The bytes of a path that is not.
+    Bytes(Vec<u8>),
+}
+
+/// Serializes `path` (see the module documentation).
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    match path.to_str() {
+        Some(path) => serializer.serialize_str(path),
+        None => platform::path_bytes(path).serialize(serializer),
+    }
+}
+
+/// Deserializes a path (see the module documentation).
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Str(path) => PathBuf::from(path),
+        Repr::Bytes(bytes) => platform::path_from_bytes(bytes),
+    })
+}
+
+/// Same as the functions of [`crate::path_repr`], for an optional path.
+pub mod option {
+    use super::PathRepr;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::path::PathBuf;
+
+    /// Serializes `path`, if any (see [`super::serialize`]).
+    pub fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path.clone().map(PathRepr).serialize(serializer)
+    }
+
+    /// Deserializes an optional path (see [`super::deserialize`]).
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<PathRepr>::deserialize(deserializer)?.map(|path| path.0))
+    }
+}
+
+/// A path, (de)serialized as told in the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PathRepr(#[serde(with = "crate::path_repr")] pub PathBuf);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Paths {
+        #[serde(with = "crate::path_repr")]
+        utf8: PathBuf,
+        #[serde(with = "crate::path_repr")]
+        other: PathBuf,
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn paths_that_are_not_utf8_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let paths = Paths {
+            utf8: PathBuf::from("/home/me/é"),
+            other: platform::path_from_bytes(b"/home/me/\xff".to_vec()),
+        };
+
+        let contents = toml::to_string(&paths)?;
+
+        assert!(contents.contains("utf8 = \"/home/me/é\""));
+        assert_eq!(toml::from_str::<Paths>(&contents)?, paths);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+//! What differs between the platforms the app runs on (Unix-like systems and
+//! Windows).
+
+use crate::utils::SpecialFile;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::process::Command;
+
+/// Makes the symlink `link` pointing to `target`.
+///
+/// On Windows, a directory symlink is made if `target` is a directory (a
+/// relative `target` being relative to the directory of `link`), a file
+/// symlink otherwise. Making symlinks requires either the developer mode or
+/// administrator privileges there.
+#[cfg(unix)]
+pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Makes the symlink `link` pointing to `target`.
+///
+/// On Windows, a directory symlink is made if `target` is a directory (a
+/// relative `target` being relative to the directory of `link`), a file
+/// symlink otherwise. Making symlinks requires either the developer mode or
+/// administrator privileges there.
+#[cfg(windows)]
+pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> io::Result<()> {
+    let (target, link) = (target.as_ref(), link.as_ref());
+    let resolved_target = link.parent().unwrap_or(Path::new("")).join(target);
+    if resolved_target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// Returns the relative path mirroring the absolute `path` within a directory
+/// (e.g. `home/me/.bashrc` for `/home/me/.bashrc`).
+///
+/// On Windows, the drive is kept (e.g. `C/Users/me/.bashrc` for
+/// `C:\Users\me\.bashrc`), so that the files of different drives are kept
+/// apart.
+pub fn mirror(path: &Path) -> PathBuf {
+    let mut mirror = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(drive) | Prefix::VerbatimDisk(drive) => {
+                    mirror.push(char::from(drive).to_string())
+                }
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    mirror.push(server);
+                    mirror.push(share);
+                }
+                Prefix::Verbatim(name) | Prefix::DeviceNS(name) => mirror.push(name),
+            },
+            Component::Normal(name) => mirror.push(name),
+            Component::RootDir | Component::CurDir | Component::ParentDir => {}
+        }
+    }
+
+    mirror
+}
+
+/// Returns the home directory of the current user, as given by the
+/// environment (`HOME`, or `USERPROFILE` on Windows).
+pub fn home_dir() -> Option<OsString> {
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME"));
+    #[cfg(not(windows))]
+    let home = std::env::var_os("HOME");
+
+    home
+}
+
+/// Returns the home directory of `user`, as found in `/etc/passwd`.
+pub fn home_of(user: &str) -> Option<OsString> {
+    passwd_entry(0, user).map(|fields| OsString::from(&fields[5]))
+}
+
+/// Returns the fields of the first entry of `/etc/passwd` whose field at index
+/// `key` (e.g. 0 for the user name, 2 for the uid) is `value`, if any.
+///
+/// Only the entries with all the fields up to the home directory (at index 5)
+/// are taken.
+fn passwd_entry(key: usize, value: &str) -> Option<Vec<String>> {
+    let passwd = fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|entry| {
+        let fields: Vec<&str> = entry.split(':').collect();
+        (fields.len() >= 6 && fields[key] == value)
+            .then(|| fields.into_iter().map(String::from).collect())
+    })
+}
+
+/// Returns the name of the current user, as given by the environment
+/// (`USER`, or `USERNAME` on Windows).
+pub fn user_name() -> Option<OsString> {
+    #[cfg(windows)]
+    let user = std::env::var_os("USERNAME");
+    #[cfg(not(windows))]
+    let user = std::env::var_os("USER");
+
+    user
+}
+
+/// Returns the directory of the system-wide configuration files: `/etc` (or
+/// `%PROGRAMDATA%` on Windows).
+pub fn system_config_dir() -> PathBuf {
+    #[cfg(windows)]
+    let dir = std::env::var_os("PROGRAMDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"));
+    #[cfg(not(windows))]
+    let dir = PathBuf::from("/etc");
+
+    dir
+}
+
+/// Returns the owner of the file whose metadata is `metadata`, as
+/// `<user name> (<uid>)`, the user name being looked up in `/etc/passwd`.
+///
+/// On Windows, ownership is not exposed by the standard library, so `None`
+/// is returned.
+#[cfg(unix)]
+pub fn owner(metadata: &fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let uid = metadata.uid();
+    let name = passwd_entry(2, &uid.to_string()).map(|fields| fields[0].clone());
+
+    Some(match name {
+        Some(name) => format!("{} ({})", name, uid),
+        None => uid.to_string(),
+    })
+}
+
+/// Returns the owner of the file whose metadata is `metadata`, as
+/// `<user name> (<uid>)`, the user name being looked up in `/etc/passwd`.
+///
+/// On Windows, ownership is not exposed by the standard library, so `None`
+/// is returned.
+#[cfg(windows)]
+pub fn owner(metadata: &fs::Metadata) -> Option<String> {
+    let _ = metadata;
+    None
+}
+
+/// Gives the file at `path` (not following a symlink) the owner and group of
+/// the file whose metadata is `metadata`.
+///
+/// Only a privileged user can give a file away, so being denied it is not an
+/// error: the file is then left as is. On Windows, ownership is not exposed
+/// by the standard library, so nothing is done.
+///
+/// # Errors
+///
+/// Fails when changing the owner fails for another reason than being denied
+/// it.
+#[cfg(unix)]
+pub fn copy_ownership(metadata: &fs::Metadata, path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    match std::os::unix::fs::lchown(path, Some(metadata.uid()), Some(metadata.gid())) {
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+        result => result,
+    }
+}
+
+/// Gives the file at `path` (not following a symlink) the owner and group of
+/// the file whose metadata is `metadata`.
+///
+/// Only a privileged user can give a file away, so being denied it is not an
+/// error: the file is then left as is. On Windows, ownership is not exposed
+/// by the standard library, so nothing is done.
+///
+/// # Errors
+///
+/// Fails when changing the owner fails for another reason than being denied
+/// it.
+#[cfg(windows)]
+pub fn copy_ownership(metadata: &fs::Metadata, path: &Path) -> io::Result<()> {
+    let _ = (metadata, path);
+    Ok(())
+}
+
+/// Returns the type of a special file (neither a regular file, a directory
+/// nor a symlink) whose type is `file_type`.
+///
+/// On Windows, special files are not exposed by the standard library, so
+/// [`SpecialFile::Other`] is returned.
+#[cfg(unix)]
+pub fn special_file(file_type: &fs::FileType) -> SpecialFile {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        SpecialFile::Fifo
+    } else if file_type.is_socket() {
+        SpecialFile::Socket
+    } else if file_type.is_block_device() {
+        SpecialFile::BlockDevice
+    } else if file_type.is_char_device() {
+        SpecialFile::CharDevice
+    } else {
+        SpecialFile::Other
+    }
+}
+
+/// Returns the type of a special file (neither a regular file, a directory
+/// nor a symlink) whose type is `file_type`.
+///
+/// On Windows, special files are not exposed by the standard library, so
+/// [`SpecialFile::Other`] is returned.
+#[cfg(windows)]
+pub fn special_file(file_type: &fs::FileType) -> SpecialFile {
+    let _ = file_type;
+    SpecialFile::Other
+}
+
+/// Whether the program `name` can be run, i.e. is an executable file in one
+/// of the directories of `PATH`, or at `name` if it is a path.
+pub fn is_command(name: &str) -> bool {
+    let path = Path::new(name);
+    if path.components().count() > 1 {
+        return is_executable(path);
+    }
+
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(name)))
+    })
+}
+
+/// Whether `path` is a file with one of its execute permissions set.
+///
+/// On Windows, it is a file, with one of the extensions of `PATHEXT` (e.g.
+/// `.exe`) if it has none.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Whether `path` is a file with one of its execute permissions set.
+///
+/// On Windows, it is a file, with one of the extensions of `PATHEXT` (e.g.
+/// `.exe`) if it has none.
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    if path.extension().is_some() {
+        return path.is_file();
+    }
+
+    let extensions =
+        std::env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+    extensions
+        .split(';')
+        .any(|ext| path.with_extension(ext.trim_start_matches('.')).is_file())
+}
+
+/// Returns the command running `command` with the shell of the platform:
+/// `sh -c` (or `cmd /C` on Windows).
+pub fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+
+    let mut shell = Command::new(shell);
+    shell.arg(flag).arg(command);
+    shell
+}
+
+/// Returns the bytes of `path`, as given by the platform: the bytes of the
+/// path on Unix-like systems, its UTF-16 code units (little-endian) on
+/// Windows.
+///
+/// Unlike a string, it can represent any path, even one that is not valid
+/// Unicode. It is turned back into the path with [`path_from_bytes`].
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Returns the bytes of `path`, as given by the platform: the bytes of the
+/// path on Unix-like systems, its UTF-16 code units (little-endian) on
+/// Windows.
+///
+/// Unlike a string, it can represent any path, even one that is not valid
+/// Unicode. It is turned back into the path with [`path_from_bytes`].
+#[cfg(windows)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+
+    path.as_os_str()
+        .encode_wide()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Returns the path whose bytes are `bytes` (see [`path_bytes`]).
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+/// Returns the path whose bytes are `bytes` (see [`path_bytes`]).
+#[cfg(windows)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::windows::ffi::OsStringExt;
+
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|unit| u16::from_le_bytes([unit[0], *unit.get(1).unwrap_or(&0)]))
+        .collect();
+    PathBuf::from(OsString::from_wide(&units))
+}
@@ -0,0 +1,314 @@
+//! The progress of a run, kept as it goes so that the run can be resumed if
+//! interrupted (see [`crate::cli::Cli::resume`]).
+
+use crate::error::Context;
+use crate::utils;
+use crate::Error;
+use clap::crate_name;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The lines of the symlink-specification files processed by a run.
+///
+/// The lines of a file being processed in order, only the last one processed
+/// is kept for each file, along with the hash of its contents, so that the
+/// progress of a file edited since is dropped. The links selected by the run (see
+/// [`crate::cli::Cli::only`] and [`crate::cli::Cli::tags`]) are kept as well,
+/// as the lines processed by a run making only some links are not processed
+/// for the other ones.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::progress::Progress;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = Progress::default_path()?;
+/// let mut progress = Progress::new(Path::new("/home/me/dotfiles"), &[], &[]);
+/// progress.start(Path::new("/home/me/dotfiles/sls"), "d2a84f4b8b650937ec8f73cd8be2c74a");
+/// progress.done(Path::new("/home/me/dotfiles/sls"), 3);
+/// progress.save(&path)?;
+///
+/// if let Some(progress) = Progress::load(&path)? {
+///     assert!(progress.is_done(Path::new("/home/me/dotfiles/sls"), 2));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Stored", into = "Stored")]
+pub struct Progress {
+    /// The directory scanned by the run.
+    dir: PathBuf,
+    /// The patterns of the only links made by the run, if any.
+    only: Vec<String>,
+    /// The tags of the only lines processed by the run, if any.
+    tags: Vec<String>,
+    /// The progress of each (canonical) symlink-specification file.
+    files: BTreeMap<PathBuf, File>,
+}
+
+/// The progress of a symlink-specification file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct File {
+    /// The hash of the contents of the file (see [`crate::utils::hash_file`]).
+    hash: String,
+    /// The last line processed.
+    line: u64,
+}
+
+/// How [`Progress`] is written.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Stored {
+    #[serde(with = "crate::path_repr")]
+    dir: PathBuf,
+    only: Vec<String>,
+    tags: Vec<String>,
+    files: Vec<StoredFile>,
+}
+
+/// A file in [`Stored::files`].
+#[derive(Serialize, Deserialize)]
+struct StoredFile {
+    #[serde(with = "crate::path_repr")]
+    sls: PathBuf,
+    #[serde(default)]
+    hash: String,
+    line: u64,
+}
+
+impl From<Stored> for Progress {
+    fn from(stored: Stored) -> Self {
+        let files = stored
+            .files
+            .into_iter()
+            .map(|stored| {
+                let file = File {
+                    hash: stored.hash,
+                    line: stored.line,
+                };
+                (stored.sls, file)
+            })
+            .collect();
+
+        Progress {
+            dir: stored.dir,
+            only: stored.only,
+            tags: stored.tags,
+            files,
+        }
+    }
+}
+
+impl From<Progress> for Stored {
+    fn from(progress: Progress) -> Self {
+        let files = progress
+            .files
+            .into_iter()
+            .map(|(sls, file)| StoredFile {
+                sls,
+                hash: file.hash,
+                line: file.line,
+            })
+            .collect();
+
+        Stored {
+            dir: progress.dir,
+            only: progress.only,
+            tags: progress.tags,
+            files,
+        }
+    }
+}
+
+impl Progress {
+    /// Returns the progress of a run scanning `dir`, making only the links
+    /// matching one of the patterns of `only` and processing only the lines
+    /// tagged with one of `tags` (all of them if empty), which has processed
+    /// nothing yet.
+    pub fn new(dir: &Path, only: &[String], tags: &[String]) -> Self {
+        Progress {
+            dir: dir.to_path_buf(),
+            only: only.to_vec(),
+            tags: tags.to_vec(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the path of the progress in the data directory of the app.
+    ///
+    /// # Errors
+    ///
+    /// Fails when no home directory is found for the current user.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn default_path() -> crate::Result<PathBuf> {
+        let project = ProjectDirs::from("rs", "", crate_name!()).ok_or_else(|| Error::Io {
+            message: String::from(
+                "Failed to find the data directory where to keep the progress of the run.",
+            ),
+            source: io::ErrorKind::NotFound.into(),
+        })?;
+
+        Ok(project.data_dir().join("progress.toml"))
+    }
+
+    /// Loads the progress at `path`, or `None` if there is no file at `path`
+    /// (i.e. no run has been interrupted).
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file at `path` can't be read or is not valid.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn load(path: &Path) -> crate::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read the progress of the run {}.", path.display()))?;
+        toml::from_str(&contents).map(Some).parse_context(|| {
+            format!(
+                "Failed to parse the progress of the run {}.",
+                path.display()
+            )
+        })
+    }
+
+    /// Saves the progress at `path`, creating the parent directories if
+    /// needed.
+    ///
+    /// The file is replaced at once, so that an interrupted run never leaves
+    /// it half-written.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file can't be written.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).io_context(|| {
+                format!(
+                    "Failed to create directory {} to save the progress of the run in it.",
+                    parent.display()
+                )
+            })?;
+        }
+        let contents = toml::to_string(self).parse_context(|| {
+            format!(
+                "Failed to serialize the progress of the run to save it at {}.",
+                path.display()
+            )
+        })?;
+        utils::write_atomic(path, contents).io_context(|| {
+            format!(
+                "Failed to write the progress of the run {}.",
+                path.display()
+            )
+        })
+    }
+
+    /// Removes the progress at `path`, once the run has ended, if there is
+    /// one.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file exists but can't be removed.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn remove(path: &Path) -> crate::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err).io_context(|| {
+                format!(
+                    "Failed to remove the progress of the run {}.",
+                    path.display()
+                )
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `other` is the progress of the same run, i.e. one scanning the
+    /// same directory and selecting the same links, whatever the lines
+    /// processed.
+    pub fn is_of_same_run(&self, other: &Progress) -> bool {
+        (&self.dir, &self.only, &self.tags) == (&other.dir, &other.only, &other.tags)
+    }
+
+    /// Records that `sls`, whose contents hash to `hash`, is being processed,
+    /// forgetting the lines processed of it if its contents have changed
+    /// since.
+    pub fn start(&mut self, sls: &Path, hash: &str) {
+        let file = self.files.entry(sls.to_path_buf()).or_default();
+        if file.hash != hash {
+            *file = File {
+                hash: hash.to_string(),
+                line: 0,
+            };
+        }
+    }
+
+    /// Records that the line `line_no` of `sls`, and so the ones before it,
+    /// have been processed.
+    pub fn done(&mut self, sls: &Path, line_no: u64) {
+        let file = self.files.entry(sls.to_path_buf()).or_default();
+        file.line = line_no.max(file.line);
+    }
+
+    /// Whether the line `line_no` of `sls` has been processed.
+    pub fn is_done(&self, sls: &Path, line_no: u64) -> bool {
+        self.files.get(sls).is_some_and(|file| line_no <= file.line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn progress_round_trips() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data/progress.toml");
+
+        assert_eq!(Progress::load(&path)?, None);
+
+        let mut progress = Progress::new(Path::new("/dots"), &[], &[String::from("work")]);
+        progress.start(Path::new("/dots/sls"), "abc");
+        progress.done(Path::new("/dots/sls"), 3);
+        progress.done(Path::new("/dots/sls"), 2);
+        progress.save(&path)?;
+
+        let loaded = Progress::load(&path)?.unwrap();
+        assert_eq!(loaded, progress);
+        assert!(loaded.is_of_same_run(&Progress::new(
+            Path::new("/dots"),
+            &[],
+            &[String::from("work")]
+        )));
+        assert!(!loaded.is_of_same_run(&Progress::new(Path::new("/dots"), &[], &[])));
+        assert!(loaded.is_done(Path::new("/dots/sls"), 3));
+        assert!(!loaded.is_done(Path::new("/dots/sls"), 4));
+        assert!(!loaded.is_done(Path::new("/dots/other/sls"), 1));
+
+        let mut resumed = loaded.clone();
+        resumed.start(Path::new("/dots/sls"), "abc");
+        assert!(resumed.is_done(Path::new("/dots/sls"), 3));
+        resumed.start(Path::new("/dots/sls"), "edited");
+        assert!(!resumed.is_done(Path::new("/dots/sls"), 1));
+
+        Progress::remove(&path)?;
+        Progress::remove(&path)?;
+        assert_eq!(Progress::load(&path)?, None);
+
+        Ok(())
+    }
+}
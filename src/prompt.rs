@@ -1,10 +1,22 @@
 //! Utilities for prompting the user in the terminal.
 
+use crate::error::Context;
+use crate::line;
+use crate::platform;
+use crate::style::Paint;
+use crate::utils;
 use crate::utils::trim_newline;
-use anyhow::Context;
-use crossterm::style::Stylize;
+use crate::utils::{special_file, SpecialFile};
+use crate::Error;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 const INDENT: &str = "    ";
 const ACTION_HELP: &str = "[s]kip : Don't create the symlink and move on to the next one.
@@ -12,13 +24,112 @@ const ACTION_HELP: &str = "[s]kip : Don't create the symlink and move on to the
 [b]ackup : Move the existing file in BACKUP_DIR, then make the current symlink.
 [B]ackup all : [b]ackup for the current symlink and all further symlink conflicting with an existing file.
 [o]verwrite : Overwrite the existing file with the symlink (beware data loss!)
-[O]verwrite all : [o]verwrite for the current symlink and all further symlink conflicting with an existing file.";
+[O]verwrite all : [o]verwrite for the current symlink and all further symlink conflicting with an existing file.
+re[n]ame : Rename the existing file in place, appending rename_suffix (.orig by default) to its name, then make the current symlink.
+re[N]ame all : re[n]ame for the current symlink and all further symlink conflicting with an existing file.
+[a]dopt : Move the existing file to the target path (replacing the target), then make the current symlink.
+[A]dopt all : [a]dopt for the current symlink and all further symlink conflicting with an existing file.
+[fs] [fb] [fo] [fn] [fa] : [s]kip, [b]ackup, [o]verwrite, re[n]ame or [a]dopt for the current symlink and all further symlink conflicting with an existing file in the current file specifying symlinks.
+[q]uit : Don't create the symlink, and stop processing the symlinks specified (the run ends with exit status 4).
+[d]iff : Show the differences between the existing file and the target (only when both are text files).
+[v]iew : Show the type, size, modification time and owner of the existing file.";
+const STALE_SYMLINK_HELP: &str = "[r]elink : Replace the existing symlink by the current one.
+[R]elink all : [r]elink for the current symlink and all further symlink conflicting with an existing symlink.
+[s]kip : Don't create the symlink and move on to the next one.
+[S]kip all : [s]kip for the current symlink and all further symlink conflicting with an existing file.
+[b]ackup : Move the existing symlink in BACKUP_DIR, then make the current symlink.
+[B]ackup all : [b]ackup for the current symlink and all further symlink conflicting with an existing file.
+[fr] [fs] [fb] : [r]elink, [s]kip or [b]ackup for the current symlink and all further symlink conflicting with an existing file in the current file specifying symlinks.
+[q]uit : Don't create the symlink, and stop processing the symlinks specified (the run ends with exit status 4).
+[v]iew : Show the type, size, modification time and owner of the existing symlink.";
+const BROKEN_SYMLINK_HELP: &str = "[r]emove : Remove the broken symlink.
+[R]emove all : [r]emove for the current broken symlink and all further ones.
+[k]eep : Keep the broken symlink and move on to the next one.
+[K]eep all : [k]eep for the current broken symlink and all further ones.";
+
+/// Where the answers to the prompts come from.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{self, Input};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let input = Input::answers(["n"].map(String::from));
+/// assert!(!prompt::adopt_prompt(&input, "/.../target", "/.../link")?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub enum Input {
+    /// The lines entered in stdin.
+    #[default]
+    Stdin,
+    /// Predetermined answers, used in order (see [`crate::cli::Cli::answers`]).
+    Answers(Mutex<VecDeque<String>>),
+}
+
+impl Input {
+    /// Returns the input made of the predetermined `answers`, used in order.
+    pub fn answers(answers: impl IntoIterator<Item = String>) -> Self {
+        Input::Answers(Mutex::new(answers.into_iter().collect()))
+    }
+
+    /// Reads the next answer: a line of stdin (without its newline), or the
+    /// next predetermined answer, printed as if it had been entered.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Reading stdin fails, or it has no line left.
+    /// - There is no predetermined answer left.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn read_line(&self) -> crate::Result<String> {
+        match self {
+            Input::Stdin => read_answer(&mut io::stdin().lock()),
+            Input::Answers(answers) => {
+                let input = answers
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .ok_or_else(|| Error::Io {
+                        message: String::from("No answer left for the prompt (see --answers)."),
+                        source: io::ErrorKind::UnexpectedEof.into(),
+                    })?;
+                println!("{}", input);
+
+                Ok(input)
+            }
+        }
+    }
+
+    /// Whether the answers are the ones entered by the user.
+    fn is_interactive(&self) -> bool {
+        matches!(self, Input::Stdin)
+    }
+}
 
-fn get_stdin_line_input() -> anyhow::Result<String> {
+/// Reads an answer from `reader`, i.e. its next line without the newline.
+///
+/// # Errors
+///
+/// Fails when reading fails, or when `reader` has no line left (instead of
+/// prompting again and again).
+///
+/// See [`crate::Error`] for the kinds of errors.
+fn read_answer(reader: &mut impl BufRead) -> crate::Result<String> {
     let mut input = String::new();
-    io::stdin()
+    let read = reader
         .read_line(&mut input)
-        .with_context(|| "Error reading stdin input.")?;
+        .io_context(|| "Error reading stdin input.")?;
+    if read == 0 {
+        return Err(Error::Io {
+            message: String::from("No answer left in stdin."),
+            source: io::ErrorKind::UnexpectedEof.into(),
+        });
+    }
     // Need this because the newline of Enter is included in the input
     trim_newline(&mut input);
 
@@ -32,37 +143,54 @@ trait PromptOptions {
     fn get_valid_inputs() -> Vec<String>;
 }
 
+/// Returns the input choosing `option`.
+fn input_of<PO: PromptOptions + PartialEq>(option: &PO) -> String {
+    PO::get_valid_inputs()
+        .into_iter()
+        .find(|input| PO::match_input(input).as_ref() == Some(option))
+        .unwrap_or_default()
+}
+
+/// Returns the input answering `confirmed` to a confirmation.
+fn confirm_input(confirmed: &bool) -> String {
+    String::from(if *confirmed { "y" } else { "n" })
+}
+
+/// Prompts with `mess` until the input, read from `input`, matches one of the
+/// options `PO`.
+///
+/// `infos` are the inputs (along with what returns their message) showing a
+/// message before prompting again, like `h` showing the help. A message is
+/// only made when asked for, as some need reading files.
 fn prompt_option<PO: PromptOptions>(
+    input: &Input,
     mess: &str,
-    help_input: Option<&str>,
-    help_mess: Option<&str>,
-) -> anyhow::Result<PO> {
-    let has_help = help_input.is_some() && help_mess.is_some();
-    let help_input = help_input.unwrap_or("");
-    let help_mess = help_mess.unwrap_or("");
-
+    infos: &[(&str, &dyn Fn() -> String)],
+) -> crate::Result<PO> {
+    // Hold stdout during the whole prompt, so that what other threads print
+    // doesn't get in the middle of it.
+    let _stdout = io::stdout().lock();
     loop {
         print!("{}", mess);
         io::stdout().flush()?;
-        let input = get_stdin_line_input()?;
+        let input = input.read_line()?;
 
         if let Some(opt) = PO::match_input(&input) {
             return Ok(opt);
-        } else if has_help && input == help_input {
+        } else if let Some((_, info_mess)) = infos.iter().find(|(info, _)| *info == input) {
             println!("{INDENT}----------");
-            for line in help_mess.lines() {
+            for line in info_mess().lines() {
                 println!("{INDENT}{}", line);
             }
             println!("{INDENT}----------");
         } else {
-            let mut help_key = String::from("");
-            if has_help {
-                help_key = format!(", {}", help_input);
-            }
+            let valid_inputs: Vec<String> = PO::get_valid_inputs()
+                .into_iter()
+                .chain(infos.iter().map(|(info, _)| info.to_string()))
+                .collect();
             println!(
-                "{INDENT}Wrong input! Valid inputs are: {}{}. Try again.",
-                PO::get_valid_inputs().join(", "),
-                help_key,
+                "{INDENT}Wrong input! Valid inputs are: {}. Try again.",
+                valid_inputs.join(", "),
             );
         }
     }
@@ -88,6 +216,7 @@ impl PromptOptions for ErrorPromptOptions {
 ///
 /// # Parameters
 ///
+/// - `input`: Where the answer comes from.
 /// - `err_mess`: The error message to show the user.
 ///
 /// # Errors
@@ -97,26 +226,27 @@ impl PromptOptions for ErrorPromptOptions {
 /// # Examples
 ///
 /// ```rust,no_run
-/// use mksls::prompt;
+/// use mksls::prompt::{self, Input};
 ///
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-/// prompt::error_prompt("The error message...")?;
+/// prompt::error_prompt(&Input::Stdin, "The error message...")?;
 /// # Ok(())
 /// # }
 /// ```
-pub fn error_prompt(err_mess: &str) -> anyhow::Result<()> {
+pub fn error_prompt(input: &Input, err_mess: &str) -> crate::Result<()> {
     let prompt_mess = format!(
         "(?) {}\n{}Enter a key to continue: ",
         err_mess.red(),
         INDENT
     );
-    let _ = prompt_option::<ErrorPromptOptions>(&prompt_mess, None, None)?;
+    let _ = prompt_option::<ErrorPromptOptions>(input, &prompt_mess, &[])?;
 
     Ok(())
 }
 
 /// Options the user can choose when confronted to a conflict that prevents
 /// the creation of a symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AlreadyExistPromptOptions {
     /// Don't create the symlink and move on to the next one.
     Skip,
@@ -130,6 +260,32 @@ pub enum AlreadyExistPromptOptions {
     Overwrite,
     /// Overwrite for the current symlink and all further symlink conflicting with an existing file.
     AlwaysOverwrite,
+    /// Rename the existing file in place, appending a suffix to its name, then make the current
+    /// symlink.
+    Rename,
+    /// Rename for the current symlink and all further symlink conflicting with an existing file.
+    AlwaysRename,
+    /// Move the existing file to the target path (replacing the target), then make the current symlink.
+    Adopt,
+    /// Adopt for the current symlink and all further symlink conflicting with an existing file.
+    AlwaysAdopt,
+    /// Skip for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileSkip,
+    /// Backup for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileBackup,
+    /// Overwrite for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileOverwrite,
+    /// Rename for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileRename,
+    /// Adopt for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileAdopt,
+    /// Don't create the symlink, and stop processing the symlinks specified.
+    Quit,
 }
 
 impl PromptOptions for AlreadyExistPromptOptions {
@@ -141,6 +297,16 @@ impl PromptOptions for AlreadyExistPromptOptions {
             "B" => Some(AlreadyExistPromptOptions::AlwaysBackup),
             "o" => Some(AlreadyExistPromptOptions::Overwrite),
             "O" => Some(AlreadyExistPromptOptions::AlwaysOverwrite),
+            "n" => Some(AlreadyExistPromptOptions::Rename),
+            "N" => Some(AlreadyExistPromptOptions::AlwaysRename),
+            "a" => Some(AlreadyExistPromptOptions::Adopt),
+            "A" => Some(AlreadyExistPromptOptions::AlwaysAdopt),
+            "fs" => Some(AlreadyExistPromptOptions::FileSkip),
+            "fb" => Some(AlreadyExistPromptOptions::FileBackup),
+            "fo" => Some(AlreadyExistPromptOptions::FileOverwrite),
+            "fn" => Some(AlreadyExistPromptOptions::FileRename),
+            "fa" => Some(AlreadyExistPromptOptions::FileAdopt),
+            "q" => Some(AlreadyExistPromptOptions::Quit),
             _ => None,
         }
     }
@@ -153,17 +319,93 @@ impl PromptOptions for AlreadyExistPromptOptions {
             String::from("B"),
             String::from("o"),
             String::from("O"),
+            String::from("n"),
+            String::from("N"),
+            String::from("a"),
+            String::from("A"),
+            String::from("fs"),
+            String::from("fb"),
+            String::from("fo"),
+            String::from("fn"),
+            String::from("fa"),
+            String::from("q"),
         ]
     }
 }
 
+/// The size up to which the first line of an existing regular file is read to
+/// be shown in the conflict prompt (see [`ExistingFile::first_line`]).
+const SMALL_FILE_SIZE: u64 = 64 * 1024;
+
+/// The number of characters of the first line shown in the conflict prompt,
+/// beyond which it is truncated.
+const FIRST_LINE_WIDTH: usize = 60;
+
+/// What is known of the file already existing at a link path, shown inline in
+/// the conflict prompt (see [`already_exist_prompt`]) so that the user can
+/// usually decide without looking at the file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct ExistingFile {
+    /// The type of the file, if it is a special file.
+    pub special: Option<SpecialFile>,
+    /// The size of the file in bytes, if it is a regular file.
+    pub size: Option<u64>,
+    /// When the file was last modified, if known.
+    pub modified: Option<chrono::DateTime<chrono::Local>>,
+    /// The first non-empty line of the file (truncated if too long), if it is a
+    /// small regular text file.
+    pub first_line: Option<String>,
+}
+
+impl ExistingFile {
+    /// Reads what is known of the file at `path`, without following a
+    /// symlink, leaving out what can't be read.
+    pub(crate) fn of(path: &Path) -> Self {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return Self::default();
+        };
+
+        let is_file = metadata.is_file();
+        let first_line = (is_file && metadata.len() <= SMALL_FILE_SIZE)
+            .then(|| fs::read_to_string(path).ok())
+            .flatten()
+            .and_then(|contents| {
+                let line = contents
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())?;
+                Some(match line.char_indices().nth(FIRST_LINE_WIDTH) {
+                    Some((end, _)) => format!("{}...", &line[..end]),
+                    None => line.to_string(),
+                })
+            });
+
+        ExistingFile {
+            special: special_file(path),
+            size: is_file.then_some(metadata.len()),
+            modified: metadata.modified().ok().map(chrono::DateTime::from),
+            first_line,
+        }
+    }
+}
+
 /// Prompts the user to choose one of the [`AlreadyExistPromptOptions`] when
 /// faced with a conflict preventing the creation of the desired symlink.
 ///
+/// The size, modification time and first line of the existing file (see
+/// [`ExistingFile`]) are shown inline, when known, and a special file (e.g. a
+/// FIFO) is told as such. If it is a regular file, a `[d]iff` input shows its
+/// differences with the target before prompting again. A `[v]iew` input shows
+/// what the existing file is (see [`crate::utils::describe`]). Both are only
+/// read when asked for.
+///
 /// # Parameters
 ///
+/// - `input`: Where the answer comes from.
 /// - `target_path_str`: A string representation of the target's path.
 /// - `link_path_str`: A string representation of the link's path.
+/// - `diff`: Returns the differences between the existing file and the
+///   target, if both are regular text files.
 ///
 /// # Returns
 ///
@@ -173,28 +415,1122 @@ impl PromptOptions for AlreadyExistPromptOptions {
 /// # Examples
 ///
 /// ```rust,no_run
-/// use mksls::prompt;
+/// use mksls::prompt::{self, Input};
 ///
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-/// prompt::already_exist_prompt("/.../target", "/.../link")?;
+/// prompt::already_exist_prompt(&Input::Stdin, "/.../target", "/.../link", &|| None)?;
 /// # Ok(())
 /// # }
 /// ```
 pub fn already_exist_prompt(
+    input: &Input,
+    target_path_str: &str,
+    link_path_str: &str,
+    diff: &dyn Fn() -> Option<String>,
+) -> crate::Result<AlreadyExistPromptOptions> {
+    let link = Path::new(link_path_str);
+    let existing = ExistingFile::of(link);
+    let what = match existing.special {
+        Some(special) => format!("A {} (special file)", special),
+        None => String::from("A file"),
+    };
+    let mut details = vec![];
+    if let Some(size) = existing.size {
+        details.push(format!("{} bytes", size));
+    }
+    if let Some(modified) = existing.modified {
+        details.push(format!(
+            "modified {}",
+            modified.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+        ));
+    }
+    let details = if details.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", details.join(", "))
+    };
+    let first_line = match &existing.first_line {
+        Some(line) => format!("{}First line: {}\n", INDENT, line.as_str().dark_grey()),
+        None => String::new(),
+    };
+    let comparable = existing.size.is_some();
+    let prompt_mess = format!(
+        "(?) {} -> {}
+{}{} already exists at link path{}.
+{}{}[s]kip [S]kip all [b]ackup [B]ackup all [o]verwrite [O]verwrite all re[n]ame re[N]ame all [a]dopt [A]dopt all [fs/fb/fo/fn/fa] for the file [q]uit {}[v]iew [h]elp: ",
+        link_path_str.red(),
+        target_path_str,
+        INDENT,
+        what,
+        details,
+        first_line,
+        INDENT,
+        if comparable { "[d]iff " } else { "" }
+    );
+    let help = || ACTION_HELP.to_string();
+    let description = || utils::describe(link);
+    let diff = || {
+        diff().unwrap_or_else(|| {
+            String::from("No differences to show, the file or the target not being text.")
+        })
+    };
+    let mut infos: Vec<(&str, &dyn Fn() -> String)> = vec![("h", &help), ("v", &description)];
+    if comparable {
+        infos.push(("d", &diff));
+    }
+    let option = prompt_option::<AlreadyExistPromptOptions>(input, &prompt_mess, &infos)?;
+
+    Ok(option)
+}
+
+/// Options the user can choose when the file preventing the creation of a
+/// symlink is itself a symlink, pointing somewhere else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleSymlinkPromptOptions {
+    /// Replace the existing symlink by the current one.
+    Relink,
+    /// Relink for the current symlink and all further symlink conflicting with an existing symlink.
+    AlwaysRelink,
+    /// Don't create the symlink and move on to the next one.
+    Skip,
+    /// Skip for the current symlink and all further symlink conflicting with an existing file.
+    AlwaysSkip,
+    /// Move the existing symlink in BACKUP_DIR, then make the current symlink.
+    Backup,
+    /// Backup for the current symlink and all further symlink conflicting with an existing file.
+    AlwaysBackup,
+    /// Relink for the current symlink and all further symlink conflicting with an existing symlink
+    /// in the current symlink-specification file.
+    FileRelink,
+    /// Skip for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileSkip,
+    /// Backup for the current symlink and all further symlink conflicting with an existing file
+    /// in the current symlink-specification file.
+    FileBackup,
+    /// Don't create the symlink, and stop processing the symlinks specified.
+    Quit,
+}
+
+impl PromptOptions for StaleSymlinkPromptOptions {
+    fn match_input(input: &str) -> Option<Self> {
+        match input {
+            "r" => Some(StaleSymlinkPromptOptions::Relink),
+            "R" => Some(StaleSymlinkPromptOptions::AlwaysRelink),
+            "s" => Some(StaleSymlinkPromptOptions::Skip),
+            "S" => Some(StaleSymlinkPromptOptions::AlwaysSkip),
+            "b" => Some(StaleSymlinkPromptOptions::Backup),
+            "B" => Some(StaleSymlinkPromptOptions::AlwaysBackup),
+            "fr" => Some(StaleSymlinkPromptOptions::FileRelink),
+            "fs" => Some(StaleSymlinkPromptOptions::FileSkip),
+            "fb" => Some(StaleSymlinkPromptOptions::FileBackup),
+            "q" => Some(StaleSymlinkPromptOptions::Quit),
+            _ => None,
+        }
+    }
+
+    fn get_valid_inputs() -> Vec<String> {
+        vec![
+            String::from("r"),
+            String::from("R"),
+            String::from("s"),
+            String::from("S"),
+            String::from("b"),
+            String::from("B"),
+            String::from("fr"),
+            String::from("fs"),
+            String::from("fb"),
+            String::from("q"),
+        ]
+    }
+}
+
+/// Prompts the user to choose one of the [`StaleSymlinkPromptOptions`] when
+/// a symlink pointing somewhere else prevents the creation of the desired symlink.
+///
+/// A `[v]iew` input shows what the existing symlink is (see
+/// [`crate::utils::describe`]).
+///
+/// # Parameters
+///
+/// - `input`: Where the answer comes from.
+/// - `target_path_str`: A string representation of the target's path.
+/// - `link_path_str`: A string representation of the link's path.
+/// - `current_target_path_str`: A string representation of the path the
+///   existing symlink points to.
+///
+/// # Returns
+///
+/// The option chosen by the user, or an error if reading/writing from/to
+/// stdin/stdout failed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{self, Input};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// prompt::stale_symlink_prompt(&Input::Stdin, "/.../target", "/.../link", "/.../other_target")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn stale_symlink_prompt(
+    input: &Input,
     target_path_str: &str,
     link_path_str: &str,
-) -> anyhow::Result<AlreadyExistPromptOptions> {
+    current_target_path_str: &str,
+) -> crate::Result<StaleSymlinkPromptOptions> {
     let prompt_mess = format!(
         "(?) {} -> {}
-{}A file already exists at link path.
-{}[s]kip [S]kip all [b]ackup [B]ackup all [o]verwrite [O]verwrite all [h]elp: ",
+{}A symlink already exists at link path, pointing to {}.
+{}[r]elink [R]elink all [s]kip [S]kip all [b]ackup [B]ackup all [fr/fs/fb] for the file [q]uit [v]iew [h]elp: ",
         link_path_str.red(),
         target_path_str,
         INDENT,
+        current_target_path_str,
         INDENT
     );
-    let input =
-        prompt_option::<AlreadyExistPromptOptions>(&prompt_mess, Some("h"), Some(ACTION_HELP))?;
+    let option = prompt_option::<StaleSymlinkPromptOptions>(
+        input,
+        &prompt_mess,
+        &[
+            ("h", &|| STALE_SYMLINK_HELP.to_string()),
+            ("v", &|| utils::describe(Path::new(link_path_str))),
+        ],
+    )?;
 
-    Ok(input)
+    Ok(option)
+}
+
+/// Options the user can choose when asked for confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmPromptOptions {
+    /// Confirm.
+    Yes,
+    /// Don't confirm.
+    No,
+}
+
+impl PromptOptions for ConfirmPromptOptions {
+    fn match_input(input: &str) -> Option<Self> {
+        match input {
+            "y" | "yes" => Some(ConfirmPromptOptions::Yes),
+            "n" | "no" => Some(ConfirmPromptOptions::No),
+            _ => None,
+        }
+    }
+
+    fn get_valid_inputs() -> Vec<String> {
+        vec![String::from("y"), String::from("n")]
+    }
+}
+
+/// Prompts the user to confirm that every conflicting file should be
+/// overwritten by the symlink, without backup, the answer coming from
+/// `input`.
+///
+/// # Returns
+///
+/// Whether the user confirmed, or an error if reading/writing from/to
+/// stdin/stdout failed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{self, Input};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// if !prompt::always_overwrite_prompt(&Input::Stdin)? {
+///     return Ok(());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn always_overwrite_prompt(input: &Input) -> crate::Result<bool> {
+    let prompt_mess = format!(
+        "(?) {}
+{}Every file conflicting with a symlink will be overwritten, without backup.
+{}Continue? [y]es [n]o: ",
+        "You asked to always overwrite.".red(),
+        INDENT,
+        INDENT
+    );
+    let option = prompt_option::<ConfirmPromptOptions>(input, &prompt_mess, &[])?;
+
+    Ok(matches!(option, ConfirmPromptOptions::Yes))
+}
+
+/// Prompts the user to confirm that the existing file at link path should be
+/// adopted, i.e. moved to the target path, which doesn't exist.
+///
+/// # Parameters
+///
+/// - `input`: Where the answer comes from.
+/// - `target_path_str`: A string representation of the target's path.
+/// - `link_path_str`: A string representation of the link's path.
+///
+/// # Returns
+///
+/// Whether the user confirmed, or an error if reading/writing from/to
+/// stdin/stdout failed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{self, Input};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// if prompt::adopt_prompt(&Input::Stdin, "/.../target", "/.../link")? {
+///     // Adopt...
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn adopt_prompt(
+    input: &Input,
+    target_path_str: &str,
+    link_path_str: &str,
+) -> crate::Result<bool> {
+    let prompt_mess = format!(
+        "(?) {} -> {}
+{}The target does not exist, but a file exists at link path.
+{}Adopt it (move it to the target path, then make the symlink)? [y]es [n]o: ",
+        link_path_str.red(),
+        target_path_str,
+        INDENT,
+        INDENT
+    );
+    let option = prompt_option::<ConfirmPromptOptions>(input, &prompt_mess, &[])?;
+
+    Ok(matches!(option, ConfirmPromptOptions::Yes))
+}
+
+/// Prompts the user to confirm that the existing directory at link path
+/// should be overwritten, i.e. deleted with all its entries.
+///
+/// # Parameters
+///
+/// - `input`: Where the answer comes from.
+/// - `link_path_str`: A string representation of the link's path.
+/// - `entries`: The number of entries within the directory, recursively.
+///
+/// # Returns
+///
+/// Whether the user confirmed, or an error if reading/writing from/to
+/// stdin/stdout failed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{self, Input};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// if prompt::dir_overwrite_prompt(&Input::Stdin, "/.../link", 42)? {
+///     // Overwrite...
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn dir_overwrite_prompt(
+    input: &Input,
+    link_path_str: &str,
+    entries: u64,
+) -> crate::Result<bool> {
+    let prompt_mess = format!(
+        "(?) {}
+{}The file to overwrite is a directory, whose {} entries would all be deleted.
+{}Overwrite it anyway? [y]es [n]o: ",
+        link_path_str.red(),
+        INDENT,
+        entries,
+        INDENT
+    );
+    let option = prompt_option::<ConfirmPromptOptions>(input, &prompt_mess, &[])?;
+
+    Ok(matches!(option, ConfirmPromptOptions::Yes))
+}
+
+/// Options the user can choose when confronted to a broken symlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokenSymlinkPromptOptions {
+    /// Remove the broken symlink.
+    Remove,
+    /// Remove the current broken symlink and all further ones.
+    AlwaysRemove,
+    /// Keep the broken symlink and move on to the next one.
+    Keep,
+    /// Keep the current broken symlink and all further ones.
+    AlwaysKeep,
+}
+
+impl PromptOptions for BrokenSymlinkPromptOptions {
+    fn match_input(input: &str) -> Option<Self> {
+        match input {
+            "r" => Some(BrokenSymlinkPromptOptions::Remove),
+            "R" => Some(BrokenSymlinkPromptOptions::AlwaysRemove),
+            "k" => Some(BrokenSymlinkPromptOptions::Keep),
+            "K" => Some(BrokenSymlinkPromptOptions::AlwaysKeep),
+            _ => None,
+        }
+    }
+
+    fn get_valid_inputs() -> Vec<String> {
+        vec![
+            String::from("r"),
+            String::from("R"),
+            String::from("k"),
+            String::from("K"),
+        ]
+    }
+}
+
+/// Prompts the user to choose one of the [`BrokenSymlinkPromptOptions`] when
+/// faced with a symlink whose target doesn't exist.
+///
+/// # Parameters
+///
+/// - `input`: Where the answer comes from.
+/// - `target_path_str`: A string representation of the target's path.
+/// - `link_path_str`: A string representation of the link's path.
+///
+/// # Returns
+///
+/// The option chosen by the user, or an error if reading/writing from/to
+/// stdin/stdout failed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{self, Input};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// prompt::broken_symlink_prompt(&Input::Stdin, "/.../target", "/.../link")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn broken_symlink_prompt(
+    input: &Input,
+    target_path_str: &str,
+    link_path_str: &str,
+) -> crate::Result<BrokenSymlinkPromptOptions> {
+    let prompt_mess = format!(
+        "(?) {} -> {}
+{}The target of the symlink doesn't exist.
+{}[r]emove [R]emove all [k]eep [K]eep all [h]elp: ",
+        link_path_str.red(),
+        target_path_str,
+        INDENT,
+        INDENT
+    );
+    let option = prompt_option::<BrokenSymlinkPromptOptions>(
+        input,
+        &prompt_mess,
+        &[("h", &|| BROKEN_SYMLINK_HELP.to_string())],
+    )?;
+
+    Ok(option)
+}
+
+/// How the [`crate::engine::Engine`] asks for decisions to be taken, so that
+/// it can be embedded in something else than a terminal (e.g. a GUI).
+///
+/// The engine uses a [`TerminalPrompter`] unless told otherwise (see
+/// [`crate::engine::Engine::prompter`]). It is shared with the threads
+/// processing files in parallel, hence the `Send + Sync` bounds.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::prompt::{
+///     AlreadyExistPromptOptions, BrokenSymlinkPromptOptions, Prompter, StaleSymlinkPromptOptions,
+/// };
+/// use std::path::Path;
+///
+/// /// Skips every conflict and keeps every broken symlink.
+/// #[derive(Debug)]
+/// struct Cautious;
+///
+/// impl Prompter for Cautious {
+///     fn resolve_conflict(
+///         &self,
+///         _: &Path,
+///         _: &Path,
+///         _: &dyn Fn() -> Option<String>,
+///     ) -> mksls::Result<AlreadyExistPromptOptions> {
+///         Ok(AlreadyExistPromptOptions::Skip)
+///     }
+///
+///     fn resolve_stale_symlink(
+///         &self,
+///         _: &Path,
+///         _: &Path,
+///         _: &Path,
+///     ) -> mksls::Result<StaleSymlinkPromptOptions> {
+///         Ok(StaleSymlinkPromptOptions::Skip)
+///     }
+///
+///     fn confirm_adopt(&self, _: &Path, _: &Path) -> mksls::Result<bool> {
+///         Ok(false)
+///     }
+///
+///     fn confirm_dir_overwrite(&self, _: &Path, _: u64) -> mksls::Result<bool> {
+///         Ok(false)
+///     }
+///
+///     fn resolve_broken_symlink(
+///         &self,
+///         _: &Path,
+///         _: &Path,
+///     ) -> mksls::Result<BrokenSymlinkPromptOptions> {
+///         Ok(BrokenSymlinkPromptOptions::Keep)
+///     }
+///
+///     fn acknowledge_error(&self, message: &str) -> mksls::Result<()> {
+///         eprintln!("{}", message);
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait Prompter: Debug + Send + Sync {
+    /// Chooses what to do when a file already exists where the link `link`,
+    /// pointing to `target`, is about to be made (see [`already_exist_prompt`]).
+    ///
+    /// `diff` returns the differences between the existing file and the
+    /// target, if both are regular text files. They are only computed when
+    /// called, e.g. when the user asks for them.
+    fn resolve_conflict(
+        &self,
+        target: &Path,
+        link: &Path,
+        diff: &dyn Fn() -> Option<String>,
+    ) -> crate::Result<AlreadyExistPromptOptions>;
+
+    /// Chooses what to do when a symlink, pointing to `current_target`,
+    /// already exists where the link `link`, pointing to `target`, is about
+    /// to be made (see [`stale_symlink_prompt`]).
+    fn resolve_stale_symlink(
+        &self,
+        target: &Path,
+        link: &Path,
+        current_target: &Path,
+    ) -> crate::Result<StaleSymlinkPromptOptions>;
+
+    /// Whether to adopt the file at `link`, i.e. to move it to `target` (which
+    /// doesn't exist) and make the link in its place (see [`adopt_prompt`]).
+    fn confirm_adopt(&self, target: &Path, link: &Path) -> crate::Result<bool>;
+
+    /// Whether to overwrite the directory at `link`, holding `entries`
+    /// entries (recursively), i.e. to delete it all to make the link (see
+    /// [`dir_overwrite_prompt`]).
+    fn confirm_dir_overwrite(&self, link: &Path, entries: u64) -> crate::Result<bool>;
+
+    /// Chooses what to do with the symlink `link`, whose target `target`
+    /// doesn't exist (see [`broken_symlink_prompt`]).
+    fn resolve_broken_symlink(
+        &self,
+        target: &Path,
+        link: &Path,
+    ) -> crate::Result<BrokenSymlinkPromptOptions>;
+
+    /// Acknowledges the error `message`, which doesn't stop the run (see
+    /// [`error_prompt`]).
+    fn acknowledge_error(&self, message: &str) -> crate::Result<()>;
+}
+
+/// What to do instead of prompting when nobody is there to answer, i.e. when
+/// stdin is not a terminal (e.g. under cron or in CI) and no answers are
+/// predetermined (see [`TerminalPrompter::noninteractive`]).
+///
+/// Answers piped to stdin are still read, unless skipping.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum NoninteractiveAction {
+    /// Answer as cautiously as possible: skip the conflicting links, keep the
+    /// broken symlinks, and don't adopt nor overwrite directories.
+    Skip,
+    /// Read the answers from stdin all the same (e.g. piped to it), but fail
+    /// when it has none left, telling which link would have been prompted
+    /// for.
+    #[default]
+    Fail,
+}
+
+/// The prompts of a [`Prompter`] that can be answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Prompt {
+    /// See [`Prompter::resolve_conflict`].
+    Conflict,
+    /// See [`Prompter::resolve_stale_symlink`].
+    StaleSymlink,
+    /// See [`Prompter::confirm_adopt`].
+    Adopt,
+    /// See [`Prompter::confirm_dir_overwrite`].
+    DirOverwrite,
+    /// See [`Prompter::resolve_broken_symlink`].
+    BrokenSymlink,
+}
+
+impl Prompt {
+    /// Returns the most cautious answer to the prompt, given for
+    /// [`NoninteractiveAction::Skip`].
+    fn cautious_answer(self) -> &'static str {
+        match self {
+            Prompt::Conflict | Prompt::StaleSymlink => "s",
+            Prompt::Adopt | Prompt::DirOverwrite => "n",
+            Prompt::BrokenSymlink => "k",
+        }
+    }
+}
+
+/// A prompt answered during a [`Session`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Answer {
+    /// The prompt answered.
+    pub prompt: Prompt,
+    /// The link the prompt was about, relative to the home directory (as
+    /// `~/...`) when in it, so that it matches the same link on another
+    /// machine.
+    #[serde(with = "crate::path_repr")]
+    pub link: PathBuf,
+    /// The input answering the prompt (e.g. `s` to skip a conflict).
+    pub answer: String,
+}
+
+/// The prompts answered during a run, recorded to apply the same decisions
+/// again, e.g. on another machine (see [`crate::cli::Cli::record`] and
+/// [`crate::cli::Cli::replay`]).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::prompt::{Answer, Prompt, Session};
+/// use std::path::{Path, PathBuf};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut session = Session::default();
+/// session.answers.push(Answer {
+///     prompt: Prompt::Conflict,
+///     link: PathBuf::from("/home/me/.vimrc"),
+///     answer: String::from("b"),
+/// });
+/// session.save(Path::new("session.json"))?;
+///
+/// let session = Session::load(Path::new("session.json"))?;
+/// assert_eq!(
+///     session.answer(Prompt::Conflict, Path::new("/home/me/.vimrc")),
+///     Some("b")
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The prompts answered, in order.
+    pub answers: Vec<Answer>,
+}
+
+impl Session {
+    /// Loads the session saved at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file at `path` can't be read or is not a valid session.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read the session {}.", path.display()))?;
+        serde_json::from_str(&contents)
+            .parse_context(|| format!("Failed to parse the session {}.", path.display()))
+    }
+
+    /// Saves the session at `path`, in JSON.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the session can't be written.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        let contents = serde_json::to_string_pretty(self).parse_context(|| {
+            format!(
+                "Failed to serialize the session to save it at {}.",
+                path.display()
+            )
+        })?;
+        fs::write(path, contents + "\n")
+            .io_context(|| format!("Failed to write the session {}.", path.display()))
+    }
+
+    /// Returns the answer to `prompt` about the link `link`, if answered.
+    pub fn answer(&self, prompt: Prompt, link: &Path) -> Option<&str> {
+        self.answers
+            .iter()
+            .find(|answer| {
+                answer.prompt == prompt
+                    && line::expand_tilde(&answer.link.to_string_lossy()) == link
+            })
+            .map(|answer| answer.answer.as_str())
+    }
+
+    /// Records `answer` to `prompt` about the link `link` (see [`Answer::link`]).
+    pub fn record(&mut self, prompt: Prompt, link: &Path, answer: String) {
+        let home = platform::home_dir().map(PathBuf::from);
+        let link = match home
+            .as_deref()
+            .and_then(|home| link.strip_prefix(home).ok())
+        {
+            Some(rest) => Path::new("~").join(rest),
+            None => link.to_path_buf(),
+        };
+        self.answers.push(Answer {
+            prompt,
+            link,
+            answer,
+        });
+    }
+}
+
+/// The [`Prompter`] asking the user in the terminal, with the prompts of
+/// this module.
+///
+/// The answers are the lines entered in stdin, or predetermined ones (see
+/// [`TerminalPrompter::new`]). They can also be replayed from a previous
+/// [`Session`] (see [`TerminalPrompter::replaying`]), and recorded (see
+/// [`TerminalPrompter::recording`]). When stdin is not a terminal, nobody
+/// may be there to answer (see [`TerminalPrompter::noninteractive`]).
+#[derive(Debug)]
+pub struct TerminalPrompter {
+    input: Input,
+    /// What to do when stdin is not a terminal.
+    noninteractive: NoninteractiveAction,
+    /// Whether stdin is a terminal.
+    is_terminal: fn() -> bool,
+    /// The session whose answers are given instead of prompting, if any.
+    replayed: Option<Session>,
+    /// The session in which to record the answers, if any.
+    recorded: Option<Arc<Mutex<Session>>>,
+}
+
+impl Default for TerminalPrompter {
+    fn default() -> Self {
+        Self {
+            input: Input::default(),
+            noninteractive: NoninteractiveAction::default(),
+            is_terminal: || io::stdin().is_terminal(),
+            replayed: None,
+            recorded: None,
+        }
+    }
+}
+
+impl TerminalPrompter {
+    /// Returns the prompter whose answers come from `input`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::prompt::{Input, TerminalPrompter};
+    ///
+    /// let prompter = TerminalPrompter::new(Input::answers(["s", "b"].map(String::from)));
+    /// ```
+    pub fn new(input: Input) -> Self {
+        Self {
+            input,
+            ..Self::default()
+        }
+    }
+
+    /// Answers the prompts answered in `session` the same way (see
+    /// [`Session::answer`]), the other ones being asked as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mksls::prompt::{Session, TerminalPrompter};
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session = Session::load(Path::new("session.json"))?;
+    /// let prompter = TerminalPrompter::default().replaying(session);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn replaying(mut self, session: Session) -> Self {
+        self.replayed = Some(session);
+        self
+    }
+
+    /// Records every prompt answered, and the answer, in `session`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::prompt::{Session, TerminalPrompter};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let session = Arc::new(Mutex::new(Session::default()));
+    /// let prompter = TerminalPrompter::default().recording(Arc::clone(&session));
+    /// ```
+    pub fn recording(mut self, session: Arc<Mutex<Session>>) -> Self {
+        self.recorded = Some(session);
+        self
+    }
+
+    /// Does `action` when the answers would be read from stdin but it is not a
+    /// terminal, [`NoninteractiveAction::Fail`] by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::prompt::{NoninteractiveAction, TerminalPrompter};
+    ///
+    /// let prompter = TerminalPrompter::default().noninteractive(NoninteractiveAction::Skip);
+    /// ```
+    pub fn noninteractive(mut self, action: NoninteractiveAction) -> Self {
+        self.noninteractive = action;
+        self
+    }
+
+    /// Takes stdin for not being a terminal, nobody being there to answer
+    /// even if it is (e.g. while watching for changes, see
+    /// [`crate::watch::watch`]): the prompts are answered as set with
+    /// [`TerminalPrompter::noninteractive`], and the errors are only printed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mksls::prompt::{NoninteractiveAction, TerminalPrompter};
+    ///
+    /// let prompter = TerminalPrompter::default()
+    ///     .noninteractive(NoninteractiveAction::Skip)
+    ///     .unattended();
+    /// ```
+    pub fn unattended(mut self) -> Self {
+        self.is_terminal = || false;
+        self
+    }
+
+    /// Asks `prompt` about the link `link` with `ask`, giving it the input
+    /// to read the answer from: the answer replayed, if any and valid, or else
+    /// the input of the prompter (see [`TerminalPrompter::ask_input`]). Then
+    /// records the answer, as given by `answer_of`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `ask` fails (see [`TerminalPrompter::ask_input`]).
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn ask<T>(
+        &self,
+        prompt: Prompt,
+        link: &Path,
+        ask: impl Fn(&Input) -> crate::Result<T>,
+        answer_of: impl FnOnce(&T) -> String,
+    ) -> crate::Result<T> {
+        let replayed = self
+            .replayed
+            .as_ref()
+            .and_then(|session| session.answer(prompt, link))
+            .map(|answer| ask(&Input::answers([answer.to_string()])));
+        let chosen = match replayed {
+            Some(Err(Error::Io { source, .. }))
+                if source.kind() == io::ErrorKind::UnexpectedEof =>
+            {
+                // The answer replayed is not valid for the prompt (e.g. the
+                // session was edited), so the prompt is asked as usual.
+                self.ask_input(prompt.cautious_answer(), &link.display(), &ask)?
+            }
+            Some(chosen) => chosen?,
+            None => self.ask_input(prompt.cautious_answer(), &link.display(), ask)?,
+        };
+        if let Some(recorded) = &self.recorded {
+            recorded
+                .lock()
+                .unwrap()
+                .record(prompt, link, answer_of(&chosen));
+        }
+
+        Ok(chosen)
+    }
+
+    /// Asks about `about` with `ask`, giving it the input of the prompter,
+    /// unless it is stdin and it is not a terminal: then `cautious_answer`
+    /// is given instead with [`NoninteractiveAction::Skip`], and stdin is
+    /// read all the same with [`NoninteractiveAction::Fail`] (e.g. for
+    /// answers piped to it), failing if it has none left.
+    ///
+    /// # Errors
+    ///
+    /// Fails when:
+    ///
+    /// - Nobody can answer, and [`NoninteractiveAction::Fail`] is to be done.
+    /// - `ask` fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    fn ask_input<T>(
+        &self,
+        cautious_answer: &str,
+        about: &dyn fmt::Display,
+        ask: impl FnOnce(&Input) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        if !self.input.is_interactive() || (self.is_terminal)() {
+            return ask(&self.input);
+        }
+
+        match self.noninteractive {
+            NoninteractiveAction::Skip => ask(&Input::answers([cautious_answer.to_string()])),
+            NoninteractiveAction::Fail => ask(&self.input).map_err(|err| match err {
+                Error::Io { source, .. } if source.kind() == io::ErrorKind::UnexpectedEof => {
+                    Error::Io {
+                        message: format!(
+                            "Can't prompt about {}, as stdin is not a terminal and has no answer left. Choose what to do with --noninteractive-action, or answer the prompts with --answers.",
+                            about
+                        ),
+                        source,
+                    }
+                }
+                err => err,
+            }),
+        }
+    }
+
+    /// Prompts the user to confirm that every conflicting file should be
+    /// overwritten (see [`always_overwrite_prompt`]), not confirming when
+    /// nobody can answer and [`NoninteractiveAction::Skip`] is to be done.
+    ///
+    /// # Errors
+    ///
+    /// Fails when nobody can answer and [`NoninteractiveAction::Fail`] is to
+    /// be done, or when prompting fails.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use mksls::prompt::TerminalPrompter;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// if !TerminalPrompter::default().confirm_always_overwrite()? {
+    ///     return Ok(());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn confirm_always_overwrite(&self) -> crate::Result<bool> {
+        self.ask_input("n", &"always overwriting", always_overwrite_prompt)
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn resolve_conflict(
+        &self,
+        target: &Path,
+        link: &Path,
+        diff: &dyn Fn() -> Option<String>,
+    ) -> crate::Result<AlreadyExistPromptOptions> {
+        self.ask(
+            Prompt::Conflict,
+            link,
+            |input| {
+                already_exist_prompt(
+                    input,
+                    &target.to_string_lossy(),
+                    &link.to_string_lossy(),
+                    diff,
+                )
+            },
+            input_of,
+        )
+    }
+
+    fn resolve_stale_symlink(
+        &self,
+        target: &Path,
+        link: &Path,
+        current_target: &Path,
+    ) -> crate::Result<StaleSymlinkPromptOptions> {
+        self.ask(
+            Prompt::StaleSymlink,
+            link,
+            |input| {
+                stale_symlink_prompt(
+                    input,
+                    &target.to_string_lossy(),
+                    &link.to_string_lossy(),
+                    &current_target.to_string_lossy(),
+                )
+            },
+            input_of,
+        )
+    }
+
+    fn confirm_adopt(&self, target: &Path, link: &Path) -> crate::Result<bool> {
+        self.ask(
+            Prompt::Adopt,
+            link,
+            |input| adopt_prompt(input, &target.to_string_lossy(), &link.to_string_lossy()),
+            confirm_input,
+        )
+    }
+
+    fn confirm_dir_overwrite(&self, link: &Path, entries: u64) -> crate::Result<bool> {
+        self.ask(
+            Prompt::DirOverwrite,
+            link,
+            |input| dir_overwrite_prompt(input, &link.to_string_lossy(), entries),
+            confirm_input,
+        )
+    }
+
+    fn resolve_broken_symlink(
+        &self,
+        target: &Path,
+        link: &Path,
+    ) -> crate::Result<BrokenSymlinkPromptOptions> {
+        self.ask(
+            Prompt::BrokenSymlink,
+            link,
+            |input| {
+                broken_symlink_prompt(input, &target.to_string_lossy(), &link.to_string_lossy())
+            },
+            input_of,
+        )
+    }
+
+    /// The error is only reported when stdin is not a terminal, or when the
+    /// answers are predetermined (so that they are not used for errors).
+    fn acknowledge_error(&self, message: &str) -> crate::Result<()> {
+        if !self.input.is_interactive() || !(self.is_terminal)() {
+            eprintln!("{}", format!("(!) {}", message).red());
+            return Ok(());
+        }
+
+        error_prompt(&self.input, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn existing_file_tells_size_and_first_line() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let file = dir.child("vimrc");
+        file.write_str(&format!("\n  set nocompatible\n{}\n", "x".repeat(100)))?;
+        let long = dir.child("long");
+        long.write_str(&"y".repeat(100))?;
+
+        let existing = ExistingFile::of(&file);
+        assert_eq!(existing.size, Some(121));
+        assert!(existing.modified.is_some());
+        assert_eq!(existing.first_line.as_deref(), Some("set nocompatible"));
+        assert_eq!(
+            ExistingFile::of(&long).first_line,
+            Some(format!("{}...", "y".repeat(60)))
+        );
+
+        let existing = ExistingFile::of(&dir);
+        assert_eq!((existing.size, existing.first_line), (None, None));
+        assert_eq!(
+            ExistingFile::of(&dir.child("missing")),
+            ExistingFile::default()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn prompts_are_answered_cautiously_when_nobody_can_answer() {
+        let (target, link) = (Path::new("/dots/a"), Path::new("/home/a"));
+
+        let skipping = TerminalPrompter {
+            is_terminal: || false,
+            ..TerminalPrompter::default()
+        }
+        .noninteractive(NoninteractiveAction::Skip);
+        assert!(matches!(
+            skipping.resolve_conflict(target, link, &|| None),
+            Ok(AlreadyExistPromptOptions::Skip)
+        ));
+        assert!(matches!(skipping.confirm_adopt(target, link), Ok(false)));
+        assert!(matches!(skipping.confirm_always_overwrite(), Ok(false)));
+
+        // Predetermined answers are given all the same.
+        let answering = TerminalPrompter {
+            is_terminal: || false,
+            ..TerminalPrompter::new(Input::answers([String::from("b")]))
+        };
+        assert!(matches!(
+            answering.resolve_conflict(target, link, &|| None),
+            Ok(AlreadyExistPromptOptions::Backup)
+        ));
+    }
+
+    #[test]
+    fn sessions_record_links_relative_to_the_home_directory() {
+        let Some(home) = platform::home_dir() else {
+            return;
+        };
+        let link = Path::new(&home).join(".vimrc");
+        let mut session = Session::default();
+
+        session.record(Prompt::Conflict, &link, String::from("b"));
+        session.record(Prompt::Adopt, Path::new("/etc/hosts"), String::from("n"));
+
+        assert_eq!(session.answers[0].link, Path::new("~/.vimrc"));
+        assert_eq!(session.answer(Prompt::Conflict, &link), Some("b"));
+        assert_eq!(session.answers[1].link, Path::new("/etc/hosts"));
+        assert_eq!(
+            session.answer(Prompt::Adopt, Path::new("/etc/hosts")),
+            Some("n")
+        );
+    }
+
+    #[test]
+    fn invalid_replayed_answers_are_asked_again() {
+        let (target, link) = (Path::new("/dots/a"), Path::new("/a"));
+        let mut session = Session::default();
+        session.record(Prompt::Conflict, link, String::from("x"));
+
+        let prompter =
+            TerminalPrompter::new(Input::answers([String::from("b")])).replaying(session);
+
+        assert!(matches!(
+            prompter.resolve_conflict(target, link, &|| None),
+            Ok(AlreadyExistPromptOptions::Backup)
+        ));
+    }
+
+    #[test]
+    fn unattended_prompters_never_read_stdin() {
+        let (target, link) = (Path::new("/dots/a"), Path::new("/home/a"));
+        let prompter = TerminalPrompter::default()
+            .noninteractive(NoninteractiveAction::Skip)
+            .unattended();
+
+        assert!(matches!(
+            prompter.resolve_conflict(target, link, &|| None),
+            Ok(AlreadyExistPromptOptions::Skip)
+        ));
+        assert!(matches!(prompter.confirm_dir_overwrite(link, 3), Ok(false)));
+        assert!(prompter.acknowledge_error("Invalid line.").is_ok());
+    }
+
+    #[test]
+    fn answers_are_read_until_there_is_none_left() {
+        let mut stdin = &b"s\r\nb\n"[..];
+
+        assert_eq!(read_answer(&mut stdin).unwrap(), "s");
+        assert_eq!(read_answer(&mut stdin).unwrap(), "b");
+        assert!(matches!(
+            read_answer(&mut stdin),
+            Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
 }
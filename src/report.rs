@@ -0,0 +1,216 @@
+//! The report of a run, written to a file at its end (see
+//! [`crate::cli::Cli::report`]), independently of what is printed to the
+//! terminal.
+
+use crate::engine::Summary;
+use crate::error::Context;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The format of a report.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// One line per entry, then the summary, as printed to the terminal.
+    #[default]
+    Text,
+    /// An object with the `entries` and the `summary`.
+    Json,
+    /// One row per entry (`action,link,target,sls,line,message`), the last
+    /// one, whose action is `summary`, holding the summary in its message.
+    Csv,
+}
+
+/// Something that happened during a run, reported.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    /// What has been done for a link (see [`crate::event::Action`]), or
+    /// `invalid` for an invalid line, or `failed` for an error.
+    pub action: String,
+    /// Path to the link, if about one.
+    pub link: Option<String>,
+    /// Path to the target of the link, if about one.
+    pub target: Option<String>,
+    /// Path to the symlink-specification file concerned, if any.
+    pub sls: Option<String>,
+    /// The line number concerned in `sls`, if any.
+    pub line: Option<u64>,
+    /// What went wrong, for an invalid line or an error.
+    pub message: Option<String>,
+}
+
+/// A report in JSON.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    entries: &'a [Entry],
+    summary: &'a Summary,
+}
+
+/// Writes the report of a run made of `entries` and ending with `summary` to
+/// the file at `path`, in `format`.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - Creating or writing to the file fails.
+/// - Serializing the report fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::engine::Summary;
+/// use mksls::report::{self, Entry, ReportFormat};
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let entries = [Entry {
+///     action: String::from("created"),
+///     link: Some(String::from("/home/me/.vimrc")),
+///     target: Some(String::from("/home/me/dotfiles/vimrc")),
+///     sls: Some(String::from("/home/me/dotfiles/sls")),
+///     line: Some(1),
+///     message: None,
+/// }];
+/// report::write(
+///     Path::new("/tmp/report.json"),
+///     ReportFormat::Json,
+///     &entries,
+///     &Summary::default(),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write(
+    path: &Path,
+    format: ReportFormat,
+    entries: &[Entry],
+    summary: &Summary,
+) -> crate::Result<()> {
+    let file = File::create(path)
+        .io_context(|| format!("Failed to create the report {}.", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    let written = match format {
+        ReportFormat::Text => write_text(&mut writer, entries, summary),
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &JsonReport { entries, summary })
+                .parse_context(|| format!("Failed to write the report {}.", path.display()))?;
+            writeln!(writer)
+        }
+        ReportFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(&mut writer);
+            let summary = Entry {
+                action: String::from("summary"),
+                message: Some(summary.to_string().replace("\n    ", "\n")),
+                ..Entry::default()
+            };
+            for entry in entries.iter().chain([&summary]) {
+                csv.serialize(entry)
+                    .parse_context(|| format!("Failed to write the report {}.", path.display()))?;
+            }
+            csv.flush()
+        }
+    };
+
+    written
+        .and_then(|_| writer.flush())
+        .io_context(|| format!("Failed to write the report {}.", path.display()))
+}
+
+/// Writes the report in [`ReportFormat::Text`].
+fn write_text<W: Write>(
+    mut writer: W,
+    entries: &[Entry],
+    summary: &Summary,
+) -> std::io::Result<()> {
+    for entry in entries {
+        write!(writer, "{}", entry.action)?;
+        if let (Some(link), Some(target)) = (&entry.link, &entry.target) {
+            write!(writer, " {} -> {}", link, target)?;
+        }
+        match (&entry.sls, entry.line) {
+            (Some(sls), Some(line)) => write!(writer, " ({}, line {})", sls, line)?,
+            (Some(sls), None) => write!(writer, " ({})", sls)?,
+            _ => {}
+        }
+        if let Some(message) = &entry.message {
+            write!(writer, ": {}", message.replace('\n', "\n    "))?;
+        }
+        writeln!(writer)?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "{}", summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::fs;
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                action: String::from("created"),
+                link: Some(String::from("/home/a")),
+                target: Some(String::from("/dots/a")),
+                sls: Some(String::from("/dots/sls")),
+                line: Some(1),
+                message: None,
+            },
+            Entry {
+                action: String::from("invalid"),
+                sls: Some(String::from("/dots/sls")),
+                line: Some(2),
+                message: Some(String::from("Invalid, with a comma.")),
+                ..Entry::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn reports_are_written_in_every_format() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let summary = Summary {
+            created: 1,
+            invalid: 1,
+            ..Summary::default()
+        };
+
+        let text = dir.child("report.txt");
+        write(&text, ReportFormat::Text, &entries(), &summary)?;
+        let text = fs::read_to_string(text)?;
+        assert!(text.starts_with(
+            "created /home/a -> /dots/a (/dots/sls, line 1)
+invalid (/dots/sls, line 2): Invalid, with a comma.
+"
+        ));
+
+        let json = dir.child("report.json");
+        write(&json, ReportFormat::Json, &entries(), &summary)?;
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(json)?)?;
+        assert_eq!(json["entries"][1]["line"], 2);
+        assert_eq!(json["summary"]["created"], 1);
+
+        let csv = dir.child("report.csv");
+        write(&csv, ReportFormat::Csv, &entries(), &summary)?;
+        let csv = fs::read_to_string(csv)?;
+        let rows: Vec<&str> = csv.lines().take(3).collect();
+        assert_eq!(
+            rows,
+            [
+                "action,link,target,sls,line,message",
+                "created,/home/a,/dots/a,/dots/sls,1,",
+                "invalid,,,/dots/sls,2,\"Invalid, with a comma.\"",
+            ]
+        );
+        assert!(csv.contains("\nsummary,,,,,\"Summary:\n"));
+
+        Ok(())
+    }
+}
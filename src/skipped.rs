@@ -0,0 +1,192 @@
+//! The links skipped because they conflicted with an existing file, kept
+//! across runs so that they can be resolved later (see
+//! [`crate::cli::Command::Resolve`]).
+
+use crate::error::Context;
+use crate::Error;
+use clap::crate_name;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The links skipped by the runs and not made since.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::skipped::Skipped;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = Skipped::default_path()?;
+/// let mut skipped = Skipped::load(&path)?;
+/// skipped.add(Path::new("/home/me/.vimrc"), Path::new("/home/me/.dotfiles/vimrc"));
+/// skipped.save(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Stored", into = "Stored")]
+pub struct Skipped {
+    /// The skipped links, each one associated with its target.
+    links: BTreeMap<PathBuf, PathBuf>,
+}
+
+/// How [`Skipped`] is written.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Stored {
+    links: Vec<StoredLink>,
+}
+
+/// A link in [`Stored::links`].
+#[derive(Serialize, Deserialize)]
+struct StoredLink {
+    #[serde(with = "crate::path_repr")]
+    link: PathBuf,
+    #[serde(with = "crate::path_repr")]
+    target: PathBuf,
+}
+
+impl From<Stored> for Skipped {
+    fn from(stored: Stored) -> Self {
+        let links = stored
+            .links
+            .into_iter()
+            .map(|stored| (stored.link, stored.target))
+            .collect();
+
+        Skipped { links }
+    }
+}
+
+impl From<Skipped> for Stored {
+    fn from(skipped: Skipped) -> Self {
+        let links = skipped
+            .links
+            .into_iter()
+            .map(|(link, target)| StoredLink { link, target })
+            .collect();
+
+        Stored { links }
+    }
+}
+
+impl Skipped {
+    /// Returns the path of the skipped links in the data directory of the app.
+    ///
+    /// # Errors
+    ///
+    /// Fails when no home directory is found for the current user.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn default_path() -> crate::Result<PathBuf> {
+        let project = ProjectDirs::from("rs", "", crate_name!()).ok_or_else(|| Error::Io {
+            message: String::from(
+                "Failed to find the data directory where to keep the skipped links.",
+            ),
+            source: io::ErrorKind::NotFound.into(),
+        })?;
+
+        Ok(project.data_dir().join("skipped.toml"))
+    }
+
+    /// Loads the skipped links at `path`, or none if there is no file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file at `path` can't be read or is not valid.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read the skipped links {}.", path.display()))?;
+        toml::from_str(&contents)
+            .parse_context(|| format!("Failed to parse the skipped links {}.", path.display()))
+    }
+
+    /// Saves the skipped links at `path`, creating the parent directories if
+    /// needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file can't be written.
+    ///
+    /// See [`crate::Error`] for the kinds of errors.
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).io_context(|| {
+                format!(
+                    "Failed to create directory {} to save the skipped links in it.",
+                    parent.display()
+                )
+            })?;
+        }
+        let contents = toml::to_string(self).parse_context(|| {
+            format!(
+                "Failed to serialize the skipped links to save them at {}.",
+                path.display()
+            )
+        })?;
+        fs::write(path, contents)
+            .io_context(|| format!("Failed to write the skipped links {}.", path.display()))
+    }
+
+    /// Adds the link `link`, pointing to `target`, to the skipped links.
+    pub fn add(&mut self, link: &Path, target: &Path) {
+        self.links.insert(link.to_path_buf(), target.to_path_buf());
+    }
+
+    /// Removes the link `link` from the skipped links.
+    pub fn remove(&mut self, link: &Path) {
+        self.links.remove(link);
+    }
+
+    /// Whether there are no skipped links.
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Returns an iterator over the skipped links, each one with its target.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Path)> {
+        self.links
+            .iter()
+            .map(|(link, target)| (link.as_path(), target.as_path()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn skipped_links_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data/skipped.toml");
+
+        assert!(Skipped::load(&path)?.is_empty());
+
+        let mut skipped = Skipped::default();
+        skipped.add(Path::new("/home/me/.vimrc"), Path::new("/dots/vimrc"));
+        skipped.add(Path::new("/home/me/.bashrc"), Path::new("/dots/bashrc"));
+        skipped.remove(Path::new("/home/me/.vimrc"));
+        skipped.save(&path)?;
+
+        let loaded = Skipped::load(&path)?;
+        assert_eq!(loaded, skipped);
+        assert_eq!(
+            loaded.iter().collect::<Vec<_>>(),
+            vec![(Path::new("/home/me/.bashrc"), Path::new("/dots/bashrc"))]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+//! Parsing of symlink-specification files written in TOML.
+//!
+//! A TOML symlink-specification file is made of `link` entries and an
+//! optional `include` array:
+//!
+//! ```toml
+//! include = ["../common/sls"]
+//!
+//! [[link]]
+//! target = "nvim"
+//! link = "~/.config/nvim"
+//!
+//! [[link]]
+//! kind = "copy"
+//! target = "app.conf"
+//! link = "~/.app.conf"
+//! ```
+//!
+//! Each entry is interpreted like the equivalent line of a plain-text
+//! symlink-specification file (see [`crate::line::line_type`]).
+
+use crate::line;
+use crate::line::LineType;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use toml::Spanned;
+
+/// The contents of a TOML symlink-specification file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlsFile {
+    #[serde(default)]
+    include: Vec<Spanned<String>>,
+    #[serde(default)]
+    link: Vec<Link>,
+}
+
+/// A `[[link]]` entry.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Link {
+    #[serde(default)]
+    kind: Kind,
+    target: Spanned<String>,
+    link: String,
+}
+
+/// The kind of link a `[[link]]` entry specifies.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    #[default]
+    Symlink,
+    Hard,
+    Copy,
+    Template,
+    Dir,
+}
+
+impl Kind {
+    /// The prefix of the equivalent plain-text specification, if any.
+    fn prefix(&self) -> Option<&'static str> {
+        match self {
+            Kind::Symlink => None,
+            Kind::Hard => Some("hard"),
+            Kind::Copy => Some("copy"),
+            Kind::Template => Some("template"),
+            Kind::Dir => Some("dir"),
+        }
+    }
+}
+
+/// Returns the specifications of a TOML symlink-specification file, along
+/// with the line number where each is written.
+///
+/// Includes come first, then links, in the order they are written.
+///
+/// # Parameters
+///
+/// - `contents`: The contents of the TOML symlink-specification file.
+/// - `sls_dir`: The directory containing the file. Should be absolute.
+/// - `allow_missing_targets`: Whether a symlink specification whose target
+///   doesn't exist is valid (see [`crate::line::spec_type`]).
+/// - `vars`: The variables that can be referenced in paths, by name.
+///
+/// # Errors
+///
+/// Fails when `contents` is not valid TOML, or doesn't have the expected
+/// structure (see the [module documentation](self)).
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::line::{Invalid, LineType};
+/// use mksls::sls_toml;
+/// use std::collections::HashMap;
+/// use std::path::Path;
+///
+/// let contents = r#"
+/// [[link]]
+/// target = "/does/not/exist"
+/// link = "/some/link"
+/// "#;
+/// let sls_dir = Path::new("/home/my_user/.dotfiles");
+/// let specs = sls_toml::specs(contents, sls_dir, false, &HashMap::new()).unwrap();
+/// assert_eq!(specs, vec![(3, LineType::Invalid(Invalid::TargetDoesNotExist))]);
+/// ```
+pub fn specs(
+    contents: &str,
+    sls_dir: &Path,
+    allow_missing_targets: bool,
+    vars: &HashMap<String, String>,
+) -> Result<Vec<(u64, LineType)>, toml::de::Error> {
+    let sls_file: SlsFile = toml::from_str(contents)?;
+    let line_no = |offset: usize| (contents[..offset].matches('\n').count() + 1) as u64;
+
+    let includes = sls_file.include.iter().map(|path| {
+        (
+            line_no(path.span().start),
+            line::spec_type(
+                &["include", path.get_ref()],
+                sls_dir,
+                allow_missing_targets,
+                vars,
+            ),
+        )
+    });
+    let links = sls_file.link.iter().map(|link| {
+        let mut tokens: Vec<&str> = link.kind.prefix().into_iter().collect();
+        tokens.push(link.target.get_ref());
+        tokens.push(&link.link);
+        (
+            line_no(link.target.span().start),
+            line::spec_type(&tokens, sls_dir, allow_missing_targets, vars),
+        )
+    });
+
+    Ok(includes.chain(links).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::specs;
+    use crate::line::{Invalid, LineType};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::collections::HashMap;
+
+    #[test]
+    fn specs_interprets_entries_like_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("config/app.conf").touch()?;
+        dir.child("ifcmd").touch()?;
+        dir.child("other.sls").touch()?;
+
+        let contents = r#"include = ["other.sls"]
+
+[[link]]
+target = "config/app.conf"
+link = "/tmp/app.conf"
+
+[[link]]
+kind = "dir"
+target = "config"
+link = "/tmp/config"
+
+[[link]]
+kind = "hard"
+target = "config"
+link = "/tmp/config"
+
+[[link]]
+target = "ifcmd"
+link = "/tmp/ifcmd"
+"#;
+        assert_eq!(
+            specs(contents, &dir, false, &HashMap::new())?,
+            vec![
+                (1, LineType::Include(dir.child("other.sls").to_path_buf())),
+                (
+                    4,
+                    LineType::SlsSpec {
+                        target: dir.child("config/app.conf").to_path_buf(),
+                        link: "/tmp/app.conf".into(),
+                    }
+                ),
+                (
+                    9,
+                    LineType::SlsDirSpec {
+                        target_dir: dir.child("config").to_path_buf(),
+                        link_dir: "/tmp/config".into(),
+                    }
+                ),
+                (14, LineType::Invalid(Invalid::TargetIsADirectory)),
+                (
+                    18,
+                    LineType::SlsSpec {
+                        target: dir.child("ifcmd").to_path_buf(),
+                        link: "/tmp/ifcmd".into(),
+                    }
+                ),
+            ]
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn specs_errors_on_unexpected_structure() {
+        let vars = HashMap::new();
+        assert!(specs("[[link]]\ntarget = \"/a\"\n", "/".as_ref(), false, &vars).is_err());
+        assert!(specs(
+            "[[link]]\nkind = \"soft\"\ntarget = \"/a\"\nlink = \"/b\"\n",
+            "/".as_ref(),
+            false,
+            &vars
+        )
+        .is_err());
+        assert!(specs(
+            "[[links]]\ntarget = \"/a\"\nlink = \"/b\"\n",
+            "/".as_ref(),
+            false,
+            &vars
+        )
+        .is_err());
+    }
+}
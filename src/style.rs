@@ -0,0 +1,190 @@
+//! The styling (colors, bold...) of what is printed to the terminal.
+//!
+//! Everything printed is styled through [`Paint`], so that styling can be
+//! turned off as a whole, depending on [`crate::cli::Cli::color`] (see
+//! [`init`]).
+
+use clap::ValueEnum;
+use crossterm::style::{self, StyledContent, Stylize};
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::io;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// When to style what is printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal, unless told otherwise by the `NO_COLOR`,
+    /// `CLICOLOR_FORCE` or `CLICOLOR` environment variables.
+    #[default]
+    Auto,
+    /// Always.
+    Always,
+    /// Never.
+    Never,
+}
+
+/// Whether styling is enabled: [`UNSET`] until [`init`] is called (or
+/// styling is first needed), then [`ENABLED`] or [`DISABLED`].
+static STATE: AtomicU8 = AtomicU8::new(UNSET);
+const UNSET: u8 = 0;
+const ENABLED: u8 = 1;
+const DISABLED: u8 = 2;
+
+/// Enables or disables styling for the rest of the program, according to
+/// `choice`.
+///
+/// With [`ColorChoice::Auto`], styling is disabled when `NO_COLOR` is set
+/// (and not empty), enabled when `CLICOLOR_FORCE` is set (and neither empty
+/// nor `0`), disabled when `CLICOLOR` is `0`, and otherwise enabled only when
+/// stdout is a terminal. This is also what applies when [`init`] isn't
+/// called.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::style::{self, ColorChoice};
+///
+/// style::init(ColorChoice::Never);
+/// assert!(!style::enabled());
+/// ```
+pub fn init(choice: ColorChoice) {
+    let enabled = enabled_by(choice, |name| env::var_os(name), io::stdout().is_terminal());
+    // Otherwise, crossterm leaves the colors out when NO_COLOR is set.
+    style::force_color_output(enabled);
+    STATE.store(if enabled { ENABLED } else { DISABLED }, Ordering::Relaxed);
+}
+
+/// Returns whether styling is enabled (see [`init`]).
+pub fn enabled() -> bool {
+    match STATE.load(Ordering::Relaxed) {
+        UNSET => {
+            init(ColorChoice::Auto);
+            enabled()
+        }
+        state => state == ENABLED,
+    }
+}
+
+/// Returns whether styling is enabled by `choice`, given `var` returning the
+/// value of the environment variable of the given name, if set, and whether
+/// stdout is a terminal.
+fn enabled_by<F: Fn(&str) -> Option<OsString>>(choice: ColorChoice, var: F, tty: bool) -> bool {
+    let set = |name| var(name).filter(|value| !value.is_empty());
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto if set("NO_COLOR").is_some() => false,
+        ColorChoice::Auto if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") => true,
+        ColorChoice::Auto if set("CLICOLOR").is_some_and(|value| value == "0") => false,
+        ColorChoice::Auto => tty,
+    }
+}
+
+/// Returns `content` styled by `style` if styling is enabled, as is
+/// otherwise.
+fn paint<D: Display, F: FnOnce(StyledContent<D>) -> StyledContent<D>>(
+    content: D,
+    style: F,
+) -> String {
+    if enabled() {
+        style(style::style(content)).to_string()
+    } else {
+        content.to_string()
+    }
+}
+
+/// Defines the methods of [`Paint`], each one applying the [`Stylize`]
+/// method of the same name.
+macro_rules! paint_methods {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[doc = concat!("Returns the content styled with [`Stylize::", stringify!($name), "`], if styling is enabled.")]
+            fn $name(self) -> String {
+                paint(self, Stylize::$name)
+            }
+        )*
+    };
+}
+
+/// Styles what is printed, if styling is enabled (see [`init`]).
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::style::{self, ColorChoice, Paint};
+///
+/// style::init(ColorChoice::Never);
+/// assert_eq!("(s) /link -> /target".dark_blue(), "(s) /link -> /target");
+/// ```
+pub trait Paint: Display + Sized {
+    paint_methods!(
+        red,
+        green,
+        yellow,
+        cyan,
+        dark_red,
+        dark_green,
+        dark_yellow,
+        dark_blue,
+        dark_magenta,
+        dark_cyan,
+        dark_grey,
+        bold,
+        underlined,
+    );
+}
+
+impl<D: Display> Paint for D {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_honors_the_environment_then_the_terminal() {
+        let vars = |set: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                set.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+
+        assert!(enabled_by(ColorChoice::Auto, vars(&[]), true));
+        assert!(!enabled_by(ColorChoice::Auto, vars(&[]), false));
+        assert!(!enabled_by(
+            ColorChoice::Auto,
+            vars(&[("NO_COLOR", "1")]),
+            true
+        ));
+        assert!(enabled_by(
+            ColorChoice::Auto,
+            vars(&[("NO_COLOR", "")]),
+            true
+        ));
+        assert!(enabled_by(
+            ColorChoice::Auto,
+            vars(&[("CLICOLOR_FORCE", "1")]),
+            false
+        ));
+        assert!(!enabled_by(
+            ColorChoice::Auto,
+            vars(&[("CLICOLOR", "0")]),
+            true
+        ));
+        assert!(!enabled_by(
+            ColorChoice::Auto,
+            vars(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]),
+            true
+        ));
+        assert!(enabled_by(
+            ColorChoice::Always,
+            vars(&[("NO_COLOR", "1")]),
+            false
+        ));
+        assert!(!enabled_by(ColorChoice::Never, vars(&[]), true));
+    }
+}
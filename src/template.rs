@@ -0,0 +1,151 @@
+//! Rendering of the template files used by template specifications.
+
+pub mod error;
+
+use crate::platform;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+
+lazy_static! {
+    /// A regex to find references to variables (`{{ NAME }}`) in a template.
+    pub static ref TEMPLATE_VAR_RE: Regex =
+        Regex::new(r"\{\{\s*(?<name>[A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap();
+}
+
+/// The names of the built-in variables (see [`builtin_var`]).
+pub const BUILTIN_VARS: [&str; 4] = ["hostname", "os", "arch", "user"];
+
+/// Returns the value of the built-in variable `name`, or `None` if it is not
+/// one or its value can't be found.
+///
+/// The built-in variables are:
+///
+/// - `hostname`: The name of the machine.
+/// - `os`: The operating system (e.g. `linux`, `macos` or `windows`, see
+///   [`std::env::consts::OS`]).
+/// - `arch`: The architecture of the CPU (e.g. `x86_64` or `aarch64`, see
+///   [`std::env::consts::ARCH`]).
+/// - `user`: The name of the current user (`USER`, or `USERNAME` on
+///   Windows).
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::template;
+///
+/// assert_eq!(template::builtin_var("os").as_deref(), Some(std::env::consts::OS));
+/// assert_eq!(template::builtin_var("shell"), None);
+/// ```
+pub fn builtin_var(name: &str) -> Option<String> {
+    match name {
+        "hostname" => hostname::get()
+            .ok()
+            .map(|hostname| hostname.to_string_lossy().into_owned()),
+        "os" => Some(String::from(env::consts::OS)),
+        "arch" => Some(String::from(env::consts::ARCH)),
+        "user" => platform::user_name().map(|user| user.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// Renders `template`, replacing each reference to a variable (see
+/// [`struct@TEMPLATE_VAR_RE`]) by its value.
+///
+/// A variable is looked up, in order:
+///
+/// 1. Among `vars`, i.e. the user-defined variables.
+/// 2. Among the built-in variables (see [`builtin_var`]).
+/// 3. Among the environment variables.
+///
+/// # Parameters
+///
+/// - `template`: The contents of the template.
+/// - `vars`: The user-defined variables.
+///
+/// # Errors
+///
+/// Fails with [`error::UndefinedVar`] at the first referenced variable that
+/// is not defined.
+///
+/// # Examples
+///
+/// ```rust
+/// use mksls::template;
+/// use std::collections::HashMap;
+///
+/// let vars = HashMap::from([(String::from("email"), String::from("me@example.com"))]);
+/// let rendered = template::render("email = {{ email }}", &vars).unwrap();
+/// assert_eq!(rendered, "email = me@example.com");
+/// ```
+pub fn render(
+    template: &str,
+    vars: &HashMap<String, String>,
+) -> Result<String, error::UndefinedVar> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut last = 0;
+    for caps in TEMPLATE_VAR_RE.captures_iter(template) {
+        let reference = caps.get(0).unwrap();
+        let name = &caps["name"];
+        let value = lookup(name, vars).ok_or_else(|| error::UndefinedVar(String::from(name)))?;
+
+        rendered.push_str(&template[last..reference.start()]);
+        rendered.push_str(&value);
+        last = reference.end();
+    }
+    rendered.push_str(&template[last..]);
+
+    Ok(rendered)
+}
+
+/// Returns the value of the variable `name` (see [`render`] for the lookup order).
+fn lookup(name: &str, vars: &HashMap<String, String>) -> Option<String> {
+    if let Some(value) = vars.get(name) {
+        return Some(value.clone());
+    }
+    if let Some(value) = builtin_var(name) {
+        return Some(value);
+    }
+    env::var_os(name).map(|value| value.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_replaces_vars_by_priority() {
+        env::set_var("MKSLS_TEST_TEMPLATE_VAR", "from env");
+        let vars = HashMap::from([
+            (String::from("name"), String::from("from vars")),
+            (
+                String::from("MKSLS_TEST_TEMPLATE_OVERRIDDEN"),
+                String::from("from vars"),
+            ),
+        ]);
+        env::set_var("MKSLS_TEST_TEMPLATE_OVERRIDDEN", "from env");
+
+        let rendered = render(
+            "{{name}}, {{ MKSLS_TEST_TEMPLATE_VAR }}, {{ MKSLS_TEST_TEMPLATE_OVERRIDDEN }}, { name }",
+            &vars,
+        );
+
+        assert_eq!(
+            rendered,
+            Ok(String::from("from vars, from env, from vars, { name }"))
+        );
+    }
+
+    #[test]
+    fn render_errors_on_undefined_var() {
+        env::remove_var("MKSLS_TEST_TEMPLATE_UNDEFINED");
+
+        assert_eq!(
+            render("x = {{ MKSLS_TEST_TEMPLATE_UNDEFINED }}", &HashMap::new()),
+            Err(error::UndefinedVar(String::from(
+                "MKSLS_TEST_TEMPLATE_UNDEFINED"
+            )))
+        );
+    }
+}
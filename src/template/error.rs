@@ -0,0 +1,16 @@
+//! Errors related to [`crate::template`].
+
+use core::fmt;
+use std::{error, fmt::Debug};
+
+#[derive(Debug, PartialEq, Eq)]
+/// An error for when a template references a variable that is not defined.
+pub struct UndefinedVar(pub String);
+
+impl fmt::Display for UndefinedVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The variable {} is not defined.", self.0)
+    }
+}
+
+impl error::Error for UndefinedVar {}
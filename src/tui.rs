@@ -0,0 +1,405 @@
+//! The terminal user interface of [`crate::cli::Cli::tui`], listing the
+//! conflicts of a run so that the user resolves them all before any is acted
+//! upon.
+
+use crate::error::Context;
+use crate::style::Paint;
+use crate::utils;
+use crate::Error;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, queue, terminal};
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+
+/// The help shown at the top of the interface.
+const HELP: &str = "↑/↓ (k/j) move, [s]kip [b]ackup [o]verwrite re[n]ame [a]dopt [r]elink (uppercase for all), Enter apply, [q]uit";
+
+/// A link conflicting with an existing file, to resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The target of the link.
+    pub target: PathBuf,
+    /// The link, where the existing file is.
+    pub link: PathBuf,
+    /// Whether the existing file is a symlink pointing somewhere else, the
+    /// link being a symlink too.
+    pub stale: bool,
+}
+
+impl Conflict {
+    /// Returns the resolutions possible, the first one being the default.
+    ///
+    /// These are the ones the prompts offer, i.e. relinking only for a stale
+    /// symlink, and neither overwriting, renaming nor adopting for one.
+    pub fn resolutions(&self) -> &'static [Resolution] {
+        if self.stale {
+            &[Resolution::Skip, Resolution::Relink, Resolution::Backup]
+        } else {
+            &[
+                Resolution::Skip,
+                Resolution::Backup,
+                Resolution::Overwrite,
+                Resolution::Rename,
+                Resolution::Adopt,
+            ]
+        }
+    }
+}
+
+/// How to resolve a [`Conflict`], as chosen in the interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Don't make the link.
+    Skip,
+    /// Backup the existing file, then make the link.
+    Backup,
+    /// Make the link without backup, overwriting the existing file.
+    Overwrite,
+    /// Rename the existing file in place, then make the link.
+    Rename,
+    /// Replace the existing symlink by the link.
+    Relink,
+    /// Move the existing file to the target path, then make the link.
+    Adopt,
+}
+
+impl Resolution {
+    /// The name shown in the interface.
+    fn name(self) -> &'static str {
+        match self {
+            Resolution::Skip => "skip",
+            Resolution::Backup => "backup",
+            Resolution::Overwrite => "overwrite",
+            Resolution::Rename => "rename",
+            Resolution::Relink => "relink",
+            Resolution::Adopt => "adopt",
+        }
+    }
+
+    /// Returns the resolution chosen with `key` (lowercase), if any.
+    fn from_key(key: char) -> Option<Self> {
+        match key {
+            's' => Some(Resolution::Skip),
+            'b' => Some(Resolution::Backup),
+            'o' => Some(Resolution::Overwrite),
+            'n' => Some(Resolution::Rename),
+            'r' => Some(Resolution::Relink),
+            'a' => Some(Resolution::Adopt),
+            _ => None,
+        }
+    }
+}
+
+/// What to do once a key has been handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    /// Wait for the next key.
+    Continue,
+    /// Resolve the conflicts as chosen.
+    Apply,
+    /// Resolve none of the conflicts.
+    Quit,
+}
+
+/// The state of the interface.
+struct State<'a> {
+    conflicts: &'a [Conflict],
+    /// Returns the differences between the existing file and the target of
+    /// the conflict at the index given, if both are text files.
+    diff: &'a dyn Fn(usize) -> Option<String>,
+    /// The description of the existing file and its differences with the
+    /// target, for each conflict, once it has been selected.
+    details: Vec<Option<(String, Option<String>)>>,
+    /// The resolution chosen for each conflict.
+    resolutions: Vec<Resolution>,
+    /// The index of the conflict selected.
+    selected: usize,
+}
+
+impl<'a> State<'a> {
+    fn new(conflicts: &'a [Conflict], diff: &'a dyn Fn(usize) -> Option<String>) -> Self {
+        Self {
+            conflicts,
+            diff,
+            details: vec![None; conflicts.len()],
+            resolutions: conflicts
+                .iter()
+                .map(|conflict| conflict.resolutions()[0])
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    /// Updates the state according to `key`.
+    fn handle(&mut self, key: KeyEvent) -> Outcome {
+        let last = self.conflicts.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Outcome::Quit
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return Outcome::Quit,
+            KeyCode::Enter => return Outcome::Apply,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Char(key) => {
+                let Some(resolution) = Resolution::from_key(key.to_ascii_lowercase()) else {
+                    return Outcome::Continue;
+                };
+                if key.is_ascii_uppercase() {
+                    for (conflict, chosen) in self.conflicts.iter().zip(&mut self.resolutions) {
+                        if conflict.resolutions().contains(&resolution) {
+                            *chosen = resolution;
+                        }
+                    }
+                } else if self.conflicts[self.selected]
+                    .resolutions()
+                    .contains(&resolution)
+                {
+                    self.resolutions[self.selected] = resolution;
+                    self.selected = (self.selected + 1).min(last);
+                }
+            }
+            _ => {}
+        }
+
+        Outcome::Continue
+    }
+
+    /// Draws the interface into `out`, a terminal of `height` rows: the help,
+    /// the list of conflicts (scrolled to the one selected) and the details
+    /// of the one selected, read the first time it is.
+    fn draw<W: Write>(&mut self, out: &mut W, height: u16) -> io::Result<()> {
+        let height = usize::from(height).max(6);
+        let list_height = (height - 2) / 2;
+        let first = self
+            .selected
+            .saturating_sub(list_height - 1)
+            .min(self.conflicts.len().saturating_sub(list_height));
+
+        let mut lines = vec![
+            format!("{} conflict(s) to resolve. {}", self.conflicts.len(), HELP).bold(),
+            String::new(),
+        ];
+        for (i, (conflict, resolution)) in self
+            .conflicts
+            .iter()
+            .zip(&self.resolutions)
+            .enumerate()
+            .skip(first)
+            .take(list_height)
+        {
+            let line = format!(
+                "{} [{:<9}] {} -> {}",
+                if i == self.selected { ">" } else { " " },
+                resolution.name(),
+                conflict.link.display(),
+                conflict.target.display()
+            );
+            lines.push(if i == self.selected {
+                line.cyan()
+            } else {
+                line
+            });
+        }
+        lines.resize(list_height + 2, String::new());
+
+        let conflict = &self.conflicts[self.selected];
+        let possible: Vec<&str> = conflict
+            .resolutions()
+            .iter()
+            .map(|resolution| resolution.name())
+            .collect();
+        lines.push(format!("Possible: {}", possible.join(", ")).dark_grey());
+        lines.push(String::from("The existing file:"));
+        let (description, diff) = self.details[self.selected]
+            .get_or_insert_with(|| (utils::describe(&conflict.link), (self.diff)(self.selected)));
+        lines.extend(description.lines().map(|line| format!("    {}", line)));
+        if let Some(diff) = diff {
+            lines.extend(diff.lines().map(String::from));
+        }
+
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        for (row, line) in lines.iter().take(height).enumerate() {
+            // The rows fit in a u16, as there are no more than `height` ones.
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            write!(out, "{}", line)?;
+        }
+        out.flush()
+    }
+}
+
+/// Shows `conflicts` in a terminal user interface where the user navigates
+/// through them, sees the details of each one (the existing file, and the
+/// differences with the target), and chooses how to resolve each one
+/// (skipping it by default), before applying everything at once.
+///
+/// The details of a conflict are only read once it is selected, `diff`
+/// returning the differences between the existing file and the target of the
+/// conflict at the index given, if both are text files.
+///
+/// # Returns
+///
+/// The resolution chosen for each conflict, in order, or `None` if the user
+/// quit.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - stdin or stdout is not a terminal.
+/// - Reading from or writing to the terminal fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use mksls::tui::{self, Conflict};
+/// use std::path::PathBuf;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let conflicts = vec![Conflict {
+///     target: PathBuf::from("/home/me/dotfiles/vimrc"),
+///     link: PathBuf::from("/home/me/.vimrc"),
+///     stale: false,
+/// }];
+/// if let Some(resolutions) = tui::resolve(&conflicts, |_| None)? {
+///     println!("{:?}", resolutions);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn resolve(
+    conflicts: &[Conflict],
+    diff: impl Fn(usize) -> Option<String>,
+) -> crate::Result<Option<Vec<Resolution>>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(Error::Params {
+            message: String::from("Got tui, but it needs a terminal."),
+            source: None,
+        });
+    }
+    if conflicts.is_empty() {
+        return Ok(Some(vec![]));
+    }
+
+    let mut out = io::stdout();
+    let _terminal = RawTerminal::enter(&mut out)
+        .io_context(|| "Failed to set up the terminal for the interface.")?;
+    let mut state = State::new(conflicts, &diff);
+    loop {
+        let (_, height) = terminal::size().io_context(|| "Failed to get the terminal size.")?;
+        state.draw(&mut out, height)?;
+        let key = match event::read().io_context(|| "Failed to read from the terminal.")? {
+            // Only presses, as releases are reported too on Windows.
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match state.handle(key) {
+            Outcome::Continue => {}
+            Outcome::Apply => return Ok(Some(state.resolutions)),
+            Outcome::Quit => return Ok(None),
+        }
+    }
+}
+
+/// The terminal, in raw mode and on the alternate screen, until dropped.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter<W: Write>(out: &mut W) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let terminal = RawTerminal;
+        queue!(
+            out,
+            terminal::EnterAlternateScreen,
+            terminal::DisableLineWrap,
+            cursor::Hide
+        )?;
+        out.flush()?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        let _ = queue!(
+            out,
+            cursor::Show,
+            terminal::EnableLineWrap,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conflict(link: &str, stale: bool) -> Conflict {
+        Conflict {
+            target: PathBuf::from("/target"),
+            link: PathBuf::from(link),
+            stale,
+        }
+    }
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn keys_choose_the_resolutions_possible() {
+        let conflicts = [
+            conflict("/file", false),
+            conflict("/symlink", true),
+            conflict("/other", false),
+        ];
+        let mut state = State::new(&conflicts, &|_| None);
+
+        assert_eq!(state.handle(press(KeyCode::Char('o'))), Outcome::Continue);
+        assert_eq!(state.selected, 1);
+        // Overwriting isn't possible for a stale symlink.
+        state.handle(press(KeyCode::Char('o')));
+        assert_eq!(state.selected, 1);
+        state.handle(press(KeyCode::Char('r')));
+        state.handle(press(KeyCode::Char('B')));
+        state.handle(press(KeyCode::Up));
+        state.handle(press(KeyCode::Char('s')));
+
+        assert_eq!(
+            state.resolutions,
+            [Resolution::Backup, Resolution::Skip, Resolution::Backup]
+        );
+        assert_eq!(state.handle(press(KeyCode::Enter)), Outcome::Apply);
+        assert_eq!(state.handle(press(KeyCode::Char('q'))), Outcome::Quit);
+    }
+
+    #[test]
+    fn details_are_read_once_selected() -> io::Result<()> {
+        let conflicts = [conflict("/file", false), conflict("/other", false)];
+        let compared = std::cell::RefCell::new(vec![]);
+        let diff = |i| {
+            compared.borrow_mut().push(i);
+            None
+        };
+        let mut state = State::new(&conflicts, &diff);
+
+        state.draw(&mut io::sink(), 20)?;
+        state.draw(&mut io::sink(), 20)?;
+        assert_eq!(*compared.borrow(), [0]);
+        state.handle(press(KeyCode::Down));
+        state.draw(&mut io::sink(), 20)?;
+        assert_eq!(*compared.borrow(), [0, 1]);
+
+        Ok(())
+    }
+}
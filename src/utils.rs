@@ -1,9 +1,205 @@
-use anyhow::Context;
-use crossterm::style::Stylize;
+use crate::backups;
+use crate::error::Context;
+use crate::filesystem::Fs;
+use crate::operation::{CopyOp, HardlinkOp, Operation, OperationKind, RenderOp, SymlinkOp};
+use crate::platform;
+use crate::style::Paint;
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fs;
+use std::io;
 use std::io::Write;
-use std::os::unix;
-use std::path::Path;
+use std::path::{self, Path, PathBuf};
+use std::process;
+use walkdir::WalkDir;
+
+/// The kinds of links that can be made, each one by an [`Operation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A symbolic link.
+    Symlink,
+    /// A hard link.
+    Hardlink,
+    /// A copy of the target, which is not a link per se, but is made (and
+    /// conflicts are handled) the same way.
+    Copy,
+    /// A file with the given contents (a rendered template), which is not a
+    /// link per se, but is made (and conflicts are handled) the same way.
+    Rendered(String),
+}
+
+impl LinkKind {
+    /// Returns the operation making this kind of link.
+    pub fn operation(&self) -> Box<dyn Operation + '_> {
+        match self {
+            LinkKind::Symlink => Box::new(SymlinkOp),
+            LinkKind::Hardlink => Box::new(HardlinkOp),
+            LinkKind::Copy => Box::new(CopyOp),
+            LinkKind::Rendered(contents) => Box::new(RenderOp { contents }),
+        }
+    }
+
+    /// Makes the link `link` pointing to `target`, through `fs` for a symlink
+    /// or a copy (see [`Operation::make`]).
+    pub fn make(&self, fs: &dyn Fs, target: &Path, link: &Path) -> io::Result<()> {
+        self.operation().make(fs, target, link)
+    }
+
+    /// Returns the command making the link `link` pointing to `target` (`ln -s`,
+    /// `ln` or `cp`), to run it with other permissions, or `None` for a
+    /// rendered template, which is not made by a command (see
+    /// [`Operation::command`]).
+    pub fn command(&self, target: &Path, link: &Path) -> Option<Vec<OsString>> {
+        self.operation().command(target, link)
+    }
+
+    /// Whether `link` already is the link pointing to `target`.
+    ///
+    /// For a symlink, it means that `link` is a symlink whose target is `target`,
+    /// or an equivalent path unless `exact` (both being resolved against the
+    /// directory containing `link` if relative, then canonicalized).
+    /// For a hard link, it means that `link` and `target` are the same file.
+    /// For a copy, it means that `link` is a regular file with the same contents as `target`.
+    /// For a rendered template, it means that `link` is a regular file with the rendered contents.
+    ///
+    /// A symlink is looked for through `fs` (see [`Operation::is_made`]).
+    pub fn is_made(
+        &self,
+        fs: &dyn Fs,
+        target: &Path,
+        link: &Path,
+        exact: bool,
+    ) -> io::Result<bool> {
+        self.operation().is_made(fs, target, link, exact)
+    }
+}
+
+impl From<OperationKind> for LinkKind {
+    fn from(kind: OperationKind) -> Self {
+        match kind {
+            OperationKind::Symlink => LinkKind::Symlink,
+            OperationKind::Hard => LinkKind::Hardlink,
+            OperationKind::Copy => LinkKind::Copy,
+        }
+    }
+}
+
+/// Writes the shell script `path` running the `commands`, one per line, each
+/// argument being quoted, stopping at the first one failing.
+///
+/// It is meant to be run as root, to make the links the app has been denied
+/// (see [`crate::cli::Cli::root_script`]).
+///
+/// # Errors
+///
+/// Fails when writing the script fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn write_root_script(path: &Path, commands: &[Vec<OsString>]) -> crate::Result<()> {
+    let mut script = format!(
+        "#!/bin/sh
+# The links mksls has been denied for lack of permissions. Run as root, e.g. with:
+#     sudo sh {}
+set -e
+",
+        path.display()
+    )
+    .into_bytes();
+    for command in commands {
+        let args: Vec<Vec<u8>> = command.iter().map(|arg| sh_quote(arg)).collect();
+        script.extend(args.join(&b' '));
+        script.push(b'\n');
+    }
+
+    fs::write(path, script)
+        .io_context(|| format!("Failed to write the root script {}", path.display()))
+}
+
+/// Returns `arg` as taken literally by a POSIX shell, i.e. as is if it is
+/// only made of characters without special meaning, single-quoted otherwise.
+fn sh_quote(arg: &OsStr) -> Vec<u8> {
+    let bytes = arg.as_encoded_bytes();
+    if !bytes.is_empty()
+        && bytes
+            .iter()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_./=+,:@%".contains(byte))
+    {
+        return bytes.to_vec();
+    }
+
+    let mut quoted = vec![b'\''];
+    for byte in bytes {
+        match byte {
+            b'\'' => quoted.extend(b"'\\''"),
+            byte => quoted.push(*byte),
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+/// Returns the path to `target` relative to the directory containing `link`
+/// (like `ln -sr` does), so that a symlink at `link` storing it points to `target`.
+///
+/// The symlinks in the existing part of both paths are resolved first, so
+/// that `..` components go where expected.
+/// Both `target` and `link` should be absolute.
+pub fn relative_target(target: &Path, link: &Path) -> PathBuf {
+    let target = resolve_existing(target);
+    let link_dir = resolve_existing(link.parent().unwrap_or(Path::new("/")));
+
+    let target_components: Vec<_> = target.components().collect();
+    let dir_components: Vec<_> = link_dir.components().collect();
+    let common = target_components
+        .iter()
+        .zip(&dir_components)
+        .take_while(|(target_comp, dir_comp)| target_comp == dir_comp)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..dir_components.len() {
+        relative.push("..");
+    }
+    for component in &target_components[common..] {
+        relative.push(component);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+
+    relative
+}
+
+/// Resolves the symlinks in the longest existing ancestor of `path` (the
+/// last component of `path` excluded), keeping the rest as is.
+fn resolve_existing(path: &Path) -> PathBuf {
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    match fs::canonicalize(parent) {
+        Ok(parent) => parent.join(file_name),
+        Err(_) => resolve_existing(parent).join(file_name),
+    }
+}
+
+/// Returns `field` with its backslashes, tabs, newlines and carriage returns
+/// escaped (as `\\`, `\t`, `\n` and `\r`), so that it fits in a field of a
+/// tab-separated line.
+pub fn escape_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
 
 pub fn trim_newline(s: &mut String) {
     if s.ends_with('\n') {
@@ -30,7 +226,7 @@ pub fn trim_newline(s: &mut String) {
 /// - `writer`: Where to write feeback to.
 /// - `target`: Path to the target of the symlink.
 /// - `link`: Path to the symlink.
-pub fn skip<W: Write>(mut writer: W, target: &Path, link: &Path) -> anyhow::Result<()> {
+pub fn skip<W: Write>(mut writer: W, target: &Path, link: &Path) -> crate::Result<()> {
     writeln!(
         writer,
         "{}",
@@ -45,8 +241,8 @@ pub fn skip<W: Write>(mut writer: W, target: &Path, link: &Path) -> anyhow::Resu
     Ok(())
 }
 
-/// Backs up the existing file at path `link`, then makes the symlink
-/// at path `link`, pointing to `target`.
+/// Backs up the existing file at path `link`, then makes the link of kind
+/// `kind` at path `link`, pointing to `target`.
 ///
 /// Finally, writes feeback into `writer` in the form of:
 ///
@@ -59,63 +255,739 @@ pub fn skip<W: Write>(mut writer: W, target: &Path, link: &Path) -> anyhow::Resu
 /// # Parameters
 ///
 /// - `writer`: Where to write feedback to.
+/// - `fs`: The file system to back up the existing file and make the link in.
 /// - `backup_dir`: Path to backup directory.
-/// - `target`: Path to the target of the symlink.
-/// - `link`: Path to the symlink.
+/// - `run`: The identifier of the current run. The backups of a run are all
+///   put in the directory `backup_dir/run`.
+/// - `kind`: The kind of link to make.
+/// - `target`: Path to the target of the link.
+/// - `link`: Path to the link.
+///
+/// # Returns
+///
+/// The path of the backup.
 ///
 /// # Errors
 ///
 /// Fails when:
 ///
 /// - The existing file fails to be backed up, i.e. fails to be moved
-///   to the backup directory.
-/// - The symlink creation fails.
+///   to the backup directory of the run.
+/// - The link creation fails.
 /// - Writing into `writer` fails.
 ///
-/// These are `anyhow` errors, so most of the time, you just want to
-/// propagate them.
+/// See [`crate::Error`] for the kinds of errors.
 pub fn backup<W: Write>(
     mut writer: W,
+    fs: &dyn Fs,
     backup_dir: &Path,
+    run: &str,
+    kind: &LinkKind,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<PathBuf> {
+    let backup = move_into(fs, &backup_dir.join(run), link)?;
+    make_or_restore(fs, kind, target, link, &backup)?;
+
+    writeln!(
+        writer,
+        "{}",
+        format!(
+            "(b) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )
+        .dark_green()
+    )?;
+
+    Ok(backup)
+}
+
+/// Renames the existing file at path `link` in place, appending `suffix` to
+/// its name (then `.1`, `.2`, etc. if a file of that name already exists),
+/// then makes the link of kind `kind` at path `link`, pointing to `target`.
+///
+/// Finally, writes feeback into `writer` in the form of:
+///
+/// ```text
+/// (n) <link> -> <target>
+/// ```
+///
+/// in green.
+///
+/// # Parameters
+///
+/// - `writer`: Where to write feedback to.
+/// - `fs`: The file system to rename the existing file and make the link in.
+/// - `suffix`: What to append to the name of the existing file.
+/// - `kind`: The kind of link to make.
+/// - `target`: Path to the target of the link.
+/// - `link`: Path to the link.
+///
+/// # Returns
+///
+/// The path the existing file has been renamed to.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - Renaming the existing file fails.
+/// - The link creation fails (the existing file is then renamed back).
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn rename<W: Write>(
+    mut writer: W,
+    fs: &dyn Fs,
+    suffix: &str,
+    kind: &LinkKind,
     target: &Path,
     link: &Path,
-) -> anyhow::Result<()> {
-    let mut new_name;
-    match link.file_stem() {
-        Some(file_stem) => {
-            new_name = format!(
-                "{}_backup_{}",
-                file_stem.to_string_lossy(),
-                chrono::Local::now().to_rfc3339()
-            );
-            if let Some(extension) = link.extension() {
-                new_name.push_str(&format!(".{}", extension.to_string_lossy()));
+) -> crate::Result<PathBuf> {
+    let mut name = link.as_os_str().to_os_string();
+    name.push(suffix);
+    let mut renamed = PathBuf::from(&name);
+    let mut n = 1;
+    while fs.exists(&renamed) {
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", n));
+        renamed = PathBuf::from(numbered);
+        n += 1;
+    }
+
+    fs.rename(link, &renamed).backup_context(|| {
+        format!(
+            "Failed to rename {} to {}",
+            link.display(),
+            renamed.display()
+        )
+    })?;
+    make_or_restore(fs, kind, target, link, &renamed)?;
+
+    writeln!(
+        writer,
+        "{}",
+        format!(
+            "(n) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )
+        .green()
+    )?;
+
+    Ok(renamed)
+}
+
+/// Moves the existing file at path `link` into the directory `dir` of the
+/// file system `fs`, under
+/// the absolute path of `link` (mirrored within `dir`, see
+/// [`platform::mirror`]) and then the current time.
+///
+/// For example, `/home/me/.config/app/config` is moved to
+/// `<dir>/home/me/.config/app/config/<time>`, so that files with the same
+/// name in different directories are kept apart.
+///
+/// # Returns
+///
+/// The path the file has been moved to.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - The absolute path of `link` can't be determined.
+/// - The directory mirroring `link` fails to be created.
+/// - The file fails to be moved.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn move_into(fs: &dyn Fs, dir: &Path, link: &Path) -> crate::Result<PathBuf> {
+    let absolute_link = path::absolute(link).backup_context(|| {
+        format!(
+            "Failed to get the absolute path of {}, needed to back it up.",
+            link.display()
+        )
+    })?;
+    let mut backup = dir.join(platform::mirror(&absolute_link));
+    fs.create_dir_all(&backup).backup_context(|| {
+        format!(
+            "Failed to backup! Couldn't create directory {} to move {} in it.",
+            backup.display(),
+            link.display()
+        )
+    })?;
+    let time = backups::time_name(|name| fs.exists(&backup.join(name)));
+    backup.push(time);
+
+    move_path(fs, link, &backup).backup_context(|| {
+        format!(
+            "Failed to backup! Couldn't move {} to {}",
+            link.display(),
+            backup.display()
+        )
+    })?;
+
+    Ok(backup)
+}
+
+/// Moves the file (or directory) at `from` to `to`, renaming it through `fs`
+/// (see [`Fs::rename`]), except that it also works across file systems.
+///
+/// When `from` and `to` are not on the same (real) file system, `from` is copied
+/// next to `to` (recursively, symlinks being copied as symlinks), preserving
+/// permissions, access and modification times, and ownership where allowed
+/// (see [`platform::copy_ownership`]), then the copy is renamed to `to` and
+/// `from` is removed. Files are copied by chunks, so that large files are
+/// not loaded in memory. A special file (see [`SpecialFile`]) is never
+/// copied, so it can't be moved to another file system.
+///
+/// # Errors
+///
+/// Fails when the file fails to be renamed for another reason than being
+/// moved to another file system, or fails to be copied or removed. If the
+/// copy fails, what has been copied so far is removed, `to` being left as
+/// it was.
+pub fn move_path(fs: &dyn Fs, from: &Path, to: &Path) -> io::Result<()> {
+    move_path_with(from, to, |from, to| fs.rename(from, to))
+}
+
+/// Does what [`move_path`] does, renaming with `rename`.
+fn move_path_with(
+    from: &Path,
+    to: &Path,
+    rename: impl Fn(&Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    match rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            let copy = temporary_sibling(to);
+            if let Err(err) = copy_preserving(from, &copy).and_then(|()| fs::rename(&copy, to)) {
+                let _ = if copy.is_dir() && !copy.is_symlink() {
+                    fs::remove_dir_all(&copy)
+                } else {
+                    fs::remove_file(&copy)
+                };
+                return Err(err);
+            }
+            if from.is_dir() && !from.is_symlink() {
+                fs::remove_dir_all(from)
+            } else {
+                fs::remove_file(from)
             }
         }
+        result => result,
+    }
+}
+
+/// Writes `contents` to the file at `path`, replacing it at once, so that it
+/// is never left half-written.
+///
+/// The contents are written to a temporary file next to `path` first, then
+/// renamed to `path`.
+///
+/// # Errors
+///
+/// Fails when the temporary file can't be written or renamed, in which case
+/// it is removed and the file at `path` is left as it was.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temporary = temporary_sibling(path);
+    let result = fs::write(&temporary, contents).and_then(|()| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+/// Returns the path of a hidden file next to `path`, to write before renaming
+/// it to `path`.
+fn temporary_sibling(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".mksls-{}", process::id()));
+
+    path.with_file_name(name)
+}
+
+/// Copies the file (or directory, recursively) at `from` to `to`, copying
+/// symlinks as symlinks, and preserving ownership where allowed, permissions,
+/// and access and modification times (except for symlinks).
+fn copy_preserving(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        platform::symlink(fs::read_link(from)?, to)?;
+        return platform::copy_ownership(&metadata, to);
+    }
+    if let Some(special) = special_file(from) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} is a {}, which can't be copied to another file system.",
+                from.display(),
+                special
+            ),
+        ));
+    }
+
+    // The copy is written (and its times set) through the handle opened before
+    // it gets the permissions, which may prevent opening it.
+    let copy = if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_preserving(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::File::open(to)?
+    } else {
+        let mut copy = fs::File::create_new(to)?;
+        io::copy(&mut fs::File::open(from)?, &mut copy)?;
+        copy
+    };
+    copy.set_times(
+        fs::FileTimes::new()
+            .set_accessed(metadata.accessed()?)
+            .set_modified(metadata.modified()?),
+    )?;
+    // Before the permissions, as changing the owner may clear the set-user-ID
+    // and set-group-ID bits.
+    platform::copy_ownership(&metadata, to)?;
+    copy.set_permissions(metadata.permissions())
+}
+
+/// Makes the link of kind `kind` at path `link`, pointing to `target`, in
+/// place of the file that has been moved to `moved`, in the file system `fs`.
+///
+/// If the link fails to be made, the moved file is moved back to `link`, so
+/// that nothing is lost.
+///
+/// # Errors
+///
+/// Fails when the link creation fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+fn make_or_restore(
+    fs: &dyn Fs,
+    kind: &LinkKind,
+    target: &Path,
+    link: &Path,
+    moved: &Path,
+) -> crate::Result<()> {
+    if let Err(err) = kind.make(fs, target, link) {
+        let _ = move_path(fs, moved, link);
+        return Err(err).link_context(|| {
+            format!(
+                "Failed to create {} -> {}",
+                link.to_string_lossy(),
+                target.to_string_lossy()
+            )
+        });
+    }
+
+    Ok(())
+}
+
+/// Overwrites existing file at path `link` by making a link of kind `kind`
+/// at path `link` (pointing to `target`) without backup.
+///
+/// A directory is removed with all its entries, hence the confirmation asked
+/// for beforehand by the engine (see [`crate::cli::Cli::allow_dir_overwrite`]).
+///
+/// Finally, writes feeback into `writer` in the form of:
+///
+/// ```text
+/// (o) <link> -> <target>
+/// ```
+///
+/// in dark red.
+///
+/// # Parameters
+///
+/// - `writer`: Where to write feedback to.
+/// - `fs`: The file system to remove the existing file from and make a
+///   symlink in.
+/// - `kind`: The kind of link to make.
+/// - `target`: Path to the target of the link.
+/// - `link`: Path to the link.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - The existing file fails to be removed.
+/// - The link creation fails.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn overwrite<W: Write>(
+    mut writer: W,
+    fs: &dyn Fs,
+    kind: &LinkKind,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<()> {
+    fs.remove(link).link_context(|| {
+        format!(
+            "Failed to remove current file {} to then make the symlink with the same path.",
+            link.to_string_lossy()
+        )
+    })?;
+
+    kind.make(fs, target, link).link_context(|| {
+        format!(
+            "Failed to create {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )
+    })?;
+
+    writeln!(
+        writer,
+        "{}",
+        format!(
+            "(o) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )
+        .dark_red()
+    )?;
+
+    Ok(())
+}
+
+/// Same as [`overwrite`], except that the existing file at path `link` is
+/// moved into the directory `save_dir` instead of being removed, so that the
+/// overwrite can be undone.
+///
+/// # Returns
+///
+/// The path the existing file has been moved to.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - The existing file fails to be moved to `save_dir`.
+/// - The link creation fails.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn overwrite_saving<W: Write>(
+    mut writer: W,
+    fs: &dyn Fs,
+    save_dir: &Path,
+    kind: &LinkKind,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<PathBuf> {
+    let saved = move_into(fs, save_dir, link)?;
+    make_or_restore(fs, kind, target, link, &saved)?;
+
+    writeln!(
+        writer,
+        "{}",
+        format!(
+            "(o) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )
+        .dark_red()
+    )?;
+
+    Ok(saved)
+}
+
+/// Replaces the existing file at path `link`, identical to a backup already
+/// made, by a link of kind `kind` at path `link` (pointing to `target`),
+/// without backing it up again.
+///
+/// The existing file is removed from `fs`, or moved into the directory
+/// `save_dir` if given, so that the replacement can be undone.
+///
+/// Finally, writes feedback into `writer` in the form of:
+///
+/// ```text
+/// (b=) <link> -> <target>
+/// ```
+///
+/// in dark green.
+///
+/// # Returns
+///
+/// The path the existing file has been moved to, if `save_dir` is given.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - The existing file fails to be removed or moved to `save_dir`.
+/// - The link creation fails.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn already_backed_up<W: Write>(
+    mut writer: W,
+    fs: &dyn Fs,
+    save_dir: Option<&Path>,
+    kind: &LinkKind,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<Option<PathBuf>> {
+    let saved = match save_dir {
+        Some(save_dir) => {
+            let saved = move_into(fs, save_dir, link)?;
+            make_or_restore(fs, kind, target, link, &saved)?;
+            Some(saved)
+        }
         None => {
-            new_name = String::from(".");
-            if let Some(extension) = link.extension() {
-                new_name.push_str(&format!(
-                    "{}_backup_{}",
-                    extension.to_string_lossy(),
-                    chrono::Local::now().to_rfc3339()
-                ));
+            fs.remove(link).link_context(|| {
+                format!(
+                    "Failed to remove current file {} to then make the symlink with the same path.",
+                    link.to_string_lossy()
+                )
+            })?;
+            kind.make(fs, target, link).link_context(|| {
+                format!(
+                    "Failed to create {} -> {}",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                )
+            })?;
+            None
+        }
+    };
+
+    writeln!(
+        writer,
+        "{}",
+        format!(
+            "(b=) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )
+        .dark_green()
+    )?;
+
+    Ok(saved)
+}
+
+/// Returns the SHA-256 hash of the contents of the file at `path`, in
+/// hexadecimal.
+///
+/// The file is read by chunks, so that large files are not loaded in memory.
+///
+/// # Errors
+///
+/// Fails when the file can't be read.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns the unified diff between the existing file at path `link` and the
+/// contents the link of kind `kind` pointing to `target` would have, colored
+/// for the terminal.
+///
+/// Returns `None` if the existing file is not a regular file, or if one of
+/// the files is not valid UTF-8 text (or can't be read).
+///
+/// # Parameters
+///
+/// - `kind`: The kind of link to make.
+/// - `target`: Path to the target of the link, relative to the directory of
+///   `link` if not absolute.
+/// - `link`: Path to the link.
+pub fn diff(kind: &LinkKind, target: &Path, link: &Path) -> Option<String> {
+    if link.is_symlink() || !link.is_file() {
+        return None;
+    }
+
+    let existing = fs::read_to_string(link).ok()?;
+    let target = link.parent().unwrap_or(Path::new("")).join(target);
+    let contents = match kind {
+        LinkKind::Rendered(contents) => contents.clone(),
+        _ if target.is_file() => fs::read_to_string(&target).ok()?,
+        _ => return None,
+    };
+
+    let diff = TextDiff::from_lines(&existing, &contents)
+        .unified_diff()
+        .header(&link.to_string_lossy(), &target.to_string_lossy())
+        .to_string();
+    let colored: Vec<String> = diff
+        .lines()
+        .map(|line| {
+            if line.starts_with("---") || line.starts_with("+++") {
+                line.bold().to_string()
+            } else if line.starts_with('-') {
+                line.red().to_string()
+            } else if line.starts_with('+') {
+                line.green().to_string()
+            } else if line.starts_with("@@") {
+                line.cyan().to_string()
+            } else {
+                line.to_string()
             }
+        })
+        .collect();
+
+    Some(colored.join("\n"))
+}
+
+/// The type of a file that is neither a regular file, a directory nor a
+/// symlink.
+///
+/// Such a file can't be read like a regular file (reading a FIFO blocks until
+/// something writes to it, reading a device reads the device), so it is never
+/// read nor copied, only removed or moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialFile {
+    /// A named pipe.
+    Fifo,
+    /// A Unix domain socket.
+    Socket,
+    /// A block device.
+    BlockDevice,
+    /// A character device.
+    CharDevice,
+    /// Another type of file, unknown to the platform.
+    Other,
+}
+
+impl fmt::Display for SpecialFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SpecialFile::Fifo => "FIFO",
+            SpecialFile::Socket => "socket",
+            SpecialFile::BlockDevice => "block device",
+            SpecialFile::CharDevice => "character device",
+            SpecialFile::Other => "file of an unknown type",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns the type of the file at `path` (without following a symlink) if it
+/// is a special file, `None` if it is a regular file, a directory, a symlink,
+/// or if there is no file at `path`.
+pub fn special_file(path: &Path) -> Option<SpecialFile> {
+    let file_type = fs::symlink_metadata(path).ok()?.file_type();
+    if file_type.is_file() || file_type.is_dir() || file_type.is_symlink() {
+        return None;
+    }
+
+    Some(platform::special_file(&file_type))
+}
+
+/// Returns a description of the file at `path` (without following a
+/// symlink), one property per line: its type (with the path it points to for
+/// a symlink), size, modification time and owner.
+///
+/// A property that can't be read is left out.
+pub fn describe(path: &Path) -> String {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) => return format!("Failed to read the metadata of the file: {}", err),
+    };
+
+    let file_type = metadata.file_type();
+    let file_type = if file_type.is_symlink() {
+        match fs::read_link(path) {
+            Ok(current_target) => format!("symlink, pointing to {}", current_target.display()),
+            Err(_) => String::from("symlink"),
         }
+    } else if file_type.is_dir() {
+        format!("directory, of {} entries", count_entries(path))
+    } else if file_type.is_file() {
+        String::from("regular file")
+    } else {
+        format!("{} (special file)", platform::special_file(&file_type))
+    };
+    let mut description = vec![
+        format!("Type     : {}", file_type),
+        format!("Size     : {} bytes", metadata.len()),
+    ];
+    if let Ok(modified) = metadata.modified() {
+        description.push(format!(
+            "Modified : {}",
+            chrono::DateTime::<chrono::Local>::from(modified)
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+        ));
     }
+    if let Some(owner) = platform::owner(&metadata) {
+        description.push(format!("Owner    : {}", owner));
+    }
+
+    description.join("\n")
+}
 
-    let mut backup = backup_dir.to_path_buf();
-    backup.push(new_name);
+/// Returns the number of entries within the directory `dir`, recursively
+/// (without following symlinks), the ones that can't be read being left out.
+pub fn count_entries(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .into_iter()
+        .filter(Result::is_ok)
+        .count() as u64
+}
 
-    fs::rename(link, &backup).with_context(|| {
+/// Adopts the existing file at path `link`, i.e. moves it to `target`
+/// (replacing what is there, if anything), then makes the link of kind
+/// `kind` at path `link`, pointing to `target`.
+///
+/// A symlink at `link` is not adopted, as it is not the file itself.
+///
+/// Finally, writes feedback into `writer` in the form of:
+///
+/// ```text
+/// (a) <link> -> <target>
+/// ```
+///
+/// in dark yellow.
+///
+/// # Parameters
+///
+/// - `writer`: Where to write feedback to.
+/// - `fs`: The file system to move the existing file and make the link in.
+/// - `kind`: The kind of link to make.
+/// - `target`: Path to the target of the link.
+/// - `link`: Path to the link.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - `link` is a symlink.
+/// - The existing file fails to be moved to `target`.
+/// - The link creation fails.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn adopt<W: Write>(
+    mut writer: W,
+    fs: &dyn Fs,
+    kind: &LinkKind,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<()> {
+    if fs.is_symlink(link) {
+        return Err(io::Error::from(io::ErrorKind::InvalidInput)).io_context(|| {
+            format!(
+                "Failed to adopt {}! It is a symlink, not a file to adopt.",
+                link.to_string_lossy()
+            )
+        });
+    }
+    move_path(fs, link, target).io_context(|| {
         format!(
-            "Failed to backup! Couldn't move {} to {}",
-            link.display(),
-            backup.display()
+            "Failed to adopt {}! Couldn't move it to {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
         )
     })?;
 
-    unix::fs::symlink(target, link).with_context(|| {
+    kind.make(fs, target, link).link_context(|| {
         format!(
             "Failed to create {} -> {}",
             link.to_string_lossy(),
@@ -127,59 +999,105 @@ pub fn backup<W: Write>(
         writer,
         "{}",
         format!(
-            "(b) {} -> {}",
+            "(a) {} -> {}",
             link.to_string_lossy(),
             target.to_string_lossy()
         )
-        .dark_green()
+        .dark_yellow()
     )?;
 
     Ok(())
 }
 
-/// Overwrites existing file at path `link` by making a symlink
-/// at path `link` (pointing to `target`) without backup.
-///
-/// Finally, writes feeback into `writer` in the form of:
+/// Replaces the symlink at `link`, pointing to another path, by one pointing
+/// to `target`.
+///
+/// The replacement is atomic: the new symlink is made next to `link`, then
+/// renamed to `link`.
+///
+/// Finally, writes feedback into `writer` in the form of:
+///
+/// ```text
+/// (r) <link> -> <target>
+/// ```
+///
+/// in dark cyan.
+///
+/// # Parameters
+///
+/// - `writer`: Where to write feedback to.
+/// - `fs`: The file system the symlink lives in.
+/// - `target`: Path to the target of the symlink.
+/// - `link`: Path to the symlink.
+///
+/// # Returns
+///
+/// The path the symlink pointed to before being replaced.
+///
+/// # Errors
+///
+/// Fails when:
+///
+/// - The existing symlink fails to be read.
+/// - The new symlink fails to be made or renamed to `link`.
+/// - Writing into `writer` fails.
+///
+/// See [`crate::Error`] for the kinds of errors.
+pub fn relink<W: Write>(
+    writer: W,
+    fs: &dyn Fs,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<PathBuf> {
+    replace_reporting(writer, fs, target, link, 'r')
+}
+
+/// Replaces the broken symlink at `link` by one pointing to `target`, like
+/// [`relink`], but writes feedback into `writer` in the form of:
 ///
 /// ```text
-/// (o) <link> -> <target>
+/// (f) <link> -> <target>
 /// ```
 ///
-/// in dark red.
+/// in dark cyan.
 ///
-/// # Parameters
+/// # Returns
 ///
-/// - `writer`: Where to write feedback to.
-/// - `target`: Path to the target of the symlink.
-/// - `link`: Path to the symlink.
+/// The path the broken symlink pointed to.
 ///
 /// # Errors
 ///
-/// Fails when:
-///
-/// - The existing file fails to be removed.
-/// - The symlink creation fails.
-/// - Writing into `writer` fails.
+/// Same as [`relink`].
 ///
-/// These are `anyhow` errors, so most of the time, you just want to
-/// propagate them.
-pub fn overwrite<W: Write>(mut writer: W, target: &Path, link: &Path) -> anyhow::Result<()> {
-    if link.is_dir() {
-        fs::remove_dir_all(link)
-            .with_context(|| format!("Failed to remove current directory {} to then make the symlink with the same path.", link.to_string_lossy()))?;
-    } else {
-        fs::remove_file(link).with_context(|| {
-            format!(
-                "Failed to remove current file {} to then make the symlink with the same path.",
-                link.to_string_lossy()
-            )
-        })?;
-    }
+/// See [`crate::Error`] for the kinds of errors.
+pub fn repair<W: Write>(
+    writer: W,
+    fs: &dyn Fs,
+    target: &Path,
+    link: &Path,
+) -> crate::Result<PathBuf> {
+    replace_reporting(writer, fs, target, link, 'f')
+}
 
-    unix::fs::symlink(target, link).with_context(|| {
+/// Replaces the symlink at `link` by one pointing to `target`, then writes
+/// feedback into `writer`, starting with `letter` (see [`relink`]).
+fn replace_reporting<W: Write>(
+    mut writer: W,
+    fs: &dyn Fs,
+    target: &Path,
+    link: &Path,
+    letter: char,
+) -> crate::Result<PathBuf> {
+    let previous = fs.read_link(link).link_context(|| {
         format!(
-            "Failed to create {} -> {}",
+            "Failed to read current symlink {} to then replace it.",
+            link.to_string_lossy()
+        )
+    })?;
+    replace_symlink(fs, target, link).link_context(|| {
+        format!(
+            "Failed to replace {} by {} -> {}",
+            link.to_string_lossy(),
             link.to_string_lossy(),
             target.to_string_lossy()
         )
@@ -189,20 +1107,51 @@ pub fn overwrite<W: Write>(mut writer: W, target: &Path, link: &Path) -> anyhow:
         writer,
         "{}",
         format!(
-            "(o) {} -> {}",
+            "({}) {} -> {}",
+            letter,
             link.to_string_lossy(),
             target.to_string_lossy()
         )
-        .dark_red()
+        .dark_cyan()
     )?;
 
-    Ok(())
+    Ok(previous)
+}
+
+/// Whether `link` is a broken symlink, i.e. one pointing to nothing, looked
+/// for through `fs`.
+///
+/// A relative path pointed to is resolved against the directory containing
+/// `link`.
+pub fn is_broken_symlink(fs: &dyn Fs, link: &Path) -> bool {
+    let Ok(pointed) = fs.read_link(link) else {
+        return false;
+    };
+    let link_dir = link.parent().unwrap_or(Path::new(""));
+    !fs.exists(&link_dir.join(pointed))
+}
+
+/// Atomically replaces the file at `link` by a symlink pointing to `target`,
+/// through `fs`.
+pub fn replace_symlink(fs: &dyn Fs, target: &Path, link: &Path) -> io::Result<()> {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(link.file_name().unwrap_or_default());
+    tmp_name.push(".mksls-relink");
+    let tmp = link.with_file_name(tmp_name);
+    if fs.is_symlink(&tmp) {
+        fs.remove(&tmp)?;
+    }
+
+    fs.symlink(target, &tmp)?;
+    fs.rename(&tmp, link).inspect_err(|_| {
+        let _ = fs.remove(&tmp);
+    })
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::dir::Dir;
+    use crate::filesystem::RealFs;
     use assert_fs::fixture::NamedTempFile;
     use assert_fs::fixture::TempDir;
     use assert_fs::prelude::*;
@@ -210,8 +1159,78 @@ pub mod tests {
     use std::path::PathBuf;
     use std::str;
 
-    pub fn vec_are_equal<T: Eq>(v1: &Vec<T>, v2: &Vec<T>) -> bool {
-        v1.len() == v2.len() && v1.iter().fold(true, |acc, el| acc && v2.contains(el))
+    const RUN: &str = "20240102T030405";
+
+    pub fn vec_are_equal<T: Eq>(v1: &[T], v2: &[T]) -> bool {
+        v1.len() == v2.len() && v1.iter().all(|el| v2.contains(el))
+    }
+
+    #[test]
+    fn escape_field_escapes_separators_and_backslashes() {
+        assert_eq!(escape_field("/home/me/.vimrc"), "/home/me/.vimrc");
+        assert_eq!(escape_field("a\tb\nc\rd\\e"), "a\\tb\\nc\\rd\\\\e");
+    }
+
+    #[test]
+    fn diff_compares_regular_text_files_only() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let target = dir.child("target");
+        target.write_str("same\nnew\n")?;
+        let link = dir.child("link");
+        link.write_str("same\nold\n")?;
+
+        let diff = diff(&LinkKind::Symlink, &target, &link).expect("Both are text files.");
+        assert!(diff.contains(&"-old".red().to_string()));
+        assert!(diff.contains(&"+new".green().to_string()));
+        assert!(diff.contains("same"));
+
+        let diff = super::diff(
+            &LinkKind::Rendered(String::from("same\nold\n")),
+            &target,
+            &link,
+        )
+        .expect("The existing file is a text file.");
+        assert!(!diff.contains("old"));
+
+        assert!(super::diff(&LinkKind::Symlink, &dir, &link).is_none());
+        assert!(super::diff(&LinkKind::Symlink, &target, &dir).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn describe_tells_the_type_and_size() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let file = dir.child("file");
+        file.write_str("12345")?;
+        let link = dir.child("link");
+        platform::symlink("/somewhere", &link)?;
+
+        let description = describe(&file);
+        assert!(description.contains("Type     : regular file"));
+        assert!(description.contains("Size     : 5 bytes"));
+        assert!(describe(&link).contains("Type     : symlink, pointing to /somewhere"));
+        assert!(describe(&dir).contains("Type     : directory"));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_files_are_told_and_never_copied() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let socket = dir.child("socket");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket)?;
+
+        assert_eq!(special_file(&socket), Some(SpecialFile::Socket));
+        assert_eq!(special_file(&dir), None);
+        assert!(describe(&socket).contains("Type     : socket (special file)"));
+
+        let err = copy_preserving(&socket, &dir.child("copy")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(!dir.child("copy").exists());
+
+        Ok(())
     }
 
     #[test]
@@ -239,6 +1258,30 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn move_into_names_backups_apart_and_without_colons() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let backup_dir = TempDir::new()?;
+        let file = NamedTempFile::new("file")?;
+        file.touch()?;
+        let first = move_into(&RealFs, &backup_dir, &file)?;
+        file.touch()?;
+        let second = move_into(&RealFs, &backup_dir, &file)?;
+
+        assert_ne!(first, second);
+        for backup in [first, second] {
+            let name = backup.file_name().unwrap().to_string_lossy();
+            assert!(!name.contains(':'), "{} contains a ':'.", name);
+            assert!(backup.is_file());
+        }
+
+        // Ensure deletion happens.
+        backup_dir.close()?;
+        file.close()?;
+
+        Ok(())
+    }
+
     #[test]
     fn backup_feedback_has_right_format() -> Result<(), Box<dyn std::error::Error>> {
         let mut feedback = vec![];
@@ -248,7 +1291,15 @@ pub mod tests {
         let conflicting_file = NamedTempFile::new("conflicting_file")?;
         conflicting_file.write_str("Contents of conflicting file.")?;
 
-        backup(&mut feedback, &backup_dir, &target, &conflicting_file)?;
+        backup(
+            &mut feedback,
+            &RealFs,
+            &backup_dir,
+            RUN,
+            &LinkKind::Symlink,
+            &target,
+            &conflicting_file,
+        )?;
         let feedback = str::from_utf8(&feedback[..]).expect("Should be valid utf-8 characters.");
 
         let expected_feedback = format!(
@@ -286,30 +1337,24 @@ pub mod tests {
         let target = NamedTempFile::new("target")?;
         target.touch()?;
 
-        backup(&mut feedback, &backup_dir, &target, &conflicting_file)?;
-
-        // Check that a file containing the name of `conflicting_file` exists in `backup_dir`.
-        let d = Dir::build(backup_dir.to_path_buf())
-            .expect("Path of `backup_dir` should be valid at this point.");
-        let mut at_least_one_file_containing_conflicting_file_name = false;
-        let mut backup_file: Option<PathBuf> = None;
-        for file in d.iter_on_files() {
-            if file
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .contains(conflicting_file_name)
-            {
-                backup_file = Some(file.clone());
-                at_least_one_file_containing_conflicting_file_name = true;
-            }
-        }
-        assert!(at_least_one_file_containing_conflicting_file_name);
+        let backup_file = backup(
+            &mut feedback,
+            &RealFs,
+            &backup_dir,
+            RUN,
+            &LinkKind::Symlink,
+            &target,
+            &conflicting_file,
+        )?;
+
+        // Check that `backup_file` is within the directory mirroring the path of `conflicting_file`
+        // in the directory of the run in `backup_dir`.
+        let mirror_dir = backup_dir
+            .join(RUN)
+            .join(conflicting_file.strip_prefix("/")?);
+        assert_eq!(backup_file.parent(), Some(mirror_dir.as_path()));
 
         // Check that `backup_file` has the contents of the conflicting file.
-        let backup_file = backup_file.expect(
-            "Should have found a file containing the name of `conflicting_file` in `backup_dir`.",
-        );
         let backup_file_contents = std::fs::read_to_string(backup_file)?;
         assert_eq!(backup_file_contents, conflicting_file_contents);
 
@@ -321,6 +1366,214 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn rename_keeps_file_next_to_link() -> Result<(), Box<dyn std::error::Error>> {
+        let mut feedback = vec![];
+        let dir = TempDir::new()?;
+        let link = dir.child("link");
+        link.write_str("Contents of conflicting file.")?;
+        dir.child("link.orig").write_str("Already renamed.")?;
+        let target = dir.child("target");
+        target.touch()?;
+
+        let renamed = rename(
+            &mut feedback,
+            &RealFs,
+            ".orig",
+            &LinkKind::Symlink,
+            &target,
+            &link,
+        )?;
+
+        assert_eq!(renamed, dir.child("link.orig.1").path());
+        assert_eq!(
+            std::fs::read_to_string(&renamed)?,
+            "Contents of conflicting file."
+        );
+        assert_eq!(std::fs::read_link(&link)?, target.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn already_backed_up_replaces_file_by_link() -> Result<(), Box<dyn std::error::Error>> {
+        let mut feedback = vec![];
+        let dir = TempDir::new()?;
+        let save_dir = TempDir::new()?;
+        let target = dir.child("target");
+        target.touch()?;
+        let link = dir.child("link");
+        link.write_str("Contents of conflicting file.")?;
+
+        let saved = already_backed_up(
+            &mut feedback,
+            &RealFs,
+            None,
+            &LinkKind::Symlink,
+            &target,
+            &link,
+        )?;
+        assert_eq!(saved, None);
+        assert_eq!(fs::read_link(&link)?, target.path());
+        let feedback = str::from_utf8(&feedback[..]).expect("Should be valid utf-8 characters.");
+        assert!(feedback.contains(&format!(
+            "(b=) {} -> {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        )));
+
+        fs::remove_file(&link)?;
+        link.write_str("Contents of conflicting file.")?;
+        let saved = already_backed_up(
+            io::sink(),
+            &RealFs,
+            Some(&save_dir),
+            &LinkKind::Symlink,
+            &target,
+            &link,
+        )?;
+        assert_eq!(
+            fs::read_to_string(saved.expect("Should have been saved."))?,
+            "Contents of conflicting file."
+        );
+
+        dir.close()?;
+        save_dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_preserving_preserves_tree_and_metadata() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        use std::time::{Duration, SystemTime};
+
+        let dir = TempDir::new()?;
+        let from = dir.child("from");
+        from.child("file").write_str("Contents of file.")?;
+        platform::symlink("file", from.child("symlink"))?;
+        fs::set_permissions(from.child("file"), fs::Permissions::from_mode(0o640))?;
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::open(from.child("file"))?
+            .set_times(fs::FileTimes::new().set_modified(modified))?;
+        fs::File::open(&from)?.set_times(fs::FileTimes::new().set_modified(modified))?;
+        // Only allowed when privileged, otherwise the owner stays the current user.
+        let _ = std::os::unix::fs::chown(from.child("file"), Some(1234), Some(1234));
+
+        let to = dir.child("to");
+        copy_preserving(&from, &to)?;
+
+        assert_eq!(fs::read_to_string(to.child("file"))?, "Contents of file.");
+        assert_eq!(fs::read_link(to.child("symlink"))?, Path::new("file"));
+        let metadata = fs::metadata(to.child("file"))?;
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(metadata.modified()?, modified);
+        let from_metadata = fs::metadata(from.child("file"))?;
+        assert_eq!(
+            (metadata.uid(), metadata.gid()),
+            (from_metadata.uid(), from_metadata.gid())
+        );
+        assert_eq!(fs::metadata(&to)?.modified()?, modified);
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn root_script_makes_the_links() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("it's a target").touch()?;
+        let target = dir.child("it's a target").to_path_buf();
+        let link = dir.child("link $HOME").to_path_buf();
+        let script = dir.child("root.sh").to_path_buf();
+
+        write_root_script(
+            &script,
+            &[LinkKind::Symlink.command(&target, &link).unwrap()],
+        )?;
+        let status = std::process::Command::new("sh").arg(&script).status()?;
+
+        assert!(status.success());
+        assert_eq!(fs::read_link(&link)?, target);
+        assert_eq!(
+            LinkKind::Rendered(String::new()).command(&target, &link),
+            None
+        );
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn move_path_moves_file() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("from").write_str("Contents of file.")?;
+
+        move_path(&RealFs, &dir.child("from"), &dir.child("to"))?;
+
+        dir.child("from").assert(predicate::path::missing());
+        dir.child("to").assert("Contents of file.");
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn move_path_copies_across_file_systems() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new()?;
+        let crosses_devices = |_: &Path, _: &Path| Err(io::ErrorKind::CrossesDevices.into());
+        let from = dir.child("from");
+        from.child("file").write_str("Contents of file.")?;
+        fs::set_permissions(from.child("file"), fs::Permissions::from_mode(0o200))?;
+
+        move_path_with(&from, &dir.child("to"), crosses_devices)?;
+        from.assert(predicate::path::missing());
+        let moved = dir.child("to/file");
+        let mode = fs::metadata(&moved)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o200);
+        fs::set_permissions(&moved, fs::Permissions::from_mode(0o600))?;
+        moved.assert("Contents of file.");
+
+        // The copy fails on the special file, leaving the existing file as is.
+        from.create_dir_all()?;
+        let _listener = std::os::unix::net::UnixListener::bind(from.child("sock"))?;
+        let existing = dir.child("existing");
+        existing.write_str("Existing.")?;
+        assert!(move_path_with(&from, &existing, crosses_devices).is_err());
+        existing.assert("Existing.");
+        from.child("sock").assert(predicate::path::exists());
+        assert_eq!(fs::read_dir(&dir)?.count(), 3);
+
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_file_hashes_contents() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        dir.child("a").write_str("abc")?;
+        dir.child("b").write_str("abc")?;
+
+        assert_eq!(
+            hash_file(&dir.child("a"))?,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hash_file(&dir.child("a"))?, hash_file(&dir.child("b"))?);
+
+        dir.close()?;
+
+        Ok(())
+    }
+
     #[test]
     fn backup_fails_when_no_conflicting_file() -> Result<(), Box<dyn std::error::Error>> {
         let mut feedback = vec![];
@@ -329,7 +1582,16 @@ pub mod tests {
         let conflicting_file = NamedTempFile::new("conflicting_file")?;
         let target = NamedTempFile::new("target")?;
 
-        assert!(backup(&mut feedback, &backup_dir, &target, &conflicting_file).is_err());
+        assert!(backup(
+            &mut feedback,
+            &RealFs,
+            &backup_dir,
+            RUN,
+            &LinkKind::Symlink,
+            &target,
+            &conflicting_file,
+        )
+        .is_err());
 
         // Ensure deletion happens.
         backup_dir.close()?;
@@ -347,7 +1609,13 @@ pub mod tests {
         let conflicting_file = NamedTempFile::new("conflicting_file")?;
         conflicting_file.write_str("Contents of conflicting file.")?;
 
-        overwrite(&mut feedback, &target, &conflicting_file)?;
+        overwrite(
+            &mut feedback,
+            &RealFs,
+            &LinkKind::Symlink,
+            &target,
+            &conflicting_file,
+        )?;
         let feedback = str::from_utf8(&feedback[..]).expect("Should be valid utf-8 characters.");
 
         let expected_feedback = format!(
@@ -383,7 +1651,13 @@ pub mod tests {
         let target = NamedTempFile::new("target")?;
         target.touch()?;
 
-        overwrite(&mut feedback, &target, &conflicting_file)?;
+        overwrite(
+            &mut feedback,
+            &RealFs,
+            &LinkKind::Symlink,
+            &target,
+            &conflicting_file,
+        )?;
 
         // Check that a symlink to `target` exists in place of `conflicting_file`.
         assert!(predicate::path::is_symlink().eval(&conflicting_file));
@@ -399,6 +1673,87 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn symlink_to_an_equivalent_path_is_made() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let target = dir.child("dots").child("nvim");
+        target.create_dir_all()?;
+        dir.child("home").create_dir_all()?;
+        let link = dir.child("home").child("nvim");
+        std::os::unix::fs::symlink("../dots/./nvim/", &link)?;
+
+        assert!(LinkKind::Symlink.is_made(&RealFs, &target, &link, false)?);
+        assert!(LinkKind::Symlink.is_made(&RealFs, Path::new("../dots/nvim"), &link, false)?);
+        assert!(!LinkKind::Symlink.is_made(&RealFs, &target, &link, true)?);
+        assert!(!LinkKind::Symlink.is_made(&RealFs, &dir.child("dots"), &link, false)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn overwrite_with_hardlink_makes_same_file() -> Result<(), Box<dyn std::error::Error>> {
+        let mut feedback = vec![];
+        let dir = TempDir::new()?;
+        let conflicting_file = dir.child("link");
+        conflicting_file.write_str("Contents of conflicting file.")?;
+        let target = dir.child("target");
+        target.write_str("Contents of target.")?;
+
+        assert!(!LinkKind::Hardlink.is_made(&RealFs, &target, &conflicting_file, false)?);
+        overwrite(
+            &mut feedback,
+            &RealFs,
+            &LinkKind::Hardlink,
+            &target,
+            &conflicting_file,
+        )?;
+
+        assert!(!conflicting_file.is_symlink());
+        assert!(LinkKind::Hardlink.is_made(&RealFs, &target, &conflicting_file, false)?);
+        assert_eq!(
+            std::fs::read_to_string(&conflicting_file)?,
+            "Contents of target."
+        );
+
+        // Ensure deletion happens.
+        dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn backup_with_copy_copies_target() -> Result<(), Box<dyn std::error::Error>> {
+        let mut feedback = vec![];
+        let backup_dir = TempDir::new()?;
+        let dir = TempDir::new()?;
+        let conflicting_file = dir.child("link");
+        conflicting_file.write_str("Contents of conflicting file.")?;
+        let target = dir.child("target");
+        target.write_str("Contents of target.")?;
+
+        assert!(!LinkKind::Copy.is_made(&RealFs, &target, &conflicting_file, false)?);
+        backup(
+            &mut feedback,
+            &RealFs,
+            &backup_dir,
+            RUN,
+            &LinkKind::Copy,
+            &target,
+            &conflicting_file,
+        )?;
+
+        assert!(!conflicting_file.is_symlink());
+        assert!(LinkKind::Copy.is_made(&RealFs, &target, &conflicting_file, false)?);
+        assert!(!LinkKind::Hardlink.is_made(&RealFs, &target, &conflicting_file, false)?);
+
+        // Ensure deletion happens.
+        backup_dir.close()?;
+        dir.close()?;
+
+        Ok(())
+    }
+
     #[test]
     fn overwrite_fails_when_no_conflicting_file() -> Result<(), Box<dyn std::error::Error>> {
         let mut feedback = vec![];
@@ -407,7 +1762,14 @@ pub mod tests {
         let target = NamedTempFile::new("target")?;
         target.touch()?;
 
-        assert!(overwrite(&mut feedback, &target, &conflicting_file).is_err());
+        assert!(overwrite(
+            &mut feedback,
+            &RealFs,
+            &LinkKind::Symlink,
+            &target,
+            &conflicting_file
+        )
+        .is_err());
 
         // Ensure deletion happens.
         conflicting_file.close()?;
@@ -415,4 +1777,104 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn relative_target_is_relative_to_link_dir() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = TempDir::new()?;
+        let root = tmp_dir.path().canonicalize()?;
+        fs::create_dir_all(root.join("dots/nvim"))?;
+        fs::create_dir_all(root.join("home/.config"))?;
+
+        assert_eq!(
+            relative_target(&root.join("dots/nvim"), &root.join("home/.config/nvim")),
+            PathBuf::from("../../dots/nvim")
+        );
+        assert_eq!(
+            relative_target(&root.join("dots/vimrc"), &root.join("dots/nvim/vimrc")),
+            PathBuf::from("../vimrc")
+        );
+        assert_eq!(
+            relative_target(&root.join("dots/a"), &root.join("new/dir/a")),
+            PathBuf::from("../../dots/a")
+        );
+
+        platform::symlink(root.join("home"), root.join("home_link"))?;
+        assert_eq!(
+            relative_target(&root.join("dots/a"), &root.join("home_link/.config/a")),
+            PathBuf::from("../../dots/a")
+        );
+
+        tmp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn relink_replaces_symlink() -> Result<(), Box<dyn std::error::Error>> {
+        let mut feedback = vec![];
+        let tmp_dir = TempDir::new()?;
+        let old_target = tmp_dir.child("old_target");
+        old_target.touch()?;
+        let target = tmp_dir.child("target");
+        target.touch()?;
+        let link = tmp_dir.child("link");
+        platform::symlink(&old_target, &link)?;
+
+        let previous = relink(&mut feedback, &RealFs, &target, &link)?;
+
+        assert_eq!(previous, old_target.to_path_buf());
+        assert_eq!(fs::read_link(&link)?, target.to_path_buf());
+        assert_eq!(
+            fs::read_dir(&tmp_dir)?.count(),
+            3,
+            "no temporary symlink should be left behind"
+        );
+        assert_eq!(
+            str::from_utf8(&feedback)?,
+            format!(
+                "{}\n",
+                format!(
+                    "(r) {} -> {}",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                )
+                .dark_cyan()
+            )
+        );
+
+        tmp_dir.close()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn adopt_moves_file_to_target() -> Result<(), Box<dyn std::error::Error>> {
+        let mut feedback = vec![];
+        let tmp_dir = TempDir::new()?;
+        let target = tmp_dir.child("dots/target");
+        target.write_str("old contents")?;
+        let link = tmp_dir.child("link");
+        link.write_str("live contents")?;
+
+        adopt(&mut feedback, &RealFs, &LinkKind::Symlink, &target, &link)?;
+
+        assert_eq!(fs::read_link(&link)?, target.to_path_buf());
+        target.assert("live contents");
+        assert_eq!(
+            str::from_utf8(&feedback)?,
+            format!(
+                "{}\n",
+                format!(
+                    "(a) {} -> {}",
+                    link.to_string_lossy(),
+                    target.to_string_lossy()
+                )
+                .dark_yellow()
+            )
+        );
+
+        tmp_dir.close()?;
+
+        Ok(())
+    }
 }
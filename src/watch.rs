@@ -0,0 +1,207 @@
+//! Running the engine again whenever a symlink-specification file changes.
+
+use crate::dir::SLS_IGNORE_FILENAME;
+use crate::engine::Engine;
+use crate::error::Context;
+use crate::params::Params;
+use crate::prompt::{NoninteractiveAction, TerminalPrompter};
+use crate::style::Paint;
+use notify::event::EventKind;
+use notify::{Event, RecursiveMode, Watcher};
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for more changes after one, so that a batch of changes
+/// (e.g. a `git checkout`) triggers a single run.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Runs the engine (see [`Engine::run`]), then runs it again whenever a
+/// symlink-specification file (or a `.slsignore` file) within
+/// [`Params::dir`] changes, until interrupted.
+///
+/// The runs are meant to be uninteractive, so if no action to take for
+/// conflicts is given (see [`Params::always_skip`] and co.), conflicting
+/// links are skipped. Nothing is ever prompted: the prompts left are answered
+/// cautiously, and the invalid lines only reported (see
+/// [`TerminalPrompter::unattended`]).
+/// A run failing doesn't stop the watch, the error is printed instead.
+///
+/// # Parameters
+///
+/// - `params`: Parameters for each run of the engine.
+///
+/// # Errors
+///
+/// Fails when the file system notifications can't be set up.
+///
+/// See [`crate::Error`] for the kinds of errors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+/// use mksls::cfg::Config;
+/// use mksls::cli::Cli;
+/// use mksls::params::Params;
+/// use mksls::watch;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let cli = Cli::parse();
+/// let cfg: Config = confy::load("my_crate", "config")?;
+/// let params = Params::new(cli, cfg)?;
+///
+/// watch::watch(params)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn watch(mut params: Params) -> crate::Result<()> {
+    if !(params.always_skip
+        || params.always_backup
+        || params.always_overwrite
+        || params.always_adopt)
+    {
+        params.always_skip = true;
+    }
+
+    let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(sender)
+        .watch_context(|| "Failed to set up file system notifications.")?;
+    // A single file is watched through its directory, as editors often
+    // replace the file instead of writing to it.
+    let (watched, mode) = match params.dir.parent() {
+        Some(parent) if params.dir.is_file() && parent != Path::new("") => {
+            (parent, RecursiveMode::NonRecursive)
+        }
+        _ if params.dir.is_file() => (Path::new("."), RecursiveMode::NonRecursive),
+        _ => (params.dir.as_path(), RecursiveMode::Recursive),
+    };
+    watcher
+        .watch(watched, mode)
+        .watch_context(|| format!("Failed to watch {}.", params.dir.display()))?;
+
+    run(&params);
+    loop {
+        let Ok(event) = receiver.recv() else {
+            return Ok(());
+        };
+        if !is_relevant(&params, event) {
+            continue;
+        }
+        // Swallow the changes following closely.
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+        println!();
+        println!("{}", "(w) Change detected, running again...".dark_cyan());
+        run(&params);
+    }
+}
+
+/// Runs the engine, without prompting, printing the error if it fails.
+fn run(params: &Params) {
+    let prompter = TerminalPrompter::default()
+        .noninteractive(NoninteractiveAction::Skip)
+        .unattended();
+    if let Err(err) = Engine::new(params.clone()).prompter(prompter).run() {
+        eprintln!("{}", format!("(!) Error: {:#}", err).red());
+    }
+    println!();
+    println!(
+        "{}",
+        format!("(w) Watching {}...", params.dir.to_string_lossy()).dark_cyan()
+    );
+}
+
+/// Whether `event` is about a symlink-specification or `.slsignore` file
+/// being modified.
+fn is_relevant(params: &Params, event: notify::Result<Event>) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    event.paths.iter().any(|path| is_watched_file(params, path))
+}
+
+/// Whether `path` is the path of a symlink-specification or `.slsignore` file.
+///
+/// When [`Params::dir`] is a single file, only that file is, whatever its
+/// name.
+fn is_watched_file(params: &Params, path: &Path) -> bool {
+    if params.dir.is_file() {
+        return path == params.dir
+            || matches!(
+                (fs::canonicalize(path), fs::canonicalize(&params.dir)),
+                (Ok(path), Ok(dir)) if path == dir
+            );
+    }
+
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+
+    name == SLS_IGNORE_FILENAME || params.filename.iter().any(|pattern| pattern.matches(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::Config;
+    use crate::cli::Cli;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use clap::Parser;
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    #[test]
+    fn only_changes_to_sls_and_ignore_files_are_relevant() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let dir = TempDir::new()?;
+        let cli = Cli::try_parse_from(["mksls", dir.path().to_str().unwrap()])?;
+        let params = Params::new(cli, Config::default())?;
+        let event = |kind, name| Ok(Event::new(kind).add_path(dir.child(name).to_path_buf()));
+
+        assert!(is_relevant(
+            &params,
+            event(EventKind::Modify(ModifyKind::Any), "sub/sls")
+        ));
+        assert!(is_relevant(
+            &params,
+            event(EventKind::Create(CreateKind::File), SLS_IGNORE_FILENAME)
+        ));
+        assert!(!is_relevant(
+            &params,
+            event(EventKind::Modify(ModifyKind::Any), "vimrc")
+        ));
+        assert!(!is_relevant(
+            &params,
+            event(EventKind::Access(AccessKind::Any), "sls")
+        ));
+        assert!(!is_relevant(&params, Err(notify::Error::generic("lost"))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_file_is_watched_when_given_one() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let file = dir.child("links.txt");
+        file.touch()?;
+        dir.child("sls").touch()?;
+        let cli = Cli::try_parse_from(["mksls", file.path().to_str().unwrap()])?;
+        let params = Params::new(cli, Config::default())?;
+
+        assert!(is_watched_file(&params, file.path()));
+        assert!(is_watched_file(
+            &params,
+            &dir.path().join(".").join("links.txt")
+        ));
+        assert!(!is_watched_file(&params, dir.child("sls").path()));
+        assert!(!is_watched_file(&params, dir.child("other.txt").path()));
+
+        Ok(())
+    }
+}